@@ -10,11 +10,11 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use wolkenwelten_client::RenderInitArgs;
 use wolkenwelten_client::{ClientState, Mesh, MeshVertex, Texture};
-use wolkenwelten_core::{Message, Reactor, BLOCKS};
+use wolkenwelten_core::{BlockId, Message, Reactor, BLOCKS, GAME_LOG};
 
 #[derive(Clone, Copy, Debug, Default)]
 struct BlockMining {
-    pub _block: u8,
+    pub _block: BlockId,
     pub damage: u16,
     pub block_health: u16,
 }
@@ -40,9 +40,9 @@ impl BlockMiningMap {
         });
     }
 
-    pub fn mine(&mut self, pos: IVec3, block: u8, dmg: u16, block_health: u16) -> bool {
+    pub fn mine(&mut self, pos: IVec3, block: BlockId, dmg: u16, block_health: u16) -> bool {
         if let Some(m) = self.map.get_mut(&pos) {
-            m.damage += dmg;
+            m.damage = m.damage.saturating_add(dmg);
             if m.damage > m.block_health {
                 m.damage = 1;
                 return true;
@@ -51,7 +51,7 @@ impl BlockMiningMap {
             self.map.insert(
                 pos,
                 BlockMining {
-                    damage: 2,
+                    damage: dmg,
                     _block: block,
                     block_health,
                 },
@@ -149,16 +149,25 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
         let mining = mining.clone();
         let f = move |reactor: &Reactor<Message>, msg: Message| {
             if let Message::GameTick { ticks } = msg {
-                let player = player.borrow();
+                let mut player = player.borrow_mut();
                 if let Some((pos, block)) = player.mining() {
                     let mut world = world.borrow_mut();
                     BLOCKS.with(|blocks| {
                         let bt = blocks.borrow();
                         if let Some(bt) = bt.get(block as usize) {
                             let mut mining = mining.borrow_mut();
-                            if mining.mine(pos, block, 2, bt.block_health()) {
+                            let damage = player.mining_damage();
+                            if mining.mine(pos, block, damage, bt.block_health()) {
                                 world.set_block(pos, 0);
                                 reactor.defer(Message::BlockBreak { pos, block });
+                                if let Some(tool) = player.wear_tool(bt.mining_cat()) {
+                                    GAME_LOG.with(|log| {
+                                        log.borrow_mut().push(format!(
+                                            "Your {} broke",
+                                            tool.name().to_lowercase()
+                                        ))
+                                    });
+                                }
                             }
                         }
                         if (ticks & 0x7F) == 0 {
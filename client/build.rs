@@ -0,0 +1,44 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Collect every .vox file below dir, sorted so the output stays stable
+fn vox_files(dir: &Path, ret: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("failed to read asset dir")
+        .map(|e| e.unwrap().path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            vox_files(&path, ret);
+        } else if path.extension().map(|e| e == "vox").unwrap_or(false) {
+            ret.push(path);
+        }
+    }
+}
+
+/// Embed every model within assets/ keyed by its path, so new items,
+/// projectiles and mobs only need a .vox file and a JSON entry
+fn main() {
+    println!("cargo:rerun-if-changed=./assets/");
+    let assets = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("assets");
+    let mut files = vec![];
+    vox_files(&assets, &mut files);
+
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("vox_files.rs");
+    let mut fh = File::create(out).unwrap();
+    writeln!(fh, "pub const VOX_FILES: &[(&str, &[u8])] = &[").unwrap();
+    for path in files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let name = path
+            .strip_prefix(&assets)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(fh, "    ({:?}, include_bytes!({:?})),", name, path).unwrap();
+    }
+    writeln!(fh, "];").unwrap();
+}
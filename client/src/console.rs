@@ -0,0 +1,193 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use winit::event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use wolkenwelten_core::{run_console_line, Message, Reactor};
+
+const MAX_HISTORY: usize = 32;
+
+/// The key closing a menu might also arrive as a DeviceEvent after the menu
+/// has already been closed, so we have to make sure that Escape doesn't quit
+/// the game right away.
+pub(crate) fn swallow_pending_key(
+    swallow_key: &mut Option<VirtualKeyCode>,
+    event: &Event<()>,
+) -> bool {
+    let Some(key) = *swallow_key else {
+        return false;
+    };
+    match event {
+        Event::DeviceEvent {
+            event: DeviceEvent::Key(input),
+            ..
+        }
+        | Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input, .. },
+            ..
+        } if input.virtual_keycode == Some(key) => {
+            if input.state == ElementState::Released {
+                *swallow_key = None;
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Text input for chatting and entering commands, opened by pressing T or /
+#[derive(Clone, Debug, Default)]
+pub struct Console {
+    open: bool,
+    skip_char: bool,
+    swallow_key: Option<VirtualKeyCode>,
+    input: String,
+    history: Vec<String>,
+    history_pos: usize,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[inline]
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.input.clear();
+        self.history_pos = self.history.len();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.input.clear();
+    }
+
+    fn submit(&mut self, reactor: &Reactor<Message>) {
+        let line = std::mem::take(&mut self.input);
+        self.close();
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        run_console_line(reactor, &line);
+    }
+
+    fn history_step(&mut self, delta: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+        let pos = (self.history_pos as i32 + delta).clamp(0, self.history.len() as i32) as usize;
+        self.history_pos = pos;
+        self.input = self.history.get(pos).cloned().unwrap_or_default();
+    }
+
+    fn handle_key(&mut self, reactor: &Reactor<Message>, code: VirtualKeyCode) {
+        match code {
+            VirtualKeyCode::Escape => {
+                self.swallow_key = Some(code);
+                self.close();
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                self.swallow_key = Some(code);
+                self.submit(reactor);
+            }
+            VirtualKeyCode::Back => {
+                self.input.pop();
+            }
+            VirtualKeyCode::Up => self.history_step(-1),
+            VirtualKeyCode::Down => self.history_step(1),
+            _ => (),
+        }
+    }
+
+    /// Handle a winit event, returns true if the event has been consumed by
+    /// the console and should not be passed on to the regular input handling.
+    pub fn handle_winit_event(&mut self, reactor: &Reactor<Message>, event: &Event<()>) -> bool {
+        if !self.open {
+            if swallow_pending_key(&mut self.swallow_key, event) {
+                return true;
+            }
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                match code {
+                    VirtualKeyCode::T => {
+                        self.open();
+                        self.skip_char = true;
+                        return true;
+                    }
+                    VirtualKeyCode::Slash => {
+                        self.open();
+                        self.skip_char = false;
+                        return true;
+                    }
+                    _ => (),
+                }
+            }
+            return false;
+        }
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => {
+                if self.skip_char {
+                    self.skip_char = false;
+                } else if !c.is_control() {
+                    self.input.push(*c);
+                }
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                self.handle_key(reactor, *code);
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. },
+                ..
+            }
+            | Event::DeviceEvent {
+                event: DeviceEvent::Key(_),
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
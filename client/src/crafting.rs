@@ -0,0 +1,150 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::console::swallow_pending_key;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+use wolkenwelten_core::{Message, Reactor, RecipeId, RECIPES};
+
+/// Lets players browse through all recipes and craft them, opened by pressing K
+#[derive(Clone, Debug, Default)]
+pub struct CraftingMenu {
+    open: bool,
+    swallow_key: Option<VirtualKeyCode>,
+    selection: usize,
+}
+
+impl CraftingMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[inline]
+    pub fn selection(&self) -> usize {
+        self.selection
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    fn select_step(&mut self, delta: i32) {
+        let len = RECIPES.with(|recipes| recipes.borrow().len()) as i32;
+        if len == 0 {
+            return;
+        }
+        self.selection = (self.selection as i32 + delta).rem_euclid(len) as usize;
+    }
+
+    fn craft(&self, reactor: &Reactor<Message>) {
+        reactor.dispatch(Message::PlayerCraft {
+            recipe: self.selection as RecipeId,
+        });
+    }
+
+    fn handle_key(&mut self, reactor: &Reactor<Message>, code: VirtualKeyCode) {
+        match code {
+            VirtualKeyCode::Escape | VirtualKeyCode::K => {
+                self.swallow_key = Some(code);
+                self.close();
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Space => {
+                self.craft(reactor)
+            }
+            VirtualKeyCode::Up | VirtualKeyCode::W => self.select_step(-1),
+            VirtualKeyCode::Down | VirtualKeyCode::S => self.select_step(1),
+            _ => (),
+        }
+    }
+
+    /// Handle a winit event, returns true if the event has been consumed by
+    /// the menu and should not be passed on to the regular input handling.
+    pub fn handle_winit_event(&mut self, reactor: &Reactor<Message>, event: &Event<()>) -> bool {
+        if !self.open {
+            if swallow_pending_key(&mut self.swallow_key, event) {
+                return true;
+            }
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::K),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                self.open();
+                return true;
+            }
+            return false;
+        }
+
+        match event {
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                self.handle_key(reactor, *code);
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32,
+                };
+                if y != 0.0 {
+                    self.select_step(-y.signum() as i32);
+                }
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        button: MouseButton::Left,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.craft(reactor);
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. },
+                ..
+            }
+            | Event::DeviceEvent {
+                event: DeviceEvent::Key(_) | DeviceEvent::MouseMotion { .. },
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
@@ -22,11 +22,13 @@ pub enum Key {
     Crouch,
     Sprint,
     Drop,
+    Interact,
 
     Primary,
     Secondary,
     Tertiary,
     Reload,
+    NewRun,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -104,22 +106,6 @@ impl InputState {
                 event: WindowEvent::KeyboardInput { input, .. },
                 ..
             } => match input {
-                KeyboardInput {
-                    state: ElementState::Pressed,
-                    virtual_keycode: Some(VirtualKeyCode::N),
-                    ..
-                } => {
-                    reactor.dispatch(Message::PlayerNoClip { no_clip: true });
-                }
-
-                KeyboardInput {
-                    state: ElementState::Pressed,
-                    virtual_keycode: Some(VirtualKeyCode::M),
-                    ..
-                } => {
-                    reactor.dispatch(Message::PlayerNoClip { no_clip: false });
-                }
-
                 KeyboardInput {
                     state: ElementState::Pressed,
                     virtual_keycode: Some(VirtualKeyCode::Key1),
@@ -212,6 +198,12 @@ impl InputState {
                     ..
                 } => self.key_up_down(Key::Drop, state == ElementState::Pressed),
 
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(VirtualKeyCode::F),
+                    ..
+                } => self.key_up_down(Key::Interact, state == ElementState::Pressed),
+
                 KeyboardInput {
                     state,
                     virtual_keycode: Some(VirtualKeyCode::W),
@@ -262,6 +254,12 @@ impl InputState {
                     ..
                 } => self.key_up_down(Key::Reload, state == ElementState::Pressed),
 
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(VirtualKeyCode::N),
+                    ..
+                } => self.key_up_down(Key::NewRun, state == ElementState::Pressed),
+
                 KeyboardInput {
                     state,
                     virtual_keycode: Some(VirtualKeyCode::LShift),
@@ -274,6 +272,12 @@ impl InputState {
         }
     }
 
+    /// Release every button, used when another part of the UI captures the keyboard
+    #[inline]
+    pub fn release_all(&mut self) {
+        self.button_states = Default::default();
+    }
+
     #[inline]
     pub fn key_up_down(&mut self, k: Key, state: bool) {
         self.button_states[k] = state;
@@ -322,8 +326,16 @@ impl InputState {
 
     pub fn tick(&mut self, game: &GameState, reactor: &Reactor<Message>) -> bool {
         if game.player().is_dead() {
-            if self.button_states[Key::Reload] && game.player().is_dead() {
-                reactor.dispatch(Message::ResetEverything);
+            // Either retry the same seed or start a new one
+            let seed = if self.button_states[Key::Reload] {
+                Some(game.run().seed())
+            } else if self.button_states[Key::NewRun] {
+                Some(rand::random())
+            } else {
+                None
+            };
+            if let Some(seed) = seed {
+                reactor.dispatch(Message::RunStart { seed });
                 return true;
             }
         } else {
@@ -365,8 +377,15 @@ impl InputState {
                 }
             }
 
+            if self.button_states[Key::Interact] {
+                let o = game.player().raycast(&game.world(), RaycastReturn::Within);
+                if let Some(pos) = o {
+                    reactor.dispatch(Message::PlayerInteract { pos });
+                }
+            }
+
             if self.button_states[Key::Tertiary] {
-                //reactor.dispatch(Message::PlayerShoot);
+                reactor.dispatch(Message::PlayerUseItem);
             }
 
             if self.button_states[Key::Drop] {
@@ -0,0 +1,230 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::console::swallow_pending_key;
+use glam::IVec2;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+};
+use wolkenwelten_core::{GameState, Message, Reactor, EQUIPMENT_SIZE, HOTBAR_SIZE, INVENTORY_SIZE};
+
+pub const SLOT_SIZE: i32 = 64;
+
+/// How many slots the backpack part of the inventory screen has per row
+const BACKPACK_COLUMNS: i32 = 10;
+
+/// Every slot, including the equipment slots following the inventory
+pub const SLOT_COUNT: usize = INVENTORY_SIZE + EQUIPMENT_SIZE;
+
+/// Position and size of an inventory slot on screen, the hotbar sits in the
+/// bottom right corner and the rest is centered on screen, with the
+/// equipment in a separate row above the backpack.
+pub fn slot_rect(i: usize, (window_width, window_height): (u32, u32)) -> (IVec2, IVec2) {
+    let size = IVec2::new(SLOT_SIZE, SLOT_SIZE);
+    if i < HOTBAR_SIZE {
+        let x = window_width as i32 - (HOTBAR_SIZE as i32 - i as i32) * SLOT_SIZE;
+        let y = window_height as i32 - SLOT_SIZE;
+        return (IVec2::new(x, y), size);
+    }
+    if i >= INVENTORY_SIZE {
+        let (w, h) = backpack_size();
+        let x = window_width as i32 / 2 - w / 2 + (i - INVENTORY_SIZE) as i32 * SLOT_SIZE;
+        let y = window_height as i32 / 2 - h / 2 - EQUIPMENT_OFFSET;
+        return (IVec2::new(x, y), size);
+    }
+    let i = (i - HOTBAR_SIZE) as i32;
+    let (w, h) = backpack_size();
+    let x = window_width as i32 / 2 - w / 2 + (i % BACKPACK_COLUMNS) * SLOT_SIZE;
+    let y = window_height as i32 / 2 - h / 2 + (i / BACKPACK_COLUMNS) * SLOT_SIZE;
+    (IVec2::new(x, y), size)
+}
+
+/// How far above the backpack the equipment row starts, leaving room for labels
+const EQUIPMENT_OFFSET: i32 = SLOT_SIZE + SLOT_SIZE / 2;
+
+fn backpack_size() -> (i32, i32) {
+    let rows = (INVENTORY_SIZE - HOTBAR_SIZE) as i32 / BACKPACK_COLUMNS;
+    (BACKPACK_COLUMNS * SLOT_SIZE, rows * SLOT_SIZE)
+}
+
+/// The area covered by the backpack and equipment, with a little bit of padding
+pub fn backpack_rect((window_width, window_height): (u32, u32)) -> (IVec2, IVec2) {
+    let (w, h) = backpack_size();
+    let pad = SLOT_SIZE / 4;
+    let pos = IVec2::new(
+        window_width as i32 / 2 - w / 2 - pad,
+        window_height as i32 / 2 - h / 2 - EQUIPMENT_OFFSET - pad * 2,
+    );
+    (pos, IVec2::new(w + pad * 2, h + EQUIPMENT_OFFSET + pad * 3))
+}
+
+fn within((pos, size): (IVec2, IVec2), p: IVec2) -> bool {
+    p.x >= pos.x && p.y >= pos.y && p.x < pos.x + size.x && p.y < pos.y + size.y
+}
+
+/// Which slot is at a particular position on screen
+pub fn slot_at(p: IVec2, window_size: (u32, u32)) -> Option<usize> {
+    (0..SLOT_COUNT).find(|i| within(slot_rect(*i, window_size), p))
+}
+
+/// The whole inventory, opened by pressing I or Tab. Stacks can be dragged
+/// around with the left mouse button, the right one only picks up half a stack.
+/// Letting go of a stack outside of the inventory drops it. Equipment is worn
+/// by dragging it into the matching slot of the row above the backpack.
+#[derive(Clone, Debug, Default)]
+pub struct InventoryScreen {
+    open: bool,
+    swallow_key: Option<VirtualKeyCode>,
+    cursor: IVec2,
+    drag: Option<(usize, u16)>,
+}
+
+impl InventoryScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[inline]
+    pub fn cursor(&self) -> IVec2 {
+        self.cursor
+    }
+
+    /// The slot and amount currently being dragged around
+    #[inline]
+    pub fn drag(&self) -> Option<(usize, u16)> {
+        self.drag
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.drag = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.drag = None;
+    }
+
+    fn start_drag(&mut self, game: &GameState, window_size: (u32, u32), half: bool) {
+        let Some(slot) = slot_at(self.cursor, window_size) else {
+            return;
+        };
+        let amount = game.player().slot(slot).map(|i| i.amount()).unwrap_or(0);
+        if amount == 0 {
+            return;
+        }
+        let amount = if half { amount.div_ceil(2) } else { amount };
+        self.drag = Some((slot, amount));
+    }
+
+    fn stop_drag(&mut self, reactor: &Reactor<Message>, window_size: (u32, u32)) {
+        let Some((from, amount)) = self.drag.take() else {
+            return;
+        };
+        if let Some(to) = slot_at(self.cursor, window_size) {
+            reactor.dispatch(Message::PlayerInventoryMove { from, to, amount });
+        } else if !within(backpack_rect(window_size), self.cursor) {
+            reactor.dispatch(Message::PlayerInventoryDrop { slot: from, amount });
+        }
+    }
+
+    fn is_toggle_key(code: VirtualKeyCode) -> bool {
+        matches!(code, VirtualKeyCode::I | VirtualKeyCode::Tab)
+    }
+
+    /// Handle a winit event, returns true if the event has been consumed by
+    /// the inventory screen and should not be passed on to the regular input handling.
+    pub fn handle_winit_event(
+        &mut self,
+        reactor: &Reactor<Message>,
+        game: &GameState,
+        window_size: (u32, u32),
+        event: &Event<()>,
+    ) -> bool {
+        if !self.open {
+            if swallow_pending_key(&mut self.swallow_key, event) {
+                return true;
+            }
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                if Self::is_toggle_key(*code) {
+                    self.open();
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        match event {
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                if *code == VirtualKeyCode::Escape || Self::is_toggle_key(*code) {
+                    self.swallow_key = Some(*code);
+                    self.close();
+                }
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                self.cursor = IVec2::new(position.x as i32, position.y as i32);
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { button, state, .. },
+                ..
+            } => {
+                match (button, state) {
+                    (MouseButton::Left, ElementState::Pressed) => {
+                        self.start_drag(game, window_size, false)
+                    }
+                    (MouseButton::Right, ElementState::Pressed) => {
+                        self.start_drag(game, window_size, true)
+                    }
+                    (MouseButton::Left | MouseButton::Right, ElementState::Released) => {
+                        self.stop_drag(reactor, window_size)
+                    }
+                    _ => (),
+                }
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { .. } | WindowEvent::MouseWheel { .. },
+                ..
+            }
+            | Event::DeviceEvent {
+                event: DeviceEvent::Key(_) | DeviceEvent::MouseMotion { .. },
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
@@ -1,12 +1,12 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
 pub use self::frustum::Frustum;
-pub use self::meshes::{BlockMesh, Mesh, MeshVertex, VoxelMesh};
+pub use self::meshes::{voxel_data, BlockMesh, Mesh, MeshVertex, VoxelMesh};
 pub use self::queue::QueueEntry;
-pub use self::render::{prepare_frame, render_frame, FADE_DISTANCE, RENDER_DISTANCE};
+pub use self::render::{draw_item, prepare_frame, render_frame, FADE_DISTANCE, RENDER_DISTANCE};
 pub use self::render_reactor::{RenderInit, RenderInitArgs, RenderPassArgs, RenderReactor};
 pub use self::state::{ClientState, ShaderList};
-pub use self::texture::{Texture, TextureArray};
+pub use self::texture::{Texture, TextureArray, ITEM_ICON_ROW, STATUS_ICON_ROW};
 pub use self::winit::start_client;
 
 mod frustum;
@@ -18,5 +18,9 @@ mod state;
 mod texture;
 mod winit;
 
+pub mod console;
+pub mod crafting;
 pub mod input;
+pub mod inventory_screen;
+pub mod perk_menu;
 pub mod ui;
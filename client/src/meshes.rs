@@ -2,10 +2,12 @@
 // All rights reserved. AGPL-3.0+ license.
 pub mod block;
 pub mod mesh;
+pub mod sprite;
 pub mod text;
 pub mod voxel;
 
 pub use self::block::*;
 pub use self::mesh::*;
+pub use self::sprite::*;
 pub use self::text::*;
 pub use self::voxel::*;
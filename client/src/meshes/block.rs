@@ -16,8 +16,8 @@ pub struct BlockMesh {
     buffer: glium::VertexBuffer<BlockVertex>,
     first_created: Instant,
     last_updated: Instant,
-    pub side_square_count: [usize; 6],
-    pub side_start: [usize; 6],
+    pub side_square_count: [usize; 7],
+    pub side_start: [usize; 7],
 }
 
 impl BlockMesh {
@@ -64,15 +64,15 @@ impl BlockMesh {
     }
 
     pub fn index_count(&self) -> u32 {
-        ((self.side_start[5] + self.side_square_count[5]) * 6) as u32
+        ((self.side_start[6] + self.side_square_count[6]) * 6) as u32
     }
 
     pub fn new(display: &glium::Display) -> Result<BlockMesh> {
         let buffer: glium::VertexBuffer<BlockVertex> = glium::VertexBuffer::empty(display, 0)?;
         Ok(Self {
             buffer,
-            side_square_count: [0; 6],
-            side_start: [0; 6],
+            side_square_count: [0; 7],
+            side_start: [0; 7],
             first_created: Instant::now(),
             last_updated: Instant::now(),
         })
@@ -92,7 +92,7 @@ impl BlockMesh {
             wolkenwelten_meshgen::generate(chunks, lights, block_types);
         self.side_square_count = side_start_count;
         self.side_start[0] = 0;
-        for i in 1..6 {
+        for i in 1..7 {
             self.side_start[i] = self.side_start[i - 1] + self.side_square_count[i - 1];
         }
         self.buffer = glium::VertexBuffer::dynamic(display, &vertices)?;
@@ -113,7 +113,7 @@ impl BlockMesh {
             wolkenwelten_meshgen::generate_simple(chunk, light, block_types);
         self.side_square_count = side_start_count;
         self.side_start[0] = 0;
-        for i in 1..6 {
+        for i in 1..7 {
             self.side_start[i] = self.side_start[i - 1] + self.side_square_count[i - 1];
         }
         self.buffer = glium::VertexBuffer::dynamic(display, &vertices)?;
@@ -135,7 +135,7 @@ impl BlockMesh {
             wolkenwelten_meshgen::generate_fluid(chunks, lights, fluids, fluid_types);
         self.side_square_count = side_start_count;
         self.side_start[0] = 0;
-        for i in 1..6 {
+        for i in 1..7 {
             self.side_start[i] = self.side_start[i - 1] + self.side_square_count[i - 1];
         }
         self.buffer = glium::VertexBuffer::dynamic(display, &vertices)?;
@@ -185,7 +185,7 @@ impl BlockMesh {
         };
 
         if mask == 0b111111 {
-            let index_count = (self.side_start[5] + self.side_square_count[5]) * 6;
+            let index_count = self.index_count() as usize;
             if let Some(indeces) = fe.block_indeces().slice(..index_count) {
                 frame.draw(
                     self.buffer(),
@@ -196,7 +196,8 @@ impl BlockMesh {
                 )?;
             }
         } else {
-            for i in (0..6).filter(|i| (mask & (1 << i)) != 0) {
+            // The last side contains faces that can be seen from every direction
+            for i in (0..7).filter(|i| *i == 6 || (mask & (1 << i)) != 0) {
                 let start_offset = self.side_start[i] * 6;
                 let index_count = start_offset + (self.side_square_count[i] * 6);
                 if index_count == 0 {
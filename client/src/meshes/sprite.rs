@@ -0,0 +1,342 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use rgb::RGBA8;
+use wolkenwelten_core::StatusEffectKind;
+
+/// Some items are drawn as 16x16 sprites, with every character referring to an
+/// entry in the palette, and a dot meaning the pixel is empty.
+pub type ItemSprite = [&'static str; 16];
+
+const PICKAXE: ItemSprite = [
+    "................",
+    "....hhhhhhhh....",
+    "..hhHHHHHHHHhh..",
+    ".hhH...ss...Hhh.",
+    ".hh....ss....hh.",
+    ".h.....ss.....h.",
+    ".......ss.......",
+    ".......ss.......",
+    ".......SS.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......SS.......",
+    ".......ss.......",
+    ".......ss.......",
+    "................",
+];
+
+const AXE: ItemSprite = [
+    "................",
+    ".......ss.hhh...",
+    ".......sshhHhh..",
+    ".......sshHHHh..",
+    ".......sshHHHh..",
+    ".......sshhHhh..",
+    ".......ss.hhh...",
+    ".......ss.......",
+    ".......SS.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......SS.......",
+    ".......ss.......",
+    ".......ss.......",
+    "................",
+];
+
+const SHOVEL: ItemSprite = [
+    "................",
+    "......hhhh......",
+    ".....hHHHHh.....",
+    ".....hHHHHh.....",
+    ".....hHHHHh.....",
+    "......hhhh......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......SS.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......ss.......",
+    ".......SS.......",
+    "......ssss......",
+    "......s..s......",
+    "................",
+];
+
+const HELMET: ItemSprite = [
+    "................",
+    "................",
+    "................",
+    ".....hhhhhh.....",
+    "....hHHHHHHh....",
+    "...hHHhhhhHHh...",
+    "...hHh....hHh...",
+    "...hHh....hHh...",
+    "...hh......hh...",
+    "...hh......hh...",
+    "...h........h...",
+    "................",
+    "................",
+    "................",
+    "................",
+    "................",
+];
+
+const CHESTPLATE: ItemSprite = [
+    "................",
+    "................",
+    "..hhh......hhh..",
+    ".hHHhh....hhHHh.",
+    ".hHHHhhhhhhHHHh.",
+    ".hhHHHHHHHHHHhh.",
+    "...hHHHHHHHHh...",
+    "...hHHHhhHHHh...",
+    "...hHHhHHhHHh...",
+    "...hHHHhhHHHh...",
+    "...hHHHHHHHHh...",
+    "...hhHHHHHHhh...",
+    "....hhhhhhhh....",
+    "................",
+    "................",
+    "................",
+];
+
+const BOOTS: ItemSprite = [
+    "................",
+    "................",
+    "................",
+    "................",
+    "...hhh....hhh...",
+    "...hHh....hHh...",
+    "...hHh....hHh...",
+    "...hHh....hHh...",
+    "...hHh....hHh...",
+    "..hhHh...hhHh...",
+    ".hHHHh..hHHHh...",
+    ".hHHHh..hHHHh...",
+    ".hhhhh..hhhhh...",
+    "................",
+    "................",
+    "................",
+];
+
+const AMULET: ItemSprite = [
+    "................",
+    "...ss......ss...",
+    "...s........s...",
+    "...s........s...",
+    "...s........s...",
+    "....s......s....",
+    "....s......s....",
+    ".....s....s.....",
+    "......s..s......",
+    "......hhhh......",
+    ".....hHHHHh.....",
+    ".....hHHHHh.....",
+    ".....hHHHHh.....",
+    "......hhhh......",
+    "................",
+    "................",
+];
+
+const RING: ItemSprite = [
+    "................",
+    "................",
+    "................",
+    "......hHHh......",
+    ".......hh.......",
+    ".....hhhhhh.....",
+    "....hH....Hh....",
+    "...hH......Hh...",
+    "...h........h...",
+    "...h........h...",
+    "...hh......hh...",
+    "....hh....hh....",
+    ".....hhhhhh.....",
+    "................",
+    "................",
+    "................",
+];
+
+const POISON: ItemSprite = [
+    "................",
+    ".......hh.......",
+    ".......hh.......",
+    "......hHHh......",
+    "......hHHh......",
+    ".....hHHHHh.....",
+    ".....hHHHHh.....",
+    "....hHHHHHHh....",
+    "...hHHHHHHHHh...",
+    "...hHHHHHHHHh...",
+    "...hHHHHHHHhh...",
+    "...hhHHHHHHhh...",
+    "....hhHHHHhh....",
+    ".....hhhhhh.....",
+    "................",
+    "................",
+];
+
+const REGENERATION: ItemSprite = [
+    "................",
+    "................",
+    "................",
+    "...hhh....hhh...",
+    "..hHHHh..hHHHh..",
+    ".hHHHHHhhHHHHHh.",
+    ".hHHHHHHHHHHHHh.",
+    ".hHHHHHHHHHHHHh.",
+    ".hhHHHHHHHHHHhh.",
+    "..hhHHHHHHHHhh..",
+    "...hhHHHHHHhh...",
+    "....hhHHHHhh....",
+    ".....hhHHhh.....",
+    "......hhhh......",
+    ".......hh.......",
+    "................",
+];
+
+const HASTE: ItemSprite = [
+    "................",
+    ".........hhhh...",
+    "........hHHh....",
+    ".......hHHh.....",
+    "......hHHh......",
+    ".....hHHh.......",
+    "....hHHHhhhhh...",
+    "...hHHHHHHHHh...",
+    "...hhhhhHHHh....",
+    ".......hHHh.....",
+    "......hHHh......",
+    ".....hHHh.......",
+    "....hHHh........",
+    "...hhhh.........",
+    "................",
+    "................",
+];
+
+const BURNING: ItemSprite = [
+    "................",
+    ".......h........",
+    "......hh........",
+    "......hHh...h...",
+    ".....hHHh..hh...",
+    "..h..hHHHh.hHh..",
+    "..hh.hHHHhhHHh..",
+    ".hHhhHHHHHHHHh..",
+    ".hHHHHHhHHHHHHh.",
+    ".hHHHHh.hHHHHHh.",
+    ".hHHHh...hHHHHh.",
+    ".hHHHh...hHHHHh.",
+    "..hHHHh.hHHHHh..",
+    "...hhHHHHHHhh...",
+    ".....hhhhhh.....",
+    "................",
+];
+
+const SAPLING: ItemSprite = [
+    "................",
+    "................",
+    "......hhh.......",
+    ".....hHHhh......",
+    "....hHHhh.hhh...",
+    "....hhhh.hHHhh..",
+    ".....hh..hhHhh..",
+    "......s...hhh...",
+    ".hhh..s..s......",
+    "hHHhh.ss.s......",
+    ".hhhh..sss......",
+    "...hss..s.......",
+    ".......sS.......",
+    ".......sS.......",
+    "......sSSs......",
+    "................",
+];
+
+/// Characters used within the sprites, in palette order
+const PALETTE_KEYS: [char; 4] = ['s', 'S', 'h', 'H'];
+
+/// Look up a built-in sprite by the name used in assets/items.json
+pub fn item_sprite(name: &str) -> Option<&'static ItemSprite> {
+    match name {
+        "pickaxe" => Some(&PICKAXE),
+        "axe" => Some(&AXE),
+        "shovel" => Some(&SHOVEL),
+        "helmet" => Some(&HELMET),
+        "chestplate" => Some(&CHESTPLATE),
+        "boots" => Some(&BOOTS),
+        "amulet" => Some(&AMULET),
+        "ring" => Some(&RING),
+        "sapling" => Some(&SAPLING),
+        _ => None,
+    }
+}
+
+/// The sprite and colors used for the HUD icon of a status effect
+pub fn status_effect_sprite(kind: StatusEffectKind) -> (&'static ItemSprite, [RGBA8; 2]) {
+    match kind {
+        StatusEffectKind::Poison => (
+            &POISON,
+            [
+                RGBA8::new(0x2A, 0x7A, 0x1C, 0xFF),
+                RGBA8::new(0x7C, 0xD8, 0x4A, 0xFF),
+            ],
+        ),
+        StatusEffectKind::Regeneration => (
+            &REGENERATION,
+            [
+                RGBA8::new(0xA0, 0x18, 0x30, 0xFF),
+                RGBA8::new(0xF0, 0x60, 0x80, 0xFF),
+            ],
+        ),
+        StatusEffectKind::Haste => (
+            &HASTE,
+            [
+                RGBA8::new(0xC8, 0xA0, 0x00, 0xFF),
+                RGBA8::new(0xF8, 0xE8, 0x60, 0xFF),
+            ],
+        ),
+        StatusEffectKind::Burning => (
+            &BURNING,
+            [
+                RGBA8::new(0xC0, 0x38, 0x00, 0xFF),
+                RGBA8::new(0xF8, 0xB0, 0x20, 0xFF),
+            ],
+        ),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/vox_files.rs"));
+
+/// Look up a built-in .vox model by its path within assets/, like "crab/idle_1.vox"
+pub fn voxel_data(name: &str) -> Option<&'static [u8]> {
+    VOX_FILES
+        .iter()
+        .find(|(model, _)| *model == name)
+        .map(|(_, data)| *data)
+}
+
+/// The handle is always wooden, the head gets tinted using the item colors
+pub fn sprite_palette(colors: [RGBA8; 2]) -> [[u8; 4]; 4] {
+    let [head, highlight] = colors;
+    [
+        [0x64, 0x44, 0x1C, 0xFF],
+        [0x46, 0x30, 0x0C, 0xFF],
+        [head.r, head.g, head.b, head.a],
+        [highlight.r, highlight.g, highlight.b, highlight.a],
+    ]
+}
+
+/// Iterate over all non-empty pixels of a sprite, returning x, y (starting
+/// at the top) and the palette index.
+pub fn sprite_pixels(sprite: &ItemSprite) -> impl Iterator<Item = (u32, u32, usize)> + '_ {
+    sprite.iter().enumerate().flat_map(|(y, row)| {
+        row.chars().enumerate().filter_map(move |(x, c)| {
+            PALETTE_KEYS
+                .iter()
+                .position(|k| *k == c)
+                .map(|i| (x as u32, y as u32, i))
+        })
+    })
+}
@@ -1,11 +1,13 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use crate::meshes::{sprite_pixels, ItemSprite};
 use crate::BlockMesh;
 use anyhow::{anyhow, Result};
+use glam::IVec3;
 use glium::texture::Texture2dArray;
 use glium::{uniform, Surface};
 use std::time::Instant;
-use wolkenwelten_core::{BlockType, ChunkBlockData, ChunkLightData};
+use wolkenwelten_core::{BlockId, BlockType, ChunkBlockData, ChunkLightData};
 use wolkenwelten_meshgen;
 use wolkenwelten_meshgen::BlockVertex;
 
@@ -28,7 +30,7 @@ impl VoxelMesh {
         let trans_pos: [f32; 3] = self.trans_pos();
         let mat_mvp = mat_mvp.to_cols_array_2d();
 
-        let index_count = (self.mesh.side_start[5] + self.mesh.side_square_count[5]) * 6;
+        let index_count = self.mesh.index_count() as usize;
         if let Some(indeces) = indeces.slice(..index_count) {
             frame.draw(
                 self.mesh.buffer(),
@@ -69,42 +71,49 @@ impl VoxelMesh {
         self.trans_pos
     }
 
-    fn texture_from_palette(
+    fn texture_from_colors(
         display: &glium::Display,
-        palette: &vox_format::types::Palette,
+        colors: impl Iterator<Item = [u8; 4]>,
     ) -> Result<Texture2dArray> {
-        let tiles = palette
-            .iter()
-            .map(|(_i, c)| {
-                let buf = [c.r, c.g, c.b, c.a];
-                glium::texture::RawImage2d::from_raw_rgba_reversed(&buf[0..], (1, 1))
-            })
+        let tiles = colors
+            .map(|c| glium::texture::RawImage2d::from_raw_rgba_reversed(&c[0..], (1, 1)))
             .collect();
         let ret = Texture2dArray::new(display, tiles)?;
         Ok(ret)
     }
 
+    fn texture_from_palette(
+        display: &glium::Display,
+        palette: &vox_format::types::Palette,
+    ) -> Result<Texture2dArray> {
+        Self::texture_from_colors(display, palette.iter().map(|(_i, c)| [c.r, c.g, c.b, c.a]))
+    }
+
     fn mesh_from_model(
         display: &glium::Display,
         model: &vox_format::types::Model,
     ) -> Result<BlockMesh> {
         let mut chunk = ChunkBlockData::new();
         model.voxels.iter().for_each(|vox| {
-            let b = vox.color_index.into();
+            let b: u8 = vox.color_index.into();
             let pos = [
                 (vox.point.x + 1).into(),
                 (vox.point.z + 1).into(),
                 (vox.point.y + 1).into(),
             ]
             .into();
-            chunk.set_block(b, pos);
+            chunk.set_block(b.into(), pos);
         });
-        let light = ChunkLightData::new_simple(&chunk);
+        Self::mesh_from_chunk(display, &chunk)
+    }
+
+    fn mesh_from_chunk(display: &glium::Display, chunk: &ChunkBlockData) -> Result<BlockMesh> {
+        let light = ChunkLightData::new_simple(chunk);
 
         let mut ret = BlockMesh::new(display)?;
         ret.update_simple(
             display,
-            &chunk,
+            chunk,
             &light,
             &BlockType::get_vox_types(),
             Instant::now(),
@@ -131,4 +140,28 @@ impl VoxelMesh {
         }
         Err(anyhow!("Couldn't create mesh from .vox"))
     }
+
+    /// Extrude a 16x16 sprite into a mesh that is 2 voxels thick
+    pub fn from_sprite(
+        display: &glium::Display,
+        sprite: &ItemSprite,
+        palette: &[[u8; 4]],
+    ) -> Result<Self> {
+        let mut chunk = ChunkBlockData::new();
+        for (x, y, i) in sprite_pixels(sprite) {
+            for z in 1..=2 {
+                let pos = IVec3::new(x as i32 + 1, 16 - y as i32, z);
+                chunk.set_block(i as BlockId + 1, pos);
+            }
+        }
+        let mesh = Self::mesh_from_chunk(display, &chunk)?;
+        // The first entry is never used, since block 0 is empty
+        let colors = std::iter::once([0; 4]).chain(palette.iter().copied());
+        let texture = Self::texture_from_colors(display, colors)?;
+        Ok(Self {
+            mesh,
+            texture,
+            trans_pos: [-8.0, -8.0, -1.0],
+        })
+    }
 }
@@ -0,0 +1,158 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::console::swallow_pending_key;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+};
+use wolkenwelten_core::{GameState, Message, Reactor};
+
+/// Lets players pick one of the perks offered on level up, opened by
+/// pressing P while a choice is pending.
+#[derive(Clone, Debug, Default)]
+pub struct PerkMenu {
+    open: bool,
+    swallow_key: Option<VirtualKeyCode>,
+    selection: usize,
+}
+
+impl PerkMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[inline]
+    pub fn selection(&self) -> usize {
+        self.selection
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.selection = 0;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    fn choose(&mut self, reactor: &Reactor<Message>, game: &GameState, choice: usize) {
+        reactor.dispatch(Message::PlayerChoosePerk { choice });
+        self.selection = 0;
+        if game.player().perk_choice().is_none() {
+            self.close();
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        reactor: &Reactor<Message>,
+        game: &GameState,
+        code: VirtualKeyCode,
+        choices: usize,
+    ) {
+        match code {
+            VirtualKeyCode::Escape | VirtualKeyCode::P => {
+                self.swallow_key = Some(code);
+                self.close();
+            }
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Space => {
+                self.choose(reactor, game, self.selection)
+            }
+            VirtualKeyCode::Up | VirtualKeyCode::W => {
+                self.selection = (self.selection + choices - 1) % choices
+            }
+            VirtualKeyCode::Down | VirtualKeyCode::S => {
+                self.selection = (self.selection + 1) % choices
+            }
+            VirtualKeyCode::Key1 => self.choose(reactor, game, 0),
+            VirtualKeyCode::Key2 => self.choose(reactor, game, 1),
+            VirtualKeyCode::Key3 => self.choose(reactor, game, 2),
+            VirtualKeyCode::Key4 => self.choose(reactor, game, 3),
+            VirtualKeyCode::Key5 => self.choose(reactor, game, 4),
+            _ => (),
+        }
+    }
+
+    /// Handle a winit event, returns true if the event has been consumed by
+    /// the menu and should not be passed on to the regular input handling.
+    pub fn handle_winit_event(
+        &mut self,
+        reactor: &Reactor<Message>,
+        game: &GameState,
+        event: &Event<()>,
+    ) -> bool {
+        let choices = game.player().perk_choice().map(|c| c.len()).unwrap_or(0);
+        if self.open && choices == 0 {
+            self.close();
+        }
+        if !self.open {
+            if swallow_pending_key(&mut self.swallow_key, event) {
+                return true;
+            }
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                if choices > 0 {
+                    self.open();
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        match event {
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                self.handle_key(reactor, game, *code, choices);
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        button: MouseButton::Left,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                self.choose(reactor, game, self.selection);
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { .. } | WindowEvent::MouseInput { .. },
+                ..
+            }
+            | Event::DeviceEvent {
+                event: DeviceEvent::Key(_),
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+}
@@ -8,6 +8,9 @@ use wolkenwelten_core::{Character, ChunkRequestQueue, GameState, CHUNK_SIZE};
 
 pub mod chungus;
 mod held_item;
+mod item;
+
+pub use self::item::draw_item;
 
 // Should be in CHUNK_SIZE multiples, since otherwise we render a lot of transparent fragments
 pub const RENDER_DISTANCE: f32 = if cfg!(target_arch = "arm") || cfg!(target_arch = "aarch64") {
@@ -1,6 +1,6 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::ClientState;
+use crate::{draw_item, ClientState};
 use anyhow::Result;
 use glam::{Mat4, Vec3};
 use wolkenwelten_core::{CharacterAnimation, GameState, Item};
@@ -34,15 +34,10 @@ pub fn draw(
     let item = game.player().item();
 
     match item {
-        Item::Block(bi) => fe.meshes.blocks[bi.block as usize].draw(
-            frame,
-            &fe.textures.blocks_raw,
-            &fe.shaders.mesh,
-            &mvp,
-        ),
         Item::None => fe
             .meshes
             .fist
             .draw(frame, fe.block_indeces(), &fe.shaders.voxel, &mvp, 1.0),
+        item => draw_item(frame, fe, item, &mvp),
     }
 }
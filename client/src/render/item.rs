@@ -0,0 +1,39 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::ClientState;
+use anyhow::Result;
+use glam::Mat4;
+use wolkenwelten_core::{BlockId, Item, ItemIcon, ITEMS};
+
+fn draw_block(
+    frame: &mut glium::Frame,
+    fe: &ClientState,
+    block: BlockId,
+    mvp: &Mat4,
+) -> Result<()> {
+    fe.meshes.blocks[block as usize].draw(frame, &fe.textures.blocks_raw, &fe.shaders.mesh, mvp)
+}
+
+/// Draw an item the way it looks in the world, used for held items as well as drops
+pub fn draw_item(frame: &mut glium::Frame, fe: &ClientState, item: Item, mvp: &Mat4) -> Result<()> {
+    match item {
+        Item::None => Ok(()),
+        Item::Block(bi) => draw_block(frame, fe, bi.block, mvp),
+        Item::Stack(s) => {
+            let icon = ITEMS.with(|items| {
+                items
+                    .borrow()
+                    .get(s.item as usize)
+                    .map(|it| it.icon().clone())
+                    .unwrap_or_default()
+            });
+            if let ItemIcon::Block(block) = icon {
+                draw_block(frame, fe, block, mvp)
+            } else {
+                fe.meshes
+                    .item(s.item)
+                    .draw(frame, fe.block_indeces(), &fe.shaders.voxel, mvp, 1.0)
+            }
+        }
+    }
+}
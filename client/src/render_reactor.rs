@@ -4,7 +4,7 @@ use super::render::chungus::{chungus_block_pass, chungus_fluid_pass};
 use crate::ClientState;
 use glam::Mat4;
 use glium::Surface;
-use wolkenwelten_core::{ChunkRequestQueue, Entity, GameState, Message, Reactor, CHUNK_SIZE};
+use wolkenwelten_core::{ChunkRequestQueue, GameState, Message, Reactor, CHUNK_SIZE};
 
 pub struct RenderPassArgs<'a> {
     pub frame: &'a mut glium::Frame,
@@ -26,7 +26,6 @@ pub struct RenderInitArgs<'a> {
 
 pub type RenderPass = Box<dyn Fn(RenderPassArgs) -> RenderPassArgs>;
 pub type RenderInit = Box<dyn Fn(RenderInitArgs) -> RenderInitArgs>;
-pub type EntityProvider = Box<dyn Fn(&mut Vec<Entity>)>;
 pub struct RenderReactor {
     pub pre_world_render: Vec<RenderPass>,
     pub world_render: Vec<RenderPass>,
@@ -35,8 +34,6 @@ pub struct RenderReactor {
 
     pub hud_3d_render: Vec<RenderPass>,
     pub hud_2d_render: Vec<RenderPass>,
-
-    pub entity_provider: Vec<EntityProvider>,
 }
 
 fn clear_pass(mut a: RenderPassArgs) -> RenderPassArgs {
@@ -75,8 +72,6 @@ impl Default for RenderReactor {
 
             hud_3d_render: vec![],
             hud_2d_render: vec![],
-
-            entity_provider: vec![],
         }
     }
 }
@@ -86,14 +81,6 @@ impl RenderReactor {
         Default::default()
     }
 
-    pub fn entity_pos(&self) -> Vec<Entity> {
-        let mut r = vec![];
-        for p in self.entity_provider.iter() {
-            p(&mut r);
-        }
-        r
-    }
-
     pub fn init(
         &mut self,
         reactor: &mut Reactor<Message>,
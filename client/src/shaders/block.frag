@@ -16,7 +16,14 @@ void main() {
  | they have been generated so their sudden appearance is less jarring.
  */
 	float fade_start = fade_distance - 8.0;
+	vec4 tex_color = texture(cur_tex, tex_coord);
+	/* Glass, ladders and plants have fully transparent parts which we
+	 | just cut out, this way we don't have to sort individual faces.
+	 */
+	if (tex_color.a < 0.5) {
+		discard;
+	}
 	vec3 light_color = vec3(light_value, light_value, light_value);
-	vec4 color = vec4(texture(cur_tex, tex_coord).rgb * light_color, color_alpha);
+	vec4 color = vec4(tex_color.rgb * light_color, color_alpha);
 	frag_color = color * (1.0 - smoothstep(fade_start, fade_distance, length(view_position)));
 }
@@ -22,11 +22,20 @@ void main(){
      */
 	uint side = side_and_light & 0x7u;
 
-    /* We divide the uv coordinates by 2 since we have 4 variations per
+    /* The 4th bit tells us to rotate the texture by 90 degrees, which is
+     | used for rotated blocks like logs lying on their side.
+     */
+	uvec2 uv = taxis[side >> 1];
+	if ((side_and_light & 0x8u) != 0u) {
+		uv = uv.yx;
+	}
+
+    /* We divide the uv coordinates by 4, 2 because positions are stored in
+     | half-voxel units, and another 2 since we have 4 variations per
      | texture right now. If we ever add more we have to increase this value.
      | Here we also mirror the textures, so that they are the right side up.
      */
-	tex_coord = vec3(uvec3(taxis[side >> 1], texture_index)) * vec3(0.5, -0.5, 1.0);
+	tex_coord = vec3(uvec3(uv, texture_index)) * vec3(0.25, -0.25, 1.0);
 
     /* Finally we extract the 4-bit lightness value, turn it into a float
      | in the range of 0.0-1.0 and then multiply it by itself to make theater
@@ -38,8 +47,9 @@ void main(){
     /* To determine the position we multiply by our MVP matrix after adding
      | our transPos uniform value, this is done so that our position within
      | a chunk can fit in 5-bits, without this step we would need 16-bit
-     | values, per axis...
+     | values, per axis... Positions are in half-voxel units so that we
+     | can have slabs and stairs, so we have to scale them down here.
      */
-    vec4 world_position = (vec4(pos, 1.0) + vec4(trans_pos,0.0));
+    vec4 world_position = (vec4(vec3(pos) * 0.5, 1.0) + vec4(trans_pos,0.0));
 	gl_Position = mat_mvp * world_position;
 }
@@ -1,6 +1,10 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use crate::console::Console;
+use crate::crafting::CraftingMenu;
+use crate::inventory_screen::InventoryScreen;
 use crate::meshes::{BlockMesh, TextMesh};
+use crate::perk_menu::PerkMenu;
 use crate::RENDER_DISTANCE;
 use anyhow::Result;
 use glam::{f32::Vec3, i32::IVec3};
@@ -33,6 +37,10 @@ pub struct ClientState {
 
     pub ui_mesh: TextMesh,
     show_debug_info: bool,
+    console: Console,
+    crafting: CraftingMenu,
+    inventory_screen: InventoryScreen,
+    perk_menu: PerkMenu,
 
     ticks: u64,
     cur_fov: f32,
@@ -68,6 +76,10 @@ impl ClientState {
             ui_mesh,
 
             show_debug_info: false,
+            console: Console::new(),
+            crafting: CraftingMenu::new(),
+            inventory_screen: InventoryScreen::new(),
+            perk_menu: PerkMenu::new(),
             cur_fov: 90.0,
             cur_fps: 0,
             frame_count: 0,
@@ -107,6 +119,46 @@ impl ClientState {
         self.show_debug_info = s;
     }
 
+    #[inline]
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+
+    #[inline]
+    pub fn console_mut(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
+    #[inline]
+    pub fn crafting(&self) -> &CraftingMenu {
+        &self.crafting
+    }
+
+    #[inline]
+    pub fn crafting_mut(&mut self) -> &mut CraftingMenu {
+        &mut self.crafting
+    }
+
+    #[inline]
+    pub fn inventory_screen(&self) -> &InventoryScreen {
+        &self.inventory_screen
+    }
+
+    #[inline]
+    pub fn inventory_screen_mut(&mut self) -> &mut InventoryScreen {
+        &mut self.inventory_screen
+    }
+
+    #[inline]
+    pub fn perk_menu(&self) -> &PerkMenu {
+        &self.perk_menu
+    }
+
+    #[inline]
+    pub fn perk_menu_mut(&mut self) -> &mut PerkMenu {
+        &mut self.perk_menu
+    }
+
     pub fn request_redraw(&mut self) {
         self.display.gl_window().window().request_redraw();
     }
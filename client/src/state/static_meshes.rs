@@ -1,20 +1,26 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use crate::meshes::{item_sprite, sprite_palette, voxel_data};
 use crate::{Mesh, MeshVertex, VoxelMesh};
 use anyhow::Result;
-use wolkenwelten_core::BLOCKS;
+use wolkenwelten_core::{ItemIcon, ItemId, BLOCKS, ITEMS};
 
 /// This struct is meant to be a simple way to store
 /// all static meshes included with WW.
 #[derive(Debug)]
 pub struct MeshList {
     pub fist: VoxelMesh,
+    pub bag: VoxelMesh,
     pub blocks: Vec<Mesh>,
+    pub items: Vec<Option<VoxelMesh>>,
 }
 
 impl MeshList {
     fn gen_block_meshes(display: &glium::Display) -> Result<Vec<Mesh>> {
-        let tile_size = 64.0 / 2048.0;
+        // Tiles are stacked vertically, so their height in texture coordinates
+        // depends on how many there are.
+        let img = image::load_from_memory(include_bytes!("../../assets/blocks.png"))?;
+        let tile_size = img.width() as f32 / img.height() as f32;
         BLOCKS.with(|blocks| {
             Ok(blocks
                 .borrow()
@@ -28,11 +34,41 @@ impl MeshList {
         })
     }
 
+    /// Items that look like blocks, or whose model is unknown, get None
+    fn gen_item_meshes(display: &glium::Display) -> Result<Vec<Option<VoxelMesh>>> {
+        ITEMS.with(|items| {
+            items
+                .borrow()
+                .iter()
+                .map(|it| match it.icon() {
+                    ItemIcon::Sprite(name) => item_sprite(name)
+                        .map(|s| VoxelMesh::from_sprite(display, s, &sprite_palette(it.colors())))
+                        .transpose(),
+                    ItemIcon::Voxel(name) => voxel_data(name)
+                        .map(|data| VoxelMesh::from_vox_data(display, data))
+                        .transpose(),
+                    _ => Ok(None),
+                })
+                .collect()
+        })
+    }
+
+    /// The mesh for an item, falling back to a bag for items without one
+    #[inline]
+    pub fn item(&self, item: ItemId) -> &VoxelMesh {
+        self.items
+            .get(item as usize)
+            .and_then(|m| m.as_ref())
+            .unwrap_or(&self.bag)
+    }
+
     /// Load all the the models from the build-in raw .obj/.vox bytes.
     pub fn new(display: &glium::Display) -> Result<Self> {
         Ok(Self {
             blocks: Self::gen_block_meshes(display)?,
+            items: Self::gen_item_meshes(display)?,
             fist: VoxelMesh::from_vox_data(display, include_bytes!("../../assets/fist.vox"))?,
+            bag: VoxelMesh::from_vox_data(display, include_bytes!("../../assets/bag.vox"))?,
         })
     }
 }
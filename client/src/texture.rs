@@ -6,7 +6,19 @@ use glium::{
     uniforms::Sampler,
 };
 use image::{DynamicImage, Rgba};
-use wolkenwelten_core::BLOCKS;
+use wolkenwelten_core::{ItemIcon, BLOCKS, ITEMS, STATUS_EFFECT_KINDS};
+
+use crate::meshes::{
+    item_sprite, sprite_palette, sprite_pixels, status_effect_sprite, voxel_data, ItemSprite,
+};
+
+/// Row within the GUI texture the item icons start at, the rows above are
+/// used for block icons.
+pub const ITEM_ICON_ROW: u32 = 4;
+
+/// Row within the GUI texture containing the status effect icons, in the
+/// order of STATUS_EFFECT_KINDS.
+pub const STATUS_ICON_ROW: u32 = 7;
 
 #[derive(Debug)]
 pub struct Texture {
@@ -98,6 +110,85 @@ impl Texture {
         })
     }
 
+    fn sprite_icon(sprite: &ItemSprite, palette: &[[u8; 4]], tile_size: u32) -> DynamicImage {
+        let scale = (tile_size / 16).max(1);
+        let mut img = image::RgbaImage::new(tile_size, tile_size);
+        for (x, y, i) in sprite_pixels(sprite) {
+            for sx in 0..scale {
+                for sy in 0..scale {
+                    img.put_pixel(x * scale + sx, y * scale + sy, Rgba(palette[i]));
+                }
+            }
+        }
+        img.into()
+    }
+
+    /// Look at a .vox model from the front, only drawing the closest voxels
+    fn voxel_icon(data: &[u8], tile_size: u32) -> Result<DynamicImage> {
+        let vox_data = vox_format::from_slice(data)?;
+        let mut img = image::RgbaImage::new(tile_size, tile_size);
+        let Some(model) = vox_data.models.first() else {
+            return Ok(img.into());
+        };
+        let size = model.size.x.max(model.size.z).max(1);
+        let scale = (tile_size / size).max(1);
+        let off_x = (tile_size - (model.size.x * scale).min(tile_size)) / 2;
+        let off_y = (tile_size - (model.size.z * scale).min(tile_size)) / 2;
+        let mut depth = vec![i32::MAX; (size * size) as usize];
+        for vox in model.voxels.iter() {
+            let x = vox.point.x as u32;
+            let z = vox.point.z as u32;
+            let d = vox.point.y as i32;
+            if x >= size || z >= size || depth[(z * size + x) as usize] <= d {
+                continue;
+            }
+            depth[(z * size + x) as usize] = d;
+            let c = vox_data.palette.get(vox.color_index);
+            let y = size - 1 - z;
+            for sx in 0..scale {
+                for sy in 0..scale {
+                    let px = off_x + x * scale + sx;
+                    let py = off_y + y * scale + sy;
+                    if px < tile_size && py < tile_size {
+                        img.put_pixel(px, py, Rgba([c.r, c.g, c.b, c.a]));
+                    }
+                }
+            }
+        }
+        Ok(img.into())
+    }
+
+    /// Build an icon for every item in ITEMS, in the same order, items that
+    /// look like blocks reuse the block icon.
+    pub fn build_item_icons(
+        block_icons: &[DynamicImage],
+        tile_size: u32,
+    ) -> Result<Vec<DynamicImage>> {
+        ITEMS.with(|items| {
+            items
+                .borrow()
+                .iter()
+                .map(|it| match it.icon() {
+                    ItemIcon::Block(b) => Ok(block_icons
+                        .get(*b as usize)
+                        .cloned()
+                        .unwrap_or_else(|| image::RgbaImage::new(tile_size, tile_size).into())),
+                    ItemIcon::Sprite(name) => Ok(match item_sprite(name) {
+                        Some(sprite) => {
+                            Self::sprite_icon(sprite, &sprite_palette(it.colors()), tile_size)
+                        }
+                        None => image::RgbaImage::new(tile_size, tile_size).into(),
+                    }),
+                    ItemIcon::Voxel(name) => match voxel_data(name) {
+                        Some(data) => Self::voxel_icon(data, tile_size),
+                        None => Ok(image::RgbaImage::new(tile_size, tile_size).into()),
+                    },
+                    ItemIcon::None => Ok(image::RgbaImage::new(tile_size, tile_size).into()),
+                })
+                .collect()
+        })
+    }
+
     pub fn gui_texture(
         display: &glium::Display,
         gui_bytes: &'static [u8],
@@ -114,6 +205,21 @@ impl Texture {
             let y = (i as u32 / 32) * tile_size;
             image::imageops::overlay(&mut img, icon, x as i64, y as i64);
         }
+        for (i, icon) in Self::build_item_icons(&icons, tile_size)?
+            .iter()
+            .enumerate()
+        {
+            let x = (i as u32 % 32) * tile_size;
+            let y = (ITEM_ICON_ROW + i as u32 / 32) * tile_size;
+            image::imageops::overlay(&mut img, icon, x as i64, y as i64);
+        }
+        for (i, kind) in STATUS_EFFECT_KINDS.iter().enumerate() {
+            let (sprite, colors) = status_effect_sprite(*kind);
+            let icon = Self::sprite_icon(sprite, &sprite_palette(colors), tile_size);
+            let x = i as u32 * tile_size;
+            let y = STATUS_ICON_ROW * tile_size;
+            image::imageops::overlay(&mut img, &icon, x as i64, y as i64);
+        }
 
         let img = RawImage2d::from_raw_rgba(img.into_raw(), image_dimensions);
         let texture = Texture2d::new(display, img)?;
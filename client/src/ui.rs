@@ -1,10 +1,14 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::ClientState;
-use wolkenwelten_core::{ChunkRequestQueue, GameState};
+use crate::{ClientState, STATUS_ICON_ROW};
+use wolkenwelten_core::{ChunkRequestQueue, GameState, STATUS_EFFECT_KINDS};
 
+mod console;
+mod crafting;
 mod inventory;
 mod log;
+mod perks;
+mod run;
 
 fn prepare_healthbar(fe: &mut ClientState, game: &GameState, x: i16, y: i16, heart_beat: bool) {
     let health = game.player().health();
@@ -98,6 +102,74 @@ fn prepare_experience(fe: &mut ClientState, game: &GameState, x: i16, y: i16, si
     }
 }
 
+/// Armor and speed bonuses from equipment, shown below the hearts
+fn prepare_equipment_stats(fe: &mut ClientState, game: &GameState, x: i16, y: i16) {
+    let player = game.player();
+    let (armor, speed) = (player.armor(), player.speed_bonus());
+    let mut text = String::new();
+    if armor > 0 {
+        text.push_str(&format!("Armor {}  ", armor));
+    }
+    if speed != 0 {
+        text.push_str(&format!("Speed {:+}%", speed));
+    }
+    if !text.is_empty() {
+        fe.ui_mesh
+            .push_string(x, y, 2, [0xFF, 0xFF, 0xFF, 0xFF], text.trim_end());
+    }
+}
+
+/// Icons for every active status effect, below the FPS counter
+fn prepare_status_effects(fe: &mut ClientState, game: &GameState) {
+    let (window_width, _window_height) = fe.window_size();
+    let player = game.player();
+    let rgba = [0xFF, 0xFF, 0xFF, 0xFF];
+    for (i, effect) in player.effects().iter().enumerate() {
+        let Some(icon) = STATUS_EFFECT_KINDS.iter().position(|k| *k == effect.kind) else {
+            continue;
+        };
+        let x = window_width as i16 - 48 - i as i16 * 40;
+        let y = 32;
+        let tex = (icon as i16 * 4, STATUS_ICON_ROW as i16 * 4, 4, 4);
+        fe.ui_mesh.push_box((x, y, 32, 32), tex, rgba);
+        let text = if effect.strength > 1 {
+            format!("{}x{}s", effect.strength, effect.seconds_left())
+        } else {
+            format!("{}s", effect.seconds_left())
+        };
+        fe.ui_mesh.push_string(x, y + 34, 1, rgba, text.as_str());
+    }
+}
+
+/// Name and health of the boss the player is fighting, at the top of the screen
+fn prepare_boss_bar(fe: &mut ClientState, game: &GameState) {
+    let Some(bar) = game.boss_bar().clone() else {
+        return;
+    };
+    let (window_width, _window_height) = fe.window_size();
+    let width = 512;
+    let x = window_width as i16 / 2 - width / 2;
+    let y = 24;
+    let tex = (76, 124, 4, 4);
+
+    let name = if bar.phase > 0 {
+        format!("{} {}", bar.name, "!".repeat(bar.phase))
+    } else {
+        bar.name
+    };
+    let name_x = window_width as i16 / 2 - name.len() as i16 * 8;
+    fe.ui_mesh
+        .push_string(name_x, y, 2, [0xFF, 0xFF, 0xFF, 0xFF], name.as_str());
+
+    let y = y + 24;
+    fe.ui_mesh
+        .push_box((x - 2, y - 2, width + 4, 20), tex, [0x10, 0x10, 0x10, 0xCF]);
+    let percent = bar.health.max(0) as f32 / bar.max_health.max(1) as f32;
+    let filled = (width as f32 * percent) as i16;
+    fe.ui_mesh
+        .push_box((x, y, filled, 16), tex, [0xD0, 0x20, 0x30, 0xFF]);
+}
+
 fn prepare_fps(fe: &mut ClientState) {
     let (window_width, _window_height) = fe.window_size();
     let fps_text = format!("{}", fe.fps());
@@ -157,40 +229,20 @@ fn prepare_crosshair(fe: &mut ClientState) {
     fe.ui_mesh.push_box(pos, tex, [0xFF, 0xFF, 0xFF, 0x7F]);
 }
 
-fn prepare_death_overlay(fe: &mut ClientState, game: &GameState) {
-    if !game.player().is_dead() {
-        return;
-    }
-    let (window_width, window_height) = fe.window_size();
-
-    let x = window_width as i16 / 2 - 128;
-    let y = window_height as i16 / 2 - 16;
-    let rgba = [0xFF, 0xFF, 0xFF, 0xFF];
-    let text = "You died";
-    fe.ui_mesh.push_string(x, y, 4, rgba, text);
-    let x = x - 128;
-    let y = y + 48;
-    let text = format!(
-        "You reached level {} with a score of {}",
-        game.player().experience().level(),
-        game.score()
-    );
-    fe.ui_mesh.push_string(x, y, 2, rgba, &text);
-
-    let x = x + 48;
-    let y = y + 56;
-    fe.ui_mesh
-        .push_string(x, y, 2, rgba, "Press R to try once more");
-}
-
 pub fn prepare(fe: &mut ClientState, game: &GameState, request: &ChunkRequestQueue) {
     prepare_fps(fe);
+    prepare_status_effects(fe, game);
     prepare_crosshair(fe);
+    prepare_boss_bar(fe, game);
     prepare_healthbar(fe, game, 96, 16, true);
+    prepare_equipment_stats(fe, game, 100, 56);
     prepare_experience(fe, game, 16, 16, 64);
-    prepare_death_overlay(fe, game);
+    run::prepare(fe, game);
     prepare_debug_text(fe, game, request);
     log::prepare(fe);
+    console::prepare(fe);
     inventory::prepare(fe, game);
+    crafting::prepare(fe, game);
+    perks::prepare(fe, game);
     fe.ui_mesh.prepare(&fe.display);
 }
@@ -0,0 +1,25 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::ClientState;
+
+pub fn prepare(fe: &mut ClientState) {
+    if !fe.console().is_open() {
+        return;
+    }
+    let (window_width, window_height) = fe.window_size();
+
+    let p = (8, window_height as i16 - 40, window_width as i16 - 16, 32);
+    let tex = (76, 124, 4, 4);
+    let rgba = [0x00, 0x00, 0x00, 0x9F];
+    fe.ui_mesh.push_box(p, tex, rgba);
+
+    let cursor = if (fe.ticks() / 500) & 1 == 0 { "_" } else { "" };
+    let text = format!("> {}{}", fe.console().input(), cursor);
+    fe.ui_mesh.push_string(
+        16,
+        window_height as i16 - 32,
+        2,
+        [0xFF, 0xFF, 0xFF, 0xFF],
+        &text,
+    );
+}
@@ -0,0 +1,66 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::inventory::item_icon_tex;
+use crate::ClientState;
+use wolkenwelten_core::{BlockItem, GameState, Item, RecipeId, RECIPES};
+
+/// How many recipes are visible at once, the list scrolls along with the selection
+const VISIBLE_RECIPES: usize = 9;
+const ROW_HEIGHT: i16 = 40;
+
+pub fn prepare(fe: &mut ClientState, game: &GameState) {
+    if !fe.crafting().is_open() {
+        return;
+    }
+    let (window_width, window_height) = fe.window_size();
+    let craftable = game.player().craftable_recipes(&game.world());
+    let selection = fe.crafting().selection();
+
+    let w = 960;
+    let h = VISIBLE_RECIPES as i16 * ROW_HEIGHT + 48;
+    let x = window_width as i16 / 2 - w / 2;
+    let y = window_height as i16 / 2 - h / 2;
+    fe.ui_mesh
+        .push_box((x, y, w, h), (76, 124, 4, 4), [0x00, 0x00, 0x00, 0x9F]);
+    fe.ui_mesh
+        .push_string(x + 8, y + 8, 2, [0xFF, 0xFF, 0xFF, 0xFF], "Crafting");
+
+    RECIPES.with(|recipes| {
+        let recipes = recipes.borrow();
+        let first = selection
+            .saturating_sub(VISIBLE_RECIPES / 2)
+            .min(recipes.len().saturating_sub(VISIBLE_RECIPES));
+        for (i, recipe) in recipes.iter().enumerate().skip(first).take(VISIBLE_RECIPES) {
+            let row_y = y + 40 + (i - first) as i16 * ROW_HEIGHT;
+            if i == selection {
+                fe.ui_mesh.push_box(
+                    (x + 4, row_y, w - 8, ROW_HEIGHT),
+                    (76, 124, 4, 4),
+                    [0xFF, 0xFF, 0xFF, 0x3F],
+                );
+            }
+            let rgba = if craftable.contains(&(i as RecipeId)) {
+                [0xFF, 0xFF, 0xFF, 0xFF]
+            } else {
+                [0x8F, 0x8F, 0x8F, 0xFF]
+            };
+            let output = recipe.output();
+            if let Some(tex) = item_icon_tex(output) {
+                fe.ui_mesh.push_box((x + 8, row_y + 4, 32, 32), tex, rgba);
+            }
+            let mut text = format!("{}x {} <- ", output.amount(), output.name());
+            let ingredients: Vec<String> = recipe
+                .ingredients()
+                .iter()
+                .map(|i| format!("{}x {}", i.amount(), i.name()))
+                .collect();
+            text.push_str(&ingredients.join(", "));
+            if let Some(station) = recipe.station() {
+                let name = Item::from(BlockItem::new(station, 1)).name();
+                text.push_str(&format!(" @ {}", name));
+            }
+            fe.ui_mesh
+                .push_string(x + 48, row_y + 12, 2, rgba, text.as_str());
+        }
+    });
+}
@@ -1,8 +1,20 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use crate::inventory_screen::{backpack_rect, slot_rect, SLOT_COUNT, SLOT_SIZE};
 use crate::ClientState;
+use crate::ITEM_ICON_ROW;
 use glam::IVec2;
-use wolkenwelten_core::{GameState, Item};
+use wolkenwelten_core::{GameState, Item, EQUIPMENT_SLOTS, HOTBAR_SIZE, INVENTORY_SIZE, ITEMS};
+
+/// Texture coordinates of the icon for item within the GUI texture
+pub fn item_icon_tex(item: Item) -> Option<(i16, i16, i16, i16)> {
+    let (x, y) = match item {
+        Item::None => return None,
+        Item::Block(bi) => (bi.block as u32 % 32, bi.block as u32 / 32),
+        Item::Stack(s) => (s.item as u32 % 32, ITEM_ICON_ROW + s.item as u32 / 32),
+    };
+    Some(((x * 4) as i16, (y * 4) as i16, 4, 4))
+}
 
 fn prepare_slot(
     fe: &mut ClientState,
@@ -21,44 +33,117 @@ fn prepare_slot(
     let rgba = [0xFF, 0xFF, 0xFF, 0xFF];
     fe.ui_mesh.push_box(p, tex, rgba);
 
-    if let Item::Block(bi) = item {
-        let p = (
-            (pos.x + size.x / 16) as i16,
-            (pos.y + size.x / 16) as i16,
-            (size.x - size.x / 8) as i16,
-            (size.y - size.y / 8) as i16,
-        );
-        let tex = (
-            ((bi.block % 32) * 4) as i16,
-            ((bi.block / 32) * 4) as i16,
-            4,
-            4,
-        );
-        let rgba = [0xFF, 0xFF, 0xFF, 0xFF];
-        fe.ui_mesh.push_box(p, tex, rgba);
+    let p = (
+        (pos.x + size.x / 16) as i16,
+        (pos.y + size.x / 16) as i16,
+        (size.x - size.x / 8) as i16,
+        (size.y - size.y / 8) as i16,
+    );
+    let Some(tex) = item_icon_tex(item) else {
+        return;
+    };
+    let rgba = [0xFF, 0xFF, 0xFF, 0xFF];
+    fe.ui_mesh.push_box(p, tex, rgba);
 
-        let text = format!("{}x", bi.amount);
-        fe.ui_mesh.push_string(
-            (pos.x + 4) as i16,
-            (pos.y + size.y - 12) as i16,
-            1,
-            rgba,
-            text.as_str(),
-        );
-    }
+    // Stackable items show how many there are, the rest how worn out they are
+    let text = match item {
+        Item::Stack(s) if item.max_stack() <= 1 && s.durability > 0 => {
+            let max = ITEMS.with(|items| {
+                items
+                    .borrow()
+                    .get(s.item as usize)
+                    .map(|it| it.durability())
+                    .unwrap_or(1)
+            });
+            format!("{}%", s.durability as u32 * 100 / max.max(1) as u32)
+        }
+        _ if item.max_stack() <= 1 => return,
+        _ => format!("{}x", item.amount()),
+    };
+    fe.ui_mesh.push_string(
+        (pos.x + 4) as i16,
+        (pos.y + size.y - 12) as i16,
+        1,
+        rgba,
+        text.as_str(),
+    );
 }
 
 pub fn prepare(fe: &mut ClientState, game: &GameState) {
-    let (window_width, window_height) = fe.window_size();
+    let window_size = fe.window_size();
     let player = game.player();
+    let screen_open = fe.inventory_screen().is_open();
+    let drag = fe.inventory_screen().drag();
+
+    if screen_open {
+        let (pos, size) = backpack_rect(window_size);
+        let p = (pos.x as i16, pos.y as i16, size.x as i16, size.y as i16);
+        fe.ui_mesh
+            .push_box(p, (76, 124, 4, 4), [0x00, 0x00, 0x00, 0x9F]);
+    }
 
     let active_i = player.inventory_active();
-    let inv = player.inventory();
-    let x = window_width as i32 - inv.len() as i32 * 64;
-    let y = (window_height - 64) as i32;
-    let size = IVec2::new(64, 64);
-    for (i, item) in inv.iter().enumerate() {
-        let pos = IVec2::new(x + i as i32 * 64, y);
-        prepare_slot(fe, game, pos, size, i == active_i, *item);
+    let slots = if screen_open { SLOT_COUNT } else { HOTBAR_SIZE };
+    for i in 0..slots {
+        let Some(item) = player.slot(i) else {
+            continue;
+        };
+        // The part of a stack that is being dragged is drawn at the cursor instead
+        let item = match drag {
+            Some((from, amount)) if from == i => item.with_amount(item.amount() - amount),
+            _ => item,
+        };
+        let (pos, size) = slot_rect(i, window_size);
+        prepare_slot(fe, game, pos, size, i == active_i, item);
+    }
+
+    if screen_open {
+        let rgba = [0xCF, 0xCF, 0xCF, 0xFF];
+        for (i, slot) in EQUIPMENT_SLOTS.iter().enumerate() {
+            let (pos, _) = slot_rect(INVENTORY_SIZE + i, window_size);
+            fe.ui_mesh
+                .push_string(pos.x as i16, (pos.y - 12) as i16, 1, rgba, slot.name());
+        }
+        let (pos, _) = slot_rect(INVENTORY_SIZE + EQUIPMENT_SLOTS.len(), window_size);
+        let text = format!(
+            "Armor {}  Speed {:+}%  Max health {}",
+            player.armor(),
+            player.speed_bonus(),
+            player.health().max_health()
+        );
+        fe.ui_mesh.push_string(
+            (pos.x + 16) as i16,
+            (pos.y + SLOT_SIZE / 2 - 8) as i16,
+            2,
+            rgba,
+            text.as_str(),
+        );
+    }
+
+    if let Some((from, amount)) = drag {
+        if let Some(item) = player.slot(from) {
+            let tex = item_icon_tex(item);
+            let cursor = fe.inventory_screen().cursor();
+            let half = SLOT_SIZE / 2;
+            if let Some(tex) = tex {
+                let p = (
+                    (cursor.x - half) as i16,
+                    (cursor.y - half) as i16,
+                    SLOT_SIZE as i16,
+                    SLOT_SIZE as i16,
+                );
+                fe.ui_mesh.push_box(p, tex, [0xFF, 0xFF, 0xFF, 0xFF]);
+            }
+            if amount > 1 {
+                let text = format!("{}x", amount);
+                fe.ui_mesh.push_string(
+                    (cursor.x - half + 4) as i16,
+                    (cursor.y + half - 12) as i16,
+                    1,
+                    [0xFF, 0xFF, 0xFF, 0xFF],
+                    text.as_str(),
+                );
+            }
+        }
     }
 }
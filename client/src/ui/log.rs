@@ -7,12 +7,17 @@ pub fn prepare(fe: &mut ClientState) {
     let (_window_width, window_height) = fe.window_size();
 
     let x = 16;
-    let y = (window_height - 32) as i32;
+    let console_open = fe.console().is_open();
+    let y = (window_height - if console_open { 64 } else { 32 }) as i32;
 
     GAME_LOG.with(|log| {
         for (i, s) in log.borrow().entries().iter().enumerate() {
             let (s, t) = s;
-            let a = t.elapsed().as_millis().min(255) as u8;
+            let a = if console_open {
+                0xFF
+            } else {
+                t.elapsed().as_millis().min(255) as u8
+            };
             fe.ui_mesh.push_string(
                 x as i16,
                 (y - i as i32 * 20) as i16,
@@ -0,0 +1,62 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::ClientState;
+use wolkenwelten_core::{GameState, PROGRESSION};
+
+const ROW_HEIGHT: i16 = 56;
+
+pub fn prepare(fe: &mut ClientState, game: &GameState) {
+    let player = game.player();
+    let Some(choice) = player.perk_choice() else {
+        return;
+    };
+    let (window_width, window_height) = fe.window_size();
+    let white = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    if !fe.perk_menu().is_open() {
+        let text = match player.pending_perk_choices() {
+            1 => "Level up! Press P to choose a perk".to_string(),
+            n => format!("Level up! Press P to choose {} perks", n),
+        };
+        let x = window_width as i16 / 2 - text.len() as i16 * 8;
+        fe.ui_mesh.push_string(x, 96, 2, white, text.as_str());
+        return;
+    }
+
+    let w = 640;
+    let h = choice.len() as i16 * ROW_HEIGHT + 48;
+    let x = window_width as i16 / 2 - w / 2;
+    let y = window_height as i16 / 2 - h / 2;
+    fe.ui_mesh
+        .push_box((x, y, w, h), (76, 124, 4, 4), [0x00, 0x00, 0x00, 0x9F]);
+    fe.ui_mesh
+        .push_string(x + 8, y + 8, 2, white, "Choose a perk");
+
+    let selection = fe.perk_menu().selection();
+    PROGRESSION.with(|p| {
+        let p = p.borrow();
+        for (i, id) in choice.iter().enumerate() {
+            let Some(perk) = p.perk(*id) else {
+                continue;
+            };
+            let row_y = y + 40 + i as i16 * ROW_HEIGHT;
+            if i == selection {
+                fe.ui_mesh.push_box(
+                    (x + 4, row_y, w - 8, ROW_HEIGHT),
+                    (76, 124, 4, 4),
+                    [0xFF, 0xFF, 0xFF, 0x3F],
+                );
+            }
+            let name = format!("{}: {}", i + 1, perk.name());
+            fe.ui_mesh
+                .push_string(x + 12, row_y + 8, 2, white, name.as_str());
+            fe.ui_mesh.push_string(
+                x + 44,
+                row_y + 32,
+                1,
+                [0xCF, 0xCF, 0xCF, 0xFF],
+                perk.description(),
+            );
+        }
+    });
+}
@@ -0,0 +1,83 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::ClientState;
+use wolkenwelten_core::{GameState, RunStats};
+
+fn push_centered(fe: &mut ClientState, y: i16, size: i32, rgba: [u8; 4], text: &str) {
+    let (window_width, _) = fe.window_size();
+    let x = window_width as i16 / 2 - (text.len() as i32 * size * 4) as i16;
+    fe.ui_mesh.push_string(x, y, size, rgba, text);
+}
+
+fn format_time(stats: &RunStats) -> String {
+    let secs = stats.seconds();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// The summary shown once the player died, along with the high score table
+pub fn prepare(fe: &mut ClientState, game: &GameState) {
+    if !game.player().is_dead() {
+        return;
+    }
+    let run = *game.run();
+    let stats = run.stats();
+    let (_, window_height) = fe.window_size();
+    let white = [0xFF, 0xFF, 0xFF, 0xFF];
+    let grey = [0xCF, 0xCF, 0xCF, 0xFF];
+    let gold = [0xFF, 0xD7, 0x40, 0xFF];
+
+    let mut y = window_height as i16 / 2 - 256;
+    push_centered(fe, y, 4, white, "You died");
+    y += 48;
+    let text = format!(
+        "You reached level {} with a score of {}",
+        game.player().experience().level(),
+        game.score()
+    );
+    push_centered(fe, y, 2, white, &text);
+    y += 24;
+    let text = format!(
+        "Time {}   Kills {}   Blocks mined {}   Depth {}",
+        format_time(stats),
+        stats.kills,
+        stats.blocks_mined,
+        stats.depth
+    );
+    push_centered(fe, y, 2, grey, &text);
+    y += 24;
+    let text = format!("Seed {}", run.seed());
+    push_centered(fe, y, 2, grey, &text);
+    if let Some(rank) = run.rank() {
+        y += 32;
+        let text = format!("New high score, rank #{}", rank + 1);
+        push_centered(fe, y, 2, gold, &text);
+    }
+
+    y += 48;
+    push_centered(fe, y, 2, white, "High scores");
+    y += 24;
+    let high_scores = game.high_scores();
+    for (i, e) in high_scores.entries().iter().enumerate() {
+        let rgba = if Some(i) == run.rank() { gold } else { grey };
+        let text = format!(
+            "#{:<2} {:>8}  Lvl {:>3}  {}  Seed {:>10}",
+            i + 1,
+            e.score,
+            e.level,
+            format_time(&e.stats),
+            e.seed
+        );
+        push_centered(fe, y, 2, rgba, &text);
+        y += 20;
+    }
+    drop(high_scores);
+
+    y += 28;
+    push_centered(
+        fe,
+        y,
+        2,
+        white,
+        "Press R to retry this seed or N to start a new one",
+    );
+}
@@ -84,6 +84,7 @@ fn run_event_loop(
     let event_loop = state.event_loop;
     let mut request = ChunkRequestQueue::new();
     let mut render_reactor = RenderReactor::new();
+    let mut cursor_grabbed = true;
 
     render_reactor.init(&mut reactor, &render, &game, render_init_fun);
 
@@ -95,7 +96,7 @@ fn run_event_loop(
             Event::DeviceEvent {
                 event: winit::event::DeviceEvent::MouseMotion { .. },
                 ..
-            } => {
+            } if cursor_grabbed => {
                 use winit::dpi::PhysicalPosition;
 
                 let (x, y) = render.window_size();
@@ -111,7 +112,7 @@ fn run_event_loop(
                 event: WindowEvent::Focused(b),
                 ..
             } => {
-                if b {
+                if b && cursor_grabbed {
                     grab_cursor(render.display.gl_window().window());
                 } else {
                     ungrab_cursor(render.display.gl_window().window());
@@ -179,7 +180,7 @@ fn run_event_loop(
                     render.clear();
                 }
                 game.tick(&reactor, &mut request);
-                game.world_mut().handle_requests(&mut request, &reactor);
+                game.world_mut().handle_requests(&mut request);
                 render.request_redraw();
                 if !game.running() {
                     *control_flow = ControlFlow::Exit;
@@ -187,7 +188,35 @@ fn run_event_loop(
             }
             _ => {}
         };
-        input.handle_winit_event(&reactor, event);
+        let window_size = render.window_size();
+        if render.console_mut().handle_winit_event(&reactor, &event)
+            || render.crafting_mut().handle_winit_event(&reactor, &event)
+            || render
+                .perk_menu_mut()
+                .handle_winit_event(&reactor, &game, &event)
+            || render.inventory_screen_mut().handle_winit_event(
+                &reactor,
+                &game,
+                window_size,
+                &event,
+            )
+        {
+            input.release_all();
+        } else {
+            input.handle_winit_event(&reactor, event);
+        }
+
+        // The inventory screen needs a visible cursor to drag items around
+        let want_grab = !render.inventory_screen().is_open();
+        if want_grab != cursor_grabbed {
+            cursor_grabbed = want_grab;
+            let ctx = render.display.gl_window();
+            if want_grab {
+                grab_cursor(ctx.window());
+            } else {
+                ungrab_cursor(ctx.window());
+            }
+        }
     });
 }
 
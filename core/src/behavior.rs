@@ -0,0 +1,263 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Global behavior id, index into BEHAVIORS
+pub type BehaviorId = u16;
+
+thread_local! {
+    pub static BEHAVIORS:RefCell<Vec<Behavior>> = RefCell::new(Behavior::new_default());
+}
+
+/// The result of ticking a node within a behavior tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BehaviorStatus {
+    Success,
+    Failure,
+    /// Still busy, will be ticked again next time
+    Running,
+}
+
+/// Questions a behavior tree may ask about the mob and its surroundings
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum BehaviorCondition {
+    /// The player is alive and within aggro range, or within give up range
+    /// while already going after them
+    PlayerNear,
+    /// The player is close enough to be attacked
+    PlayerInReach,
+    /// Got hurt by the player a short while ago
+    Provoked,
+    /// Health is below this fraction of max health
+    HealthBelow(f32),
+    /// Further than this many blocks away from where the mob spawned
+    FarFromHome(f32),
+    /// Other mobs of the same type are around
+    FlockNearby,
+    Underwater,
+}
+
+/// Things a behavior tree can make a mob do
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorAction {
+    /// Stand still
+    Idle,
+    /// Stroll around near home, with some breaks in between
+    Wander,
+    /// Run away from the player
+    Flee,
+    /// Follow the player
+    Chase,
+    /// Strike the player, fails if they are out of reach
+    Attack,
+    /// Walk back to where the mob spawned
+    ReturnHome,
+    /// Move along with other mobs of the same type
+    Flock,
+    /// Use the special attack of an elite or the current boss phase, fails
+    /// if there is none or it's still cooling down
+    Special,
+}
+
+/// A node within a behavior tree, see assets/behaviors.json
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum BehaviorNode {
+    /// Tick children in order until one of them doesn't fail
+    Selector(Vec<BehaviorNode>),
+    /// Tick children in order until one of them doesn't succeed
+    Sequence(Vec<BehaviorNode>),
+    /// Swap success and failure of the child
+    Invert(Box<BehaviorNode>),
+    Condition(BehaviorCondition),
+    Action(BehaviorAction),
+}
+
+/// Whatever runs a behavior tree has to answer conditions and carry out actions
+pub trait BehaviorContext {
+    fn check(&mut self, condition: BehaviorCondition) -> bool;
+    fn act(&mut self, action: BehaviorAction) -> BehaviorStatus;
+}
+
+impl BehaviorNode {
+    /// Evaluate the tree from this node on, the whole tree is evaluated
+    /// every tick, so conditions higher up can interrupt running actions.
+    pub fn tick(&self, ctx: &mut impl BehaviorContext) -> BehaviorStatus {
+        match self {
+            Self::Selector(children) => {
+                for c in children.iter() {
+                    match c.tick(ctx) {
+                        BehaviorStatus::Failure => continue,
+                        status => return status,
+                    }
+                }
+                BehaviorStatus::Failure
+            }
+            Self::Sequence(children) => {
+                for c in children.iter() {
+                    match c.tick(ctx) {
+                        BehaviorStatus::Success => continue,
+                        status => return status,
+                    }
+                }
+                BehaviorStatus::Success
+            }
+            Self::Invert(child) => match child.tick(ctx) {
+                BehaviorStatus::Success => BehaviorStatus::Failure,
+                BehaviorStatus::Failure => BehaviorStatus::Success,
+                BehaviorStatus::Running => BehaviorStatus::Running,
+            },
+            Self::Condition(c) => {
+                if ctx.check(*c) {
+                    BehaviorStatus::Success
+                } else {
+                    BehaviorStatus::Failure
+                }
+            }
+            Self::Action(a) => ctx.act(*a),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Self::Selector(children) | Self::Sequence(children) => {
+                if children.is_empty() {
+                    return Err(anyhow!("Selectors and sequences need at least one child"));
+                }
+                children.iter().try_for_each(|c| c.validate())
+            }
+            Self::Invert(child) => child.validate(),
+            Self::Condition(BehaviorCondition::HealthBelow(f)) if !(0.0..=1.0).contains(f) => {
+                Err(anyhow!("health_below has to be between 0 and 1"))
+            }
+            Self::Condition(BehaviorCondition::FarFromHome(d)) if d.is_nan() || *d < 0.0 => {
+                Err(anyhow!("far_from_home can't be negative"))
+            }
+            Self::Condition(_) | Self::Action(_) => Ok(()),
+        }
+    }
+}
+
+/// A named behavior tree, mob types refer to these by id
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Behavior {
+    id: String,
+    root: BehaviorNode,
+}
+
+impl Behavior {
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn root(&self) -> &BehaviorNode {
+        &self.root
+    }
+
+    /// Returns the behavior trees from assets/behaviors.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "behaviors.json",
+            include_str!("../assets/behaviors.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of behavior trees, the position in the list
+    /// determines the numeric id.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(json, "behavior", BehaviorId::MAX as usize, |b: Behavior| {
+            b.root.validate()?;
+            Ok(b)
+        })
+    }
+}
+
+/// Look up the numeric id of a behavior tree by its string id
+pub fn behavior_id(id: &str) -> Option<BehaviorId> {
+    BEHAVIORS.with(|behaviors| {
+        behaviors
+            .borrow()
+            .iter()
+            .position(|b| b.id() == id)
+            .map(|i| i as BehaviorId)
+    })
+}
+
+/// Same as behavior_id, but returns an error naming the missing behavior
+pub fn behavior_id_or_err(id: &str) -> Result<BehaviorId> {
+    behavior_id(id).ok_or_else(|| anyhow!("Unknown behavior id {:?}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Stub {
+        near: bool,
+        actions: Vec<BehaviorAction>,
+    }
+
+    impl BehaviorContext for Stub {
+        fn check(&mut self, condition: BehaviorCondition) -> bool {
+            condition == BehaviorCondition::PlayerNear && self.near
+        }
+        fn act(&mut self, action: BehaviorAction) -> BehaviorStatus {
+            self.actions.push(action);
+            match action {
+                BehaviorAction::Attack => BehaviorStatus::Failure,
+                BehaviorAction::Idle => BehaviorStatus::Success,
+                _ => BehaviorStatus::Running,
+            }
+        }
+    }
+
+    #[test]
+    fn test_behavior_tree() {
+        assert!(behavior_id("aggressive").is_some());
+        assert!(behavior_id_or_err("nothing").is_err());
+
+        let b = Behavior::from_json(
+            r#"[{ "id": "test", "root": { "selector": [
+                { "sequence": [{ "condition": "player_near" }, { "selector": [
+                    { "action": "attack" }, { "action": "chase" } ] }] },
+                { "sequence": [{ "invert": { "condition": "provoked" } }, { "action": "idle" }] },
+                { "action": "wander" }
+            ] } }]"#,
+        )
+        .unwrap();
+        let root = b[0].root();
+
+        let mut ctx = Stub::default();
+        assert_eq!(root.tick(&mut ctx), BehaviorStatus::Success);
+        assert_eq!(ctx.actions, vec![BehaviorAction::Idle]);
+
+        let mut ctx = Stub {
+            near: true,
+            ..Default::default()
+        };
+        assert_eq!(root.tick(&mut ctx), BehaviorStatus::Running);
+        assert_eq!(
+            ctx.actions,
+            vec![BehaviorAction::Attack, BehaviorAction::Chase]
+        );
+
+        let bad = [
+            r#"[{ "id": "a", "root": { "selector": [] } }]"#,
+            r#"[{ "id": "a", "root": { "condition": { "health_below": 2.0 } } }]"#,
+            r#"[{ "id": "a", "root": { "action": "explode" } }]"#,
+            r#"[{ "id": "a", "root": { "action": "idle" } }, { "id": "a", "root": { "action": "idle" } }]"#,
+        ];
+        for json in bad {
+            assert!(Behavior::from_json(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{block_id, BlockId, BlockItem, Item, BLOCK_STACK_SIZE};
+use serde::{Deserialize, Serialize};
+
+pub const CHEST_SLOTS: usize = 27;
+
+/// How many block entity ticks it takes to smelt a single item
+pub const FURNACE_SMELT_TIME: u16 = 40;
+
+/// Which block smelts into which
+const SMELTING: [(&str, &str); 2] = [("sand", "glass"), ("marble_block", "marble_blocks")];
+
+/// How many block entity ticks a single item of fuel burns for
+const FUEL: [(&str, u16); 5] = [
+    ("coal", 320),
+    ("spruce_log", 120),
+    ("oak_log", 120),
+    ("birch_log", 120),
+    ("boards", 60),
+];
+
+/// The kind of block entity a block type gets, set via the "entity" key
+/// in assets/blocks.json
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockEntityKind {
+    #[default]
+    None,
+    Chest,
+    Sign,
+    Furnace,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Furnace {
+    input: Item,
+    fuel: Item,
+    output: Item,
+    progress: u16,
+    burn_left: u16,
+}
+
+/// State attached to a single block position, for blocks that need to
+/// remember more than their id and metadata.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BlockEntity {
+    Chest { items: Vec<Item> },
+    Sign { text: String },
+    Furnace(Furnace),
+}
+
+fn smelt_result(item: Item) -> Option<BlockId> {
+    let Item::Block(bi) = item else {
+        return None;
+    };
+    SMELTING
+        .iter()
+        .find(|(from, _)| block_id(from) == Some(bi.block))
+        .and_then(|(_, to)| block_id(to))
+}
+
+fn is_smelt_result(item: Item) -> bool {
+    let Item::Block(bi) = item else {
+        return false;
+    };
+    SMELTING
+        .iter()
+        .any(|(_, to)| block_id(to) == Some(bi.block))
+}
+
+fn fuel_value(item: Item) -> Option<u16> {
+    let Item::Block(bi) = item else {
+        return None;
+    };
+    FUEL.iter()
+        .find(|(id, _)| block_id(id) == Some(bi.block))
+        .map(|(_, v)| *v)
+}
+
+impl Furnace {
+    #[inline]
+    pub fn progress(&self) -> u16 {
+        self.progress
+    }
+
+    #[inline]
+    pub fn is_burning(&self) -> bool {
+        self.burn_left > 0
+    }
+
+    fn can_smelt(&self) -> bool {
+        match smelt_result(self.input) {
+            Some(result) => match self.output {
+                Item::None => true,
+                Item::Block(bi) => bi.block == result && bi.amount < BLOCK_STACK_SIZE,
+                Item::Stack(_) => false,
+            },
+            None => false,
+        }
+    }
+
+    fn tick(&mut self) -> bool {
+        let can_smelt = self.can_smelt();
+        if self.burn_left == 0 {
+            if !can_smelt {
+                let changed = self.progress > 0;
+                self.progress = 0;
+                return changed;
+            }
+            let Some(v) = fuel_value(self.fuel) else {
+                return false;
+            };
+            self.fuel.take_one();
+            self.burn_left = v;
+        }
+        self.burn_left -= 1;
+        if !can_smelt {
+            self.progress = 0;
+            return true;
+        }
+        self.progress += 1;
+        if self.progress >= FURNACE_SMELT_TIME {
+            self.progress = 0;
+            if let Some(result) = smelt_result(self.input) {
+                self.input.take_one();
+                self.output.merge(BlockItem::new(result, 1).into());
+            }
+        }
+        true
+    }
+
+    fn insert(&mut self, item: Item) -> Item {
+        if smelt_result(item).is_some() {
+            self.input.merge(item)
+        } else if fuel_value(item).is_some() {
+            self.fuel.merge(item)
+        } else if is_smelt_result(item) {
+            // Lets players put back results that didn't fit into their inventory
+            self.output.merge(item)
+        } else {
+            item
+        }
+    }
+}
+
+impl BlockEntity {
+    /// Create an empty block entity of a particular kind
+    pub fn new(kind: BlockEntityKind) -> Option<Self> {
+        match kind {
+            BlockEntityKind::None => None,
+            BlockEntityKind::Chest => Some(Self::Chest {
+                items: vec![Item::None; CHEST_SLOTS],
+            }),
+            BlockEntityKind::Sign => Some(Self::Sign {
+                text: String::new(),
+            }),
+            BlockEntityKind::Furnace => Some(Self::Furnace(Furnace::default())),
+        }
+    }
+
+    pub fn kind(&self) -> BlockEntityKind {
+        match self {
+            Self::Chest { .. } => BlockEntityKind::Chest,
+            Self::Sign { .. } => BlockEntityKind::Sign,
+            Self::Furnace(_) => BlockEntityKind::Furnace,
+        }
+    }
+
+    /// Every item stored within, these get dropped when the block breaks
+    pub fn contents(&self) -> Vec<Item> {
+        let items = match self {
+            Self::Chest { items } => items.clone(),
+            Self::Sign { .. } => vec![],
+            Self::Furnace(f) => vec![f.input, f.fuel, f.output],
+        };
+        items.into_iter().filter(|i| *i != Item::None).collect()
+    }
+
+    /// Advance the entity by one block entity tick, returns true if its state changed
+    pub fn tick(&mut self) -> bool {
+        match self {
+            Self::Furnace(f) => f.tick(),
+            _ => false,
+        }
+    }
+
+    /// Put item into the entity, returning whatever didn't fit
+    pub fn insert(&mut self, item: Item) -> Item {
+        match self {
+            Self::Chest { items } => items.iter_mut().fold(item, |item, slot| slot.merge(item)),
+            Self::Furnace(f) => f.insert(item),
+            Self::Sign { .. } => item,
+        }
+    }
+
+    /// Take all items out of a chest, or the finished items out of a furnace
+    pub fn take(&mut self) -> Vec<Item> {
+        let items = match self {
+            Self::Chest { items } => std::mem::replace(items, vec![Item::None; CHEST_SLOTS]),
+            Self::Furnace(f) => vec![std::mem::take(&mut f.output)],
+            Self::Sign { .. } => vec![],
+        };
+        items.into_iter().filter(|i| *i != Item::None).collect()
+    }
+
+    pub fn set_text(&mut self, new_text: &str) -> bool {
+        if let Self::Sign { text } = self {
+            *text = new_text.to_string();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// A short summary suitable for the game log
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Chest { items } => {
+                let used = items.iter().filter(|i| **i != Item::None).count();
+                format!("Chest: {}/{} slots used", used, items.len())
+            }
+            Self::Sign { text } if text.is_empty() => "The sign is blank".to_string(),
+            Self::Sign { text } => format!("The sign reads: {}", text),
+            Self::Furnace(f) => format!(
+                "Furnace: {}, {}% done",
+                if f.is_burning() { "burning" } else { "cold" },
+                f.progress as u32 * 100 / FURNACE_SMELT_TIME as u32
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chest() {
+        let mut chest = BlockEntity::new(BlockEntityKind::Chest).unwrap();
+        assert_eq!(chest.kind(), BlockEntityKind::Chest);
+        assert!(chest.contents().is_empty());
+        assert_eq!(chest.insert(BlockItem::new(3, 60).into()), Item::None);
+        assert_eq!(chest.insert(BlockItem::new(3, 60).into()), Item::None);
+        assert_eq!(
+            chest.contents(),
+            vec![BlockItem::new(3, 99).into(), BlockItem::new(3, 21).into()]
+        );
+        assert_eq!(chest.take().len(), 2);
+        assert!(chest.contents().is_empty());
+        assert!(BlockEntity::new(BlockEntityKind::None).is_none());
+    }
+
+    #[test]
+    fn test_furnace() {
+        let sand = block_id("sand").unwrap();
+        let glass = block_id("glass").unwrap();
+        let coal = block_id("coal").unwrap();
+        let mut furnace = BlockEntity::new(BlockEntityKind::Furnace).unwrap();
+        let stone: Item = BlockItem::new(block_id("stone").unwrap(), 1).into();
+        assert_eq!(furnace.insert(stone), stone);
+        assert_eq!(furnace.insert(BlockItem::new(sand, 2).into()), Item::None);
+        assert!(!furnace.tick());
+        assert_eq!(furnace.insert(BlockItem::new(coal, 1).into()), Item::None);
+        for _ in 0..FURNACE_SMELT_TIME * 2 {
+            assert!(furnace.tick());
+        }
+        assert_eq!(furnace.take(), vec![BlockItem::new(glass, 2).into()]);
+        assert!(furnace.contents().is_empty());
+    }
+
+    #[test]
+    fn test_entities_survive_gc() {
+        use crate::{BlockMeta, Chungus, ChunkBlockData, ChunkRequestQueue, CHUNK_BITS};
+        use glam::{IVec3, Vec3};
+
+        let mut world = Chungus::new().unwrap();
+        let pos = IVec3::new(40, 1, 1);
+        world.insert(pos >> CHUNK_BITS, ChunkBlockData::new());
+        let sign = block_id("sign").unwrap();
+        world.set_block_with_meta(pos, sign, BlockMeta::from_raw(2));
+        let mut entity = BlockEntity::new(BlockEntityKind::Sign).unwrap();
+        entity.set_text("Home");
+        world.set_block_entity(pos, Some(entity.clone()));
+
+        world.gc_block(Vec3::new(10000.0, 0.0, 0.0), 32.0);
+        assert_eq!(world.chunk_count(), 0);
+        assert_eq!(world.block_entity_save().len(), 1);
+
+        let mut request = ChunkRequestQueue::new();
+        request.block(pos >> CHUNK_BITS);
+        world.handle_requests(&mut request);
+        assert_eq!(world.get_block(pos), Some(sign));
+        assert_eq!(world.get_block_meta(pos), Some(BlockMeta::from_raw(2)));
+        assert_eq!(world.get_block_entity(pos), Some(&entity));
+    }
+}
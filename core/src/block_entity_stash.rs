@@ -0,0 +1,168 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{BlockEntity, GameState, Message, Reactor, CHUNK_BITS};
+use anyhow::{anyhow, Context, Result};
+use glam::IVec3;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Increased whenever the block entity save format changes in an incompatible way
+pub const BLOCK_ENTITY_SAVE_VERSION: u32 = 1;
+
+/// A block entity along with the block it sits in, since worldgen can't
+/// bring back either of them once its chunk got dropped.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedBlockEntity {
+    pub pos: IVec3,
+    /// String id of the block, so saves survive changes to blocks.json
+    pub block: String,
+    /// Raw BlockMeta, for the way a chest is facing for example
+    #[serde(default)]
+    pub meta: u8,
+    pub entity: BlockEntity,
+}
+
+/// Block entities of chunks that aren't loaded right now, sorted by chunk
+#[derive(Clone, Debug, Default)]
+pub struct BlockEntityStash {
+    chunks: HashMap<IVec3, Vec<SavedBlockEntity>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlockEntitySave {
+    version: u32,
+    /// Block entities only make sense within the world they were saved in
+    seed: u32,
+    #[serde(default)]
+    entities: Vec<SavedBlockEntity>,
+}
+
+impl BlockEntityStash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, entity: SavedBlockEntity) {
+        self.chunks
+            .entry(entity.pos >> CHUNK_BITS)
+            .or_default()
+            .push(entity);
+    }
+
+    /// Remove and return every block entity stashed within chunk
+    pub fn take(&mut self, chunk: IVec3) -> Vec<SavedBlockEntity> {
+        self.chunks.remove(&chunk).unwrap_or_default()
+    }
+
+    /// Every stashed block entity, in no particular order
+    pub fn entities(&self) -> impl Iterator<Item = &SavedBlockEntity> + '_ {
+        self.chunks.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(|e| e.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    pub fn save_json(&self, seed: u32) -> Result<String> {
+        let save = BlockEntitySave {
+            version: BLOCK_ENTITY_SAVE_VERSION,
+            seed,
+            entities: self.entities().cloned().collect(),
+        };
+        Ok(serde_json::to_string_pretty(&save)?)
+    }
+
+    /// Block entities saved within a world with a different seed get dropped
+    pub fn load_json(json: &str, seed: u32) -> Result<Self> {
+        let save: BlockEntitySave =
+            serde_json::from_str(json).context("Invalid block entity save")?;
+        if save.version > BLOCK_ENTITY_SAVE_VERSION {
+            return Err(anyhow!(
+                "Block entity save version {} is newer than {}",
+                save.version,
+                BLOCK_ENTITY_SAVE_VERSION
+            ));
+        }
+        let mut ret = Self::new();
+        if save.seed == seed {
+            save.entities.into_iter().for_each(|e| ret.insert(e));
+        }
+        Ok(ret)
+    }
+
+    pub fn save_to_file(&self, path: &Path, seed: u32) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.save_json(seed)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &Path, seed: u32) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        Self::load_json(&json, seed)
+    }
+
+    pub fn add_handler(reactor: &mut Reactor<Message>, game: &GameState) {
+        if let Some(path) = game.block_entity_save_path().map(|p| p.to_path_buf()) {
+            let world = game.world_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let world = world.borrow();
+                let stash = world.block_entity_save();
+                if let Err(err) = stash.save_to_file(&path, world.seed()) {
+                    eprintln!(
+                        "Couldn't save the block entities to {}: {:#}",
+                        path.display(),
+                        err
+                    );
+                }
+            };
+            reactor.add_sink(Message::WorldSave, Box::new(f));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockEntityKind;
+
+    #[test]
+    fn test_block_entity_stash() {
+        let sign = |x: i32| SavedBlockEntity {
+            pos: IVec3::new(x, 1, 1),
+            block: "sign".to_string(),
+            meta: 0,
+            entity: BlockEntity::new(BlockEntityKind::Sign).unwrap(),
+        };
+        let mut stash = BlockEntityStash::new();
+        assert!(stash.is_empty());
+        stash.insert(sign(1));
+        stash.insert(sign(2));
+        stash.insert(sign(-1));
+        assert_eq!(stash.len(), 3);
+
+        let json = stash.save_json(5).unwrap();
+        let mut loaded = BlockEntityStash::load_json(&json, 5).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert!(BlockEntityStash::load_json(&json, 6).unwrap().is_empty());
+        assert!(BlockEntityStash::load_json(r#"{ "version": 99, "seed": 5 }"#, 5).is_err());
+
+        let taken = loaded.take(IVec3::ZERO);
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].entity.kind(), BlockEntityKind::Sign);
+        assert_eq!(loaded.len(), 1);
+    }
+}
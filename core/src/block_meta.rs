@@ -0,0 +1,238 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::Side;
+use glam::Vec3;
+use serde::Deserialize;
+
+/// Which part of the metadata determines how a block is rotated
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockOrientation {
+    #[default]
+    None,
+    /// Rotated around the Y axis so that the front faces a particular side, like stairs
+    Facing,
+    /// Aligned along one of the 3 axes, like logs
+    Axis,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Axis {
+    #[default]
+    Y = 0,
+    X,
+    Z,
+}
+
+const FACING_MASK: u8 = 0b111;
+const AXIS_SHIFT: u8 = 3;
+const AXIS_MASK: u8 = 0b11 << AXIS_SHIFT;
+const STATE_SHIFT: u8 = 5;
+const STATE_MASK: u8 = 0b111 << STATE_SHIFT;
+
+/// Per-voxel metadata packed into a single byte, the lower 3 bits store the
+/// side a block is facing, the next 2 the axis it is aligned to and the upper
+/// 3 bits are free for block specific state, like whether a door is open.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BlockMeta(u8);
+
+impl BlockMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn from_raw(v: u8) -> Self {
+        Self(v)
+    }
+
+    #[inline]
+    pub fn raw(&self) -> u8 {
+        self.0
+    }
+
+    #[inline]
+    pub fn is_default(&self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub fn facing(&self) -> Side {
+        Side::from(self.0 & FACING_MASK)
+    }
+
+    pub fn with_facing(self, side: Side) -> Self {
+        Self((self.0 & !FACING_MASK) | u8::from(side))
+    }
+
+    #[inline]
+    pub fn axis(&self) -> Axis {
+        match (self.0 & AXIS_MASK) >> AXIS_SHIFT {
+            1 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+
+    pub fn with_axis(self, axis: Axis) -> Self {
+        Self((self.0 & !AXIS_MASK) | ((axis as u8) << AXIS_SHIFT))
+    }
+
+    /// Block specific state, in the range 0..8
+    #[inline]
+    pub fn state(&self) -> u8 {
+        (self.0 & STATE_MASK) >> STATE_SHIFT
+    }
+
+    pub fn with_state(self, state: u8) -> Self {
+        Self((self.0 & !STATE_MASK) | ((state << STATE_SHIFT) & STATE_MASK))
+    }
+
+    /// Determine the metadata of a newly placed block, dir being the
+    /// direction the player is looking at.
+    pub fn from_direction(orientation: BlockOrientation, dir: Vec3) -> Self {
+        let a = dir.abs();
+        match orientation {
+            BlockOrientation::None => Self::new(),
+            BlockOrientation::Axis => Self::new().with_axis(if a.x > a.y && a.x > a.z {
+                Axis::X
+            } else if a.z > a.y {
+                Axis::Z
+            } else {
+                Axis::Y
+            }),
+            // The front of the block should face the player
+            BlockOrientation::Facing => Self::new().with_facing(if a.x > a.z {
+                if dir.x > 0.0 {
+                    Side::Left
+                } else {
+                    Side::Right
+                }
+            } else if dir.z > 0.0 {
+                Side::Back
+            } else {
+                Side::Front
+            }),
+        }
+    }
+
+    /// Map a side in the world to the side of the unrotated block, the bool
+    /// is true if the texture has to be rotated by 90 degrees.
+    pub fn unrotate_side(&self, orientation: BlockOrientation, side: Side) -> (Side, bool) {
+        use Side::*;
+        match orientation {
+            BlockOrientation::None => (side, false),
+            BlockOrientation::Axis => match self.axis() {
+                Axis::Y => (side, false),
+                Axis::X => match side {
+                    Right => (Top, false),
+                    Left => (Bottom, false),
+                    Top => (Left, true),
+                    Bottom => (Right, true),
+                    s => (s, true),
+                },
+                Axis::Z => match side {
+                    Front => (Top, false),
+                    Back => (Bottom, false),
+                    Top => (Back, false),
+                    Bottom => (Front, false),
+                    s => (s, true),
+                },
+            },
+            BlockOrientation::Facing => match (self.facing(), side) {
+                (Back, Front) => (Back, false),
+                (Back, Back) => (Front, false),
+                (Back, Left) => (Right, false),
+                (Back, Right) => (Left, false),
+                (Right, Right) => (Front, false),
+                (Right, Left) => (Back, false),
+                (Right, Back) => (Right, false),
+                (Right, Front) => (Left, false),
+                (Left, Left) => (Front, false),
+                (Left, Right) => (Back, false),
+                (Left, Front) => (Right, false),
+                (Left, Back) => (Left, false),
+                (Left | Right, s) => (s, true),
+                (_, s) => (s, false),
+            },
+        }
+    }
+
+    /// Map a point within a block, every component being in the range
+    /// 0.0..1.0, to the corresponding point within the unrotated block.
+    pub fn unrotate_point(&self, orientation: BlockOrientation, p: Vec3) -> Vec3 {
+        if orientation != BlockOrientation::Facing {
+            return p;
+        }
+        match self.facing() {
+            Side::Back => Vec3::new(1.0 - p.x, p.y, 1.0 - p.z),
+            Side::Right => Vec3::new(1.0 - p.z, p.y, p.x),
+            Side::Left => Vec3::new(p.z, p.y, 1.0 - p.x),
+            _ => p,
+        }
+    }
+
+    /// The inverse of unrotate_point, turns a point from the unrotated
+    /// shape into the rotated block.
+    pub fn rotate_point(&self, orientation: BlockOrientation, p: Vec3) -> Vec3 {
+        if orientation != BlockOrientation::Facing {
+            return p;
+        }
+        match self.facing() {
+            Side::Back => Vec3::new(1.0 - p.x, p.y, 1.0 - p.z),
+            Side::Right => Vec3::new(p.z, p.y, 1.0 - p.x),
+            Side::Left => Vec3::new(1.0 - p.z, p.y, p.x),
+            _ => p,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_meta() {
+        let m = BlockMeta::new();
+        assert!(m.is_default());
+        assert_eq!(m.facing(), Side::Front);
+        assert_eq!(m.axis(), Axis::Y);
+        let m = m.with_facing(Side::Left).with_axis(Axis::Z).with_state(5);
+        assert_eq!(m.facing(), Side::Left);
+        assert_eq!(m.axis(), Axis::Z);
+        assert_eq!(m.state(), 5);
+        let m = m.with_state(2).with_facing(Side::Back);
+        assert_eq!(m.facing(), Side::Back);
+        assert_eq!(m.axis(), Axis::Z);
+        assert_eq!(m.state(), 2);
+        assert_eq!(BlockMeta::from_raw(m.raw()), m);
+    }
+
+    #[test]
+    fn test_orientation() {
+        let facing = BlockOrientation::Facing;
+        let m = BlockMeta::from_direction(facing, Vec3::new(0.9, -0.3, 0.1));
+        assert_eq!(m.facing(), Side::Left);
+        assert_eq!(m.unrotate_side(facing, Side::Left), (Side::Front, false));
+        assert_eq!(m.unrotate_side(facing, Side::Top), (Side::Top, true));
+        let p = m.unrotate_point(facing, Vec3::new(0.1, 0.5, 0.3));
+        assert!((p - Vec3::new(0.3, 0.5, 0.9)).length() < 0.001);
+
+        let m = BlockMeta::from_direction(facing, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(m.facing(), Side::Front);
+        assert_eq!(
+            m.unrotate_point(facing, Vec3::new(0.1, 0.2, 0.3)),
+            Vec3::new(0.1, 0.2, 0.3)
+        );
+
+        let axis = BlockOrientation::Axis;
+        let m = BlockMeta::from_direction(axis, Vec3::new(0.2, 0.1, -0.8));
+        assert_eq!(m.axis(), Axis::Z);
+        assert_eq!(m.unrotate_side(axis, Side::Front), (Side::Top, false));
+        assert_eq!(m.unrotate_side(axis, Side::Left), (Side::Left, true));
+        assert_eq!(
+            BlockMeta::new().unrotate_side(BlockOrientation::None, Side::Back),
+            (Side::Back, false)
+        );
+    }
+}
@@ -1,9 +1,15 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::Side;
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{Aabb, BlockEntityKind, BlockId, BlockMeta, BlockOrientation, Side, BLOCKS};
+use anyhow::{anyhow, Context, Result};
+use glam::Vec3;
 use rgb::RGBA8;
+use serde::Deserialize;
+use std::collections::HashMap;
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MiningCategory {
     #[default]
     None,
@@ -12,14 +18,76 @@ pub enum MiningCategory {
     Shovel(u8),
 }
 
+impl MiningCategory {
+    /// The tool tier required to mine a block efficiently
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Axe(l) | Self::Pickaxe(l) | Self::Shovel(l) => *l,
+        }
+    }
+}
+
+/// How a block is drawn and which part of it can be collided with
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockShape {
+    #[default]
+    Cube,
+    /// The lower half of a cube
+    Slab,
+    /// A lower half, with the upper half only covering the back
+    Stairs,
+    /// Two diagonal quads, mostly used for small plants
+    CrossPlant,
+}
+
+impl BlockShape {
+    /// Check whether a point within a block, with every component being
+    /// in the range 0.0..1.0, would be inside of this shape.
+    pub fn contains(&self, p: Vec3) -> bool {
+        match self {
+            Self::Cube | Self::CrossPlant => true,
+            Self::Slab => p.y < 0.5,
+            Self::Stairs => p.y < 0.5 || p.z < 0.5,
+        }
+    }
+
+    /// The boxes making up this shape, within the unit cube
+    pub fn boxes(&self) -> &'static [Aabb] {
+        const CUBE: [Aabb; 1] = [Aabb::new(Vec3::ZERO, Vec3::ONE)];
+        const SLAB: [Aabb; 1] = [Aabb::new(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))];
+        const STAIRS: [Aabb; 2] = [
+            Aabb::new(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0)),
+            Aabb::new(Vec3::new(0.0, 0.5, 0.0), Vec3::new(1.0, 1.0, 0.5)),
+        ];
+        match self {
+            Self::Cube | Self::CrossPlant => &CUBE,
+            Self::Slab => &SLAB,
+            Self::Stairs => &STAIRS,
+        }
+    }
+}
+
 const WHITE: RGBA8 = RGBA8::new(255, 255, 255, 255);
 #[derive(Clone, Debug, Default)]
 pub struct BlockType {
+    id: String,
     name: String,
     texture_index: [u8; 6],
     colors: [RGBA8; 2],
     mining_cat: MiningCategory,
     block_health: u16,
+    transparent: bool,
+    solid: bool,
+    climbable: bool,
+    friction: f32,
+    shape: BlockShape,
+    orientation: BlockOrientation,
+    entity: BlockEntityKind,
+    /// Id of the loot table rolled when this block breaks, stays a string
+    /// since loot tables are loaded after, and refer to, the blocks.
+    loot: Option<String>,
 }
 
 impl BlockType {
@@ -40,13 +108,26 @@ impl BlockType {
         let name = name.to_string();
         let colors = [WHITE; 2];
         Self {
+            id: String::new(),
             mining_cat: MiningCategory::None,
             name,
             texture_index,
             colors,
             block_health: 50,
+            transparent: false,
+            solid: true,
+            climbable: false,
+            friction: 1.0,
+            shape: BlockShape::Cube,
+            orientation: BlockOrientation::None,
+            entity: BlockEntityKind::None,
+            loot: None,
         }
     }
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
     pub fn with_colors(mut self, a: RGBA8, b: RGBA8) -> Self {
         self.colors = [a, b];
         self
@@ -68,7 +149,43 @@ impl BlockType {
         self.block_health = block_health;
         self
     }
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+    pub fn with_solid(mut self, solid: bool) -> Self {
+        self.solid = solid;
+        self
+    }
+    pub fn with_climbable(mut self, climbable: bool) -> Self {
+        self.climbable = climbable;
+        self
+    }
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+    pub fn with_shape(mut self, shape: BlockShape) -> Self {
+        self.shape = shape;
+        self
+    }
+    pub fn with_entity(mut self, entity: BlockEntityKind) -> Self {
+        self.entity = entity;
+        self
+    }
+    pub fn with_orientation(mut self, orientation: BlockOrientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+    pub fn with_loot(mut self, loot: &str) -> Self {
+        self.loot = Some(loot.to_string());
+        self
+    }
 
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
     #[inline]
     pub fn name(&self) -> &str {
         &self.name
@@ -121,6 +238,72 @@ impl BlockType {
     pub fn block_health(&self) -> u16 {
         self.block_health
     }
+    #[inline]
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+    #[inline]
+    pub fn solid(&self) -> bool {
+        self.solid
+    }
+    #[inline]
+    pub fn climbable(&self) -> bool {
+        self.climbable
+    }
+    #[inline]
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+    #[inline]
+    pub fn shape(&self) -> BlockShape {
+        self.shape
+    }
+    #[inline]
+    pub fn orientation(&self) -> BlockOrientation {
+        self.orientation
+    }
+    #[inline]
+    pub fn entity(&self) -> BlockEntityKind {
+        self.entity
+    }
+    #[inline]
+    pub fn loot(&self) -> Option<&str> {
+        self.loot.as_deref()
+    }
+
+    /// Texture index for a side of the world, taking the rotation stored in
+    /// meta into account, the bool is true if the texture has to be rotated.
+    pub fn texture_for(&self, side: Side, meta: BlockMeta) -> (u8, bool) {
+        let (side, rotate) = meta.unrotate_side(self.orientation, side);
+        (self.texture_index[usize::from(side)], rotate)
+    }
+
+    /// Opaque cubes are the only blocks that hide the faces of their
+    /// neighbours and block light.
+    #[inline]
+    pub fn is_opaque_cube(&self) -> bool {
+        !self.transparent && self.shape == BlockShape::Cube
+    }
+
+    /// Check whether a point within this block, relative to its lower
+    /// corner, should be collided with.
+    #[inline]
+    pub fn collides_at(&self, p: Vec3, meta: BlockMeta) -> bool {
+        self.solid
+            && self
+                .shape
+                .contains(meta.unrotate_point(self.orientation, p))
+    }
+
+    /// The boxes that can be collided with, relative to the lower corner of the block
+    pub fn collision_boxes(&self, meta: BlockMeta) -> impl Iterator<Item = Aabb> + '_ {
+        let boxes = if self.solid { self.shape.boxes() } else { &[] };
+        boxes.iter().map(move |b| {
+            let a = meta.rotate_point(self.orientation, b.min);
+            let b = meta.rotate_point(self.orientation, b.max);
+            Aabb::new(a.min(b), a.max(b))
+        })
+    }
 }
 
 impl std::fmt::Display for BlockType {
@@ -130,194 +313,137 @@ impl std::fmt::Display for BlockType {
     }
 }
 
+/// The format blocks are defined in, see assets/blocks.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockTypeDefinition {
+    id: String,
+    name: String,
+    #[serde(default)]
+    texture: u8,
+    #[serde(default)]
+    sides: HashMap<Side, u8>,
+    #[serde(default)]
+    colors: Option<[String; 2]>,
+    #[serde(default)]
+    mining: MiningCategory,
+    #[serde(default = "default_block_health")]
+    health: u16,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default = "default_true")]
+    solid: bool,
+    #[serde(default)]
+    climbable: bool,
+    #[serde(default = "default_friction")]
+    friction: f32,
+    #[serde(default)]
+    shape: BlockShape,
+    #[serde(default)]
+    orientation: BlockOrientation,
+    #[serde(default)]
+    entity: BlockEntityKind,
+    #[serde(default)]
+    loot: Option<String>,
+}
+
+fn default_block_health() -> u16 {
+    50
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_friction() -> f32 {
+    1.0
+}
+
+pub(crate) fn parse_color(s: &str) -> Result<RGBA8> {
+    let hex = s
+        .strip_prefix('#')
+        .ok_or_else(|| anyhow!("Color {:?} has to start with a #", s))?;
+    if hex.len() != 6 && hex.len() != 8 {
+        return Err(anyhow!("Color {:?} has to be in the form #RRGGBB(AA)", s));
+    }
+    let v = u32::from_str_radix(hex, 16).with_context(|| format!("Invalid color {:?}", s))?;
+    let v = if hex.len() == 6 { (v << 8) | 0xFF } else { v };
+    let [r, g, b, a] = v.to_be_bytes();
+    Ok(RGBA8::new(r, g, b, a))
+}
+
+impl BlockTypeDefinition {
+    fn validate(self) -> Result<BlockType> {
+        let mut bt = BlockType::new(&self.name)
+            .with_id(&self.id)
+            .with_texture(self.texture)
+            .with_mining_cat(self.mining)
+            .with_block_health(self.health)
+            .with_transparent(self.transparent)
+            .with_solid(self.solid)
+            .with_climbable(self.climbable)
+            .with_friction(self.friction)
+            .with_shape(self.shape)
+            .with_orientation(self.orientation)
+            .with_entity(self.entity);
+        if !(0.0..=1.0).contains(&self.friction) {
+            return Err(anyhow!(
+                "Friction has to be between 0.0 and 1.0, not {}",
+                self.friction
+            ));
+        }
+        for (side, tex) in self.sides {
+            bt = bt.with_texture_side(tex, side);
+        }
+        if let Some([a, b]) = self.colors {
+            bt = bt.with_colors(parse_color(&a)?, parse_color(&b)?);
+        }
+        if let Some(loot) = &self.loot {
+            bt = bt.with_loot(loot);
+        }
+        Ok(bt)
+    }
+}
+
+/// Look up the numeric id of a block by its string id
+pub fn block_id(id: &str) -> Option<BlockId> {
+    BLOCKS.with(|blocks| {
+        blocks
+            .borrow()
+            .iter()
+            .position(|b| b.id() == id)
+            .map(|i| i as BlockId)
+    })
+}
+
+/// Same as block_id, but returns an error naming the missing block
+pub fn block_id_or_err(id: &str) -> Result<BlockId> {
+    block_id(id).ok_or_else(|| anyhow!("Unknown block id {:?}", id))
+}
+
 impl BlockType {
+    /// Returns the block definitions from assets/blocks.json, unless a mod replaces them
     pub fn new_default() -> Vec<Self> {
-        vec![
-            BlockType::new("Air"),
-            BlockType::new("Dirt")
-                .with_texture(1)
-                .with_colors(
-                    RGBA8::new(0x11, 0x0A, 0x00, 0xFF),
-                    RGBA8::new(0x20, 0x12, 0x00, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Shovel(1))
-                .with_block_health(2000),
-            BlockType::new("Grass")
-                .with_texture(16)
-                .with_texture_top(0)
-                .with_texture_bottom(1)
-                .with_colors(
-                    RGBA8::new(0x08, 0x12, 0x00, 0xFF),
-                    RGBA8::new(0x11, 0x0A, 0x00, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Shovel(1))
-                .with_block_health(2500),
-            BlockType::new("Stone")
-                .with_texture(2)
-                .with_colors(
-                    RGBA8::new(0x5E, 0x5E, 0x5E, 0xFF),
-                    RGBA8::new(0x48, 0x48, 0x48, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(8000),
-            BlockType::new("Coal")
-                .with_texture(3)
-                .with_colors(
-                    RGBA8::new(0x26, 0x26, 0x26, 0xFF),
-                    RGBA8::new(0x10, 0x10, 0x10, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(7000),
-            BlockType::new("Spruce log")
-                .with_texture(4)
-                .with_colors(
-                    RGBA8::new(0x25, 0x1B, 0x05, 0xFF),
-                    RGBA8::new(0x1D, 0x16, 0x07, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Axe(1))
-                .with_block_health(6000),
-            BlockType::new("Spruce leaves")
-                .with_texture(5)
-                .with_colors(
-                    RGBA8::new(0x12, 0x2C, 0x01, 0xFF),
-                    RGBA8::new(0x0F, 0x25, 0x01, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Dry grass")
-                .with_texture(22)
-                .with_texture_top(6)
-                .with_texture_bottom(1)
-                .with_colors(
-                    RGBA8::new(0x4B, 0x64, 0x11, 0xFF),
-                    RGBA8::new(0x4F, 0x23, 0x0A, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Shovel(1))
-                .with_block_health(2000),
-            BlockType::new("Roots")
-                .with_texture(7)
-                .with_colors(
-                    RGBA8::new(0x3E, 0x32, 0x14, 0xFF),
-                    RGBA8::new(0x29, 0x20, 0x0D, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Shovel(1))
-                .with_block_health(5000),
-            BlockType::new("Obsidian")
-                .with_texture(8)
-                .with_colors(
-                    RGBA8::new(0x22, 0x22, 0x22, 0xFF),
-                    RGBA8::new(0x17, 0x17, 0x17, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(2))
-                .with_block_health(14000),
-            BlockType::new("Oak log")
-                .with_texture(9)
-                .with_colors(
-                    RGBA8::new(0x3C, 0x2C, 0x08, 0xFF),
-                    RGBA8::new(0x2E, 0x24, 0x08, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Axe(1))
-                .with_block_health(7000),
-            BlockType::new("Oak leaves")
-                .with_texture(10)
-                .with_colors(
-                    RGBA8::new(0x27, 0x42, 0x00, 0xFF),
-                    RGBA8::new(0x18, 0x33, 0x00, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Iron ore (hematite)")
-                .with_texture(11)
-                .with_colors(
-                    RGBA8::new(0x72, 0x5B, 0x5B, 0xFF),
-                    RGBA8::new(0x5E, 0x5E, 0x5E, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(10000),
-            BlockType::new("Marble block")
-                .with_texture(12)
-                .with_colors(
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(10000),
-            BlockType::new("Marble pillar")
-                .with_texture(13)
-                .with_texture_top(12)
-                .with_texture_bottom(12)
-                .with_colors(
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(10000),
-            BlockType::new("Marble blocks")
-                .with_texture(14)
-                .with_colors(
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                    RGBA8::new(0xF0, 0xF0, 0xF0, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(1))
-                .with_block_health(10000),
-            BlockType::new("Acacia leaves")
-                .with_texture(15)
-                .with_colors(
-                    RGBA8::new(0x02, 0x30, 0x00, 0xFF),
-                    RGBA8::new(0x32, 0x6F, 0x1C, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Boards")
-                .with_texture(17)
-                .with_colors(
-                    RGBA8::new(0x8F, 0x67, 0x09, 0xFF),
-                    RGBA8::new(0xAF, 0x80, 0x13, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Axe(1))
-                .with_block_health(4000),
-            BlockType::new("Crystals")
-                .with_texture(18)
-                .with_colors(
-                    RGBA8::new(0xE8, 0x7C, 0x99, 0xFF),
-                    RGBA8::new(0xB5, 0x24, 0x4D, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Pickaxe(3))
-                .with_block_health(20000),
-            BlockType::new("Sakura leaves")
-                .with_texture(19)
-                .with_colors(
-                    RGBA8::new(0xE8, 0x7C, 0x99, 0xFF),
-                    RGBA8::new(0xB5, 0x25, 0x4D, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Birch log")
-                .with_texture(20)
-                .with_colors(
-                    RGBA8::new(0x55, 0x52, 0x52, 0xFF),
-                    RGBA8::new(0xA5, 0xA2, 0xA2, 0xFF),
-                )
-                .with_mining_cat(MiningCategory::Axe(1))
-                .with_block_health(6000),
-            BlockType::new("Flower bush")
-                .with_texture(21)
-                .with_colors(
-                    RGBA8::new(0x27, 0x42, 0x00, 0xFF),
-                    RGBA8::new(0x18, 0x33, 0x00, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Date bush")
-                .with_texture(23)
-                .with_colors(
-                    RGBA8::new(0x4F, 0x33, 0x00, 0xFF),
-                    RGBA8::new(0x94, 0x83, 0x12, 0xFF),
-                )
-                .with_block_health(1000),
-            BlockType::new("Sand")
-                .with_texture(24)
-                .with_colors(
-                    RGBA8::new(0xEC, 0xD1, 0x95, 0xFF),
-                    RGBA8::new(0xD3, 0xA7, 0x48, 0xFF),
-                )
-                .with_block_health(1400),
-        ]
+        load_definitions(
+            "blocks.json",
+            include_str!("../assets/blocks.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of block definitions, the position in the list
+    /// determines the numeric id, so the first entry has to be air.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        let ret = parse_definitions(
+            json,
+            "block",
+            BlockId::MAX as usize,
+            BlockTypeDefinition::validate,
+        )?;
+        if let Some(bt) = ret.first().filter(|bt| bt.id != "air") {
+            return Err(anyhow!("The first block has to be air, not {:?}", bt.id));
+        }
+        Ok(ret)
     }
 
     pub fn new_default_fluids() -> Vec<Self> {
@@ -367,5 +493,97 @@ mod tests {
         assert_ne!(blocks[2].tex_top(), blocks[2].tex_left());
         let dis = format!("{}", blocks[3]);
         assert_eq!(dis, "<BlockType name=Stone />");
+        assert_eq!(blocks[3].id(), "stone");
+        assert_eq!(block_id("stone"), Some(3));
+        assert_eq!(block_id("unobtainium"), None);
+    }
+
+    #[test]
+    fn test_block_definitions() {
+        let blocks = BlockType::from_json(
+            r##"[
+                { "id": "air", "name": "Air" },
+                { "id": "grass", "name": "Grass", "texture": 3, "sides": { "top": 4 },
+                  "colors": ["#102030", "#40506070"], "mining": { "shovel": 2 }, "health": 99 }
+            ]"##,
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].tex_top(), 4);
+        assert_eq!(blocks[1].tex_left(), 3);
+        assert_eq!(blocks[1].colors()[0], RGBA8::new(0x10, 0x20, 0x30, 0xFF));
+        assert_eq!(blocks[1].colors()[1], RGBA8::new(0x40, 0x50, 0x60, 0x70));
+        assert!(matches!(blocks[1].mining_cat(), MiningCategory::Shovel(2)));
+        assert_eq!(blocks[1].block_health(), 99);
+        assert_eq!(blocks[0].block_health(), 50);
+
+        assert!(blocks[1].solid());
+        assert!(!blocks[1].transparent());
+        assert!(blocks[1].is_opaque_cube());
+        assert_eq!(blocks[1].friction(), 1.0);
+
+        let blocks = BlockType::from_json(
+            r#"[
+                { "id": "air", "name": "Air", "solid": false, "transparent": true },
+                { "id": "glass", "name": "Glass", "transparent": true },
+                { "id": "slab", "name": "Slab", "shape": "slab", "friction": 0.5 },
+                { "id": "ladder", "name": "Ladder", "solid": false, "climbable": true },
+                { "id": "fern", "name": "Fern", "shape": "cross_plant", "solid": false },
+                { "id": "chest", "name": "Chest", "entity": "chest" }
+            ]"#,
+        )
+        .unwrap();
+        assert!(!blocks[1].is_opaque_cube());
+        assert_eq!(blocks[2].shape(), BlockShape::Slab);
+        assert_eq!(blocks[2].friction(), 0.5);
+        assert!(blocks[2].collides_at(Vec3::new(0.5, 0.25, 0.5), BlockMeta::new()));
+        assert!(!blocks[2].collides_at(Vec3::new(0.5, 0.75, 0.5), BlockMeta::new()));
+        assert!(!blocks[2].is_opaque_cube());
+        assert!(blocks[3].climbable());
+        assert!(!blocks[3].collides_at(Vec3::new(0.5, 0.5, 0.5), BlockMeta::new()));
+        assert_eq!(blocks[4].shape(), BlockShape::CrossPlant);
+        assert_eq!(blocks[4].entity(), BlockEntityKind::None);
+        assert_eq!(blocks[5].entity(), BlockEntityKind::Chest);
+        assert!(BlockShape::Stairs.contains(Vec3::new(0.5, 0.75, 0.25)));
+        assert!(!BlockShape::Stairs.contains(Vec3::new(0.5, 0.75, 0.75)));
+
+        let stairs = BlockType::new("Stairs")
+            .with_shape(BlockShape::Stairs)
+            .with_orientation(BlockOrientation::Facing)
+            .with_texture(1)
+            .with_texture_top(2);
+        let meta = BlockMeta::new().with_facing(Side::Back);
+        assert!(stairs.collides_at(Vec3::new(0.5, 0.75, 0.75), meta));
+        assert!(!stairs.collides_at(Vec3::new(0.5, 0.75, 0.25), meta));
+        let meta = BlockMeta::new().with_facing(Side::Right);
+        assert!(stairs.collides_at(Vec3::new(0.25, 0.75, 0.5), meta));
+        assert!(!stairs.collides_at(Vec3::new(0.75, 0.75, 0.5), meta));
+        assert_eq!(stairs.texture_for(Side::Top, meta), (2, true));
+
+        let err = |json: &str| format!("{:#}", BlockType::from_json(json).unwrap_err());
+        let e = err(
+            r##"[{ "id": "air", "name": "Air" }, { "id": "dirt", "name": "Dirt", "colors": ["red", "#000000"] }]"##,
+        );
+        assert!(e.contains("block #1 (\"dirt\")"), "{}", e);
+        let e = err(r#"[{ "id": "air", "name": "Air" }, { "id": "air", "name": "Air" }]"#);
+        assert!(e.contains("Duplicate id in block #1"), "{}", e);
+        let e = err(
+            r#"[{ "id": "air", "name": "Air" }, { "id": "stone", "name": "Stone", "texture": 300 }]"#,
+        );
+        assert!(e.contains("block #1 (\"stone\")"), "{}", e);
+        let e = err(r#"[{ "id": "air", "name": "Air" }, { "name": "Nameless" }]"#);
+        assert!(e.contains("block #1"), "{}", e);
+        let e = err(r#"[{ "id": "dirt", "name": "Dirt" }]"#);
+        assert!(e.contains("has to be air"), "{}", e);
+        let e = err(r#"[{ "id": "air", "name": "Air" }, { "id": "Bad Id", "name": "Bad" }]"#);
+        assert!(e.contains("lowercase"), "{}", e);
+        let e = err(
+            r#"[{ "id": "air", "name": "Air" }, { "id": "ice", "name": "Ice", "friction": 2 }]"#,
+        );
+        assert!(e.contains("Friction"), "{}", e);
+        let e = err(
+            r#"[{ "id": "air", "name": "Air" }, { "id": "orb", "name": "Orb", "shape": "sphere" }]"#,
+        );
+        assert!(e.contains("block #1 (\"orb\")"), "{}", e);
     }
 }
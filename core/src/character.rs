@@ -1,8 +1,24 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::{BlockItem, Chungus, Experience, GameState, Health, Item, Message, Reactor};
-use glam::{IVec3, Vec3, Vec3Swizzles};
-use std::{f32::consts::PI, time::Instant};
+use crate::{
+    base_max_health, insert_into_slots, knockback, Aabb, BlockId, BlockItem, BlockMeta, Chungus,
+    Experience, GameState, Health, Item, ItemUse, Message, MiningCategory, PerkId, Reactor,
+    StatusEffect, StatusEffects, BLOCKS, GAME_LOG, KNOCKBACK_PER_DAMAGE, PROGRESSION,
+};
+use glam::{IVec3, Vec2, Vec3, Vec3Swizzles};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use std::{cell::RefCell, f32::consts::PI, time::Instant};
+
+mod equipment;
+mod perks;
+mod save;
+pub use equipment::{armor_reduction, EQUIPMENT_SIZE};
+pub use save::CHARACTER_SAVE_VERSION;
+
+/// The first slots of the inventory make up the hotbar
+pub const HOTBAR_SIZE: usize = 10;
+pub const INVENTORY_SIZE: usize = 40;
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum CharacterAnimation {
@@ -18,16 +34,22 @@ pub struct Character {
     pub vel: Vec3,
 
     movement: Vec3,
-    mining: Option<(IVec3, u8)>,
+    mining: Option<(IVec3, BlockId)>,
+    mining_damage: u16,
 
     no_clip: bool,
     cooldown: u64,
     mining_cooldown: u64,
     health: Health,
     experience: Experience,
+    effects: StatusEffects,
+    perks: Vec<PerkId>,
+    /// Sets of perks offered on level up, waiting for the player to choose one
+    perk_choices: Vec<Vec<PerkId>>,
 
     inventory_active: usize,
     inventory: Vec<Item>,
+    equipment: [Item; EQUIPMENT_SIZE],
 
     animation: CharacterAnimation,
 }
@@ -41,16 +63,22 @@ pub enum RaycastReturn {
 
 const CHARACTER_ACCELERATION: f32 = 0.01;
 const CHARACTER_STOP_RATE: f32 = CHARACTER_ACCELERATION * 3.0;
+const CHARACTER_CLIMB_SPEED: f32 = 0.015;
+const CHARACTER_STEP_UP_SPEED: f32 = 0.025;
 
 const COL_WIDTH: f32 = 0.4;
 const COL_DEPTH: f32 = 0.4;
 
-const COL_POINT_TOP: Vec3 = Vec3::new(0.0, 0.7, 0.0);
+const COL_POINT_TOP: Vec3 = Vec3::new(0.0, 0.2, 0.0);
 const COL_POINT_BOTTOM: Vec3 = Vec3::new(0.0, -1.7, 0.0);
-const COL_POINT_LEFT: Vec3 = Vec3::new(-COL_WIDTH, -1.2, 0.0);
-const COL_POINT_RIGHT: Vec3 = Vec3::new(COL_WIDTH, -1.2, 0.0);
-const COL_POINT_FRONT: Vec3 = Vec3::new(0.0, -1.2, COL_DEPTH);
-const COL_POINT_BACK: Vec3 = Vec3::new(0.0, -1.2, -COL_DEPTH);
+/// Right below the feet, where the ground we're standing on is
+const COL_POINT_GROUND: Vec3 = Vec3::new(0.0, -1.75, 0.0);
+
+/// The collision box, relative to the position of the eyes
+const COL_BOX: Aabb = Aabb::new(
+    Vec3::new(-COL_WIDTH, COL_POINT_BOTTOM.y, -COL_DEPTH),
+    Vec3::new(COL_WIDTH, COL_POINT_TOP.y, COL_DEPTH),
+);
 
 impl Character {
     pub fn new() -> Self {
@@ -62,11 +90,15 @@ impl Character {
         self.set_rot(Vec3::new(-130.0, 0.0, 0.0));
         let inv = self.inventory_mut();
         inv.clear();
-        inv.resize(10, Item::None);
+        inv.resize(INVENTORY_SIZE, Item::None);
+        self.equipment = Default::default();
         self.set_inventory_active(0);
-        self.health.set_max_health(12);
+        self.health.set_max_health(base_max_health(1));
         self.health.set_full_health();
         self.experience_mut().reset();
+        self.effects.clear();
+        self.perks.clear();
+        self.perk_choices.clear();
     }
 
     #[inline]
@@ -85,11 +117,22 @@ impl Character {
     pub fn movement(&self) -> Vec3 {
         self.movement
     }
+    /// Take away health directly, ignoring armor, everything that hurts the
+    /// character during play should use take_damage or take_hit instead.
     #[inline]
     pub fn damage(&mut self, amount: i16) {
         self.health.damage(amount);
     }
 
+    #[inline]
+    pub fn effects(&self) -> &StatusEffects {
+        &self.effects
+    }
+    #[inline]
+    pub fn apply_effect(&mut self, effect: StatusEffect) {
+        self.effects.apply(effect);
+    }
+
     #[inline]
     pub fn experience(&self) -> &Experience {
         &self.experience
@@ -99,15 +142,43 @@ impl Character {
         &mut self.experience
     }
 
+    /// All the experience ever gained
     #[inline]
-    pub fn mining(&self) -> Option<(IVec3, u8)> {
+    pub fn score(&self) -> u64 {
+        self.experience.xp_total() + self.experience.xp()
+    }
+
+    #[inline]
+    pub fn mining(&self) -> Option<(IVec3, BlockId)> {
         self.mining
     }
     #[inline]
-    pub fn set_mining(&mut self, m: Option<(IVec3, u8)>) {
+    pub fn set_mining(&mut self, m: Option<(IVec3, BlockId)>) {
         self.mining = m;
     }
 
+    /// How much damage the block being mined takes every tick
+    #[inline]
+    pub fn mining_damage(&self) -> u16 {
+        self.mining_damage
+    }
+
+    #[inline]
+    pub fn set_mining_damage(&mut self, damage: u16) {
+        self.mining_damage = damage;
+    }
+
+    /// Wear down the held item if it is a tool meant for blocks of that
+    /// category, returns the item if it just broke
+    pub fn wear_tool(&mut self, cat: MiningCategory) -> Option<Item> {
+        let item = self.item();
+        if item.is_tool_for(cat) && self.inventory[self.inventory_active].wear() {
+            Some(item)
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn no_clip(&self) -> bool {
         self.no_clip
@@ -134,9 +205,14 @@ impl Character {
         self.movement = v;
     }
 
+    #[inline]
+    pub fn aabb(&self) -> Aabb {
+        COL_BOX.translate(self.pos)
+    }
+
     #[inline]
     pub fn may_jump(&self, world: &Chungus) -> bool {
-        world.is_solid(self.pos + COL_POINT_BOTTOM)
+        world.is_grounded(&self.aabb())
     }
     #[inline]
     pub fn may_act(&self, now: u64) -> bool {
@@ -193,7 +269,7 @@ impl Character {
 
     #[inline]
     pub fn set_inventory_active(&mut self, v: usize) {
-        self.inventory_active = v.clamp(0, self.inventory.len());
+        self.inventory_active = v.min(HOTBAR_SIZE - 1);
     }
 
     #[inline]
@@ -243,16 +319,16 @@ impl Character {
 
     pub fn switch_selection(&mut self, delta: i32) {
         if delta < 0 {
-            self.inventory_active = (self.inventory_active + 1) % self.inventory.len();
+            self.inventory_active = (self.inventory_active + 1) % HOTBAR_SIZE;
         } else {
             self.inventory_active = self.inventory_active.wrapping_sub(1);
-            if self.inventory_active > self.inventory().len() {
-                self.inventory_active = self.inventory.len() - 1;
+            if self.inventory_active >= HOTBAR_SIZE {
+                self.inventory_active = HOTBAR_SIZE - 1;
             }
         }
     }
 
-    pub fn remove_block_from_inventory(&mut self, block: u8) {
+    pub fn remove_block_from_inventory(&mut self, block: BlockId) {
         for item in self.inventory_mut().iter_mut() {
             if let Item::Block(bi) = item {
                 if bi.block != block {
@@ -267,7 +343,7 @@ impl Character {
         }
     }
 
-    pub fn add_block_to_inventory(&mut self, block: u8) {
+    pub fn add_block_to_inventory(&mut self, block: BlockId) {
         for item in self.inventory_mut().iter_mut() {
             if let Item::Block(bi) = item {
                 if bi.block != block {
@@ -288,19 +364,59 @@ impl Character {
         }
     }
 
+    /// Add a whole stack to the inventory, returning whatever didn't fit
+    pub fn add_item_to_inventory(&mut self, item: Item) -> Item {
+        insert_into_slots(&mut self.inventory, item)
+    }
+
     pub fn drop_item(&mut self, pos: usize) -> Item {
-        let item = self.item_at(pos);
-        if let Item::Block(bi) = item {
-            if bi.amount > 1 {
-                let item = BlockItem::new(bi.block, 1).into();
-                self.inventory[pos] = BlockItem::new(bi.block, bi.amount - 1).into();
-                return item;
-            }
-        }
-        self.inventory[pos] = Item::None;
+        self.inventory[pos].take_one()
+    }
+
+    /// Remove up to amount items from a slot
+    pub fn drop_items(&mut self, pos: usize, amount: u16) -> Item {
+        let Some(slot) = self.inventory.get_mut(pos) else {
+            return Item::None;
+        };
+        let amount = amount.min(slot.amount());
+        let item = slot.with_amount(amount);
+        *slot = slot.with_amount(slot.amount() - amount);
         item
     }
 
+    /// Move up to amount items from one slot to another, merging them if they
+    /// stack, swapping both slots if a whole stack is moved onto something else.
+    /// Slots from INVENTORY_SIZE onwards are the equipment slots.
+    pub fn move_inventory_item(&mut self, from: usize, to: usize, amount: u16) {
+        let (Some(src), Some(dst)) = (self.slot(from), self.slot(to)) else {
+            return;
+        };
+        if from == to || !Self::slot_accepts(to, src) {
+            return;
+        }
+        let amount = if to >= INVENTORY_SIZE {
+            amount.min(1)
+        } else {
+            amount
+        };
+        let amount = amount.min(src.amount());
+        if amount == 0 {
+            return;
+        }
+        if dst == Item::None || (dst.stacks_with(&src) && to < INVENTORY_SIZE) {
+            let mut dst = dst;
+            let left = dst.merge(src.with_amount(amount));
+            *self.slot_mut(to).unwrap() = dst;
+            *self.slot_mut(from).unwrap() = src.with_amount(src.amount() - amount + left.amount());
+        } else if amount == src.amount() && Self::slot_accepts(from, dst) {
+            *self.slot_mut(to).unwrap() = src;
+            *self.slot_mut(from).unwrap() = dst;
+        }
+        if from >= INVENTORY_SIZE || to >= INVENTORY_SIZE {
+            self.update_max_health();
+        }
+    }
+
     pub fn wrap_rot(&mut self) {
         if self.rot[0] < 0.0 {
             self.rot[0] += 360.0;
@@ -311,10 +427,25 @@ impl Character {
         self.rot[1] = self.rot[1].clamp(-90.0, 90.0);
     }
 
-    fn is_solid_pillar(&self, pos: Vec3, world: &Chungus) -> bool {
-        world.is_solid(pos)
-            || world.is_solid(pos + Vec3::new(0.0, -0.4, 0.0))
-            || world.is_solid(pos + Vec3::new(0.0, 0.8, 0.0))
+    /// Whether the character is touching a ladder or something similar
+    pub fn is_climbing(&self, world: &Chungus) -> bool {
+        world.is_climbable(self.pos + Vec3::new(0.0, -1.2, 0.0))
+            || world.is_climbable(self.pos + Vec3::new(0.0, -0.4, 0.0))
+    }
+
+    /// Walking against a slab or a stair should let us step on top of it
+    /// without having to jump, so we check whether the obstacle in front of
+    /// our feet is less than half a block high.
+    fn may_step_up(&self, world: &Chungus) -> bool {
+        let dir = self.vel.xz().normalize_or_zero() * (COL_WIDTH + 0.1);
+        if dir == Vec2::ZERO || !self.may_jump(world) {
+            return false;
+        }
+        let feet = self.pos + COL_POINT_BOTTOM + Vec3::new(dir.x, 0.1, dir.y);
+        world.is_solid(feet)
+            && !world.is_solid(feet + Vec3::new(0.0, 0.5, 0.0))
+            && !world.is_solid(feet + Vec3::new(0.0, 1.5, 0.0))
+            && !world.is_solid(self.pos + COL_POINT_TOP + Vec3::new(0.0, 0.5, 0.0))
     }
 
     fn is_underwater_point(world: &Chungus, pos: Vec3) -> bool {
@@ -356,22 +487,52 @@ impl Character {
         }
         let underwater = self.is_underwater(world);
 
+        self.effects.check_surroundings(
+            world,
+            &[
+                self.pos,
+                self.pos + Vec3::new(0.0, -1.2, 0.0),
+                self.pos + COL_POINT_BOTTOM,
+            ],
+        );
+        let damage = self.effects.tick(&mut self.health);
+        self.take_damage(reactor, damage);
+
+        let haste = self.effects.speed_multiplier();
         let accel = if self.movement.xz().length() > 0.01 {
-            CHARACTER_ACCELERATION
+            CHARACTER_ACCELERATION * haste
         } else {
             CHARACTER_STOP_RATE
         };
         let accel = if self.may_jump(world) {
-            accel
+            accel * world.friction(self.pos + COL_POINT_GROUND)
         } else {
             accel * 0.4 // Slow down player movement changes during jumps
         };
         let accel = if underwater { accel * 0.7 } else { accel };
 
-        self.vel.x = self.vel.x * (1.0 - accel) + (self.movement.x * 0.02) * accel;
-        self.vel.z = self.vel.z * (1.0 - accel) + (self.movement.z * 0.02) * accel;
-
-        self.vel.y -= if underwater { 0.0001 } else { 0.0005 };
+        let speed = 0.02 * haste * (1.0 + self.speed_bonus() as f32 / 100.0);
+        self.vel.x = self.vel.x * (1.0 - accel) + (self.movement.x * speed) * accel;
+        self.vel.z = self.vel.z * (1.0 - accel) + (self.movement.z * speed) * accel;
+
+        if self.is_climbing(world) {
+            self.vel.y = if self.movement.y < 0.0 {
+                -CHARACTER_CLIMB_SPEED
+            } else if self.movement.y > 0.0 || self.movement.xz().length() > 0.01 {
+                CHARACTER_CLIMB_SPEED
+            } else {
+                0.0
+            };
+        } else {
+            self.vel.y -= if underwater { 0.0001 } else { 0.0005 };
+        }
+        if self.may_step_up(world) {
+            self.vel.y = self.vel.y.max(CHARACTER_STEP_UP_SPEED);
+        }
+        let len = self.vel.length();
+        if len > 0.5 {
+            self.vel *= 1.0 - (len - 0.2).clamp(0.0001, 1.0);
+        }
         let old = self.vel;
 
         if underwater {
@@ -379,26 +540,8 @@ impl Character {
             self.vel.y *= 0.997;
         }
 
-        if self.is_solid_pillar(self.pos + COL_POINT_LEFT, world) {
-            self.vel.x = self.vel.x.max(0.0);
-        }
-        if self.is_solid_pillar(self.pos + COL_POINT_RIGHT, world) {
-            self.vel.x = self.vel.x.min(0.0);
-        }
-
-        if world.is_solid(self.pos + COL_POINT_BOTTOM) {
-            self.vel.y = self.vel.y.max(0.0);
-        }
-        if world.is_solid(self.pos + COL_POINT_TOP) {
-            self.vel.y = self.vel.y.min(0.0);
-        }
-
-        if self.is_solid_pillar(self.pos + COL_POINT_FRONT, world) {
-            self.vel.z = self.vel.z.min(0.0);
-        }
-        if self.is_solid_pillar(self.pos + COL_POINT_BACK, world) {
-            self.vel.z = self.vel.z.max(0.0);
-        }
+        let collision = world.sweep_aabb(&self.aabb(), self.vel);
+        self.vel = collision.slide(self.vel);
 
         let force = (old - self.vel).length();
         if force > 0.01 {
@@ -407,12 +550,7 @@ impl Character {
         if force > 0.05 {
             let amount = (force * 14.0) as i16;
             if amount > 0 {
-                let damage = amount * amount;
-                self.health.damage(damage);
-                reactor.dispatch(Message::CharacterDamage {
-                    pos: self.pos,
-                    damage,
-                });
+                self.take_damage(reactor, amount.saturating_mul(amount));
             }
         }
 
@@ -421,22 +559,18 @@ impl Character {
         }
 
         let len = self.vel.length();
-        if len > 0.5 {
-            self.vel *= 1.0 - (len - 0.2).clamp(0.0001, 1.0);
-        }
-
         if self.may_jump(world) && (len > 0.01 && cur_tick & 0x7F == 0) {
             reactor.dispatch(Message::CharacterStep { pos: self.pos });
         }
 
-        self.pos += self.vel;
+        self.pos += collision.movement;
     }
 
     pub fn raycast(&self, world: &Chungus, return_value: RaycastReturn) -> Option<IVec3> {
         let dir = self.direction() * 0.0625;
         let mut pos = self.pos();
         let mut i_pos = pos.floor().as_ivec3();
-        if world.is_solid_i(i_pos) {
+        if world.has_block_i(i_pos) {
             return Some(i_pos);
         }
 
@@ -444,7 +578,7 @@ impl Character {
             let n_pos = pos + dir;
             let c_pos = pos.floor().as_ivec3();
 
-            if (i_pos != c_pos) && world.is_solid_i(c_pos) {
+            if (i_pos != c_pos) && world.has_block_i(c_pos) {
                 return Some(if return_value == RaycastReturn::Front {
                     i_pos
                 } else {
@@ -547,14 +681,12 @@ impl Character {
         }
         {
             let player = game.player_rc();
-            let f = move |_reactor: &Reactor<Message>, msg: Message| {
-                if let Message::ItemDropPickup {
-                    item: Item::Block(bi),
-                    ..
-                } = msg
-                {
-                    for _ in 0..bi.amount {
-                        player.borrow_mut().add_block_to_inventory(bi.block);
+            let f = move |reactor: &Reactor<Message>, msg: Message| {
+                if let Message::ItemDropPickup { pos, item } = msg {
+                    let item = player.borrow_mut().add_item_to_inventory(item);
+                    if item != Item::None {
+                        // Whatever didn't fit stays where it is
+                        reactor.reply(Message::ItemDropPickup { pos, item });
                     }
                 }
             };
@@ -619,7 +751,15 @@ impl Character {
                                 player.set_animation_hit();
                                 player.set_cooldown(now + 300);
                                 let b = bi.block;
-                                world.set_block(pos, b);
+                                let dir = player.direction();
+                                let meta = BLOCKS.with(|blocks| {
+                                    blocks
+                                        .borrow()
+                                        .get(b as usize)
+                                        .map(|bt| BlockMeta::from_direction(bt.orientation(), dir))
+                                        .unwrap_or_default()
+                                });
+                                world.set_block_with_meta(pos, b, meta);
                                 player.remove_block_from_inventory(b);
                                 reactor.dispatch(Message::BlockPlace { pos, block: b });
                             }
@@ -629,6 +769,90 @@ impl Character {
             };
             reactor.add_sink(Message::PlayerBlockPlace { pos: IVec3::ZERO }, Box::new(f));
         }
+        {
+            let player = game.player_rc();
+            let world = game.world_rc();
+            let clock = game.clock_rc();
+            let f = move |_reactor: &Reactor<Message>, msg: Message| {
+                if let Message::PlayerInteract { pos } = msg {
+                    let mut player = player.borrow_mut();
+                    let now = clock.borrow().elapsed().as_millis() as u64;
+                    if !player.may_act(now) {
+                        return;
+                    }
+                    let mut world = world.borrow_mut();
+                    let Some(entity) = world.get_block_entity_mut(pos) else {
+                        return;
+                    };
+                    player.set_cooldown(now + 300);
+                    // Holding something puts it into the block, an empty hand takes items out
+                    let active = player.inventory_active();
+                    let item = player.item();
+                    if item == Item::None {
+                        for item in entity.take() {
+                            let rest = player.add_item_to_inventory(item);
+                            if rest != Item::None {
+                                entity.insert(rest);
+                            }
+                        }
+                    } else {
+                        player.inventory_mut()[active] = entity.insert(item);
+                    }
+                    let text = entity.describe();
+                    GAME_LOG.with(|log| log.borrow_mut().push(text));
+                }
+            };
+            reactor.add_sink(Message::PlayerInteract { pos: IVec3::ZERO }, Box::new(f));
+        }
+        {
+            let player = game.player_rc();
+            let clock = game.clock_rc();
+            let f = move |reactor: &Reactor<Message>, _msg: Message| {
+                let now = clock.borrow().elapsed().as_millis() as u64;
+                let use_item = {
+                    let mut player = player.borrow_mut();
+                    if !player.may_act(now) {
+                        return;
+                    }
+                    let item_use = player.item().item_use();
+                    match item_use {
+                        ItemUse::Throw(_) => {
+                            let active = player.inventory_active();
+                            player.inventory_mut()[active].take_one();
+                        }
+                        ItemUse::Equip(_) => {
+                            let name = player.item().name();
+                            if player.equip_active_item() {
+                                player.set_cooldown(now + 300);
+                                let text = format!("Equipped {}", name);
+                                GAME_LOG.with(|log| log.borrow_mut().push(text));
+                            }
+                        }
+                        _ => (),
+                    }
+                    item_use
+                };
+                if let ItemUse::Throw(projectile) = use_item {
+                    reactor.dispatch(Message::PlayerShoot { projectile });
+                }
+            };
+            reactor.add_sink(Message::PlayerUseItem, Box::new(f));
+        }
+        {
+            let player = game.player_rc();
+            let world = game.world_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                if let Message::PlayerCraft { recipe } = msg {
+                    let res = player.borrow_mut().craft(&world.borrow(), recipe);
+                    let text = match res {
+                        Ok(item) => format!("Crafted {} x {}", item.amount(), item.name()),
+                        Err(err) => format!("{}", err),
+                    };
+                    GAME_LOG.with(|log| log.borrow_mut().push(text));
+                }
+            };
+            reactor.add_sink(Message::PlayerCraft { recipe: 0 }, Box::new(f));
+        }
         {
             let player = game.player_rc();
             let clock = game.clock_rc();
@@ -640,6 +864,17 @@ impl Character {
                         if let Some(pos) = pos {
                             if let Some(b) = world.borrow_mut().get_block(pos) {
                                 let mut player = player.borrow_mut();
+                                let cat = BLOCKS.with(|blocks| {
+                                    blocks
+                                        .borrow()
+                                        .get(b as usize)
+                                        .map(|bt| bt.mining_cat())
+                                        .unwrap_or_default()
+                                });
+                                let damage = player.item().mining_damage(cat) as i32;
+                                let bonus = player.perk_stats().mining.max(-100) as i32;
+                                let damage = (damage * (100 + bonus) / 100) as u16;
+                                player.set_mining_damage(damage);
                                 player.set_mining(Some((pos, b)));
                                 if player.may_act(now) {
                                     player.set_animation_hit();
@@ -675,6 +910,58 @@ impl Character {
             };
             reactor.add_sink(Message::PlayerDropItem, Box::new(f));
         }
+        {
+            let player = game.player_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                if let Message::CharacterApplyEffect { effect } = msg {
+                    let mut player = player.borrow_mut();
+                    if !player.is_dead() {
+                        player.apply_effect(effect);
+                    }
+                }
+            };
+            reactor.add_sink(
+                Message::CharacterApplyEffect {
+                    effect: StatusEffect::default(),
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let player = game.player_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                if let Message::PlayerInventoryMove { from, to, amount } = msg {
+                    player.borrow_mut().move_inventory_item(from, to, amount);
+                }
+            };
+            reactor.add_sink(
+                Message::PlayerInventoryMove {
+                    from: 0,
+                    to: 0,
+                    amount: 0,
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let player = game.player_rc();
+            let f = move |reactor: &Reactor<Message>, msg: Message| {
+                if let Message::PlayerInventoryDrop { slot, amount } = msg {
+                    let mut player = player.borrow_mut();
+                    let item = player.drop_items(slot, amount);
+                    if item != Item::None {
+                        let vel = player.direction();
+                        let pos = player.pos() + vel * 2.0;
+                        let vel = vel * 0.03;
+                        reactor.defer(Message::CharacterDropItem { pos, vel, item });
+                    }
+                }
+            };
+            reactor.add_sink(
+                Message::PlayerInventoryDrop { slot: 0, amount: 0 },
+                Box::new(f),
+            );
+        }
         {
             let player = game.player_rc();
             let world = game.world_rc();
@@ -697,15 +984,14 @@ impl Character {
             let f = move |reactor: &Reactor<Message>, msg: Message| {
                 if let Message::CharacterGainExperience { xp, .. } = msg {
                     let mut player = player.borrow_mut();
+                    let bonus = player.perk_stats().xp.max(-100) as i64;
+                    let xp = (xp as i64 * (100 + bonus) / 100) as u64;
+                    let pos = player.pos();
                     let experience = player.experience_mut();
                     experience.gain(xp);
-                    if experience.level_up() {
+                    while experience.level_up() {
                         let level = experience.level();
-                        player.set_max_health(level as i16 * 4 + 8);
-                        reactor.defer(Message::CharacterLevelUp {
-                            pos: player.pos(),
-                            level,
-                        })
+                        reactor.defer(Message::CharacterLevelUp { pos, level })
                     }
                 }
             };
@@ -717,17 +1003,58 @@ impl Character {
                 Box::new(f),
             );
         }
-
         {
             let player = game.player_rc();
+            let rng = RefCell::new(XorShiftRng::from_entropy());
             let f = move |reactor: &Reactor<Message>, msg: Message| {
-                if let Message::MobStrike { damage, .. } = msg {
-                    let pos = {
-                        let mut player = player.borrow_mut();
-                        player.damage(damage);
-                        player.pos()
+                if let Message::CharacterLevelUp { level, .. } = msg {
+                    let rewards = player.borrow_mut().apply_level_rewards(
+                        reactor,
+                        level,
+                        &mut *rng.borrow_mut(),
+                    );
+                    GAME_LOG.with(|log| {
+                        let mut log = log.borrow_mut();
+                        log.push(format!("You reached level {}", level));
+                        rewards.into_iter().for_each(|r| log.push(r));
+                    });
+                }
+            };
+            reactor.add_sink(
+                Message::CharacterLevelUp {
+                    pos: Vec3::ZERO,
+                    level: 0,
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let player = game.player_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                if let Message::PlayerChoosePerk { choice } = msg {
+                    let text = match player.borrow_mut().choose_perk(choice) {
+                        Ok(id) => PROGRESSION.with(|p| {
+                            let p = p.borrow();
+                            let perk = p.perk(id).unwrap();
+                            format!("Chose {}: {}", perk.name(), perk.description())
+                        }),
+                        Err(err) => format!("{}", err),
                     };
-                    reactor.dispatch(Message::CharacterDamage { pos, damage });
+                    GAME_LOG.with(|log| log.borrow_mut().push(text));
+                }
+            };
+            reactor.add_sink(Message::PlayerChoosePerk { choice: 0 }, Box::new(f));
+        }
+
+        {
+            let player = game.player_rc();
+            let f = move |reactor: &Reactor<Message>, msg: Message| {
+                if let Message::MobStrike { pos, damage } = msg {
+                    let mut player = player.borrow_mut();
+                    let damage = player.take_hit(reactor, damage);
+                    let strength = damage as f32 * KNOCKBACK_PER_DAMAGE;
+                    let vel = player.vel() + knockback(pos, player.pos(), strength);
+                    player.set_vel(vel);
                 }
             };
             reactor.add_sink(
@@ -750,3 +1077,95 @@ impl Character {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_collision() {
+        let world = crate::collision::tests::test_world();
+        let reactor = Reactor::new();
+        let mut c = Character::new();
+        c.init();
+        c.set_pos(Vec3::new(0.5, 4.0, 0.5));
+        assert!(!c.may_jump(&world));
+        for tick in 0..400 {
+            c.tick(&reactor, &world, tick);
+        }
+        assert!(c.may_jump(&world));
+        assert!((c.pos().y + COL_POINT_BOTTOM.y).abs() < 0.001);
+
+        // Walking into the wall stops right in front of it
+        c.set_movement(Vec3::new(1.0, 0.0, 0.0));
+        for tick in 0..2000 {
+            c.tick(&reactor, &world, tick);
+        }
+        assert!((c.pos().x + COL_WIDTH - 4.0).abs() < 0.001);
+        assert!(!c.is_dead());
+    }
+
+    #[test]
+    fn test_move_inventory_item() {
+        let mut c = Character::new();
+        c.init();
+        assert_eq!(c.inventory().len(), INVENTORY_SIZE);
+        c.inventory_mut()[0] = BlockItem::new(3, 10).into();
+        c.move_inventory_item(0, 12, 4);
+        assert_eq!(c.inventory()[0], BlockItem::new(3, 6).into());
+        assert_eq!(c.inventory()[12], BlockItem::new(3, 4).into());
+        c.move_inventory_item(12, 0, 99);
+        assert_eq!(c.inventory()[0], BlockItem::new(3, 10).into());
+        assert_eq!(c.inventory()[12], Item::None);
+
+        c.inventory_mut()[1] = BlockItem::new(4, 1).into();
+        c.move_inventory_item(1, 0, 1);
+        assert_eq!(c.inventory()[0], BlockItem::new(4, 1).into());
+        assert_eq!(c.inventory()[1], BlockItem::new(3, 10).into());
+        // Only part of a stack can't be swapped
+        c.move_inventory_item(1, 0, 5);
+        assert_eq!(c.inventory()[1], BlockItem::new(3, 10).into());
+
+        assert_eq!(c.drop_items(1, 4), BlockItem::new(3, 4).into());
+        assert_eq!(c.drop_items(1, 99), BlockItem::new(3, 6).into());
+        assert_eq!(c.inventory()[1], Item::None);
+        assert_eq!(c.drop_items(INVENTORY_SIZE, 1), Item::None);
+
+        c.switch_selection(1);
+        assert_eq!(c.inventory_active(), HOTBAR_SIZE - 1);
+    }
+
+    #[test]
+    fn test_tool_wear_and_pickup() {
+        let mut c = Character::new();
+        c.init();
+        let pick: Item = crate::ItemStack::new(crate::item_id("stone_pickaxe").unwrap(), 1).into();
+        c.inventory_mut()[0] = pick;
+        c.set_inventory_active(0);
+        // Digging dirt with a pickaxe doesn't wear it down
+        assert_eq!(c.wear_tool(MiningCategory::Shovel(1)), None);
+        assert_eq!(c.item(), pick);
+        assert_eq!(c.wear_tool(MiningCategory::Pickaxe(1)), None);
+        assert_ne!(c.item(), pick);
+
+        // Items that don't fit into the inventory are handed back
+        let game = GameState::new().unwrap();
+        let mut reactor = Reactor::new();
+        Character::add_handler(&mut reactor, &game);
+        for slot in game.player_mut().inventory_mut().iter_mut() {
+            *slot = BlockItem::new(3, 99).into();
+        }
+        let msg = Message::ItemDropPickup {
+            pos: Vec3::ZERO,
+            item: BlockItem::new(4, 2).into(),
+        };
+        let answer = reactor.dispatch_with_answer(msg);
+        assert!(matches!(
+            answer[..],
+            [Message::ItemDropPickup { item, .. }] if item == BlockItem::new(4, 2).into()
+        ));
+        game.player_mut().inventory_mut()[5] = Item::None;
+        assert!(reactor.dispatch_with_answer(msg).is_empty());
+        assert_eq!(game.player().inventory()[5], BlockItem::new(4, 2).into());
+    }
+}
@@ -0,0 +1,219 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::{Character, INVENTORY_SIZE};
+use crate::{
+    base_max_health, EquipmentSlot, EquipmentStats, Item, ItemUse, Message, Reactor,
+    EQUIPMENT_SLOTS,
+};
+
+/// How many equipment slots a character has, they come right after the
+/// inventory when addressing slots by index.
+pub const EQUIPMENT_SIZE: usize = EQUIPMENT_SLOTS.len();
+
+/// Reduce damage by armor, with 8 armor points halving the damage. Every hit
+/// still does at least a single point of damage though.
+pub fn armor_reduction(damage: i16, armor: u16) -> i16 {
+    if damage <= 0 {
+        return damage;
+    }
+    let armor = armor as i32 + 8;
+    let reduced = (damage as i32 * 8 + armor - 1) / armor;
+    reduced.clamp(1, damage as i32) as i16
+}
+
+impl Character {
+    #[inline]
+    pub fn equipment(&self) -> &[Item; EQUIPMENT_SIZE] {
+        &self.equipment
+    }
+
+    #[inline]
+    pub fn equipment_mut(&mut self) -> &mut [Item; EQUIPMENT_SIZE] {
+        &mut self.equipment
+    }
+
+    /// Get a slot by index, equipment slots directly follow the inventory
+    pub fn slot(&self, i: usize) -> Option<Item> {
+        if i < INVENTORY_SIZE {
+            self.inventory.get(i).copied()
+        } else {
+            self.equipment.get(i - INVENTORY_SIZE).copied()
+        }
+    }
+
+    pub(super) fn slot_mut(&mut self, i: usize) -> Option<&mut Item> {
+        if i < INVENTORY_SIZE {
+            self.inventory.get_mut(i)
+        } else {
+            self.equipment.get_mut(i - INVENTORY_SIZE)
+        }
+    }
+
+    /// Whether item may be put into slot i, equipment slots only take
+    /// equipment meant for them.
+    pub fn slot_accepts(i: usize, item: Item) -> bool {
+        if i < INVENTORY_SIZE || item == Item::None {
+            return true;
+        }
+        match (EQUIPMENT_SLOTS.get(i - INVENTORY_SIZE), item.item_use()) {
+            (Some(slot), ItemUse::Equip(stats)) => stats.slot == *slot,
+            _ => false,
+        }
+    }
+
+    fn equipment_stats(&self) -> impl Iterator<Item = EquipmentStats> + '_ {
+        self.equipment.iter().filter_map(|i| match i.item_use() {
+            ItemUse::Equip(stats) => Some(stats),
+            _ => None,
+        })
+    }
+
+    /// Armor from equipment and perks, saturating instead of overflowing
+    pub fn armor(&self) -> u16 {
+        self.equipment_stats()
+            .fold(self.perk_stats().armor, |acc, s| {
+                acc.saturating_add(s.armor)
+            })
+    }
+
+    /// Movement speed modifier in percent, from equipment and perks
+    pub fn speed_bonus(&self) -> i16 {
+        self.equipment_stats()
+            .fold(self.perk_stats().speed, |acc, s| {
+                acc.saturating_add(s.speed)
+            })
+            .max(-90)
+    }
+
+    /// Max health from equipment and perks, saturating instead of overflowing
+    pub fn max_health_bonus(&self) -> i16 {
+        self.equipment_stats()
+            .fold(self.perk_stats().max_health, |acc, s| {
+                acc.saturating_add(s.max_health)
+            })
+    }
+
+    /// Recalculate max health after the level, equipment or perks changed
+    pub fn update_max_health(&mut self) {
+        let max_hp =
+            base_max_health(self.experience.level()).saturating_add(self.max_health_bonus());
+        self.health.set_max_health(max_hp.max(1));
+    }
+
+    /// Put the active item into the equipment slot it belongs to, whatever
+    /// was equipped before takes its place in the inventory.
+    pub fn equip_active_item(&mut self) -> bool {
+        let ItemUse::Equip(stats) = self.item().item_use() else {
+            return false;
+        };
+        let before = self.item();
+        let slot = INVENTORY_SIZE + stats.slot as usize;
+        self.move_inventory_item(self.inventory_active, slot, 1);
+        self.item() != before
+    }
+
+    /// Every way the character gets hurt ends up here, the damage gets
+    /// reduced by the armor worn and announced with a CharacterDamage.
+    /// Falls and status effects use this directly, so they are reduced by
+    /// armor too but don't wear it down. Returns the damage actually dealt.
+    pub fn take_damage(&mut self, reactor: &Reactor<Message>, damage: i16) -> i16 {
+        let damage = armor_reduction(damage, self.armor());
+        if damage > 0 {
+            self.health.damage(damage);
+            reactor.dispatch(Message::CharacterDamage {
+                pos: self.pos,
+                damage,
+            });
+        }
+        damage
+    }
+
+    /// Damage the character from a hit, just like take_damage but the armor
+    /// also wears down a little with every hit.
+    pub fn take_hit(&mut self, reactor: &Reactor<Message>, damage: i16) -> i16 {
+        let damage = self.take_damage(reactor, damage);
+        let mut broke = false;
+        for (i, item) in self.equipment.iter_mut().enumerate() {
+            if EQUIPMENT_SLOTS[i] != EquipmentSlot::Accessory && item.wear() {
+                broke = true;
+            }
+        }
+        if broke {
+            self.update_max_health();
+        }
+        damage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{item_id, ItemStack, PROGRESSION};
+
+    #[test]
+    fn test_equipment() {
+        assert_eq!(armor_reduction(8, 0), 8);
+        assert_eq!(armor_reduction(8, 8), 4);
+        assert_eq!(armor_reduction(1, 100), 1);
+        assert_eq!(armor_reduction(0, 4), 0);
+
+        let mut c = Character::new();
+        c.init();
+        let helmet = ItemStack::new(item_id("iron_helmet").unwrap(), 1);
+        let amulet = ItemStack::new(item_id("heart_amulet").unwrap(), 1);
+        c.inventory_mut()[0] = helmet.into();
+        c.inventory_mut()[1] = amulet.into();
+        c.inventory_mut()[2] = ItemStack::new(item_id("grenade").unwrap(), 3).into();
+
+        // Only fitting items may go into an equipment slot
+        c.move_inventory_item(2, INVENTORY_SIZE, 3);
+        assert_eq!(c.equipment()[0], Item::None);
+        c.move_inventory_item(1, INVENTORY_SIZE, 1);
+        assert_eq!(c.equipment()[0], Item::None);
+
+        assert!(c.equip_active_item());
+        assert_eq!(c.equipment()[0], helmet.into());
+        assert_eq!(c.inventory()[0], Item::None);
+        assert_eq!(c.armor(), 2);
+
+        c.move_inventory_item(1, INVENTORY_SIZE + 3, 1);
+        assert_eq!(c.max_health_bonus(), 8);
+        assert_eq!(c.health().max_health(), 20);
+
+        let reactor = Reactor::new();
+        let hp = c.health().health();
+        assert_eq!(c.take_hit(&reactor, 5), 4);
+        assert_eq!(c.health().health(), hp - 4);
+        assert!(c.equipment()[0].amount() > 0);
+        assert!(matches!(
+            reactor.log()[..],
+            [Message::CharacterDamage { damage: 4, .. }]
+        ));
+
+        // Damage that isn't a hit gets reduced as well, without wearing the armor down
+        let helmet = c.equipment()[0];
+        assert_eq!(c.take_damage(&reactor, 5), 4);
+        assert_eq!(c.equipment()[0], helmet);
+        assert_eq!(c.health().health(), hp - 8);
+
+        // Taking off equipment removes its bonus again
+        c.move_inventory_item(INVENTORY_SIZE + 3, 5, 1);
+        assert_eq!(c.health().max_health(), 12);
+
+        // Huge perk totals combined with equipment saturate instead of overflowing
+        let (tough, thick_skin) = PROGRESSION.with(|p| {
+            let p = p.borrow();
+            (
+                p.perk_id("tough").unwrap(),
+                p.perk_id("thick_skin").unwrap(),
+            )
+        });
+        c.perks = vec![tough; 10000];
+        c.perks.extend(vec![thick_skin; 40000]);
+        c.move_inventory_item(5, INVENTORY_SIZE + 3, 1);
+        assert_eq!(c.armor(), u16::MAX);
+        assert_eq!(c.max_health_bonus(), i16::MAX);
+        assert_eq!(c.health().max_health(), i16::MAX);
+        assert!(c.take_hit(&reactor, i16::MAX) >= 1);
+    }
+}
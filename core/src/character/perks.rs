@@ -0,0 +1,138 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::Character;
+use crate::{Item, LevelReward, Message, PerkId, PerkStats, Reactor, PROGRESSION};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+
+impl Character {
+    #[inline]
+    pub fn perks(&self) -> &[PerkId] {
+        &self.perks
+    }
+
+    /// The perks the player may currently choose from, if any
+    #[inline]
+    pub fn perk_choice(&self) -> Option<&[PerkId]> {
+        self.perk_choices.first().map(|c| c.as_slice())
+    }
+
+    /// How many level ups are still waiting for a perk to be chosen
+    #[inline]
+    pub fn pending_perk_choices(&self) -> usize {
+        self.perk_choices.len()
+    }
+
+    /// Combined bonuses of all perks chosen so far
+    pub fn perk_stats(&self) -> PerkStats {
+        PROGRESSION.with(|p| {
+            let p = p.borrow();
+            self.perks
+                .iter()
+                .filter_map(|id| p.perk(*id))
+                .fold(PerkStats::default(), |acc, perk| acc + perk.stats())
+        })
+    }
+
+    pub fn add_perk(&mut self, id: PerkId) {
+        self.perks.push(id);
+        self.update_max_health();
+    }
+
+    /// Pick one out of the oldest set of offered perks
+    pub fn choose_perk(&mut self, choice: usize) -> Result<PerkId> {
+        let id = *self
+            .perk_choice()
+            .ok_or_else(|| anyhow!("There is no perk to choose"))?
+            .get(choice)
+            .ok_or_else(|| anyhow!("There is no perk #{}", choice + 1))?;
+        self.perk_choices.remove(0);
+        self.add_perk(id);
+        Ok(id)
+    }
+
+    /// Grant everything PROGRESSION has in store for reaching level, returns
+    /// a description of every reward for the game log. Items that don't fit
+    /// into the inventory get dropped.
+    pub fn apply_level_rewards(
+        &mut self,
+        reactor: &Reactor<Message>,
+        level: u8,
+        rng: &mut impl Rng,
+    ) -> Vec<String> {
+        let rewards: Vec<LevelReward> = PROGRESSION.with(|p| p.borrow().rewards(level).collect());
+        let mut log = vec![];
+        for reward in rewards {
+            match reward {
+                LevelReward::MaxHealth(hp) => log.push(format!("{:+} max health", hp)),
+                LevelReward::Item(item) => {
+                    let rest = self.add_item_to_inventory(item);
+                    if rest != Item::None {
+                        reactor.defer(Message::ItemDropNew {
+                            pos: self.pos(),
+                            item: rest,
+                        });
+                    }
+                    log.push(format!("Received {} x {}", item.amount(), item.name()));
+                }
+                LevelReward::PerkChoice(count) => {
+                    let choice = PROGRESSION.with(|p| p.borrow().roll_perks(rng, count));
+                    if !choice.is_empty() {
+                        self.perk_choices.push(choice);
+                        log.push("A new perk is available, press P to choose".to_string());
+                    }
+                }
+            }
+        }
+        self.update_max_health();
+        self.health.set_full_health();
+        log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_perks() {
+        let mut rng = XorShiftRng::seed_from_u64(2);
+        let mut c = Character::new();
+        c.init();
+        c.experience_mut().gain(40);
+        assert!(c.experience_mut().level_up());
+        let reactor = Reactor::new();
+        c.apply_level_rewards(&reactor, 2, &mut rng);
+        assert_eq!(c.health().max_health(), 16);
+        assert_eq!(c.pending_perk_choices(), 1);
+        assert!(c.choose_perk(99).is_err());
+
+        let tough = PROGRESSION.with(|p| p.borrow().perk_id("tough").unwrap());
+        c.perk_choices[0] = vec![tough];
+        assert_eq!(c.choose_perk(0).unwrap(), tough);
+        assert_eq!(c.pending_perk_choices(), 0);
+        assert_eq!(c.perk_stats().max_health, 4);
+        assert_eq!(c.health().max_health(), 20);
+        assert!(c.choose_perk(0).is_err());
+
+        // Rewards that don't fit into the inventory get dropped instead
+        for slot in c.inventory_mut().iter_mut() {
+            *slot = crate::BlockItem::new(3, 99).into();
+        }
+        c.apply_level_rewards(&reactor, 2, &mut rng);
+        assert!(reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::ItemDropNew { item, .. } if item.amount() == 4)));
+
+        let huge = PerkStats {
+            max_health: i16::MAX,
+            armor: u16::MAX,
+            ..Default::default()
+        };
+        let sum = huge + huge;
+        assert_eq!((sum.max_health, sum.armor), (i16::MAX, u16::MAX));
+    }
+}
@@ -0,0 +1,302 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::{Character, EQUIPMENT_SIZE, HOTBAR_SIZE, INVENTORY_SIZE};
+use crate::{
+    block_id, item_id, BlockItem, Experience, Health, Item, ItemStack, PerkId, BLOCKS, ITEMS,
+    PROGRESSION,
+};
+use anyhow::{anyhow, Context, Result};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bump this whenever the format changes in an incompatible way
+pub const CHARACTER_SAVE_VERSION: u32 = 1;
+
+/// Items are stored using their string ids, so reordering blocks.json or
+/// items.json doesn't mess up old saves. Variants that an older version
+/// doesn't know about only cost a single slot, not the whole save.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "T", rename_all = "snake_case")]
+enum SavedItem {
+    Block {
+        id: String,
+        amount: u16,
+    },
+    Stack {
+        id: String,
+        amount: u16,
+        durability: u16,
+    },
+}
+
+impl SavedItem {
+    fn from_item(item: Item) -> Option<Self> {
+        match item {
+            Item::None => None,
+            Item::Block(bi) => {
+                let id = BLOCKS.with(|blocks| {
+                    let blocks = blocks.borrow();
+                    blocks.get(bi.block as usize).map(|b| b.id().to_string())
+                })?;
+                Some(Self::Block {
+                    id,
+                    amount: bi.amount,
+                })
+            }
+            Item::Stack(s) => {
+                let id = ITEMS.with(|items| {
+                    let items = items.borrow();
+                    items.get(s.item as usize).map(|i| i.id().to_string())
+                })?;
+                Some(Self::Stack {
+                    id,
+                    amount: s.amount,
+                    durability: s.durability,
+                })
+            }
+        }
+    }
+
+    fn into_item(self) -> Result<Item> {
+        match self {
+            Self::Block { id, amount } => {
+                let block = block_id(&id).ok_or_else(|| anyhow!("Unknown block {:?}", id))?;
+                Ok(BlockItem::new(block, amount).into())
+            }
+            Self::Stack {
+                id,
+                amount,
+                durability,
+            } => {
+                let item = item_id(&id).ok_or_else(|| anyhow!("Unknown item {:?}", id))?;
+                let mut stack = ItemStack::new(item, amount);
+                if stack.durability > 0 {
+                    stack.durability = durability.clamp(1, stack.durability);
+                }
+                Ok(stack.into())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CharacterSave {
+    version: u32,
+    pos: Vec3,
+    rot: Vec3,
+    health: Health,
+    experience: Experience,
+    inventory_active: usize,
+    /// Kept as raw values so a single broken slot can be skipped
+    inventory: Vec<(usize, serde_json::Value)>,
+    #[serde(default)]
+    equipment: Vec<(usize, serde_json::Value)>,
+    #[serde(default)]
+    perks: Vec<String>,
+    #[serde(default)]
+    perk_choices: Vec<Vec<String>>,
+}
+
+fn save_perks(perks: &[PerkId]) -> Vec<String> {
+    PROGRESSION.with(|p| {
+        let p = p.borrow();
+        perks
+            .iter()
+            .filter_map(|id| p.perk(*id).map(|perk| perk.id().to_string()))
+            .collect()
+    })
+}
+
+/// Perks are looked up by their string id, unknown ones only produce a warning
+fn load_perks(perks: &[String], warnings: &mut Vec<String>) -> Vec<PerkId> {
+    PROGRESSION.with(|p| {
+        let p = p.borrow();
+        perks
+            .iter()
+            .filter_map(|id| {
+                let perk = p.perk_id(id);
+                if perk.is_none() {
+                    warnings.push(format!("Unknown perk {:?}", id));
+                }
+                perk
+            })
+            .collect()
+    })
+}
+
+fn save_slots(slots: &[Item]) -> Result<Vec<(usize, serde_json::Value)>> {
+    slots
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| Some((i, SavedItem::from_item(*item)?)))
+        .map(|(i, item)| Ok((i, serde_json::to_value(item)?)))
+        .collect()
+}
+
+/// Restore saved slots into slots, returning a warning for every slot that couldn't be restored
+fn load_slots(
+    slots: &mut [Item],
+    saved: Vec<(usize, serde_json::Value)>,
+    accepts: impl Fn(usize, Item) -> bool,
+) -> Vec<String> {
+    let mut warnings = vec![];
+    for (i, value) in saved.into_iter() {
+        let item = serde_json::from_value::<SavedItem>(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|item| item.into_item());
+        match (slots.get_mut(i), item) {
+            (Some(slot), Ok(item)) if accepts(i, item) => {
+                *slot = item.with_amount(item.amount().min(item.max_stack()))
+            }
+            (Some(_), Ok(item)) => {
+                warnings.push(format!("{} doesn't fit into slot {}", item.name(), i))
+            }
+            (None, _) => warnings.push(format!("Slot {} doesn't exist", i)),
+            (_, Err(err)) => warnings.push(format!("Couldn't restore slot {}: {}", i, err)),
+        }
+    }
+    warnings
+}
+
+impl Character {
+    pub fn save_json(&self) -> Result<String> {
+        let save = CharacterSave {
+            version: CHARACTER_SAVE_VERSION,
+            pos: self.pos,
+            rot: self.rot,
+            health: self.health,
+            experience: self.experience,
+            inventory_active: self.inventory_active,
+            inventory: save_slots(&self.inventory)?,
+            equipment: save_slots(&self.equipment)?,
+            perks: save_perks(&self.perks),
+            perk_choices: self.perk_choices.iter().map(|c| save_perks(c)).collect(),
+        };
+        Ok(serde_json::to_string_pretty(&save)?)
+    }
+
+    /// Restore a character from a save, the character is only modified if
+    /// the save could be read. Slots that couldn't be restored are returned
+    /// as warnings.
+    pub fn load_json(&mut self, json: &str) -> Result<Vec<String>> {
+        let save: CharacterSave = serde_json::from_str(json).context("Invalid character save")?;
+        if save.version > CHARACTER_SAVE_VERSION {
+            return Err(anyhow!(
+                "Character save version {} is newer than {}",
+                save.version,
+                CHARACTER_SAVE_VERSION
+            ));
+        }
+        if !save.pos.is_finite() || !save.rot.is_finite() {
+            return Err(anyhow!("Invalid position in character save"));
+        }
+        let max_hp = save.health.max_health();
+        if max_hp <= 0 {
+            return Err(anyhow!("Invalid health in character save"));
+        }
+        let mut health = Health::new(max_hp);
+        health.damage(max_hp - save.health.health().clamp(1, max_hp));
+        if save.experience.level() == 0 || save.experience.next_level() == 0 {
+            return Err(anyhow!("Invalid experience in character save"));
+        }
+
+        let mut inventory = vec![Item::None; INVENTORY_SIZE];
+        let mut warnings = load_slots(&mut inventory, save.inventory, |_, _| true);
+        let mut equipment = [Item::None; EQUIPMENT_SIZE];
+        warnings.extend(load_slots(&mut equipment, save.equipment, |i, item| {
+            Character::slot_accepts(INVENTORY_SIZE + i, item)
+        }));
+        let perks = load_perks(&save.perks, &mut warnings);
+        let perk_choices = save
+            .perk_choices
+            .iter()
+            .map(|c| load_perks(c, &mut warnings))
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        self.init();
+        self.pos = save.pos;
+        self.rot = save.rot;
+        self.health = health;
+        self.experience = save.experience;
+        self.inventory = inventory;
+        self.equipment = equipment;
+        self.perks = perks;
+        self.perk_choices = perk_choices;
+        self.update_max_health();
+        self.inventory_active = save.inventory_active.min(HOTBAR_SIZE - 1);
+        Ok(warnings)
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first, so a crash can't leave a half written save
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.save_json()?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(&mut self, path: &Path) -> Result<Vec<String>> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        self.load_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_save() {
+        let mut c = Character::new();
+        c.init();
+        c.set_pos(Vec3::new(1.0, 2.0, 3.0));
+        c.damage(5);
+        c.experience_mut().gain(40);
+        c.experience_mut().level_up();
+        c.inventory_mut()[0] = BlockItem::new(block_id("stone").unwrap(), 12).into();
+        let mut pick = ItemStack::new(item_id("stone_pickaxe").unwrap(), 1);
+        pick.durability -= 10;
+        c.inventory_mut()[15] = pick.into();
+        c.set_inventory_active(3);
+        c.equipment_mut()[0] = ItemStack::new(item_id("iron_helmet").unwrap(), 1).into();
+        let tough = PROGRESSION.with(|p| p.borrow().perk_id("tough").unwrap());
+        c.add_perk(tough);
+        c.perk_choices.push(vec![tough]);
+
+        let json = c.save_json().unwrap();
+        let mut d = Character::new();
+        assert!(d.load_json(&json).unwrap().is_empty());
+        assert_eq!(d.pos(), c.pos());
+        assert_eq!(d.health().health(), c.health().health());
+        assert_eq!(d.experience().level(), 2);
+        assert_eq!(d.experience().xp(), c.experience().xp());
+        assert_eq!(d.inventory(), c.inventory());
+        assert_eq!(d.inventory_active(), 3);
+        assert_eq!(d.equipment(), c.equipment());
+        assert_eq!(d.perks(), &[tough]);
+        assert_eq!(d.perk_choice(), Some(&[tough][..]));
+        assert_eq!(d.health().max_health(), c.health().max_health());
+
+        // Unknown ids only lose a single slot
+        let json = json.replace("\"stone_pickaxe\"", "\"unobtainium\"");
+        let warnings = d.load_json(&json).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(d.inventory()[15], Item::None);
+        assert_eq!(d.inventory()[0], c.inventory()[0]);
+
+        let mut e = Character::new();
+        e.init();
+        assert!(e.load_json("{ \"version\": 1 }").is_err());
+        assert!(e.load_json("garbage").is_err());
+        let newer = json.replace("\"version\": 1", "\"version\": 9999");
+        assert!(e.load_json(&newer).is_err());
+        assert_eq!(e.inventory().len(), INVENTORY_SIZE);
+        assert_eq!(e.experience().level(), 1);
+    }
+}
@@ -1,6 +1,9 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::{blit_chunk_buffer, blit_chunk_data, ChunkBuffer, ChunkPosIter};
+use crate::{
+    blit_chunk_buffer, blit_chunk_data, block_id, BlockEntity, BlockEntityStash, BlockId,
+    BlockMeta, ChunkBuffer, ChunkData, ChunkPosIter, SavedBlockEntity,
+};
 use crate::{
     worldgen, worldgen::WorldgenAssetList, BlockType, ChunkBlockData, ChunkFluidData,
     ChunkLightData, ChunkRequestQueue, GameState, Message, Reactor, CHUNK_BITS, CHUNK_MASK,
@@ -10,7 +13,7 @@ use anyhow::Result;
 use glam::f32::Vec3;
 use glam::i32::IVec3;
 use noise::utils::{NoiseMap, NoiseMapBuilder, PlaneMapBuilder};
-use noise::{Perlin, Seedable};
+use noise::Perlin;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
 use std::cell::RefCell;
@@ -19,6 +22,9 @@ use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::time::Instant;
 
+/// The seed used for worlds that aren't part of a particular run
+pub const DEFAULT_SEED: u32 = 1234;
+
 thread_local! {
     pub static BLOCKS:RefCell<Vec<BlockType>> = RefCell::new(BlockType::new_default());
     pub static FLUIDS:RefCell<Vec<BlockType>> = RefCell::new(BlockType::new_default_fluids());
@@ -33,12 +39,19 @@ pub struct Chungus {
     displacement: NoiseMap,
     noise_map: NoiseMap,
     assets: WorldgenAssetList,
+    seed: u32,
+    /// Block entities of chunks that got dropped, restored once they're generated again
+    block_entity_stash: BlockEntityStash,
 }
 
 impl Chungus {
+    /// Drop block chunks far away from the player, worldgen recreates them
+    /// later. Since worldgen can't bring back chest contents or sign text,
+    /// block entities get stashed along with their block until then.
     pub fn gc_block(&mut self, player_pos: Vec3, render_distance: f32) {
         let max_d = render_distance * render_distance * 4.0;
-        self.chunks_block.retain(|&pos, _| {
+        let stash = &mut self.block_entity_stash;
+        self.chunks_block.retain(|&pos, chunk| {
             let diff: Vec3 = (pos.as_vec3() * CHUNK_SIZE as f32)
                 + Vec3::new(
                     CHUNK_SIZE as f32 / 2.0,
@@ -47,10 +60,60 @@ impl Chungus {
                 )
                 - player_pos;
             let d = diff.dot(diff);
-            d < (max_d)
+            let keep = d < (max_d);
+            if !keep {
+                Self::saved_entities(pos, chunk).for_each(|e| stash.insert(e));
+            }
+            keep
         });
     }
 
+    /// Every block entity within chunk, ready to be stashed or saved
+    fn saved_entities(
+        pos: IVec3,
+        chunk: &ChunkBlockData,
+    ) -> impl Iterator<Item = SavedBlockEntity> + '_ {
+        chunk.entities().filter_map(move |(p, entity)| {
+            let block = BLOCKS.with(|b| {
+                let block = chunk.get_block(p) as usize;
+                b.borrow().get(block).map(|bt| bt.id().to_string())
+            })?;
+            Some(SavedBlockEntity {
+                pos: (pos << CHUNK_BITS) + p,
+                block,
+                meta: chunk.get_meta(p).raw(),
+                entity: entity.clone(),
+            })
+        })
+    }
+
+    /// Put stashed block entities back into a freshly generated chunk,
+    /// along with the block they belong to.
+    fn restore_block_entities(&mut self, pos: IVec3, chunk: &mut ChunkBlockData) {
+        for saved in self.block_entity_stash.take(pos) {
+            let Some(block) = block_id(&saved.block) else {
+                continue;
+            };
+            let p = saved.pos & CHUNK_MASK;
+            chunk.set_block_with_meta(block, BlockMeta::from_raw(saved.meta), p);
+            chunk.set_entity(p, Some(saved.entity));
+        }
+    }
+
+    /// Every block entity, whether its chunk is loaded or not, for saving
+    pub fn block_entity_save(&self) -> BlockEntityStash {
+        let mut ret = self.block_entity_stash.clone();
+        for (pos, chunk) in self.chunks_block.iter() {
+            Self::saved_entities(*pos, chunk).for_each(|e| ret.insert(e));
+        }
+        ret
+    }
+
+    /// Replace the stashed block entities, like the ones loaded from a save
+    pub fn set_block_entity_stash(&mut self, stash: BlockEntityStash) {
+        self.block_entity_stash = stash;
+    }
+
     pub fn gc_simple_light(&mut self, player_pos: Vec3, render_distance: f32) {
         let max_d = render_distance * render_distance * 4.0;
         self.chunks_simple_light.retain(|&pos, _| {
@@ -81,18 +144,18 @@ impl Chungus {
         });
     }
 
-    fn fluid_tick_remove_blocked(buf: &mut ChunkBuffer, blocks: &ChunkBlockData) {
+    fn fluid_tick_remove_blocked(buf: &mut ChunkBuffer, blocks: &ChunkData<BlockId>) {
         for (x, y, z) in ChunkPosIter::new() {
-            if blocks.data[x][y][z] == 0 {
+            if blocks[x][y][z] == 0 {
                 continue;
             }
             buf[x + 1][y + 1][z + 1] = 0;
         }
     }
 
-    fn fluid_tick_flow(buf: &mut ChunkBuffer, block: &ChunkBlockData) {
+    fn fluid_tick_flow(buf: &mut ChunkBuffer, block: &ChunkData<BlockId>) {
         for (x, y, z) in ChunkPosIter::new() {
-            if block.data[x][y][z] != 0 {
+            if block[x][y][z] != 0 {
                 continue;
             }
             let x = x + 1;
@@ -120,7 +183,7 @@ impl Chungus {
             let x = CHUNK_SIZE - 1 - x;
             let y = CHUNK_SIZE - 1 - y;
             let z = CHUNK_SIZE - 1 - z;
-            if block.data[x][y][z] != 0 {
+            if block[x][y][z] != 0 {
                 continue;
             }
             let x = x + 1;
@@ -220,8 +283,9 @@ impl Chungus {
                         blit_chunk_data(&mut buf, &f.data, [1, 1 + CHUNK_SIZE as isize, 1]);
                     }
                 }
-                Self::fluid_tick_flow(&mut buf, block);
-                Self::fluid_tick_remove_blocked(&mut buf, block);
+                let block = block.decode();
+                Self::fluid_tick_flow(&mut buf, &block);
+                Self::fluid_tick_remove_blocked(&mut buf, &block);
 
                 q.push((*pos, buf));
             }
@@ -319,6 +383,60 @@ impl Chungus {
             );
         }
 
+        {
+            let world = game.world_rc();
+            let f = move |_reactor: &Reactor<Message>, msg: Message| {
+                if let Message::BlockPlace { pos, block } = msg {
+                    let kind = BLOCKS
+                        .with(|blocks| blocks.borrow().get(block as usize).map(|bt| bt.entity()));
+                    let entity = kind.and_then(BlockEntity::new);
+                    world.borrow_mut().set_block_entity(pos, entity);
+                }
+            };
+            reactor.add_sink(
+                Message::BlockPlace {
+                    pos: IVec3::ZERO,
+                    block: 0,
+                },
+                Box::new(f),
+            );
+        }
+
+        {
+            let world = game.world_rc();
+            let f = move |reactor: &Reactor<Message>, msg: Message| {
+                if let Message::BlockBreak { pos, .. } = msg {
+                    if let Some(entity) = world.borrow_mut().take_block_entity(pos) {
+                        let pos = pos.as_vec3() + Vec3::splat(0.5);
+                        for item in entity.contents() {
+                            reactor.defer(Message::ItemDropNew { pos, item });
+                        }
+                    }
+                }
+            };
+            reactor.add_sink(
+                Message::BlockBreak {
+                    pos: IVec3::ZERO,
+                    block: 0,
+                },
+                Box::new(f),
+            );
+        }
+
+        {
+            let world = game.world_rc();
+            let f = move |_reactor: &Reactor<Message>, msg: Message| {
+                if let Message::GameTick { ticks } = msg {
+                    // Block entities don't need to be very responsive, so
+                    // we only tick them every 64 game ticks (~256ms).
+                    if (ticks & 0x3F) == 0 {
+                        world.borrow_mut().tick_block_entities();
+                    }
+                }
+            };
+            reactor.add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
+        }
+
         {
             let world = game.world_rc();
             reactor.add_sink(
@@ -330,7 +448,7 @@ impl Chungus {
         }
     }
 
-    pub fn handle_requests(&mut self, request: &mut ChunkRequestQueue, reactor: &Reactor<Message>) {
+    pub fn handle_requests(&mut self, request: &mut ChunkRequestQueue) {
         let mut simple_light_reqs: HashSet<IVec3> = HashSet::new();
         let mut block_reqs: HashSet<IVec3> = HashSet::new();
 
@@ -374,7 +492,8 @@ impl Chungus {
         for pos in request.get_block_mut().drain() {
             let chunk = self.chunks_block.get(&pos);
             if chunk.is_none() {
-                let wg = worldgen::chunk(self, pos, reactor);
+                let mut wg = worldgen::chunk(self, pos);
+                self.restore_block_entities(pos, &mut wg.0);
                 self.chunks_block.insert(pos, wg.0);
                 self.chunks_fluid.insert(pos, wg.1);
             }
@@ -533,6 +652,16 @@ impl Chungus {
         self.chunks_block.get_mut(k)
     }
 
+    /// Put a chunk into the world, replacing whatever was there before
+    pub fn insert(&mut self, k: IVec3, chunk: ChunkBlockData) {
+        self.chunks_block.insert(k, chunk);
+    }
+
+    /// Put a fluid chunk into the world, replacing whatever was there before
+    pub fn insert_fluid(&mut self, k: IVec3, chunk: ChunkFluidData) {
+        self.chunks_fluid.insert(k, chunk);
+    }
+
     #[inline]
     pub fn get_light(&self, k: &IVec3) -> Option<&ChunkLightData> {
         self.chunks_complex_light.get(k)
@@ -558,42 +687,117 @@ impl Chungus {
         self.get(&cp).is_some()
     }
 
-    pub fn is_solid(&self, pos: Vec3) -> bool {
-        let cp = pos.floor().as_ivec3() >> CHUNK_BITS;
-        if let Some(chnk) = self.get(&cp) {
-            let cx = (pos.x.floor() as i32 & CHUNK_MASK) as usize;
-            let cy = (pos.y.floor() as i32 & CHUNK_MASK) as usize;
-            let cz = (pos.z.floor() as i32 & CHUNK_MASK) as usize;
-            let b = chnk.data[cx][cy][cz];
-            b != 0
-        } else {
-            false
+    /// Look up the type of the block at pos and run f on it, returns None
+    /// for air and unloaded chunks.
+    fn with_block_type<T>(&self, pos: IVec3, f: impl FnOnce(&BlockType) -> T) -> Option<T> {
+        let b = self.get_block(pos)?;
+        if b == 0 {
+            return None;
         }
+        BLOCKS.with(|blocks| blocks.borrow().get(b as usize).map(f))
+    }
+
+    pub fn is_solid(&self, pos: Vec3) -> bool {
+        let floor = pos.floor();
+        let i_pos = floor.as_ivec3();
+        let meta = self.get_block_meta(i_pos).unwrap_or_default();
+        self.with_block_type(i_pos, |bt| bt.collides_at(pos - floor, meta))
+            .unwrap_or(false)
     }
 
     pub fn is_solid_i(&self, pos: IVec3) -> bool {
-        let cp = pos >> CHUNK_BITS;
-        if let Some(chnk) = self.get(&cp) {
-            let IVec3 { x, y, z } = pos & CHUNK_MASK;
-            let b = chnk.data[x as usize][y as usize][z as usize];
-            b != 0
-        } else {
-            false
-        }
+        self.with_block_type(pos, |bt| bt.solid()).unwrap_or(false)
+    }
+
+    /// Unlike is_solid_i this also returns true for blocks that can't
+    /// be collided with, like plants or ladders.
+    pub fn has_block_i(&self, pos: IVec3) -> bool {
+        self.get_block(pos).map(|b| b != 0).unwrap_or(false)
     }
 
-    pub fn set_block(&mut self, pos: IVec3, block: u8) {
+    pub fn is_climbable(&self, pos: Vec3) -> bool {
+        self.with_block_type(pos.floor().as_ivec3(), |bt| bt.climbable())
+            .unwrap_or(false)
+    }
+
+    /// Friction of the block at pos, air and unloaded chunks count as 1.0
+    pub fn friction(&self, pos: Vec3) -> f32 {
+        self.with_block_type(pos.floor().as_ivec3(), |bt| bt.friction())
+            .unwrap_or(1.0)
+    }
+
+    pub fn set_block(&mut self, pos: IVec3, block: BlockId) {
         let cp = pos >> CHUNK_BITS;
         if let Some(chnk) = self.get_mut(&cp) {
             chnk.set_block(block, pos & CHUNK_MASK);
         }
     }
 
-    pub fn get_block(&self, pos: IVec3) -> Option<u8> {
+    pub fn get_block(&self, pos: IVec3) -> Option<BlockId> {
         let cp = pos >> CHUNK_BITS;
         self.get(&cp).map(|chnk| chnk.get_block(pos & CHUNK_MASK))
     }
 
+    pub fn set_block_with_meta(&mut self, pos: IVec3, block: BlockId, meta: BlockMeta) {
+        let cp = pos >> CHUNK_BITS;
+        if let Some(chnk) = self.get_mut(&cp) {
+            chnk.set_block_with_meta(block, meta, pos & CHUNK_MASK);
+        }
+    }
+
+    pub fn get_block_meta(&self, pos: IVec3) -> Option<BlockMeta> {
+        let cp = pos >> CHUNK_BITS;
+        self.get(&cp).map(|chnk| chnk.get_meta(pos & CHUNK_MASK))
+    }
+
+    pub fn get_block_entity(&self, pos: IVec3) -> Option<&BlockEntity> {
+        let cp = pos >> CHUNK_BITS;
+        self.get(&cp)
+            .and_then(|chnk| chnk.get_entity(pos & CHUNK_MASK))
+    }
+
+    pub fn get_block_entity_mut(&mut self, pos: IVec3) -> Option<&mut BlockEntity> {
+        let cp = pos >> CHUNK_BITS;
+        self.get_mut(&cp)
+            .and_then(|chnk| chnk.get_entity_mut(pos & CHUNK_MASK))
+    }
+
+    pub fn set_block_entity(&mut self, pos: IVec3, entity: Option<BlockEntity>) {
+        let cp = pos >> CHUNK_BITS;
+        if let Some(chnk) = self.get_mut(&cp) {
+            chnk.set_entity(pos & CHUNK_MASK, entity);
+        }
+    }
+
+    pub fn take_block_entity(&mut self, pos: IVec3) -> Option<BlockEntity> {
+        let cp = pos >> CHUNK_BITS;
+        self.get_mut(&cp)
+            .and_then(|chnk| chnk.take_entity(pos & CHUNK_MASK))
+    }
+
+    /// Tick every block entity within a loaded chunk
+    pub fn tick_block_entities(&mut self) {
+        BLOCKS.with(|blocks| {
+            let blocks = blocks.borrow();
+            for chunk in self.chunks_block.values_mut() {
+                if chunk.has_entities() {
+                    chunk.tick_entities(&blocks);
+                }
+            }
+        });
+    }
+
+    /// Light level between 0 and 15 at pos, None if it hasn't been calculated yet
+    pub fn light_level(&self, pos: IVec3) -> Option<u8> {
+        let cp = pos >> CHUNK_BITS;
+        let light = self
+            .chunks_complex_light
+            .get(&cp)
+            .or_else(|| self.chunks_simple_light.get(&cp))?;
+        let p = (pos & CHUNK_MASK).as_uvec3();
+        Some(light.data[p.x as usize][p.y as usize][p.z as usize])
+    }
+
     pub fn get_fluid_block(&self, pos: IVec3) -> Option<u8> {
         let cp = pos >> CHUNK_BITS;
         self.get_fluid(&cp).map(|chnk| chnk.get(pos & CHUNK_MASK))
@@ -617,7 +821,7 @@ impl Chungus {
                         let pos = pos + IVec3::new(x, y, z);
                         if let Some(block) = self.get_block(pos) {
                             if block != 0 {
-                                if rng.gen_ratio(1, 100) {
+                                if rng.gen_ratio(1, 100) || self.get_block_entity(pos).is_some() {
                                     reactor.defer(Message::BlockBreak { pos, block });
                                 }
                                 self.set_block(pos, 0);
@@ -634,29 +838,46 @@ impl Chungus {
         self.chunks_fluid.clear();
         self.chunks_simple_light.clear();
         self.chunks_complex_light.clear();
+        self.block_entity_stash.clear();
     }
 
-    pub fn new() -> Result<Self> {
-        let simplex: Perlin = Perlin::default();
-        simplex.set_seed(1234);
+    #[inline]
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Switch to a different seed, throwing away every chunk generated so far
+    pub fn set_seed(&mut self, seed: u32) {
+        if seed != self.seed {
+            (self.elevation, self.displacement, self.noise_map) = Self::noise_maps(seed);
+            self.seed = seed;
+        }
+        self.init();
+    }
+
+    fn noise_maps(seed: u32) -> (NoiseMap, NoiseMap, NoiseMap) {
+        let simplex = Perlin::new(seed);
         let elevation: NoiseMap = PlaneMapBuilder::<Perlin, 2>::new(simplex)
             .set_size(2048, 2048)
             .set_x_bounds(-5.0, 5.0)
             .set_y_bounds(-5.0, 5.0)
             .build();
 
-        let simplex: Perlin = Perlin::default();
-        simplex.set_seed(2345);
+        let simplex = Perlin::new(seed.wrapping_add(1111));
         let displacement: NoiseMap = PlaneMapBuilder::<Perlin, 2>::new(simplex)
             .set_size(128, 128)
             .build();
 
-        let simplex: Perlin = Perlin::default();
-        simplex.set_seed(3456);
+        let simplex = Perlin::new(seed.wrapping_add(2222));
         let noise_map: NoiseMap = PlaneMapBuilder::<Perlin, 2>::new(simplex)
             .set_size(128, 128)
             .build();
+        (elevation, displacement, noise_map)
+    }
 
+    pub fn new() -> Result<Self> {
+        let seed = DEFAULT_SEED;
+        let (elevation, displacement, noise_map) = Self::noise_maps(seed);
         let assets = WorldgenAssetList::new()?;
 
         Ok(Self {
@@ -668,6 +889,36 @@ impl Chungus {
             displacement,
             noise_map,
             assets,
+            seed,
+            block_entity_stash: BlockEntityStash::new(),
         })
     }
+
+    /// A world that is just a flat floor right below y = 0, reaching extent
+    /// blocks from the origin along x and z, with every chunk around it
+    /// loaded and lit. Meant for tests that shouldn't depend on worldgen.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn new_flat(floor: &str, extent: i32) -> Self {
+        let mut world = Self::new().unwrap();
+        let floor = crate::block_id(floor).unwrap();
+        let chunks =
+            (-extent).div_euclid(CHUNK_SIZE as i32)..=(extent - 1).div_euclid(CHUNK_SIZE as i32);
+        let mut request = ChunkRequestQueue::new();
+        for x in chunks.clone() {
+            for y in -1..=1 {
+                for z in chunks.clone() {
+                    let pos = IVec3::new(x, y, z);
+                    world.insert(pos, ChunkBlockData::new());
+                    request.simple_light(pos);
+                }
+            }
+        }
+        for x in -extent..extent {
+            for z in -extent..extent {
+                world.set_block(IVec3::new(x, -1, z), floor);
+            }
+        }
+        world.handle_requests(&mut request);
+        world
+    }
 }
@@ -31,7 +31,11 @@ fn blit_chunk_data_end(off: isize) -> (usize, usize) {
     )
 }
 
-pub fn blit_chunk_data(block_data: &mut ChunkBuffer, chunk: &ChunkData, off: [isize; 3]) {
+pub fn blit_chunk_data<T: Copy>(
+    block_data: &mut ChunkBuffer<T>,
+    chunk: &ChunkData<T>,
+    off: [isize; 3],
+) {
     let (x_start, x_end) = blit_chunk_data_end(off[0]);
     let (y_start, y_end) = blit_chunk_data_end(off[1]);
     let (z_start, z_end) = blit_chunk_data_end(off[2]);
@@ -48,7 +52,7 @@ pub fn blit_chunk_data(block_data: &mut ChunkBuffer, chunk: &ChunkData, off: [is
     }
 }
 
-pub fn blit_chunk_buffer(chunk: &mut ChunkData, buf: &ChunkBuffer) {
+pub fn blit_chunk_buffer<T: Copy>(chunk: &mut ChunkData<T>, buf: &ChunkBuffer<T>) {
     for (x, chunk) in chunk.iter_mut().enumerate() {
         let cx = (x as isize + 1) as usize;
         for (y, chunk) in chunk.iter_mut().enumerate() {
@@ -1,13 +1,30 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::{ChunkData, CHUNK_SIZE};
+use super::blit_chunk_data_end;
+use crate::{BlockEntity, BlockId, BlockMeta, BlockType, ChunkBuffer, ChunkData, CHUNK_SIZE};
 use glam::IVec3;
+use std::collections::HashMap;
 use std::time::Instant;
 
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Block data is stored as indices into a per-chunk palette of global
+/// BlockIds. The indices are packed into u64 words using as few bits as
+/// possible (0, 1, 2, 4, 8 or 16), so a chunk that only contains air
+/// needs no storage at all, and entries never straddle word boundaries.
+///
+/// Most blocks don't need any metadata, which is why it is kept in a
+/// separate map only containing non-default entries, the same goes for
+/// block entities like chests, which live alongside the blocks. Those get
+/// stashed when the chunk is garbage collected, see Chungus::gc_block.
 #[derive(Clone, Debug)]
 pub struct ChunkBlockData {
     last_updated: Instant,
-    pub data: ChunkData,
+    palette: Vec<BlockId>,
+    bits: u8,
+    words: Vec<u64>,
+    meta: HashMap<u16, BlockMeta>,
+    entities: HashMap<u16, BlockEntity>,
 }
 
 impl Default for ChunkBlockData {
@@ -16,11 +33,50 @@ impl Default for ChunkBlockData {
     }
 }
 
+#[inline]
+fn index(x: usize, y: usize, z: usize) -> usize {
+    (x * CHUNK_SIZE + y) * CHUNK_SIZE + z
+}
+
+#[inline]
+fn index_v(pos: IVec3) -> usize {
+    index(pos.x as usize, pos.y as usize, pos.z as usize)
+}
+
+/// Smallest bit width able to address n palette entries
+fn bits_for(n: usize) -> u8 {
+    match n {
+        0..=1 => 0,
+        2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        17..=256 => 8,
+        _ => 16,
+    }
+}
+
+fn word_count(bits: u8) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        CHUNK_VOLUME / (64 / bits as usize)
+    }
+}
+
 impl ChunkBlockData {
     pub fn new() -> Self {
-        let data = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-        let last_updated = Instant::now();
-        Self { data, last_updated }
+        Self::new_filled(0)
+    }
+
+    pub fn new_filled(block: BlockId) -> Self {
+        Self {
+            last_updated: Instant::now(),
+            palette: vec![block],
+            bits: 0,
+            words: vec![],
+            meta: HashMap::new(),
+            entities: HashMap::new(),
+        }
     }
 
     #[inline]
@@ -29,16 +85,216 @@ impl ChunkBlockData {
     }
 
     #[inline]
-    pub fn get_block(&self, pos: IVec3) -> u8 {
-        self.data[pos.x as usize][pos.y as usize][pos.z as usize]
+    pub fn palette(&self) -> &[BlockId] {
+        &self.palette
+    }
+
+    #[inline]
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Approximate number of bytes used for the block data
+    #[inline]
+    pub fn memory_usage(&self) -> usize {
+        self.words.len() * 8
+            + self.palette.len() * std::mem::size_of::<BlockId>()
+            + self.meta.len() * 3
+    }
+
+    /// Returns the block filling the entire chunk, if it is uniform, without decoding anything
+    #[inline]
+    pub fn is_uniform(&self) -> Option<BlockId> {
+        if self.bits == 0 {
+            Some(self.palette[0])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn get_index(&self, i: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let bits = self.bits as usize;
+        let per_word = 64 / bits;
+        let word = self.words[i / per_word];
+        let shift = (i % per_word) * bits;
+        ((word >> shift) & ((1 << bits) - 1)) as usize
+    }
+
+    #[inline]
+    fn set_index(&mut self, i: usize, v: usize) {
+        let bits = self.bits as usize;
+        let per_word = 64 / bits;
+        let shift = (i % per_word) * bits;
+        let mask = ((1u64 << bits) - 1) << shift;
+        let word = &mut self.words[i / per_word];
+        *word = (*word & !mask) | ((v as u64) << shift);
+    }
+
+    /// Repack all entries using a new bit width and palette mapping
+    fn repack(&mut self, bits: u8, remap: &[usize]) {
+        let old = std::mem::replace(
+            self,
+            Self {
+                last_updated: self.last_updated,
+                palette: vec![],
+                bits,
+                words: vec![0; word_count(bits)],
+                meta: HashMap::new(),
+                entities: HashMap::new(),
+            },
+        );
+        if bits > 0 {
+            for i in 0..CHUNK_VOLUME {
+                self.set_index(i, remap[old.get_index(i)]);
+            }
+        }
+        self.palette = old.palette;
+        self.meta = old.meta;
+        self.entities = old.entities;
+    }
+
+    /// Remove unused palette entries and shrink the bit width if possible
+    pub fn compact(&mut self) {
+        if self.bits == 0 {
+            return;
+        }
+        let mut used = vec![false; self.palette.len()];
+        for i in 0..CHUNK_VOLUME {
+            used[self.get_index(i)] = true;
+        }
+        if used.iter().all(|u| *u) {
+            return;
+        }
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+        for (i, block) in self.palette.iter().enumerate() {
+            if used[i] {
+                remap[i] = palette.len();
+                palette.push(*block);
+            }
+        }
+        let bits = bits_for(palette.len());
+        self.repack(bits, &remap);
+        self.palette = palette;
+    }
+
+    /// Return the palette index for block, adding it to the palette if necessary
+    fn palette_index(&mut self, block: BlockId) -> usize {
+        if let Some(i) = self.palette.iter().position(|b| *b == block) {
+            return i;
+        }
+        if self.palette.len() >= (1 << self.bits) {
+            self.compact();
+        }
+        if self.palette.len() >= (1 << self.bits) {
+            let remap: Vec<usize> = (0..self.palette.len()).collect();
+            self.repack(bits_for(self.palette.len() + 1), &remap);
+        }
+        self.palette.push(block);
+        self.palette.len() - 1
+    }
+
+    #[inline]
+    pub fn get_block(&self, pos: IVec3) -> BlockId {
+        self.palette[self.get_index(index_v(pos))]
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.palette[self.get_index(index(x, y, z))]
+    }
+
+    #[inline]
+    pub fn get_meta(&self, pos: IVec3) -> BlockMeta {
+        if self.meta.is_empty() {
+            return BlockMeta::default();
+        }
+        self.meta
+            .get(&(index_v(pos) as u16))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn get_entity(&self, pos: IVec3) -> Option<&BlockEntity> {
+        self.entities.get(&(index_v(pos) as u16))
+    }
+
+    #[inline]
+    pub fn get_entity_mut(&mut self, pos: IVec3) -> Option<&mut BlockEntity> {
+        self.entities.get_mut(&(index_v(pos) as u16))
+    }
+
+    /// Attach an entity to a position, replacing any previous one. Unlike
+    /// metadata, entities are not removed by set_block, since their contents
+    /// still have to be dropped once a BlockBreak gets handled.
+    pub fn set_entity(&mut self, pos: IVec3, entity: Option<BlockEntity>) {
+        let i = index_v(pos) as u16;
+        match entity {
+            Some(e) => self.entities.insert(i, e),
+            None => self.entities.remove(&i),
+        };
+    }
+
+    pub fn take_entity(&mut self, pos: IVec3) -> Option<BlockEntity> {
+        self.entities.remove(&(index_v(pos) as u16))
+    }
+
+    #[inline]
+    pub fn has_entities(&self) -> bool {
+        !self.entities.is_empty()
+    }
+
+    /// Iterate over all entities along with their position within the chunk
+    pub fn entities(&self) -> impl Iterator<Item = (IVec3, &BlockEntity)> {
+        self.entities.iter().map(|(i, e)| {
+            let i = *i as i32;
+            let s = CHUNK_SIZE as i32;
+            (IVec3::new(i / (s * s), (i / s) % s, i % s), e)
+        })
+    }
+
+    /// Tick all entities, those whose block has been replaced but that
+    /// haven't been broken yet are left alone.
+    pub fn tick_entities(&mut self, blocks: &[BlockType]) {
+        let mut entities = std::mem::take(&mut self.entities);
+        for (i, e) in entities.iter_mut() {
+            let block = self.palette[self.get_index(*i as usize)];
+            if blocks.get(block as usize).map(|bt| bt.entity()) == Some(e.kind()) {
+                e.tick();
+            }
+        }
+        self.entities = entities;
     }
 
-    pub fn set_block(&mut self, block: u8, pos: IVec3) {
+    fn set_block_raw(&mut self, block: BlockId, i: usize) {
+        if !self.meta.is_empty() {
+            self.meta.remove(&(i as u16));
+        }
+        if self.bits == 0 && self.palette[0] == block {
+            return;
+        }
+        let v = self.palette_index(block);
+        self.set_index(i, v);
+    }
+
+    pub fn set_block(&mut self, block: BlockId, pos: IVec3) {
         self.last_updated = Instant::now();
-        self.data[pos.x as usize][pos.y as usize][pos.z as usize] = block
+        self.set_block_raw(block, index_v(pos));
+    }
+
+    pub fn set_block_with_meta(&mut self, block: BlockId, meta: BlockMeta, pos: IVec3) {
+        self.set_block(block, pos);
+        if !meta.is_default() {
+            self.meta.insert(index_v(pos) as u16, meta);
+        }
     }
 
-    pub fn set_sphere(&mut self, block: u8, pos: IVec3, radius: i32) {
+    pub fn set_sphere(&mut self, block: BlockId, pos: IVec3, radius: i32) {
         self.last_updated = Instant::now();
         let rr = radius * radius;
         for cx in -radius..radius {
@@ -46,32 +302,79 @@ impl ChunkBlockData {
                 for cz in -radius..radius {
                     let dist = (cx * cx) + (cy * cy) + (cz * cz);
                     if dist < rr {
-                        self.data[(pos.x + cx) as usize][(pos.y + cy) as usize]
-                            [(pos.z + cz) as usize] = block;
+                        self.set_block_raw(block, index_v(pos + IVec3::new(cx, cy, cz)));
                     }
                 }
             }
         }
     }
 
-    pub fn set_box(&mut self, block: u8, pos: IVec3, size: IVec3) {
+    pub fn set_box(&mut self, block: BlockId, pos: IVec3, size: IVec3) {
         self.last_updated = Instant::now();
         let [w, h, d] = size.to_array();
         for cx in 0..w {
             for cy in 0..h {
                 for cz in 0..d {
-                    self.data[(pos.x + cx) as usize][(pos.y + cy) as usize]
-                        [(pos.z + cz) as usize] = block;
+                    self.set_block_raw(block, index_v(pos + IVec3::new(cx, cy, cz)));
                 }
             }
         }
     }
 
-    pub fn set_pillar(&mut self, block: u8, pos: IVec3, goal_y: i32) {
+    pub fn set_pillar(&mut self, block: BlockId, pos: IVec3, goal_y: i32) {
         let y = pos.y.max(0);
         let goal_y = goal_y.min(CHUNK_SIZE as i32);
         for y in y..goal_y {
-            self.data[pos.x as usize][y as usize][pos.z as usize] = block;
+            self.set_block_raw(block, index(pos.x as usize, y as usize, pos.z as usize));
+        }
+    }
+
+    /// Decode the whole chunk into a plain array
+    pub fn decode(&self) -> ChunkData<BlockId> {
+        let mut ret = [[[self.palette[0]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        if self.bits == 0 {
+            return ret;
+        }
+        for (x, ret) in ret.iter_mut().enumerate() {
+            for (y, ret) in ret.iter_mut().enumerate() {
+                for (z, ret) in ret.iter_mut().enumerate() {
+                    *ret = self.get(x, y, z);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Same as blit_chunk_data, but decodes only the blocks that actually end up in the buffer
+    pub fn blit_into(&self, buf: &mut ChunkBuffer<BlockId>, off: [isize; 3]) {
+        let (x_start, x_end) = blit_chunk_data_end(off[0]);
+        let (y_start, y_end) = blit_chunk_data_end(off[1]);
+        let (z_start, z_end) = blit_chunk_data_end(off[2]);
+
+        for (x, buf) in buf.iter_mut().enumerate().take(x_end).skip(x_start) {
+            let cx = (x as isize - off[0]) as usize;
+            for (y, buf) in buf.iter_mut().enumerate().take(y_end).skip(y_start) {
+                let cy = (y as isize - off[1]) as usize;
+                for (z, buf) in buf.iter_mut().enumerate().take(z_end).skip(z_start) {
+                    let cz = (z as isize - off[2]) as usize;
+                    *buf = self.get(cx, cy, cz);
+                }
+            }
+        }
+    }
+
+    /// Copy the metadata into buf, just like blit_into does for blocks.
+    /// Since metadata is sparse only non-default entries get written.
+    pub fn blit_meta_into(&self, buf: &mut ChunkBuffer, off: [isize; 3]) {
+        let size = (CHUNK_SIZE + 2) as isize;
+        for (i, meta) in self.meta.iter() {
+            let i = *i as usize;
+            let x = (i / (CHUNK_SIZE * CHUNK_SIZE)) as isize + off[0];
+            let y = ((i / CHUNK_SIZE) % CHUNK_SIZE) as isize + off[1];
+            let z = (i % CHUNK_SIZE) as isize + off[2];
+            if (0..size).contains(&x) && (0..size).contains(&y) && (0..size).contains(&z) {
+                buf[x as usize][y as usize][z as usize] = meta.raw();
+            }
         }
     }
 }
@@ -82,7 +385,7 @@ mod tests {
 
     fn sum(chunk: &ChunkBlockData) -> i32 {
         let mut acc: i32 = 0;
-        for x in chunk.data.iter() {
+        for x in chunk.decode().iter() {
             for y in x.iter() {
                 for z in y.iter() {
                     acc += *z as i32;
@@ -100,7 +403,7 @@ mod tests {
         assert_eq!(sum(&chunk), 0);
         let mut chunk = ChunkBlockData::default();
         assert_eq!(sum(&chunk), 0);
-        chunk.data[0][0][0] = 1;
+        chunk.set_block(1, IVec3::new(0, 0, 0));
         assert_eq!(sum(&chunk), 1);
         assert_eq!(chunk.get_block(IVec3::new(0, 0, 0)), 1);
         chunk.set_block(4, IVec3::new(1, 1, 1));
@@ -111,4 +414,102 @@ mod tests {
         chunk.set_sphere(1, IVec3::new(8, 8, 8), 4);
         assert_eq!(sum(&chunk), 259);
     }
+
+    #[test]
+    fn test_palette() {
+        let mut chunk = ChunkBlockData::new();
+        assert_eq!(chunk.bits(), 0);
+        assert_eq!(chunk.memory_usage(), 2);
+        assert_eq!(chunk.is_uniform(), Some(0));
+
+        chunk.set_block(1000, IVec3::new(1, 2, 3));
+        assert_eq!(chunk.bits(), 1);
+        assert_eq!(chunk.is_uniform(), None);
+        assert_eq!(chunk.get_block(IVec3::new(1, 2, 3)), 1000);
+        assert_eq!(chunk.get_block(IVec3::new(3, 2, 1)), 0);
+
+        // Force the palette to grow all the way up to 16 bits
+        for i in 0..300 {
+            let pos = IVec3::new(i % 32, i / 32, 7);
+            chunk.set_block(i as BlockId + 2000, pos);
+        }
+        assert_eq!(chunk.bits(), 16);
+        for i in 0..300 {
+            let pos = IVec3::new(i % 32, i / 32, 7);
+            assert_eq!(chunk.get_block(pos), i as BlockId + 2000);
+        }
+        assert_eq!(chunk.get_block(IVec3::new(1, 2, 3)), 1000);
+        assert_eq!(chunk.get_block(IVec3::new(31, 31, 31)), 0);
+
+        // Overwriting everything should let compact shrink the chunk again
+        chunk.set_box(BlockId::MAX, IVec3::ZERO, IVec3::splat(32));
+        chunk.set_block(3, IVec3::new(4, 5, 6));
+        chunk.compact();
+        assert_eq!(chunk.bits(), 1);
+        assert_eq!(chunk.palette(), &[BlockId::MAX, 3]);
+        assert_eq!(chunk.get_block(IVec3::new(4, 5, 6)), 3);
+        assert_eq!(chunk.get_block(IVec3::new(4, 5, 7)), BlockId::MAX);
+
+        chunk.set_block(BlockId::MAX, IVec3::new(4, 5, 6));
+        chunk.compact();
+        assert_eq!(chunk.is_uniform(), Some(BlockId::MAX));
+        assert_eq!(chunk.memory_usage(), 2);
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut chunk = ChunkBlockData::new();
+        chunk.set_block(300, IVec3::new(0, 0, 0));
+        chunk.set_block(301, IVec3::new(31, 31, 31));
+        let mut buf: ChunkBuffer<BlockId> = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+        chunk.blit_into(&mut buf, [1, 1, 1]);
+        assert_eq!(buf[1][1][1], 300);
+        assert_eq!(buf[32][32][32], 301);
+        assert_eq!(buf[0][0][0], 0);
+        chunk.blit_into(&mut buf, [1 - CHUNK_SIZE as isize, 1, 1]);
+        assert_eq!(buf[0][32][32], 301);
+    }
+
+    #[test]
+    fn test_meta() {
+        let mut chunk = ChunkBlockData::new();
+        let pos = IVec3::new(31, 2, 3);
+        let meta = BlockMeta::new().with_facing(crate::Side::Left);
+        chunk.set_block_with_meta(5, meta, pos);
+        assert_eq!(chunk.get_block(pos), 5);
+        assert_eq!(chunk.get_meta(pos), meta);
+        assert!(chunk.get_meta(IVec3::new(3, 2, 1)).is_default());
+
+        // Growing the palette must not lose any metadata
+        for i in 0..20 {
+            chunk.set_block(i + 10, IVec3::new(i as i32, 0, 0));
+        }
+        assert_eq!(chunk.get_meta(pos), meta);
+
+        let mut buf: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+        chunk.blit_meta_into(&mut buf, [1, 1, 1]);
+        assert_eq!(buf[32][3][4], meta.raw());
+        chunk.blit_meta_into(&mut buf, [-30, 1, 1]);
+        assert_eq!(buf[1][3][4], meta.raw());
+
+        // Replacing a block resets its metadata
+        chunk.set_block(5, pos);
+        assert!(chunk.get_meta(pos).is_default());
+    }
+
+    #[test]
+    fn test_entities() {
+        let mut chunk = ChunkBlockData::new();
+        let pos = IVec3::new(1, 30, 7);
+        chunk.set_entity(pos, BlockEntity::new(crate::BlockEntityKind::Sign));
+        assert!(chunk.has_entities());
+        for i in 0..20 {
+            chunk.set_block(i + 10, IVec3::new(i as i32, 0, 0));
+        }
+        assert_eq!(chunk.entities().next().unwrap().0, pos);
+        assert!(chunk.get_entity_mut(pos).unwrap().set_text("Hello"));
+        assert!(chunk.get_entity(pos).is_some());
+        assert!(chunk.take_entity(pos).is_some());
+        assert!(!chunk.has_entities());
+    }
 }
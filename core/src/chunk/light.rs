@@ -1,7 +1,7 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
 use super::*;
-use crate::{Chungus, ChunkData, ChunkPosIter, CHUNK_MASK, CHUNK_SIZE};
+use crate::{BlockId, Chungus, ChunkData, ChunkPosIter, BLOCKS, CHUNK_MASK, CHUNK_SIZE};
 use std::time::Instant;
 
 #[derive(Clone, Debug)]
@@ -35,12 +35,25 @@ impl ChunkLightData {
         self.last_updated
     }
 
-    fn sunlight(&mut self, chunk: &ChunkBlockData, light: &mut [[u8; CHUNK_SIZE]; CHUNK_SIZE]) {
+    /// Transforms the block data into a map of opaque blocks, since
+    /// light should pass through glass, plants and the like.
+    fn opaque_blocks(chunk: &ChunkData<BlockId>) -> ChunkData<bool> {
+        BLOCKS.with(|blocks| {
+            let opaque: Vec<bool> = blocks.borrow().iter().map(|b| b.is_opaque_cube()).collect();
+            let mut ret = [[[false; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+            for (x, y, z) in ChunkPosIter::new() {
+                let b = chunk[x][y][z];
+                ret[x][y][z] = b != 0 && opaque.get(b as usize).copied().unwrap_or(true);
+            }
+            ret
+        })
+    }
+
+    fn sunlight(&mut self, chunk: &ChunkData<bool>, light: &mut [[u8; CHUNK_SIZE]; CHUNK_SIZE]) {
         for y in (0..CHUNK_SIZE).rev() {
             for (x, light) in light.iter_mut().enumerate() {
                 for (z, light) in light.iter_mut().enumerate() {
-                    let b = chunk.data[x][y][z];
-                    if b != 0 {
+                    if chunk[x][y][z] {
                         *light = 0;
                         self.data[x][y][z] = 0; // blockLight[b]
                     } else {
@@ -112,23 +125,25 @@ impl ChunkLightData {
         self.blur_y();
     }
 
-    fn ambient_occlusion(&mut self, chunk: &ChunkBlockData) {
+    fn ambient_occlusion(&mut self, chunk: &ChunkData<bool>) {
         for (x, y, z) in ChunkPosIter::new() {
-            if chunk.data[x][y][z] != 0 {
+            if chunk[x][y][z] {
                 self.data[x][y][z] /= 2;
             }
         }
     }
 
     pub fn calculate(&mut self, chunk: &ChunkBlockData) {
+        let chunk = Self::opaque_blocks(&chunk.decode());
         let mut light = [[0; CHUNK_SIZE]; CHUNK_SIZE];
-        self.sunlight(chunk, &mut light);
+        self.sunlight(&chunk, &mut light);
         self.blur();
-        self.ambient_occlusion(chunk);
+        self.ambient_occlusion(&chunk);
         self.last_updated = Instant::now();
     }
 
     pub fn calculate_complex(&mut self, chunk: &ChunkBlockData, neighbors: &[&ChunkLightData; 27]) {
+        let chunk = Self::opaque_blocks(&chunk.decode());
         let mut light = [[0; CHUNK_SIZE]; CHUNK_SIZE];
         let src = neighbors[Chungus::neighbor_off(1, 2, 1)];
         for (x, light) in light.iter_mut().enumerate() {
@@ -136,7 +151,7 @@ impl ChunkLightData {
                 *light = src.data[x][0][z];
             }
         }
-        self.sunlight(chunk, &mut light);
+        self.sunlight(&chunk, &mut light);
         for x in 0..CHUNK_SIZE {
             for z in 0..CHUNK_SIZE {
                 self.data[x][0][z] = self.data[x][0][z].max(
@@ -168,7 +183,7 @@ impl ChunkLightData {
             }
         }
         self.blur();
-        self.ambient_occlusion(chunk);
+        self.ambient_occlusion(&chunk);
         self.last_updated = Instant::now();
     }
 }
@@ -0,0 +1,232 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{Chungus, BLOCKS};
+use glam::{IVec3, Vec3};
+
+/// Anything closer than this counts as touching
+const COLLISION_EPSILON: f32 = 1.0 / 4096.0;
+
+/// How far below a box we look for ground to stand on
+const GROUND_DISTANCE: f32 = 0.02;
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// A box around center extending by half_size in every direction
+    pub fn from_center(center: Vec3, half_size: Vec3) -> Self {
+        Self::new(center - half_size, center + half_size)
+    }
+
+    #[inline]
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Scale the box relative to the origin, so that it stays centered the same way
+    #[inline]
+    pub fn scale(&self, factor: f32) -> Self {
+        Self::new(self.min * factor, self.max * factor)
+    }
+
+    /// Grow the box so that it covers everything it passes when moved by movement
+    pub fn extend(&self, movement: Vec3) -> Self {
+        Self::new(
+            self.min + movement.min(Vec3::ZERO),
+            self.max + movement.max(Vec3::ZERO),
+        )
+    }
+
+    /// Whether both boxes overlap, merely touching doesn't count
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..3).all(|i| self.overlaps_on(other, i))
+    }
+
+    fn overlaps_on(&self, other: &Self, axis: usize) -> bool {
+        self.max[axis] > other.min[axis] + COLLISION_EPSILON
+            && self.min[axis] < other.max[axis] - COLLISION_EPSILON
+    }
+
+    /// Shorten movement along axis so that self stops right in front of other
+    fn clip(&self, other: &Self, axis: usize, movement: f32) -> f32 {
+        let others = [(axis + 1) % 3, (axis + 2) % 3];
+        if !others.iter().all(|i| self.overlaps_on(other, *i)) {
+            return movement;
+        }
+        if movement > 0.0 && self.max[axis] <= other.min[axis] + COLLISION_EPSILON {
+            movement.min((other.min[axis] - self.max[axis]).max(0.0))
+        } else if movement < 0.0 && self.min[axis] >= other.max[axis] - COLLISION_EPSILON {
+            movement.max((other.max[axis] - self.min[axis]).min(0.0))
+        } else {
+            movement
+        }
+    }
+}
+
+/// The result of moving a box through the world
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Collision {
+    /// How far the box could actually move
+    pub movement: Vec3,
+    /// Points away from every surface that got hit, with each component
+    /// being either -1.0, 0.0 or 1.0.
+    pub normal: Vec3,
+}
+
+impl Collision {
+    #[inline]
+    pub fn hit(&self) -> bool {
+        self.normal != Vec3::ZERO
+    }
+
+    /// Remove the part of vel going into the surfaces that got hit,
+    /// which lets things slide along walls and floors.
+    pub fn slide(&self, vel: Vec3) -> Vec3 {
+        Vec3::select(self.normal.cmpne(Vec3::ZERO), Vec3::ZERO, vel)
+    }
+}
+
+impl Chungus {
+    /// Every solid box intersecting region, in world coordinates
+    pub fn collision_boxes(&self, region: &Aabb) -> Vec<Aabb> {
+        let min = region.min.floor().as_ivec3();
+        let max = region.max.ceil().as_ivec3();
+        let mut ret = vec![];
+        BLOCKS.with(|blocks| {
+            let blocks = blocks.borrow();
+            for x in min.x..max.x {
+                for y in min.y..max.y {
+                    for z in min.z..max.z {
+                        let pos = IVec3::new(x, y, z);
+                        let Some(bt) = self
+                            .get_block(pos)
+                            .filter(|b| *b != 0)
+                            .and_then(|b| blocks.get(b as usize))
+                        else {
+                            continue;
+                        };
+                        let meta = self.get_block_meta(pos).unwrap_or_default();
+                        let offset = pos.as_vec3();
+                        ret.extend(
+                            bt.collision_boxes(meta)
+                                .map(|b| b.translate(offset))
+                                .filter(|b| b.intersects(region)),
+                        );
+                    }
+                }
+            }
+        });
+        ret
+    }
+
+    /// Whether aabb overlaps any solid block
+    pub fn collides(&self, aabb: &Aabb) -> bool {
+        !self.collision_boxes(aabb).is_empty()
+    }
+
+    /// Whether there is something solid right below aabb to stand on
+    pub fn is_grounded(&self, aabb: &Aabb) -> bool {
+        let below = Aabb::new(
+            Vec3::new(aabb.min.x, aabb.min.y - GROUND_DISTANCE, aabb.min.z),
+            Vec3::new(aabb.max.x, aabb.min.y + COLLISION_EPSILON * 2.0, aabb.max.z),
+        );
+        self.collides(&below)
+    }
+
+    /// Move aabb by movement, stopping at the first solid block along the
+    /// way. Every axis is swept on its own, starting with Y, so that
+    /// blocked movement slides along the surface that was hit. Since the
+    /// whole path is checked nothing can tunnel through thin walls, no
+    /// matter how fast it moves.
+    pub fn sweep_aabb(&self, aabb: &Aabb, movement: Vec3) -> Collision {
+        let boxes = self.collision_boxes(&aabb.extend(movement));
+        let mut aabb = *aabb;
+        let mut ret = Collision::default();
+        for axis in [1, 0, 2] {
+            let wanted = movement[axis];
+            if wanted == 0.0 {
+                continue;
+            }
+            let allowed = boxes.iter().fold(wanted, |m, b| aabb.clip(b, axis, m));
+            if allowed != wanted {
+                ret.normal[axis] = -wanted.signum();
+            }
+            ret.movement[axis] = allowed;
+            let mut offset = Vec3::ZERO;
+            offset[axis] = allowed;
+            aabb = aabb.translate(offset);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{block_id, BlockMeta, Side};
+
+    /// A stone floor right below y = 0, with a wall at x = 4
+    pub(crate) fn test_world() -> Chungus {
+        let mut world = Chungus::new_flat("stone", 8);
+        let stone = block_id("stone").unwrap();
+        // A thin wall along the z axis at x = 4
+        for y in 0..4 {
+            for z in -8..8 {
+                world.set_block(IVec3::new(4, y, z), stone);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn test_sweep() {
+        let mut world = test_world();
+        let aabb = Aabb::from_center(Vec3::new(0.5, 1.0, 0.5), Vec3::new(0.4, 1.0, 0.4));
+        assert!(!world.collides(&aabb));
+        assert!(world.is_grounded(&aabb));
+        assert!(!world.is_grounded(&aabb.translate(Vec3::new(0.0, 0.5, 0.0))));
+
+        // Falling onto the floor stops right on top of it
+        let c = world.sweep_aabb(&aabb.translate(Vec3::Y), Vec3::new(0.0, -3.0, 0.0));
+        assert!((c.movement.y + 1.0).abs() < 0.001);
+        assert_eq!(c.normal, Vec3::Y);
+
+        // Moving way too fast still doesn't tunnel through the wall
+        let c = world.sweep_aabb(&aabb, Vec3::new(20.0, 0.0, 0.0));
+        assert!((c.movement.x - 3.1).abs() < 0.001);
+        assert_eq!(c.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        // Diagonal movement slides along the wall
+        let c = world.sweep_aabb(&aabb, Vec3::new(5.0, -0.5, 2.0));
+        assert_eq!(c.movement.z, 2.0);
+        assert_eq!(c.movement.y, 0.0);
+        assert_eq!(c.normal, Vec3::new(-1.0, 1.0, 0.0));
+        assert_eq!(c.slide(Vec3::new(1.0, -1.0, 1.0)), Vec3::new(0.0, 0.0, 1.0));
+
+        // Slabs and stairs only block their lower half
+        let slab = block_id("stone_slab").unwrap();
+        world.set_block(IVec3::new(-3, 0, 0), slab);
+        let c = world.sweep_aabb(
+            &aabb.translate(Vec3::new(-3.0, 1.0, 0.0)),
+            Vec3::new(0.0, -2.0, 0.0),
+        );
+        assert!((c.movement.y + 0.5).abs() < 0.001);
+
+        let stairs = block_id("boards_stairs").unwrap();
+        let meta = BlockMeta::new().with_facing(Side::Back);
+        world.set_block_with_meta(IVec3::new(-3, 0, 3), stairs, meta);
+        let small = Aabb::from_center(Vec3::new(-2.5, 2.0, 3.25), Vec3::splat(0.2));
+        let c = world.sweep_aabb(&small, Vec3::new(0.0, -3.0, 0.0));
+        assert!((c.movement.y + 1.3).abs() < 0.001);
+        let c = world.sweep_aabb(&small.translate(Vec3::new(0.0, 0.0, 0.5)), -3.0 * Vec3::Y);
+        assert!((c.movement.y + 0.8).abs() < 0.001);
+    }
+}
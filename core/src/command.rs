@@ -0,0 +1,342 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{
+    block_id_or_err, item_id, BlockId, BlockItem, GameState, Item, ItemStack, Message,
+    RaycastReturn, Reactor, StatusEffect, StatusEffectKind, BLOCKS, GAME_LOG, MS_PER_TICK,
+    STATUS_EFFECT_KINDS,
+};
+use anyhow::{anyhow, Result};
+use glam::Vec3;
+use std::{cell::RefCell, collections::BTreeMap};
+
+thread_local! {
+    pub static COMMANDS:RefCell<CommandList> = RefCell::new(CommandList::new())
+}
+
+/// A command gets the arguments following its name, the returned String
+/// is written to the GameLog (unless it is empty).
+pub type CommandHandler = Box<dyn Fn(&Reactor<Message>, &[&str]) -> Result<String>>;
+
+pub struct Command {
+    help: String,
+    handler: CommandHandler,
+}
+
+impl Command {
+    #[inline]
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+}
+
+impl std::fmt::Debug for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Command").field("help", &self.help).finish()
+    }
+}
+
+/// Command names are case insensitive and may be written with a leading slash
+fn normalize_name(name: &str) -> String {
+    name.trim_start_matches('/').to_lowercase()
+}
+
+#[derive(Debug, Default)]
+pub struct CommandList {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register a new command, replacing any previous command using the same name
+    pub fn add(&mut self, name: &str, help: &str, handler: CommandHandler) {
+        let help = help.to_string();
+        self.commands
+            .insert(normalize_name(name), Command { help, handler });
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.commands.remove(&normalize_name(name));
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Command)> {
+        self.commands.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Parse and execute a single command line like `/tp 1 2 3`
+    pub fn run(&self, reactor: &Reactor<Message>, line: &str) -> Result<String> {
+        let mut parts = line.trim().trim_start_matches('/').split_whitespace();
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let args: Vec<&str> = parts.collect();
+        match self.get(&name) {
+            Some(cmd) => (cmd.handler)(reactor, &args),
+            None => Err(anyhow!("Unknown command /{}, try /help", name)),
+        }
+    }
+
+    pub fn add_default_commands(&mut self, game: &GameState) {
+        {
+            let player = game.player_rc();
+            self.add(
+                "tp",
+                "/tp <x> <y> <z> - Teleport to a position",
+                Box::new(move |_: &Reactor<Message>, args: &[&str]| {
+                    let pos = parse_vec3(args)?;
+                    player.borrow_mut().set_pos(pos);
+                    Ok(format!(
+                        "Teleported to {:.1} {:.1} {:.1}",
+                        pos.x, pos.y, pos.z
+                    ))
+                }),
+            );
+        }
+        {
+            let player = game.player_rc();
+            self.add(
+                "give",
+                "/give <block_or_item_id> [amount] - Give yourself some blocks or items",
+                Box::new(move |_: &Reactor<Message>, args: &[&str]| {
+                    let name = args
+                        .first()
+                        .ok_or_else(|| anyhow!("Missing block or item"))?;
+                    let amount = match args.get(1) {
+                        Some(a) => a.parse::<u16>()?,
+                        None => 1,
+                    };
+                    let item: Item = match item_id(&name.to_lowercase()) {
+                        Some(id) => ItemStack::new(id, amount).into(),
+                        None => BlockItem::new(find_block(name)?, amount).into(),
+                    };
+                    let rest = player.borrow_mut().add_item_to_inventory(item);
+                    let given = amount - rest.amount();
+                    if given == 0 {
+                        return Err(anyhow!("Your inventory is full"));
+                    }
+                    Ok(format!("Gave {} x {}", given, item.name()))
+                }),
+            );
+        }
+        {
+            let clock = game.clock_rc();
+            self.add(
+                "time",
+                "/time - Show how long the game has been running",
+                Box::new(move |_: &Reactor<Message>, _args: &[&str]| {
+                    let millis = clock.borrow().elapsed().as_millis() as u64;
+                    let secs = millis / 1000;
+                    Ok(format!(
+                        "{:02}:{:02}:{:02} ({} ticks)",
+                        secs / 3600,
+                        (secs / 60) % 60,
+                        secs % 60,
+                        millis / MS_PER_TICK
+                    ))
+                }),
+            );
+        }
+        {
+            let world = game.world_rc();
+            self.add(
+                "seed",
+                "/seed - Show the world seed",
+                Box::new(move |_: &Reactor<Message>, _args: &[&str]| {
+                    Ok(format!("Seed: {}", world.borrow().seed()))
+                }),
+            );
+        }
+        {
+            let player = game.player_rc();
+            self.add(
+                "noclip",
+                "/noclip - Toggle no-clip mode",
+                Box::new(move |reactor: &Reactor<Message>, _args: &[&str]| {
+                    let no_clip = !player.borrow().no_clip();
+                    reactor.dispatch(Message::PlayerNoClip { no_clip });
+                    Ok(format!("No-clip {}", if no_clip { "on" } else { "off" }))
+                }),
+            );
+        }
+        {
+            let player = game.player_rc();
+            self.add(
+                "kill",
+                "/kill - Kill yourself",
+                Box::new(move |reactor: &Reactor<Message>, _args: &[&str]| {
+                    let pos = {
+                        let mut player = player.borrow_mut();
+                        if player.is_dead() {
+                            return Err(anyhow!("You are already dead"));
+                        }
+                        let hp = player.health().health();
+                        player.damage(hp);
+                        player.pos()
+                    };
+                    reactor.dispatch(Message::CharacterDeath { pos });
+                    Ok(String::new())
+                }),
+            );
+        }
+        self.add(
+            "effect",
+            "/effect <kind> [strength] [seconds] - Give yourself a status effect",
+            Box::new(move |reactor: &Reactor<Message>, args: &[&str]| {
+                let name = args.first().ok_or_else(|| anyhow!("Missing effect"))?;
+                let kind = StatusEffectKind::from_name(name).ok_or_else(|| {
+                    let kinds: Vec<&str> = STATUS_EFFECT_KINDS.iter().map(|k| k.name()).collect();
+                    anyhow!("Unknown effect, try one of: {}", kinds.join(", "))
+                })?;
+                let strength = match args.get(1) {
+                    Some(s) => s.parse::<u8>()?,
+                    None => 1,
+                };
+                let seconds = match args.get(2) {
+                    Some(s) => s.parse::<f32>()?,
+                    None => 10.0,
+                };
+                let effect = StatusEffect::with_seconds(kind, strength, seconds);
+                reactor.dispatch(Message::CharacterApplyEffect { effect });
+                Ok(format!(
+                    "{} {} for {}s",
+                    kind.name(),
+                    effect.strength,
+                    seconds
+                ))
+            }),
+        );
+        {
+            let player = game.player_rc();
+            let world = game.world_rc();
+            self.add(
+                "sign",
+                "/sign <text> - Write on the sign you are looking at",
+                Box::new(move |_: &Reactor<Message>, args: &[&str]| {
+                    let mut world = world.borrow_mut();
+                    let pos = player
+                        .borrow()
+                        .raycast(&world, RaycastReturn::Within)
+                        .ok_or_else(|| anyhow!("You are not looking at a block"))?;
+                    let sign = world
+                        .get_block_entity_mut(pos)
+                        .ok_or_else(|| anyhow!("You are not looking at a sign"))?;
+                    if !sign.set_text(&args.join(" ")) {
+                        return Err(anyhow!("You are not looking at a sign"));
+                    }
+                    Ok(sign.describe())
+                }),
+            );
+        }
+        self.add(
+            "help",
+            "/help - List all available commands",
+            Box::new(move |_: &Reactor<Message>, _args: &[&str]| {
+                COMMANDS.with(|cmds| {
+                    for (_, cmd) in cmds.borrow().iter() {
+                        log(cmd.help().to_string());
+                    }
+                });
+                Ok(String::new())
+            }),
+        );
+    }
+}
+
+fn log(s: String) {
+    GAME_LOG.with(|log| log.borrow_mut().push(s));
+}
+
+fn parse_vec3(args: &[&str]) -> Result<Vec3> {
+    if args.len() != 3 {
+        return Err(anyhow!("Expected 3 coordinates"));
+    }
+    Ok(Vec3::new(
+        args[0].parse()?,
+        args[1].parse()?,
+        args[2].parse()?,
+    ))
+}
+
+fn find_block(name: &str) -> Result<BlockId> {
+    if let Ok(id) = name.parse::<BlockId>() {
+        let known = BLOCKS.with(|blocks| (id as usize) < blocks.borrow().len());
+        return if known {
+            Ok(id)
+        } else {
+            Err(anyhow!("Unknown block id {}", id))
+        };
+    }
+    block_id_or_err(&name.to_lowercase())
+}
+
+/// Handle a line of text entered into the console, lines starting with
+/// a slash are treated as commands, everything else is chat.
+pub fn run_console_line(reactor: &Reactor<Message>, line: &str) {
+    let line = line.trim();
+    if line.is_empty() {
+        return;
+    }
+    if !line.starts_with('/') {
+        log(format!("<Player> {}", line));
+        return;
+    }
+    let res = COMMANDS.with(|cmds| cmds.borrow().run(reactor, line));
+    match res {
+        Ok(s) => {
+            if !s.is_empty() {
+                log(s);
+            }
+        }
+        Err(e) => log(format!("Error: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_list() {
+        let reactor = Reactor::new();
+        let mut cmds = CommandList::new();
+        assert!(cmds.is_empty());
+        cmds.add(
+            "/Echo",
+            "/echo <text>",
+            Box::new(|_: &Reactor<Message>, args: &[&str]| Ok(args.join(" "))),
+        );
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds.run(&reactor, "/echo  a b").unwrap(), "a b");
+        assert_eq!(cmds.run(&reactor, "/ECHO").unwrap(), "");
+        assert!(cmds.run(&reactor, "/missing").is_err());
+        cmds.remove("/ECHO");
+        assert!(cmds.is_empty());
+    }
+
+    #[test]
+    fn test_find_block() {
+        let stone = block_id_or_err("stone").unwrap();
+        assert_eq!(find_block("Stone").unwrap(), stone);
+        assert_eq!(find_block(&stone.to_string()).unwrap(), stone);
+        let count = BLOCKS.with(|blocks| blocks.borrow().len());
+        assert!(find_block(&count.to_string()).is_err());
+        assert!(find_block("65535").is_err());
+        assert!(find_block("nothing").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{
+    block_id_or_err, insert_into_slots, item_id_or_err, BlockId, BlockItem, Character, Chungus,
+    Item, ItemStack,
+};
+use anyhow::{anyhow, Context, Result};
+use glam::IVec3;
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Index into RECIPES
+pub type RecipeId = u16;
+
+/// How many blocks away from the player a crafting station may be
+pub const CRAFTING_STATION_RANGE: i32 = 4;
+
+thread_local! {
+    pub static RECIPES:RefCell<Vec<Recipe>> = RefCell::new(Recipe::new_default());
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recipe {
+    id: String,
+    ingredients: Vec<Item>,
+    output: Item,
+    station: Option<BlockId>,
+}
+
+/// How many of a particular block or item there are within slots
+pub fn count_items(slots: &[Item], item: Item) -> u32 {
+    slots
+        .iter()
+        .filter(|i| i.same_kind(&item))
+        .map(|i| i.amount() as u32)
+        .sum()
+}
+
+/// Remove item from slots, taking from the last slots first so the hotbar
+/// stays intact for as long as possible. Returns false if there weren't enough.
+fn remove_items(slots: &mut [Item], item: Item) -> bool {
+    let mut left = item.amount();
+    for slot in slots.iter_mut().rev() {
+        if left == 0 {
+            break;
+        }
+        if slot.same_kind(&item) {
+            let n = left.min(slot.amount());
+            *slot = slot.with_amount(slot.amount() - n);
+            left -= n;
+        }
+    }
+    left == 0
+}
+
+/// Every distinct block within CRAFTING_STATION_RANGE of pos
+pub fn blocks_nearby(world: &Chungus, pos: IVec3) -> Vec<BlockId> {
+    let r = CRAFTING_STATION_RANGE;
+    let mut ret = vec![];
+    for x in -r..=r {
+        for y in -r..=r {
+            for z in -r..=r {
+                if let Some(b) = world.get_block(pos + IVec3::new(x, y, z)) {
+                    if b != 0 && !ret.contains(&b) {
+                        ret.push(b);
+                    }
+                }
+            }
+        }
+    }
+    ret
+}
+
+impl Recipe {
+    pub fn new(output: Item) -> Self {
+        Self {
+            output,
+            ..Default::default()
+        }
+    }
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+    pub fn with_ingredient(mut self, item: Item) -> Self {
+        self.ingredients.push(item);
+        self
+    }
+    pub fn with_station(mut self, station: BlockId) -> Self {
+        self.station = Some(station);
+        self
+    }
+
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn ingredients(&self) -> &[Item] {
+        &self.ingredients
+    }
+    #[inline]
+    pub fn output(&self) -> Item {
+        self.output
+    }
+    #[inline]
+    pub fn station(&self) -> Option<BlockId> {
+        self.station
+    }
+
+    pub fn has_ingredients(&self, slots: &[Item]) -> bool {
+        self.ingredients
+            .iter()
+            .all(|i| count_items(slots, *i) >= i.amount() as u32)
+    }
+
+    /// Whether the required station, if any, is among the nearby blocks
+    pub fn has_station(&self, nearby: &[BlockId]) -> bool {
+        self.station.map(|s| nearby.contains(&s)).unwrap_or(true)
+    }
+
+    /// Replace the ingredients within slots with the output, either everything
+    /// succeeds or slots are left untouched.
+    pub fn craft(&self, slots: &mut [Item]) -> Result<()> {
+        let mut new_slots = slots.to_vec();
+        for i in self.ingredients.iter() {
+            if !remove_items(&mut new_slots, *i) {
+                return Err(anyhow!("Not enough {}", i.name()));
+            }
+        }
+        if insert_into_slots(&mut new_slots, self.output) != Item::None {
+            return Err(anyhow!("Not enough room for {}", self.output.name()));
+        }
+        slots.copy_from_slice(&new_slots);
+        Ok(())
+    }
+}
+
+impl Character {
+    /// All recipes that can be crafted right now, with the current inventory
+    /// and at the current position.
+    pub fn craftable_recipes(&self, world: &Chungus) -> Vec<RecipeId> {
+        let nearby = blocks_nearby(world, self.pos().floor().as_ivec3());
+        RECIPES.with(|recipes| {
+            recipes
+                .borrow()
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.has_station(&nearby) && r.has_ingredients(self.inventory()))
+                .map(|(i, _)| i as RecipeId)
+                .collect()
+        })
+    }
+
+    /// Craft a recipe, returning what has been crafted
+    pub fn craft(&mut self, world: &Chungus, id: RecipeId) -> Result<Item> {
+        let recipe = RECIPES
+            .with(|recipes| recipes.borrow().get(id as usize).cloned())
+            .ok_or_else(|| anyhow!("Unknown recipe #{}", id))?;
+        if let Some(station) = recipe.station() {
+            let nearby = blocks_nearby(world, self.pos().floor().as_ivec3());
+            if !nearby.contains(&station) {
+                let name = Item::from(BlockItem::new(station, 1)).name();
+                return Err(anyhow!("You need to be near a {}", name.to_lowercase()));
+            }
+        }
+        recipe.craft(self.inventory_mut())?;
+        Ok(recipe.output())
+    }
+}
+
+/// A block or item, as used within asset definitions
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RecipeItemDefinition {
+    #[serde(default)]
+    pub block: Option<String>,
+    #[serde(default)]
+    pub item: Option<String>,
+    #[serde(default)]
+    pub amount: Option<u16>,
+}
+
+impl RecipeItemDefinition {
+    pub(crate) fn validate(&self) -> Result<Item> {
+        let amount = self.amount.unwrap_or(1);
+        if amount == 0 {
+            return Err(anyhow!("Amount has to be at least 1"));
+        }
+        match (&self.block, &self.item) {
+            (Some(b), None) => Ok(BlockItem::new(block_id_or_err(b)?, amount).into()),
+            (None, Some(i)) => Ok(ItemStack::new(item_id_or_err(i)?, amount).into()),
+            _ => Err(anyhow!("Exactly one of block or item has to be set")),
+        }
+    }
+}
+
+/// The format recipes are defined in, see assets/recipes.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeDefinition {
+    id: String,
+    output: RecipeItemDefinition,
+    ingredients: Vec<RecipeItemDefinition>,
+    #[serde(default)]
+    station: Option<String>,
+}
+
+impl RecipeDefinition {
+    fn validate(self) -> Result<Recipe> {
+        if self.ingredients.is_empty() {
+            return Err(anyhow!("Recipes need at least one ingredient"));
+        }
+        let mut r =
+            Recipe::new(self.output.validate().context("Invalid output")?).with_id(&self.id);
+        for i in self.ingredients.iter() {
+            r = r.with_ingredient(i.validate().context("Invalid ingredient")?);
+        }
+        if let Some(station) = self.station {
+            r = r.with_station(block_id_or_err(&station)?);
+        }
+        Ok(r)
+    }
+}
+
+impl Recipe {
+    /// Returns the recipes from assets/recipes.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "recipes.json",
+            include_str!("../assets/recipes.json"),
+            Self::from_json,
+        )
+    }
+
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(
+            json,
+            "recipe",
+            RecipeId::MAX as usize,
+            RecipeDefinition::validate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_id, item_id};
+
+    #[test]
+    fn test_crafting() {
+        let recipes = Recipe::new_default();
+        assert!(!recipes.is_empty());
+        let log = block_id("oak_log").unwrap();
+        let boards = block_id("boards").unwrap();
+        let recipe = recipes
+            .iter()
+            .find(|r| r.id() == "boards_from_oak")
+            .unwrap();
+
+        let mut slots = vec![Item::None; 3];
+        assert!(!recipe.has_ingredients(&slots));
+        assert!(recipe.craft(&mut slots).is_err());
+        slots[2] = BlockItem::new(log, 2).into();
+        assert!(recipe.has_ingredients(&slots));
+        recipe.craft(&mut slots).unwrap();
+        assert_eq!(count_items(&slots, BlockItem::new(boards, 1).into()), 4);
+        assert_eq!(count_items(&slots, BlockItem::new(log, 1).into()), 1);
+
+        // Crafting must not change anything if the output doesn't fit
+        let stone = block_id("stone").unwrap();
+        let mut slots = vec![
+            BlockItem::new(log, 2).into(),
+            BlockItem::new(stone, 99).into(),
+        ];
+        let before = slots.clone();
+        assert!(recipe.craft(&mut slots).is_err());
+        assert_eq!(slots, before);
+
+        let pick = recipes.iter().find(|r| r.id() == "wooden_pickaxe").unwrap();
+        let mut slots = vec![BlockItem::new(boards, 5).into()];
+        pick.craft(&mut slots).unwrap();
+        assert_eq!(
+            slots[0],
+            ItemStack::new(item_id("wooden_pickaxe").unwrap(), 1).into()
+        );
+
+        let iron = recipes.iter().find(|r| r.id() == "iron_pickaxe").unwrap();
+        assert!(!iron.has_station(&[boards]));
+        assert!(iron.has_station(&[boards, block_id("furnace").unwrap()]));
+
+        assert!(Recipe::from_json(
+            r#"[{ "id": "a", "output": { "block": "stone" }, "ingredients": [] }]"#
+        )
+        .is_err());
+        assert!(Recipe::from_json(
+            r#"[{ "id": "a", "output": { "block": "stone", "item": "grenade" }, "ingredients": [{ "block": "dirt" }] }]"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::GAME_LOG;
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Mods can replace any of the definition files in core/assets by putting
+/// a file with the same name in here, relative to the working directory.
+pub const MOD_DIR: &str = "mods";
+
+/// Ids are used within JSON files and commands, so keep them simple
+pub(crate) fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(anyhow!(
+            "Id {:?} may only contain lowercase letters, digits and underscores",
+            id
+        ));
+    }
+    Ok(())
+}
+
+/// Parse a JSON array of definitions with unique ids, like assets/blocks.json.
+/// Every entry gets deserialized and then passed through validate, errors
+/// name the offending entry like `mob #3 ("crab")`. Since definitions are
+/// referred to by their index, there may be at most max of them.
+pub(crate) fn parse_definitions<D: DeserializeOwned, T>(
+    json: &str,
+    kind: &str,
+    max: usize,
+    validate: impl Fn(D) -> Result<T>,
+) -> Result<Vec<T>> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(json)
+        .with_context(|| format!("Expected a JSON array of {} definitions", kind))?;
+    if entries.len() > max {
+        return Err(anyhow!(
+            "Too many {} definitions ({}), only {} are supported",
+            kind,
+            entries.len(),
+            max
+        ));
+    }
+    let mut ids: Vec<String> = Vec::with_capacity(entries.len());
+    let mut ret = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.into_iter().enumerate() {
+        let id = entry
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let label = if id.is_empty() {
+            format!("{} #{}", kind, i)
+        } else {
+            format!("{} #{} ({:?})", kind, i, id)
+        };
+        let def: D = serde_json::from_value(entry).with_context(|| format!("Invalid {}", label))?;
+        let t = validate_id(&id)
+            .and_then(|_| validate(def))
+            .with_context(|| format!("Invalid {}", label))?;
+        if ids.contains(&id) {
+            return Err(anyhow!("Duplicate id in {}", label));
+        }
+        ids.push(id);
+        ret.push(t);
+    }
+    Ok(ret)
+}
+
+/// Parse file from MOD_DIR, or the built-in copy if there is none. A broken
+/// mod file gets reported in the game log and the built-in copy used instead.
+pub(crate) fn load_definitions<T>(
+    file: &str,
+    builtin: &str,
+    parse: impl Fn(&str) -> Result<T>,
+) -> T {
+    load_definitions_from(Path::new(MOD_DIR), file, builtin, parse)
+}
+
+fn load_definitions_from<T>(
+    dir: &Path,
+    file: &str,
+    builtin: &str,
+    parse: impl Fn(&str) -> Result<T>,
+) -> T {
+    let path = dir.join(file);
+    if let Ok(json) = std::fs::read_to_string(&path) {
+        match parse(&json) {
+            Ok(ret) => return ret,
+            Err(err) => GAME_LOG.with(|log| {
+                let msg = format!("Couldn't load {}: {:#}", path.display(), err);
+                log.borrow_mut().push(msg);
+            }),
+        }
+    }
+    parse(builtin).unwrap_or_else(|err| panic!("Invalid built-in {}: {:#}", file, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Def {
+        id: String,
+        n: u8,
+    }
+
+    fn parse(json: &str) -> Result<Vec<(String, u8)>> {
+        parse_definitions(json, "thing", 2, |d: Def| match d.n {
+            0 => Err(anyhow!("n can't be 0")),
+            n => Ok((d.id, n)),
+        })
+    }
+
+    #[test]
+    fn test_parse_definitions() {
+        assert!(validate_id("iron_ore2").is_ok());
+        assert!(validate_id("").is_err());
+        assert!(validate_id("Iron ore").is_err());
+
+        let ok = parse(r#"[{ "id": "a", "n": 1 }, { "id": "b", "n": 2 }]"#).unwrap();
+        assert_eq!(ok, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        let err = |json| format!("{:#}", parse(json).unwrap_err());
+        assert!(err("{}").contains("JSON array of thing definitions"));
+        assert!(
+            err(r#"[{ "id": "a", "n": 1 }, { "id": "b", "n": 1 }, { "id": "c", "n": 1 }]"#)
+                .contains("Too many thing definitions (3)")
+        );
+        assert!(err(r#"[{ "id": "a", "n": 1 }, { "id": "a", "n": 2 }]"#)
+            .contains(r#"Duplicate id in thing #1 ("a")"#));
+        assert!(err(r#"[{ "id": "A", "n": 1 }]"#).contains("lowercase letters"));
+        assert!(err(r#"[{ "id": "a", "n": 0 }]"#).contains(r#"Invalid thing #0 ("a")"#));
+        assert!(err(r#"[{ "n": 1 }]"#).contains("Invalid thing #0"));
+    }
+
+    #[test]
+    fn test_load_definitions() {
+        let dir = std::env::temp_dir().join(format!("wolkenwelten-mods-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let builtin = r#"[{ "id": "a", "n": 1 }]"#;
+        let load = || load_definitions_from(&dir, "things.json", builtin, parse);
+        assert_eq!(load(), vec![("a".to_string(), 1)]);
+
+        let path = dir.join("things.json");
+        std::fs::write(&path, r#"[{ "id": "b", "n": 2 }]"#).unwrap();
+        assert_eq!(load(), vec![("b".to_string(), 2)]);
+
+        std::fs::write(&path, r#"[{ "id": "b", "n": 0 }]"#).unwrap();
+        assert_eq!(load(), vec![("a".to_string(), 1)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
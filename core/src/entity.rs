@@ -1,6 +1,6 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use super::Chungus;
+use super::{Aabb, Chungus};
 use glam::Vec3;
 use std::f32::consts::PI;
 
@@ -8,58 +8,29 @@ const ENTITY_SIZE: f32 = 0.4;
 const ENTITY_BOUNCE_RATE: f32 = 0.4;
 const ENTITY_SLIDE_RATE: f32 = 0.95;
 
-#[derive(Clone, Debug, Default)]
-pub struct Entity {
+/// The collision box used by small things like grenades or item drops
+pub const ENTITY_COL_BOX: Aabb = Aabb::new(Vec3::splat(-ENTITY_SIZE), Vec3::splat(ENTITY_SIZE));
+
+/// Where an entity is and which way it's facing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
     pub pos: Vec3,
     pub rot: Vec3,
-    pub vel: Vec3,
-    pub size: f32,
 }
 
-impl Entity {
-    pub fn new() -> Self {
-        let pos = Vec3::ZERO;
-        let rot = Vec3::ZERO;
-        let vel = Vec3::ZERO;
+impl Transform {
+    #[inline]
+    pub fn new(pos: Vec3) -> Self {
         Self {
             pos,
-            rot,
-            vel,
-            size: 1.0,
+            rot: Vec3::ZERO,
         }
     }
-    #[inline]
-    pub fn pos(&self) -> Vec3 {
-        self.pos
-    }
-    #[inline]
-    pub fn rot(&self) -> Vec3 {
-        self.rot
-    }
-    #[inline]
-    pub fn vel(&self) -> Vec3 {
-        self.vel
-    }
-    #[inline]
-    pub fn size(&self) -> f32 {
-        self.size
-    }
 
     #[inline]
-    pub fn set_vel(&mut self, vel: Vec3) {
-        self.vel = vel;
-    }
-    #[inline]
-    pub fn set_rot(&mut self, rot: Vec3) {
+    pub fn with_rot(mut self, rot: Vec3) -> Self {
         self.rot = rot;
-    }
-    #[inline]
-    pub fn set_pos(&mut self, pos: Vec3) {
-        self.pos = pos;
-    }
-    #[inline]
-    pub fn set_size(&mut self, size: f32) {
-        self.size = size;
+        self
     }
 
     pub fn direction(&self) -> Vec3 {
@@ -70,58 +41,109 @@ impl Entity {
             ((a.x - 90.0) * PI / 180.0).sin() * (-a.y * PI / 180.0).cos(),
         )
     }
+}
+
+/// Velocity and collision box of an entity that moves through the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    pub vel: Vec3,
+    /// The collision box, relative to the position of the entity
+    pub col_box: Aabb,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new(ENTITY_COL_BOX)
+    }
+}
+
+impl Physics {
+    #[inline]
+    pub fn new(col_box: Aabb) -> Self {
+        Self {
+            vel: Vec3::ZERO,
+            col_box,
+        }
+    }
+
+    #[inline]
+    pub fn with_vel(mut self, vel: Vec3) -> Self {
+        self.vel = vel;
+        self
+    }
+
+    #[inline]
+    pub fn aabb(&self, pos: Vec3) -> Aabb {
+        self.col_box.translate(pos)
+    }
 
     pub fn would_collide_at(&self, world: &Chungus, pos: Vec3) -> bool {
-        world.is_solid(pos + Vec3::new(-ENTITY_SIZE * self.size, 0.0, 0.0))
-            | world.is_solid(pos + Vec3::new(ENTITY_SIZE * self.size, 0.0, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, -ENTITY_SIZE * self.size, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, ENTITY_SIZE * self.size, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, 0.0, -ENTITY_SIZE * self.size))
-            | world.is_solid(pos + Vec3::new(0.0, 0.0, ENTITY_SIZE * self.size))
+        world.collides(&self.aabb(pos))
     }
 
-    pub fn is_colliding(&self, world: &Chungus) -> bool {
-        self.would_collide_at(world, self.pos())
+    /// Move the entity, bouncing off of whatever it hits. Returns true if
+    /// something got hit.
+    pub fn bounce(&mut self, transform: &mut Transform, world: &Chungus) -> bool {
+        self.bounce_with(transform, world, 0.0005, ENTITY_BOUNCE_RATE)
     }
 
-    pub fn tick(&mut self, world: &Chungus) -> bool {
-        let mut bounce = false;
-        let s = ENTITY_SIZE * self.size;
-        let sy = ENTITY_SIZE;
+    /// Same as bounce, but with a custom gravity and bounciness, returning
+    /// whether a block was hit.
+    pub fn bounce_with(
+        &mut self,
+        transform: &mut Transform,
+        world: &Chungus,
+        gravity: f32,
+        bounciness: f32,
+    ) -> bool {
+        let collision = world.sweep_aabb(&self.aabb(transform.pos), self.vel);
+        transform.pos += collision.movement;
 
-        if world.is_solid(self.pos + Vec3::new(s, 0.0, 0.0))
-            | world.is_solid(self.pos + Vec3::new(-s, 0.0, 0.0))
-        {
+        if collision.normal.x != 0.0 {
             self.vel *= ENTITY_SLIDE_RATE;
-            self.vel.x *= -ENTITY_BOUNCE_RATE;
-            self.pos.x += self.vel.x;
-            bounce = true;
+            self.vel.x *= -bounciness;
         }
-        if world.is_solid(self.pos + Vec3::new(0.0, sy, 0.0))
-            | world.is_solid(self.pos + Vec3::new(0.0, -sy, 0.0))
-            | world.is_solid(self.pos + Vec3::new(s, -sy, 0.0))
-            | world.is_solid(self.pos + Vec3::new(-s, -sy, 0.0))
-            | world.is_solid(self.pos + Vec3::new(0.0, -sy, s))
-            | world.is_solid(self.pos + Vec3::new(0.0, -sy, -s))
-        {
-            self.vel *= ENTITY_SLIDE_RATE;
-            self.vel.y *= -ENTITY_BOUNCE_RATE;
-            self.pos.y += self.vel.y;
-            bounce = true;
+        if collision.normal.y != 0.0 {
+            let ground = transform.pos + Vec3::new(0.0, self.col_box.min.y - 0.05, 0.0);
+            let friction = world.friction(ground);
+            self.vel *= 1.0 - (1.0 - ENTITY_SLIDE_RATE) * friction;
+            self.vel.y *= -bounciness;
         }
-        if world.is_solid(self.pos + Vec3::new(0.0, 0.0, s))
-            | world.is_solid(self.pos + Vec3::new(0.0, 0.0, -s))
-        {
+        if collision.normal.z != 0.0 {
             self.vel *= ENTITY_SLIDE_RATE;
-            self.vel.z *= -ENTITY_BOUNCE_RATE;
-            self.pos.z += self.vel.z;
-            bounce = true;
+            self.vel.z *= -bounciness;
         }
-        let v = self.vel;
-        self.pos += v;
-        self.vel.y -= 0.0005;
+        self.vel.y -= gravity;
+
+        collision.hit()
+    }
+}
+
+/// Entities with this get drawn and cast a shadow
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Renderable {
+    /// Which model to use, the meaning is up to whoever draws the entity
+    pub model: u32,
+    /// Roughly how big the entity is, used for the shadow
+    pub size: f32,
+}
+
+impl Default for Renderable {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Renderable {
+    #[inline]
+    pub fn new(size: f32) -> Self {
+        Self { model: 0, size }
+    }
 
-        bounce
+    #[inline]
+    pub fn with_model(mut self, model: u32) -> Self {
+        self.model = model;
+        self
     }
 }
 
@@ -130,14 +152,33 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_entity() {
-        let mut e = Entity::new();
-        assert_eq!(e.pos(), Vec3::new(0.0, 0.0, 0.0));
-        assert_eq!(e.rot(), Vec3::new(0.0, 0.0, 0.0));
-        assert_eq!(e.vel(), Vec3::new(0.0, 0.0, 0.0));
-        e.set_pos(Vec3::new(1.0, 2.0, 3.0));
-        assert_eq!(e.pos(), Vec3::new(1.0, 2.0, 3.0));
-        assert_eq!(e.pos(), e.clone().pos());
-        e.set_vel(Vec3::new(1.0, 1.0, 1.0));
+    fn test_transform() {
+        let t = Transform::new(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(t.pos, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(t.rot, Vec3::ZERO);
+        assert!((t.direction() - Vec3::new(0.0, 0.0, -1.0)).length() < 0.001);
+        let t = t.with_rot(Vec3::new(0.0, -90.0, 0.0));
+        assert!((t.direction() - Vec3::Y).length() < 0.001);
+    }
+
+    #[test]
+    fn test_entity_bounce() {
+        let world = crate::collision::tests::test_world();
+        let mut t = Transform::new(Vec3::new(0.5, 3.0, 0.5));
+        let mut p = Physics::default().with_vel(Vec3::new(0.0, -4.0, 0.0));
+        assert!(p.bounce(&mut t, &world));
+        assert!((t.pos.y - ENTITY_SIZE).abs() < 0.001);
+        assert!(p.vel.y > 0.0);
+        for _ in 0..2000 {
+            p.bounce(&mut t, &world);
+            assert!(t.pos.y > ENTITY_SIZE - 0.001);
+        }
+
+        // Way too fast, but it still bounces off the wall
+        t.pos = Vec3::new(0.5, 1.0, 0.5);
+        p.vel = Vec3::new(10.0, 0.0, 0.0);
+        assert!(p.bounce(&mut t, &world));
+        assert!((t.pos.x + ENTITY_SIZE - 4.0).abs() < 0.001);
+        assert!(p.vel.x < 0.0);
     }
 }
@@ -0,0 +1,452 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{
+    Aabb, Character, GameState, Health, Message, Physics, Reactor, Renderable, SpatialHash,
+    Transform,
+};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::any::{type_name, Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// How fast overlapping solid entities get pushed apart, per tick
+const ENTITY_PUSH_STRENGTH: f32 = 0.004;
+
+/// Velocity added right at the center of an explosion
+const EXPLOSION_KNOCKBACK: f32 = 0.1;
+
+/// How much velocity a single point of damage knocks something back
+pub const KNOCKBACK_PER_DAMAGE: f32 = 0.01;
+
+/// Uniquely identifies an entity, ids are never reused
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct EntityId(u64);
+
+/// Every component of a single type, ordered by entity
+pub type ComponentMap<T> = BTreeMap<EntityId, T>;
+
+trait Storage {
+    fn remove(&self, id: EntityId);
+    fn clear(&self);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> Storage for RefCell<ComponentMap<T>> {
+    fn remove(&self, id: EntityId) {
+        self.borrow_mut().remove(&id);
+    }
+    fn clear(&self) {
+        self.borrow_mut().clear();
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// All the entities in the world, with every component type being stored
+/// in its own map. Each map can be borrowed on its own, so systems can
+/// work with multiple component types at once, for example walking through
+/// every mob while also moving its transform.
+///
+/// Core provides Transform, Physics, Health and Renderable, plugins can
+/// register their own component types for things like AI.
+#[derive(Default)]
+pub struct EntityStore {
+    next_id: u64,
+    alive: BTreeSet<EntityId>,
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+}
+
+/// Returned by EntityStore::spawn, used to add the initial components
+pub struct EntityBuilder<'a> {
+    store: &'a mut EntityStore,
+    id: EntityId,
+}
+
+impl<'a> EntityBuilder<'a> {
+    #[inline]
+    pub fn with<T: 'static>(self, component: T) -> Self {
+        self.store.insert(self.id, component);
+        self
+    }
+
+    #[inline]
+    pub fn id(self) -> EntityId {
+        self.id
+    }
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        let mut ret = Self::default();
+        ret.register::<Transform>();
+        ret.register::<Physics>();
+        ret.register::<Health>();
+        ret.register::<Renderable>();
+        ret
+    }
+
+    /// Components have to be registered before they can be queried
+    pub fn register<T: 'static>(&mut self) {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(ComponentMap::<T>::new())));
+    }
+
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.alive.insert(id);
+        EntityBuilder { store: self, id }
+    }
+
+    /// Add or replace a component, registering its type if necessary
+    pub fn insert<T: 'static>(&mut self, id: EntityId, component: T) {
+        if !self.contains(id) {
+            return;
+        }
+        self.register::<T>();
+        self.components_mut::<T>().insert(id, component);
+    }
+
+    /// Remove an entity along with all of its components
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        if !self.alive.remove(&id) {
+            return false;
+        }
+        self.storages.values().for_each(|s| s.remove(id));
+        true
+    }
+
+    /// Remove every entity, registered component types are kept
+    pub fn clear(&mut self) {
+        self.alive.clear();
+        self.storages.values().for_each(|s| s.clear());
+    }
+
+    #[inline]
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.alive.contains(&id)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.alive.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.alive.is_empty()
+    }
+
+    fn storage<T: 'static>(&self) -> &RefCell<ComponentMap<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|s| s.as_any().downcast_ref())
+            .unwrap_or_else(|| panic!("Component {} was never registered", type_name::<T>()))
+    }
+
+    /// Panics when the component type wasn't registered
+    #[inline]
+    pub fn components<T: 'static>(&self) -> Ref<'_, ComponentMap<T>> {
+        self.storage::<T>().borrow()
+    }
+
+    /// Panics when the component type wasn't registered
+    #[inline]
+    pub fn components_mut<T: 'static>(&self) -> RefMut<'_, ComponentMap<T>> {
+        self.storage::<T>().borrow_mut()
+    }
+
+    #[inline]
+    pub fn get<T: Copy + 'static>(&self, id: EntityId) -> Option<T> {
+        self.components::<T>().get(&id).copied()
+    }
+
+    /// Every entity whose position is within radius of pos
+    pub fn within_radius(&self, pos: Vec3, radius: f32) -> Vec<EntityId> {
+        let rr = radius * radius;
+        self.components::<Transform>()
+            .iter()
+            .filter(|(_, t)| (t.pos - pos).length_squared() < rr)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Every entity whose collision box intersects aabb
+    pub fn overlapping(&self, aabb: &Aabb) -> Vec<EntityId> {
+        self.aabbs()
+            .into_iter()
+            .filter(|(_, b)| b.intersects(aabb))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Every pair of entities whose collision boxes intersect
+    pub fn collisions(&self) -> Vec<(EntityId, EntityId)> {
+        self.spatial_hash().pairs()
+    }
+
+    /// Sort every collision box into a spatial hash
+    pub fn spatial_hash(&self) -> SpatialHash {
+        let mut ret = SpatialHash::default();
+        for (id, aabb) in self.aabbs() {
+            ret.insert(id, aabb);
+        }
+        ret
+    }
+
+    /// Whether an entity is solid, which for now means everything that
+    /// can take damage. Small things like item drops just pass through.
+    #[inline]
+    pub fn is_solid(&self, id: EntityId) -> bool {
+        self.components::<Health>().contains_key(&id)
+    }
+
+    /// Push overlapping solid entities away from each other
+    pub fn push_apart(&self) {
+        let pairs: Vec<(EntityId, EntityId)> = self
+            .collisions()
+            .into_iter()
+            .filter(|(a, b)| self.is_solid(*a) && self.is_solid(*b))
+            .collect();
+        let transforms = self.components::<Transform>();
+        let mut physics = self.components_mut::<Physics>();
+        for (a, b) in pairs {
+            let (Some(a_pos), Some(b_pos)) = (transforms.get(&a), transforms.get(&b)) else {
+                continue;
+            };
+            let push = push_direction(a_pos.pos, b_pos.pos) * ENTITY_PUSH_STRENGTH;
+            if let Some(p) = physics.get_mut(&a) {
+                p.vel -= push;
+            }
+            if let Some(p) = physics.get_mut(&b) {
+                p.vel += push;
+            }
+        }
+    }
+
+    /// Push overlapping solid entities and the player apart
+    pub fn push_apart_character(&self, character: &mut Character) {
+        let pos = character.pos();
+        let transforms = self.components::<Transform>();
+        let mut physics = self.components_mut::<Physics>();
+        for id in self.overlapping(&character.aabb()) {
+            if !self.is_solid(id) {
+                continue;
+            }
+            let (Some(t), Some(p)) = (transforms.get(&id), physics.get_mut(&id)) else {
+                continue;
+            };
+            let push = push_direction(pos, t.pos) * ENTITY_PUSH_STRENGTH;
+            p.vel += push;
+            character.set_vel(character.vel() - push);
+        }
+    }
+
+    /// Fling every entity within power of pos outwards, getting weaker
+    /// with distance
+    pub fn explode(&self, pos: Vec3, power: f32) {
+        let mut physics = self.components_mut::<Physics>();
+        for id in self.within_radius(pos, power) {
+            let Some(t) = self.get::<Transform>(id) else {
+                continue;
+            };
+            if let Some(p) = physics.get_mut(&id) {
+                p.vel += explosion_knockback(pos, power, t.pos);
+            }
+        }
+    }
+
+    fn aabbs(&self) -> Vec<(EntityId, Aabb)> {
+        let transforms = self.components::<Transform>();
+        self.components::<Physics>()
+            .iter()
+            .filter_map(|(id, p)| Some((*id, p.aabb(transforms.get(id)?.pos))))
+            .collect()
+    }
+}
+
+/// Horizontal direction from a to b, even if both are at the same spot
+fn push_direction(a: Vec3, b: Vec3) -> Vec3 {
+    let d = Vec3::new(b.x - a.x, 0.0, b.z - a.z);
+    if d.length_squared() < 0.0001 {
+        Vec3::X
+    } else {
+        d.normalize()
+    }
+}
+
+/// Velocity that knocks something at pos away from from, with a bit of
+/// upwards motion so it actually leaves the ground
+pub fn knockback(from: Vec3, pos: Vec3, strength: f32) -> Vec3 {
+    (push_direction(from, pos) + Vec3::new(0.0, 0.5, 0.0)).normalize() * strength
+}
+
+/// Velocity that an explosion adds to something at pos
+pub fn explosion_knockback(center: Vec3, power: f32, pos: Vec3) -> Vec3 {
+    let dist = (pos - center).length();
+    if dist >= power {
+        return Vec3::ZERO;
+    }
+    let falloff = 1.0 - dist / power;
+    let dir = (pos - center + Vec3::new(0.0, 0.5, 0.0)).normalize_or_zero();
+    dir * EXPLOSION_KNOCKBACK * falloff
+}
+
+impl EntityStore {
+    pub fn add_handler(reactor: &mut Reactor<Message>, game: &GameState) {
+        {
+            let entities = game.entities_rc();
+            let player = game.player_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let entities = entities.borrow();
+                entities.push_apart();
+                let mut player = player.borrow_mut();
+                if !player.no_clip() && !player.is_dead() {
+                    entities.push_apart_character(&mut player);
+                }
+            };
+            reactor.add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
+        }
+        {
+            let entities = game.entities_rc();
+            let player = game.player_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                if let Message::Explosion { pos, power, .. } = msg {
+                    entities.borrow().explode(pos, power);
+                    let mut player = player.borrow_mut();
+                    if !player.no_clip() {
+                        let vel = player.vel() + explosion_knockback(pos, power, player.pos());
+                        player.set_vel(vel);
+                    }
+                }
+            };
+            reactor.add_sink(
+                Message::Explosion {
+                    pos: Vec3::ZERO,
+                    power: 0.0,
+                    by_player: false,
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let entities = game.entities_rc();
+            reactor.add_sink(
+                Message::ResetEverything,
+                Box::new(move |_: &Reactor<Message>, _msg: Message| {
+                    entities.borrow_mut().clear();
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Ai(u8);
+
+    #[test]
+    fn test_entity_store() {
+        let mut store = EntityStore::new();
+        let a = store
+            .spawn()
+            .with(Transform::new(Vec3::ZERO))
+            .with(Physics::default())
+            .with(Ai(1))
+            .id();
+        let b = store
+            .spawn()
+            .with(Transform::new(Vec3::new(0.5, 0.0, 0.0)))
+            .with(Physics::default())
+            .id();
+        let c = store.spawn().with(Transform::new(Vec3::splat(8.0))).id();
+        assert_ne!(a, b);
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.components::<Ai>().get(&a), Some(&Ai(1)));
+        assert_eq!(store.components::<Ai>().get(&b), None);
+
+        store.components_mut::<Ai>().get_mut(&a).unwrap().0 = 2;
+        {
+            // Different component types can be borrowed at the same time
+            let ai = store.components::<Ai>();
+            let mut transforms = store.components_mut::<Transform>();
+            for (id, ai) in ai.iter() {
+                transforms.get_mut(id).unwrap().pos.y += ai.0 as f32;
+            }
+        }
+        assert_eq!(store.get::<Transform>(a).unwrap().pos.y, 2.0);
+
+        assert_eq!(store.within_radius(Vec3::ZERO, 1.0), vec![b]);
+        assert_eq!(store.within_radius(Vec3::ZERO, 4.0), vec![a, b]);
+        assert_eq!(store.within_radius(Vec3::splat(7.5), 1.0), vec![c]);
+        assert_eq!(store.collisions(), vec![]);
+        store
+            .components_mut::<Transform>()
+            .get_mut(&a)
+            .unwrap()
+            .pos
+            .y = 0.2;
+        assert_eq!(store.collisions(), vec![(a, b)]);
+        let aabb = Aabb::from_center(Vec3::new(1.0, 0.0, 0.0), Vec3::splat(0.2));
+        assert_eq!(store.overlapping(&aabb), vec![b]);
+
+        assert!(store.despawn(a));
+        assert!(!store.despawn(a));
+        assert!(!store.contains(a));
+        assert!(store.components::<Ai>().is_empty());
+        store.insert(a, Ai(3));
+        assert!(store.components::<Ai>().is_empty());
+
+        // Solid entities get pushed apart, everything else passes through
+        let solid = |store: &mut EntityStore, x: f32| {
+            store
+                .spawn()
+                .with(Transform::new(Vec3::new(x, 0.0, 0.0)))
+                .with(Physics::default())
+                .with(Health::new(4))
+                .id()
+        };
+        let d = solid(&mut store, 0.7);
+        let e = solid(&mut store, 1.0);
+        store.push_apart();
+        assert_eq!(store.get::<Physics>(b).unwrap().vel, Vec3::ZERO);
+        assert!(store.get::<Physics>(d).unwrap().vel.x < 0.0);
+        assert!(store.get::<Physics>(e).unwrap().vel.x > 0.0);
+
+        store.explode(Vec3::new(1.0, 0.0, -1.0), 4.0);
+        let vel = store.get::<Physics>(e).unwrap().vel;
+        assert!(vel.z > 0.0 && vel.y > 0.0);
+        assert!(vel.length() < EXPLOSION_KNOCKBACK);
+        assert_eq!(store.get::<Physics>(c).map(|p| p.vel), None);
+
+        store.clear();
+        assert!(store.is_empty());
+        assert!(store.components::<Transform>().is_empty());
+        assert_ne!(store.spawn().id(), c);
+    }
+
+    #[test]
+    fn test_knockback() {
+        let k = knockback(Vec3::ZERO, Vec3::new(0.0, 0.0, 2.0), 0.1);
+        assert!((k.length() - 0.1).abs() < 0.0001);
+        assert!(k.z > 0.0 && k.y > 0.0 && k.x == 0.0);
+        assert!(knockback(Vec3::ZERO, Vec3::ZERO, 0.1).length() > 0.0);
+
+        let near = explosion_knockback(Vec3::ZERO, 5.0, Vec3::new(1.0, 0.0, 0.0));
+        let far = explosion_knockback(Vec3::ZERO, 5.0, Vec3::new(4.0, 0.0, 0.0));
+        assert!(near.length() > far.length());
+        assert!(far.x > 0.0);
+        assert_eq!(
+            explosion_knockback(Vec3::ZERO, 5.0, Vec3::splat(5.0)),
+            Vec3::ZERO
+        );
+    }
+}
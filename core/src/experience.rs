@@ -1,6 +1,9 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-#[derive(Copy, Clone, Debug)]
+use crate::{ExperienceCurve, PROGRESSION};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Experience {
     xp: u64,
     xp_total: u64,
@@ -13,7 +16,7 @@ impl Default for Experience {
         Self {
             xp: 0,
             xp_total: 0,
-            next_level: 32,
+            next_level: PROGRESSION.with(|p| p.borrow().curve().xp_for_level(1)),
             level: 1,
         }
     }
@@ -45,12 +48,17 @@ impl Experience {
     pub fn gain(&mut self, xp: u64) {
         self.xp += xp;
     }
+    /// Level up using the experience curve from PROGRESSION
     pub fn level_up(&mut self) -> bool {
-        if self.xp >= self.next_level {
+        PROGRESSION.with(|p| self.level_up_with(p.borrow().curve()))
+    }
+
+    pub fn level_up_with(&mut self, curve: &ExperienceCurve) -> bool {
+        if self.xp >= self.next_level && self.level < u8::MAX {
             self.xp_total += self.next_level;
             self.xp -= self.next_level;
-            self.next_level *= 3;
             self.level += 1;
+            self.next_level = curve.xp_for_level(self.level);
             true
         } else {
             false
@@ -59,10 +67,7 @@ impl Experience {
 
     #[inline]
     pub fn reset(&mut self) {
-        self.xp = 0;
-        self.xp_total = 0;
-        self.next_level = 32;
-        self.level = 1;
+        *self = Self::default();
     }
     #[inline]
     pub fn set_next_level(&mut self, next_level: u64) {
@@ -1,10 +1,17 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use crate::{BlockId, ItemId, ItemUse, MiningCategory, ToolKind, BLOCKS, ITEMS};
 use serde::{Deserialize, Serialize};
 
+/// How many blocks fit into a single inventory slot
+pub const BLOCK_STACK_SIZE: u16 = 99;
+
+/// Damage dealt to a block every tick while mining without a fitting tool
+pub const HAND_MINING_DAMAGE: u16 = 2;
+
 #[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BlockItem {
-    pub block: u8,
+    pub block: BlockId,
     pub amount: u16,
 }
 
@@ -15,14 +22,298 @@ impl From<BlockItem> for Item {
 }
 
 impl BlockItem {
-    pub fn new(block: u8, amount: u16) -> Self {
+    pub fn new(block: BlockId, amount: u16) -> Self {
         Self { block, amount }
     }
 }
 
+/// A stack of items from the ITEMS registry, durability is only used by
+/// items that wear out, which never stack.
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub amount: u16,
+    pub durability: u16,
+}
+
+impl From<ItemStack> for Item {
+    fn from(i: ItemStack) -> Self {
+        Item::Stack(i)
+    }
+}
+
+impl ItemStack {
+    /// Create a new stack, with full durability
+    pub fn new(item: ItemId, amount: u16) -> Self {
+        let durability = ITEMS.with(|items| {
+            items
+                .borrow()
+                .get(item as usize)
+                .map(|it| it.durability())
+                .unwrap_or_default()
+        });
+        Self {
+            item,
+            amount,
+            durability,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Item {
     #[default]
     None,
     Block(BlockItem),
+    Stack(ItemStack),
+}
+
+/// Whether a tool of that kind is meant for blocks of that category
+fn tool_fits(kind: ToolKind, cat: MiningCategory) -> bool {
+    matches!(
+        (kind, cat),
+        (ToolKind::Pickaxe, MiningCategory::Pickaxe(_))
+            | (ToolKind::Axe, MiningCategory::Axe(_))
+            | (ToolKind::Shovel, MiningCategory::Shovel(_))
+    )
+}
+
+/// Damage a tool deals to a block per tick, a matching tool of a high
+/// enough tier is a lot faster, one of a lower tier only helps a little.
+pub fn tool_mining_damage(kind: ToolKind, tier: u8, cat: MiningCategory) -> u16 {
+    let tier = tier as u16;
+    if !tool_fits(kind, cat) || tier == 0 {
+        HAND_MINING_DAMAGE
+    } else if tier >= cat.level() as u16 {
+        HAND_MINING_DAMAGE * (1 + 2 * tier)
+    } else {
+        HAND_MINING_DAMAGE + tier
+    }
+}
+
+impl Item {
+    #[inline]
+    pub fn amount(&self) -> u16 {
+        match self {
+            Item::None => 0,
+            Item::Block(bi) => bi.amount,
+            Item::Stack(s) => s.amount,
+        }
+    }
+
+    /// Same item, with a different amount, or Item::None if amount is 0
+    pub fn with_amount(self, amount: u16) -> Self {
+        match self {
+            _ if amount == 0 => Item::None,
+            Item::None => Item::None,
+            Item::Block(bi) => BlockItem::new(bi.block, amount).into(),
+            Item::Stack(s) => ItemStack { amount, ..s }.into(),
+        }
+    }
+
+    pub fn max_stack(&self) -> u16 {
+        match self {
+            Item::None => 0,
+            Item::Block(_) => BLOCK_STACK_SIZE,
+            Item::Stack(s) => ITEMS.with(|items| {
+                items
+                    .borrow()
+                    .get(s.item as usize)
+                    .map(|it| it.max_stack())
+                    .unwrap_or(1)
+            }),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Item::None => String::new(),
+            Item::Block(bi) => BLOCKS.with(|blocks| {
+                blocks
+                    .borrow()
+                    .get(bi.block as usize)
+                    .map(|bt| bt.name().to_string())
+                    .unwrap_or_default()
+            }),
+            Item::Stack(s) => ITEMS.with(|items| {
+                items
+                    .borrow()
+                    .get(s.item as usize)
+                    .map(|it| it.name().to_string())
+                    .unwrap_or_default()
+            }),
+        }
+    }
+
+    /// What happens when the item is being used, blocks are placed instead
+    pub fn item_use(&self) -> ItemUse {
+        match self {
+            Item::Stack(s) => ITEMS.with(|items| {
+                items
+                    .borrow()
+                    .get(s.item as usize)
+                    .map(|it| it.item_use())
+                    .unwrap_or_default()
+            }),
+            _ => ItemUse::None,
+        }
+    }
+
+    /// Whether both are the same block or item, regardless of amount or wear
+    pub fn same_kind(&self, other: &Item) -> bool {
+        match (self, other) {
+            (Item::Block(a), Item::Block(b)) => a.block == b.block,
+            (Item::Stack(a), Item::Stack(b)) => a.item == b.item,
+            _ => false,
+        }
+    }
+
+    /// Whether other can be put onto the same stack as self
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        match (self, other) {
+            (Item::Stack(_), Item::Stack(_)) => self.same_kind(other) && self.max_stack() > 1,
+            _ => self.same_kind(other),
+        }
+    }
+
+    /// Move as much of item onto self as fits, returning the rest
+    pub fn merge(&mut self, item: Item) -> Item {
+        if item == Item::None {
+            return item;
+        }
+        if *self == Item::None {
+            let n = item.amount().min(item.max_stack());
+            *self = item.with_amount(n);
+            return item.with_amount(item.amount() - n);
+        }
+        if !self.stacks_with(&item) {
+            return item;
+        }
+        let n = item
+            .amount()
+            .min(self.max_stack().saturating_sub(self.amount()));
+        *self = self.with_amount(self.amount() + n);
+        item.with_amount(item.amount() - n)
+    }
+
+    /// Remove a single item from the stack and return it
+    pub fn take_one(&mut self) -> Item {
+        let one = self.with_amount(1);
+        *self = self.with_amount(self.amount().saturating_sub(1));
+        one
+    }
+
+    /// Whether this is a tool meant for mining blocks of that category
+    pub fn is_tool_for(&self, cat: MiningCategory) -> bool {
+        match self.item_use() {
+            ItemUse::Tool { kind, .. } => tool_fits(kind, cat),
+            _ => false,
+        }
+    }
+
+    pub fn mining_damage(&self, cat: MiningCategory) -> u16 {
+        match self.item_use() {
+            ItemUse::Tool { kind, tier } => tool_mining_damage(kind, tier, cat),
+            _ => HAND_MINING_DAMAGE,
+        }
+    }
+
+    /// Use up some durability, returns true if the item just broke and
+    /// has therefore been replaced by Item::None.
+    pub fn wear(&mut self) -> bool {
+        if let Item::Stack(s) = self {
+            if s.durability > 0 {
+                s.durability -= 1;
+                if s.durability == 0 {
+                    *self = Item::None;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Put item into a list of slots, filling up existing stacks before using
+/// empty slots, returns whatever didn't fit.
+pub fn insert_into_slots(slots: &mut [Item], item: Item) -> Item {
+    let mut item = item;
+    for slot in slots.iter_mut() {
+        if slot.stacks_with(&item) {
+            item = slot.merge(item);
+        }
+    }
+    for slot in slots.iter_mut() {
+        if *slot == Item::None {
+            item = slot.merge(item);
+        }
+    }
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item_id;
+
+    #[test]
+    fn test_stacking() {
+        let mut slot = Item::None;
+        assert_eq!(slot.merge(BlockItem::new(3, 60).into()), Item::None);
+        assert_eq!(
+            slot.merge(BlockItem::new(3, 60).into()),
+            BlockItem::new(3, 21).into()
+        );
+        assert_eq!(slot.amount(), BLOCK_STACK_SIZE);
+        let other: Item = BlockItem::new(4, 1).into();
+        assert_eq!(slot.merge(other), other);
+        assert_eq!(slot.take_one(), BlockItem::new(3, 1).into());
+        assert_eq!(slot.amount(), BLOCK_STACK_SIZE - 1);
+
+        let pick = ItemStack::new(item_id("wooden_pickaxe").unwrap(), 1);
+        let mut slot: Item = pick.into();
+        assert_eq!(slot.merge(pick.into()), pick.into());
+        assert_eq!(slot.take_one(), pick.into());
+        assert_eq!(slot, Item::None);
+
+        let grenade = ItemStack::new(item_id("grenade").unwrap(), 3);
+        let mut slot: Item = grenade.into();
+        assert_eq!(slot.merge(grenade.into()), Item::None);
+        assert_eq!(slot.amount(), 6);
+        assert_eq!(slot.name(), "Grenade");
+    }
+
+    #[test]
+    fn test_tools() {
+        let mut pick: Item = ItemStack::new(item_id("stone_pickaxe").unwrap(), 1).into();
+        assert_eq!(
+            Item::None.mining_damage(MiningCategory::Pickaxe(1)),
+            HAND_MINING_DAMAGE
+        );
+        let stone = pick.mining_damage(MiningCategory::Pickaxe(1));
+        let obsidian = pick.mining_damage(MiningCategory::Pickaxe(2));
+        let crystal = pick.mining_damage(MiningCategory::Pickaxe(3));
+        assert_eq!(stone, obsidian);
+        assert!(crystal < obsidian && crystal > HAND_MINING_DAMAGE);
+        assert_eq!(
+            pick.mining_damage(MiningCategory::Axe(1)),
+            HAND_MINING_DAMAGE
+        );
+        assert!(pick.is_tool_for(MiningCategory::Pickaxe(3)));
+        assert!(!pick.is_tool_for(MiningCategory::Axe(1)));
+        assert!(!pick.is_tool_for(MiningCategory::None));
+        assert!(
+            tool_mining_damage(ToolKind::Axe, 3, MiningCategory::Axe(1))
+                > tool_mining_damage(ToolKind::Axe, 1, MiningCategory::Axe(1))
+        );
+        let Item::Stack(s) = pick else {
+            panic!("Expected a stack");
+        };
+        for _ in 1..s.durability {
+            assert!(!pick.wear());
+        }
+        assert!(pick.wear());
+        assert_eq!(pick, Item::None);
+        assert!(!Item::from(BlockItem::new(3, 1)).wear());
+    }
 }
@@ -0,0 +1,341 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{
+    block_id_or_err, block_type::parse_color, deserialize_projectile_id, BlockId, ProjectileTypeId,
+};
+use anyhow::{anyhow, Result};
+use rgb::RGBA8;
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Global item id, index into ITEMS
+pub type ItemId = u16;
+
+thread_local! {
+    pub static ITEMS:RefCell<Vec<ItemType>> = RefCell::new(ItemType::new_default());
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolKind {
+    #[default]
+    Pickaxe,
+    Axe,
+    Shovel,
+}
+
+/// Where on the body a piece of equipment goes, also the index into
+/// Character::equipment
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EquipmentSlot {
+    #[default]
+    Head = 0,
+    Body,
+    Feet,
+    Accessory,
+}
+
+pub const EQUIPMENT_SLOTS: [EquipmentSlot; 4] = [
+    EquipmentSlot::Head,
+    EquipmentSlot::Body,
+    EquipmentSlot::Feet,
+    EquipmentSlot::Accessory,
+];
+
+impl EquipmentSlot {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Head => "Head",
+            Self::Body => "Body",
+            Self::Feet => "Feet",
+            Self::Accessory => "Accessory",
+        }
+    }
+}
+
+/// What a piece of equipment does while being worn
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EquipmentStats {
+    pub slot: EquipmentSlot,
+    /// Every point of armor reduces incoming damage a little bit more
+    #[serde(default)]
+    pub armor: u16,
+    #[serde(default)]
+    pub max_health: i16,
+    /// Movement speed modifier in percent
+    #[serde(default)]
+    pub speed: i16,
+}
+
+/// What happens when a player uses an item
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemUse {
+    #[default]
+    None,
+    /// Mines blocks of a matching MiningCategory faster
+    Tool { kind: ToolKind, tier: u8 },
+    /// Gets thrown as a projectile of this type
+    #[serde(deserialize_with = "deserialize_projectile_id")]
+    Throw(ProjectileTypeId),
+    /// Can be worn in one of the equipment slots
+    Equip(EquipmentStats),
+}
+
+/// How an item is drawn, both in the world and within the inventory
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ItemIcon {
+    #[default]
+    None,
+    /// Looks just like a block
+    Block(BlockId),
+    /// A 16x16 sprite built into the client, tinted using the item colors
+    Sprite(String),
+    /// A .vox model built into the client
+    Voxel(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ItemType {
+    id: String,
+    name: String,
+    icon: ItemIcon,
+    colors: [RGBA8; 2],
+    max_stack: u16,
+    durability: u16,
+    item_use: ItemUse,
+}
+
+impl ItemType {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            max_stack: 99,
+            ..Default::default()
+        }
+    }
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+    pub fn with_icon(mut self, icon: ItemIcon) -> Self {
+        self.icon = icon;
+        self
+    }
+    pub fn with_colors(mut self, a: RGBA8, b: RGBA8) -> Self {
+        self.colors = [a, b];
+        self
+    }
+    pub fn with_max_stack(mut self, max_stack: u16) -> Self {
+        self.max_stack = max_stack;
+        self
+    }
+    /// Items that wear out can't be stacked
+    pub fn with_durability(mut self, durability: u16) -> Self {
+        self.durability = durability;
+        if durability > 0 {
+            self.max_stack = 1;
+        }
+        self
+    }
+    pub fn with_item_use(mut self, item_use: ItemUse) -> Self {
+        self.item_use = item_use;
+        self
+    }
+
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn icon(&self) -> &ItemIcon {
+        &self.icon
+    }
+    #[inline]
+    pub fn colors(&self) -> [RGBA8; 2] {
+        self.colors
+    }
+    #[inline]
+    pub fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+    #[inline]
+    pub fn durability(&self) -> u16 {
+        self.durability
+    }
+    #[inline]
+    pub fn item_use(&self) -> ItemUse {
+        self.item_use
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ItemIconDefinition {
+    Block(String),
+    Sprite(String),
+    Voxel(String),
+}
+
+/// The format items are defined in, see assets/items.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemTypeDefinition {
+    id: String,
+    name: String,
+    icon: Option<ItemIconDefinition>,
+    #[serde(default)]
+    colors: Option<[String; 2]>,
+    #[serde(default)]
+    stack: Option<u16>,
+    #[serde(default)]
+    durability: u16,
+    #[serde(default, rename = "use")]
+    item_use: ItemUse,
+}
+
+impl ItemTypeDefinition {
+    fn validate(self) -> Result<ItemType> {
+        let stack = match self.stack {
+            Some(0) => return Err(anyhow!("Stack size has to be at least 1")),
+            Some(s) if s > 1 && self.durability > 0 => {
+                return Err(anyhow!("Items with durability can't be stacked"))
+            }
+            Some(s) => s,
+            None => 99,
+        };
+        let icon = match self.icon {
+            None => ItemIcon::None,
+            Some(ItemIconDefinition::Block(b)) => ItemIcon::Block(block_id_or_err(&b)?),
+            Some(ItemIconDefinition::Sprite(s)) => ItemIcon::Sprite(s),
+            Some(ItemIconDefinition::Voxel(v)) if !v.ends_with(".vox") => {
+                return Err(anyhow!("Icon {:?} is not a .vox file", v))
+            }
+            Some(ItemIconDefinition::Voxel(v)) => ItemIcon::Voxel(v),
+        };
+        let mut it = ItemType::new(&self.name)
+            .with_id(&self.id)
+            .with_icon(icon)
+            .with_max_stack(stack)
+            .with_durability(self.durability)
+            .with_item_use(self.item_use);
+        if let Some([a, b]) = self.colors {
+            it = it.with_colors(parse_color(&a)?, parse_color(&b)?);
+        }
+        Ok(it)
+    }
+}
+
+/// Look up the numeric id of an item by its string id
+pub fn item_id(id: &str) -> Option<ItemId> {
+    ITEMS.with(|items| {
+        items
+            .borrow()
+            .iter()
+            .position(|i| i.id() == id)
+            .map(|i| i as ItemId)
+    })
+}
+
+/// Same as item_id, but returns an error naming the missing item
+pub fn item_id_or_err(id: &str) -> Result<ItemId> {
+    item_id(id).ok_or_else(|| anyhow!("Unknown item id {:?}", id))
+}
+
+impl ItemType {
+    /// Returns the item definitions from assets/items.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "items.json",
+            include_str!("../assets/items.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of item definitions, the position in the list
+    /// determines the numeric id.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(
+            json,
+            "item",
+            ItemId::MAX as usize,
+            ItemTypeDefinition::validate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projectile_type_id;
+
+    #[test]
+    fn test_item_definitions() {
+        let items = ItemType::new_default();
+        assert!(items.iter().any(|i| i.id() == "grenade"));
+        assert_eq!(item_id("unobtainium"), None);
+
+        let items = ItemType::from_json(
+            r##"[
+                { "id": "pick", "name": "Pick", "icon": { "sprite": "pickaxe" },
+                  "colors": ["#102030", "#405060"], "durability": 10,
+                  "use": { "tool": { "kind": "pickaxe", "tier": 2 } } },
+                { "id": "bomb", "name": "Bomb", "icon": { "voxel": "grenade.vox" }, "stack": 8, "use": { "throw": "timed_grenade" } },
+                { "id": "rock", "name": "Rock", "icon": { "block": "stone" } },
+                { "id": "hat", "name": "Hat", "durability": 10, "use": { "equip": { "slot": "head", "armor": 2 } } }
+            ]"##,
+        )
+        .unwrap();
+        assert_eq!(items[0].max_stack(), 1);
+        assert_eq!(items[0].durability(), 10);
+        assert_eq!(
+            items[0].item_use(),
+            ItemUse::Tool {
+                kind: ToolKind::Pickaxe,
+                tier: 2
+            }
+        );
+        assert_eq!(items[0].icon(), &ItemIcon::Sprite("pickaxe".to_string()));
+        assert_eq!(items[1].max_stack(), 8);
+        assert_eq!(
+            items[1].item_use(),
+            ItemUse::Throw(projectile_type_id("timed_grenade").unwrap())
+        );
+        assert_eq!(items[2].icon(), &ItemIcon::Block(3));
+        assert_eq!(items[2].max_stack(), 99);
+        assert_eq!(
+            items[3].item_use(),
+            ItemUse::Equip(EquipmentStats {
+                slot: EquipmentSlot::Head,
+                armor: 2,
+                ..Default::default()
+            })
+        );
+
+        assert!(ItemType::from_json(r#"[{ "id": "Bad", "name": "Bad" }]"#).is_err());
+        assert!(ItemType::from_json(r#"[{ "id": "a", "name": "A", "stack": 0 }]"#).is_err());
+        assert!(ItemType::from_json(
+            r#"[{ "id": "a", "name": "A", "use": { "throw": "boomerang" } }]"#
+        )
+        .is_err());
+        assert!(ItemType::from_json(
+            r#"[{ "id": "a", "name": "A", "icon": { "block": "unobtainium" } }]"#
+        )
+        .is_err());
+        assert!(
+            ItemType::from_json(r#"[{ "id": "a", "name": "A", "icon": { "voxel": "bag" } }]"#)
+                .is_err()
+        );
+        assert!(ItemType::from_json(
+            r#"[{ "id": "a", "name": "A", "stack": 5, "durability": 5 }]"#
+        )
+        .is_err());
+    }
+}
@@ -1,45 +1,95 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+mod behavior;
+mod block_entity;
+mod block_entity_stash;
+mod block_meta;
 mod block_type;
 mod character;
 mod chungus;
 mod chunk;
+mod collision;
+mod command;
+mod crafting;
+mod definition;
 mod entity;
+mod entity_store;
 mod experience;
 mod game_log;
 mod health;
 mod item;
+mod item_type;
 mod iter;
+mod loot;
 mod message;
+mod mob_stash;
+mod mob_type;
+mod pathfinding;
+mod progression;
+mod projectile_type;
 mod queue;
 mod reactor;
+mod run;
+mod spatial_hash;
 mod state;
+mod status_effect;
 mod worldgen;
 
-pub use self::character::{Character, CharacterAnimation, RaycastReturn};
-pub use self::chungus::{Chungus, BLOCKS, FLUIDS};
-pub use self::entity::Entity;
+use serde::Deserialize;
+
+pub use self::character::{
+    armor_reduction, Character, CharacterAnimation, RaycastReturn, CHARACTER_SAVE_VERSION,
+    EQUIPMENT_SIZE, HOTBAR_SIZE, INVENTORY_SIZE,
+};
+pub use self::chungus::{Chungus, BLOCKS, DEFAULT_SEED, FLUIDS};
+pub use self::command::{run_console_line, Command, CommandHandler, CommandList, COMMANDS};
+pub use self::definition::MOD_DIR;
+pub use self::entity::{Physics, Renderable, Transform, ENTITY_COL_BOX};
+pub use self::entity_store::{
+    explosion_knockback, knockback, ComponentMap, EntityBuilder, EntityId, EntityStore,
+    KNOCKBACK_PER_DAMAGE,
+};
 pub use self::game_log::{GameLog, GAME_LOG};
 pub use self::health::Health;
-pub use self::state::GameState;
+pub use self::spatial_hash::{SpatialHash, SPATIAL_HASH_CELL_SIZE};
+pub use self::state::{is_daytime, GameState, MS_PER_TICK, TICKS_PER_DAY};
+pub use behavior::*;
+pub use block_entity::*;
+pub use block_entity_stash::*;
+pub use block_meta::*;
 pub use block_type::*;
 pub use chunk::*;
+pub use collision::*;
+pub use crafting::*;
 pub use experience::*;
 pub use item::*;
+pub use item_type::*;
 pub use iter::*;
+pub use loot::*;
 pub use message::*;
+pub use mob_stash::*;
+pub use mob_type::*;
+pub use pathfinding::*;
+pub use progression::*;
+pub use projectile_type::*;
 pub use queue::*;
 pub use reactor::*;
+pub use run::*;
+pub use status_effect::*;
 
 pub const CHUNK_BITS: i32 = 5;
 pub const CHUNK_SIZE: usize = 1 << CHUNK_BITS;
 pub const CHUNK_MASK: i32 = CHUNK_SIZE as i32 - 1;
 
-pub type ChunkData = [[[u8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+/// Global block id, index into BLOCKS
+pub type BlockId = u16;
+
+pub type ChunkData<T = u8> = [[[T; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
 
-pub type ChunkBuffer = [[[u8; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+pub type ChunkBuffer<T = u8> = [[[T; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Side {
     #[default]
     Front = 0,
@@ -60,3 +110,15 @@ impl From<Side> for usize {
         s as usize
     }
 }
+impl From<u8> for Side {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => Side::Back,
+            2 => Side::Top,
+            3 => Side::Bottom,
+            4 => Side::Left,
+            5 => Side::Right,
+            _ => Side::Front,
+        }
+    }
+}
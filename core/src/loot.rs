@@ -0,0 +1,392 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{BlockId, BlockItem, Character, Item, ItemUse, RecipeItemDefinition, ToolKind, BLOCKS};
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Global loot table id, index into LOOT_TABLES
+pub type LootTableId = u16;
+
+thread_local! {
+    pub static LOOT_TABLES:RefCell<Vec<LootTable>> = RefCell::new(LootTable::new_default());
+}
+
+/// Everything loot conditions may depend on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LootContext {
+    /// Kind and tier of the tool that was used, if any
+    pub tool: Option<(ToolKind, u8)>,
+    pub level: u8,
+}
+
+impl LootContext {
+    /// Use whatever the character is holding right now and their level
+    pub fn from_character(c: &Character) -> Self {
+        let item = c.inventory().get(c.inventory_active()).copied();
+        let tool = match item.unwrap_or_default().item_use() {
+            ItemUse::Tool { kind, tier } => Some((kind, tier)),
+            _ => None,
+        };
+        Self {
+            tool,
+            level: c.experience().level(),
+        }
+    }
+}
+
+/// A tool that has to be used for an entry to drop
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LootTool {
+    pub kind: ToolKind,
+    #[serde(default)]
+    pub tier: u8,
+}
+
+/// A single possible drop within a pool, an entry without an item drops
+/// nothing, which makes the other entries of the pool less likely.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LootEntry {
+    pub item: Item,
+    pub weight: u32,
+    pub min: u16,
+    pub max: u16,
+    /// Chance of dropping anything at all once picked, between 0 and 1
+    pub chance: f32,
+    pub tool: Option<LootTool>,
+    pub min_level: u8,
+}
+
+impl LootEntry {
+    /// Whether the conditions of this entry hold within ctx
+    pub fn allows(&self, ctx: &LootContext) -> bool {
+        ctx.level >= self.min_level
+            && match (self.tool, ctx.tool) {
+                (None, _) => true,
+                (Some(want), Some((kind, tier))) => want.kind == kind && tier >= want.tier,
+                (Some(_), None) => false,
+            }
+    }
+
+    fn roll(&self, rng: &mut impl Rng) -> Item {
+        if self.item == Item::None || !rng.gen_bool(self.chance as f64) {
+            return Item::None;
+        }
+        self.item.with_amount(rng.gen_range(self.min..=self.max))
+    }
+}
+
+/// Picks an entry rolls times, weighted among the entries whose conditions hold
+#[derive(Clone, Debug, PartialEq)]
+pub struct LootPool {
+    pub rolls: u16,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootPool {
+    fn roll(&self, ctx: &LootContext, rng: &mut impl Rng, out: &mut Vec<Item>) {
+        let total: u32 = self
+            .entries
+            .iter()
+            .filter(|e| e.allows(ctx))
+            .map(|e| e.weight)
+            .sum();
+        if total == 0 {
+            return;
+        }
+        for _ in 0..self.rolls {
+            let mut pick = rng.gen_range(0..total);
+            for e in self.entries.iter().filter(|e| e.allows(ctx)) {
+                if pick < e.weight {
+                    let item = e.roll(rng);
+                    if item.amount() > 0 {
+                        out.push(item);
+                    }
+                    break;
+                }
+                pick -= e.weight;
+            }
+        }
+    }
+}
+
+/// What drops when a mob dies or a block breaks, see assets/loot.json
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LootTable {
+    id: String,
+    pools: Vec<LootPool>,
+}
+
+impl LootTable {
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn pools(&self) -> &[LootPool] {
+        &self.pools
+    }
+
+    /// Roll every pool once, rng is passed in so tests can use a fixed seed
+    pub fn roll(&self, ctx: &LootContext, rng: &mut impl Rng) -> Vec<Item> {
+        let mut ret = vec![];
+        for pool in self.pools.iter() {
+            pool.roll(ctx, rng, &mut ret);
+        }
+        ret
+    }
+
+    /// Returns the loot tables from assets/loot.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "loot.json",
+            include_str!("../assets/loot.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of loot tables, the position in the list determines
+    /// the numeric id.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(
+            json,
+            "loot table",
+            LootTableId::MAX as usize,
+            LootTableDefinition::validate,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LootEntryDefinition {
+    #[serde(default)]
+    block: Option<String>,
+    #[serde(default)]
+    item: Option<String>,
+    #[serde(default = "default_one_u32")]
+    weight: u32,
+    #[serde(default = "default_one_u16")]
+    min: u16,
+    #[serde(default = "default_one_u16")]
+    max: u16,
+    #[serde(default = "default_chance")]
+    chance: f32,
+    #[serde(default)]
+    tool: Option<LootTool>,
+    #[serde(default)]
+    min_level: u8,
+}
+
+fn default_one_u32() -> u32 {
+    1
+}
+
+fn default_one_u16() -> u16 {
+    1
+}
+
+fn default_chance() -> f32 {
+    1.0
+}
+
+impl LootEntryDefinition {
+    fn validate(self) -> Result<LootEntry> {
+        if self.min > self.max {
+            return Err(anyhow!("Loot min can't be bigger than max"));
+        }
+        if !(0.0..=1.0).contains(&self.chance) {
+            return Err(anyhow!("Chance has to be between 0 and 1"));
+        }
+        let item = if self.block.is_none() && self.item.is_none() {
+            Item::None
+        } else {
+            RecipeItemDefinition {
+                block: self.block,
+                item: self.item,
+                amount: None,
+            }
+            .validate()?
+        };
+        Ok(LootEntry {
+            item,
+            weight: self.weight,
+            min: self.min,
+            max: self.max,
+            chance: self.chance,
+            tool: self.tool,
+            min_level: self.min_level,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LootPoolDefinition {
+    #[serde(default = "default_one_u16")]
+    rolls: u16,
+    entries: Vec<LootEntryDefinition>,
+}
+
+/// The format loot tables are defined in, see assets/loot.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LootTableDefinition {
+    id: String,
+    pools: Vec<LootPoolDefinition>,
+}
+
+impl LootTableDefinition {
+    fn validate(self) -> Result<LootTable> {
+        let mut pools = Vec::with_capacity(self.pools.len());
+        for (i, pool) in self.pools.into_iter().enumerate() {
+            if pool.entries.is_empty() {
+                return Err(anyhow!("Pool #{} needs at least one entry", i));
+            }
+            let entries = pool
+                .entries
+                .into_iter()
+                .map(|e| e.validate())
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("Invalid pool #{}", i))?;
+            pools.push(LootPool {
+                rolls: pool.rolls,
+                entries,
+            });
+        }
+        Ok(LootTable { id: self.id, pools })
+    }
+}
+
+/// Look up the numeric id of a loot table by its string id
+pub fn loot_table_id(id: &str) -> Option<LootTableId> {
+    LOOT_TABLES.with(|tables| {
+        tables
+            .borrow()
+            .iter()
+            .position(|t| t.id() == id)
+            .map(|i| i as LootTableId)
+    })
+}
+
+/// Same as loot_table_id, but returns an error naming the missing table
+pub fn loot_table_id_or_err(id: &str) -> Result<LootTableId> {
+    loot_table_id(id).ok_or_else(|| anyhow!("Unknown loot table id {:?}", id))
+}
+
+/// Roll one of the LOOT_TABLES, unknown ids drop nothing
+pub fn roll_loot(table: LootTableId, ctx: &LootContext, rng: &mut impl Rng) -> Vec<Item> {
+    LOOT_TABLES.with(|tables| {
+        tables
+            .borrow()
+            .get(table as usize)
+            .map(|t| t.roll(ctx, rng))
+            .unwrap_or_default()
+    })
+}
+
+/// Everything dropped when block breaks, blocks without a loot table
+/// just drop themselves.
+pub fn roll_block_loot(block: BlockId, ctx: &LootContext, rng: &mut impl Rng) -> Vec<Item> {
+    let table = BLOCKS.with(|blocks| {
+        blocks
+            .borrow()
+            .get(block as usize)
+            .and_then(|b| b.loot().map(loot_table_id))
+    });
+    match table {
+        None => vec![BlockItem::new(block, 1).into()],
+        Some(Some(table)) => roll_loot(table, ctx, rng),
+        Some(None) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_id, item_id, BlockType};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_loot_tables() {
+        // Every loot table referenced by a block has to exist
+        for b in BlockType::new_default() {
+            if let Some(table) = b.loot() {
+                assert!(loot_table_id(table).is_some(), "{}", table);
+            }
+        }
+
+        let tables = LootTable::from_json(
+            r#"[{ "id": "test", "pools": [
+                { "entries": [{ "block": "dirt", "min": 1, "max": 3 }] },
+                { "rolls": 2, "entries": [
+                    { "item": "grenade", "tool": { "kind": "shovel", "tier": 2 } },
+                    { "weight": 3 } ] },
+                { "entries": [{ "block": "crystals", "min_level": 5, "chance": 0.5 }] }
+            ] }]"#,
+        )
+        .unwrap();
+        let t = &tables[0];
+        let dirt = block_id("dirt").unwrap();
+        let crystals = block_id("crystals").unwrap();
+        let grenade = item_id("grenade").unwrap();
+        let count = |items: &[Item], f: &dyn Fn(&Item) -> bool| -> u32 {
+            items
+                .iter()
+                .filter(|i| f(i))
+                .map(|i| i.amount() as u32)
+                .sum()
+        };
+        let is_grenade = |i: &Item| matches!(i, Item::Stack(s) if s.item == grenade);
+        let is_crystals = |i: &Item| matches!(i, Item::Block(b) if b.block == crystals);
+
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let hand = LootContext::default();
+        let shovel = LootContext {
+            tool: Some((ToolKind::Shovel, 3)),
+            level: 9,
+        };
+        let (mut grenades, mut gems) = (0, 0);
+        for _ in 0..400 {
+            let items = t.roll(&hand, &mut rng);
+            let dirt_count = count(&items, &|i| matches!(i, Item::Block(b) if b.block == dirt));
+            assert!((1..=3).contains(&dirt_count));
+            assert_eq!(count(&items, &is_grenade), 0);
+            assert_eq!(count(&items, &is_crystals), 0);
+
+            let items = t.roll(&shovel, &mut rng);
+            grenades += count(&items, &is_grenade);
+            gems += count(&items, &is_crystals);
+        }
+        // 2 rolls with a 1 in 4 chance, and a 50% chance respectively
+        assert!((100..300).contains(&grenades), "{}", grenades);
+        assert!((100..300).contains(&gems), "{}", gems);
+
+        // The same seed has to give the same loot
+        let a = t.roll(&shovel, &mut XorShiftRng::seed_from_u64(7));
+        let b = t.roll(&shovel, &mut XorShiftRng::seed_from_u64(7));
+        assert_eq!(a, b);
+
+        let stone = block_id("stone").unwrap();
+        assert_eq!(
+            roll_block_loot(stone, &hand, &mut rng),
+            vec![BlockItem::new(stone, 1).into()]
+        );
+
+        let bad = [
+            r#"[{ "id": "a", "pools": [{ "entries": [] }] }]"#,
+            r#"[{ "id": "a", "pools": [{ "entries": [{ "block": "nothing" }] }] }]"#,
+            r#"[{ "id": "a", "pools": [{ "entries": [{ "block": "dirt", "min": 2, "max": 1 }] }] }]"#,
+            r#"[{ "id": "a", "pools": [{ "entries": [{ "block": "dirt", "chance": 2.0 }] }] }]"#,
+            r#"[{ "id": "a", "pools": [{ "entries": [{ "block": "dirt", "item": "grenade" }] }] }]"#,
+            r#"[{ "id": "a", "pools": [] }, { "id": "a", "pools": [] }]"#,
+        ];
+        for json in bad {
+            assert!(LootTable::from_json(json).is_err(), "{}", json);
+        }
+    }
+}
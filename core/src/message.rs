@@ -1,6 +1,6 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::Item;
+use crate::{BlockId, EntityId, Item, ProjectileOwner, ProjectileTypeId, RecipeId, StatusEffect};
 use glam::{IVec3, Vec3};
 use serde::{Deserialize, Serialize};
 
@@ -23,8 +23,14 @@ pub enum Message {
         ticks: u64,
     },
     GameQuit,
+    /// Write everything that persists in between sessions to disk
+    WorldSave,
     GameInit,
     ResetEverything,
+    /// Start over with a fresh world generated from seed
+    RunStart {
+        seed: u32,
+    },
 
     CharacterPosRotVel {
         pos: Vec3,
@@ -63,6 +69,10 @@ pub enum Message {
     CharacterStep {
         pos: Vec3,
     },
+    /// Grant the player a status effect, see StatusEffectKind for what's available
+    CharacterApplyEffect {
+        effect: StatusEffect,
+    },
     CharacterDropItem {
         pos: Vec3,
         vel: Vec3,
@@ -70,18 +80,15 @@ pub enum Message {
     },
     BlockMine {
         pos: IVec3,
-        block: u8,
+        block: BlockId,
     },
     BlockBreak {
         pos: IVec3,
-        block: u8,
+        block: BlockId,
     },
     BlockPlace {
         pos: IVec3,
-        block: u8,
-    },
-    EntityCollision {
-        pos: Vec3,
+        block: BlockId,
     },
     ItemDropPickup {
         pos: Vec3,
@@ -94,10 +101,51 @@ pub enum Message {
     Explosion {
         pos: Vec3,
         power: f32,
+        /// Whether the player caused it, by throwing a grenade for example
+        by_player: bool,
+    },
+    ProjectileSpawn {
+        pos: Vec3,
+        vel: Vec3,
+        projectile: ProjectileTypeId,
+        owner: ProjectileOwner,
+    },
+    /// A projectile ran into something that can take damage
+    ProjectileHit {
+        pos: Vec3,
+        target: EntityId,
+        damage: i16,
+        owner: ProjectileOwner,
+    },
+    /// A projectile blew up, turning into an Explosion that also breaks blocks
+    ProjectileExplode {
+        pos: Vec3,
+        power: f32,
+        by_player: bool,
     },
 
-    PlayerShoot,
+    /// Throw a projectile from the players hand
+    PlayerShoot {
+        projectile: ProjectileTypeId,
+    },
+    PlayerUseItem,
+    PlayerCraft {
+        recipe: RecipeId,
+    },
     PlayerDropItem,
+    /// Pick one of the perks offered on level up
+    PlayerChoosePerk {
+        choice: usize,
+    },
+    PlayerInventoryMove {
+        from: usize,
+        to: usize,
+        amount: u16,
+    },
+    PlayerInventoryDrop {
+        slot: usize,
+        amount: u16,
+    },
     PlayerMove {
         direction: Vec3,
     },
@@ -113,6 +161,9 @@ pub enum Message {
     PlayerBlockPlace {
         pos: IVec3,
     },
+    PlayerInteract {
+        pos: IVec3,
+    },
     PlayerSwitchSelection {
         delta: i32,
     },
@@ -128,23 +179,27 @@ pub enum Message {
         pos: Vec3,
         damage: i16,
     },
+    /// A mob died, by_player being set if the last damage it took came from the player
     MobDied {
         pos: Vec3,
+        by_player: bool,
     },
     MobStrike {
         pos: Vec3,
         damage: i16,
     },
+    /// Apply a status effect to every mob within radius of pos
+    MobApplyEffect {
+        pos: Vec3,
+        radius: f32,
+        effect: StatusEffect,
+    },
 
     SfxPlay {
         pos: Vec3,
         volume: f32,
         sfx: SfxId,
     },
-
-    WorldgenSpawnMob {
-        pos: Vec3,
-    },
 }
 
 // Would love to replace this enum by a string or something similar
@@ -176,7 +231,9 @@ impl Message {
             | Message::BlockBreak { pos, .. }
             | Message::BlockMine { pos, .. } => Some(pos.as_vec3()),
             Message::ItemDropPickup { pos, .. }
-            | Message::EntityCollision { pos, .. }
+            | Message::ProjectileSpawn { pos, .. }
+            | Message::ProjectileHit { pos, .. }
+            | Message::ProjectileExplode { pos, .. }
             | Message::SfxPlay { pos, .. }
             | Message::CharacterStep { pos, .. }
             | Message::CharacterDeath { pos, .. }
@@ -188,6 +245,7 @@ impl Message {
             | Message::MobDied { pos, .. }
             | Message::Explosion { pos, .. }
             | Message::MobStrike { pos, .. }
+            | Message::MobApplyEffect { pos, .. }
             | Message::CharacterJump { pos, .. } => Some(*pos),
             _ => None,
         }
@@ -0,0 +1,203 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{GameState, Message, Reactor, CHUNK_BITS};
+use anyhow::{anyhow, Context, Result};
+use glam::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Increased whenever the mob save format changes in an incompatible way
+pub const MOB_SAVE_VERSION: u32 = 1;
+
+/// A mob that has been put away, either because the player went too far
+/// away or because the game got saved.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SavedMob {
+    /// String id of the mob type, so saves survive changes to mobs.json
+    pub kind: String,
+    pub pos: Vec3,
+    pub rot: Vec3,
+    pub health: i16,
+    pub home: Vec3,
+    #[serde(default)]
+    pub elite: bool,
+}
+
+/// Mobs that aren't around right now, sorted by the chunk they're in
+#[derive(Clone, Debug, Default)]
+pub struct MobStash {
+    chunks: HashMap<IVec3, Vec<SavedMob>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MobSave {
+    version: u32,
+    /// Mobs only make sense within the world they were saved in
+    seed: u32,
+    #[serde(default)]
+    mobs: Vec<SavedMob>,
+}
+
+impl MobStash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn chunk_of(pos: Vec3) -> IVec3 {
+        pos.floor().as_ivec3() >> CHUNK_BITS
+    }
+
+    pub fn insert(&mut self, mob: SavedMob) {
+        self.chunks
+            .entry(Self::chunk_of(mob.pos))
+            .or_default()
+            .push(mob);
+    }
+
+    /// Remove and return every mob stashed within chunk
+    pub fn take(&mut self, chunk: IVec3) -> Vec<SavedMob> {
+        self.chunks.remove(&chunk).unwrap_or_default()
+    }
+
+    /// How many mobs are stashed within chunk
+    pub fn count(&self, chunk: IVec3) -> usize {
+        self.chunks.get(&chunk).map(|m| m.len()).unwrap_or(0)
+    }
+
+    /// Every stashed mob, in no particular order
+    pub fn mobs(&self) -> impl Iterator<Item = &SavedMob> + '_ {
+        self.chunks.values().flatten()
+    }
+
+    /// Every chunk that has at least one mob stashed
+    pub fn chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(|m| m.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    pub fn save_json(&self, seed: u32) -> Result<String> {
+        let save = MobSave {
+            version: MOB_SAVE_VERSION,
+            seed,
+            mobs: self.chunks.values().flatten().cloned().collect(),
+        };
+        Ok(serde_json::to_string_pretty(&save)?)
+    }
+
+    /// Mobs saved within a world with a different seed get dropped
+    pub fn load_json(json: &str, seed: u32) -> Result<Self> {
+        let save: MobSave = serde_json::from_str(json).context("Invalid mob save")?;
+        if save.version > MOB_SAVE_VERSION {
+            return Err(anyhow!(
+                "Mob save version {} is newer than {}",
+                save.version,
+                MOB_SAVE_VERSION
+            ));
+        }
+        let mut ret = Self::new();
+        if save.seed == seed {
+            save.mobs.into_iter().for_each(|m| ret.insert(m));
+        }
+        Ok(ret)
+    }
+
+    pub fn save_to_file(&self, path: &Path, seed: u32) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.save_json(seed)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &Path, seed: u32) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        Self::load_json(&json, seed)
+    }
+
+    pub fn add_handler(reactor: &mut Reactor<Message>, game: &GameState) {
+        {
+            let stash = game.mob_stash_rc();
+            reactor.add_sink(
+                Message::ResetEverything,
+                Box::new(move |_: &Reactor<Message>, _msg: Message| {
+                    stash.borrow_mut().clear();
+                }),
+            );
+        }
+        // Saving is deferred, so that every mob still around had a chance
+        // to get stashed while handling GameQuit.
+        reactor.add_sink(
+            Message::GameQuit,
+            Box::new(move |reactor: &Reactor<Message>, _msg: Message| {
+                reactor.defer(Message::WorldSave);
+            }),
+        );
+        if let Some(path) = game.mob_save_path().map(|p| p.to_path_buf()) {
+            let stash = game.mob_stash_rc();
+            let world = game.world_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let seed = world.borrow().seed();
+                if let Err(err) = stash.borrow().save_to_file(&path, seed) {
+                    eprintln!("Couldn't save the mobs to {}: {:#}", path.display(), err);
+                }
+            };
+            reactor.add_sink(Message::WorldSave, Box::new(f));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mob_stash() {
+        let mob = |x: f32| SavedMob {
+            kind: "crab".to_string(),
+            pos: Vec3::new(x, 1.0, 1.0),
+            rot: Vec3::ZERO,
+            health: 7,
+            home: Vec3::ZERO,
+            elite: x < 0.0,
+        };
+        let mut stash = MobStash::new();
+        assert!(stash.is_empty());
+        stash.insert(mob(1.0));
+        stash.insert(mob(2.0));
+        stash.insert(mob(-1.0));
+        assert_eq!(stash.len(), 3);
+        assert_eq!(stash.count(IVec3::ZERO), 2);
+        assert_eq!(stash.count(IVec3::new(-1, 0, 0)), 1);
+
+        let json = stash.save_json(5).unwrap();
+        let mut loaded = MobStash::load_json(&json, 5).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert!(MobStash::load_json(&json, 6).unwrap().is_empty());
+        assert!(MobStash::load_json(r#"{ "version": 99, "seed": 5 }"#, 5).is_err());
+
+        let taken = loaded.take(IVec3::ZERO);
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].health, 7);
+        assert_eq!(loaded.count(IVec3::ZERO), 0);
+        assert_eq!(
+            loaded.chunks().collect::<Vec<_>>(),
+            vec![IVec3::new(-1, 0, 0)]
+        );
+    }
+}
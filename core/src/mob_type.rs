@@ -0,0 +1,737 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{
+    behavior_id_or_err, block_id_or_err, deserialize_projectile_id, loot_table_id_or_err,
+    roll_loot, BehaviorId, BlockId, Item, LootContext, LootTableId, ProjectileTypeId,
+};
+use anyhow::{anyhow, Context, Result};
+use glam::IVec3;
+use rand::Rng;
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Global mob type id, index into MOB_TYPES
+pub type MobTypeId = u16;
+
+thread_local! {
+    pub static MOB_TYPES:RefCell<Vec<MobType>> = RefCell::new(MobType::new_default());
+}
+
+/// A looping animation, frames are paths to .vox files built into the client
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MobAnimation {
+    pub frames: Vec<String>,
+    /// How long every frame is shown
+    pub ms: u64,
+}
+
+impl MobAnimation {
+    /// The frame to show after the animation has been playing for elapsed_ms
+    pub fn frame(&self, elapsed_ms: u128) -> &str {
+        let i = (elapsed_ms / self.ms.max(1) as u128) as usize % self.frames.len().max(1);
+        self.frames.get(i).map(|s| s.as_str()).unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.frames.is_empty() {
+            return Err(anyhow!("Animations need at least one frame"));
+        }
+        if self.ms == 0 {
+            return Err(anyhow!("Frames have to be shown for at least 1ms"));
+        }
+        if let Some(f) = self.frames.iter().find(|f| !f.ends_with(".vox")) {
+            return Err(anyhow!("Frame {:?} is not a .vox file", f));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MobAnimations {
+    pub idle: MobAnimation,
+    /// Also used while running, just played twice as fast
+    pub walk: MobAnimation,
+    pub attack: MobAnimation,
+    /// Shown while lunging at the player, falls back to the attack animation
+    #[serde(default)]
+    pub lunge: Option<MobAnimation>,
+}
+
+impl MobAnimations {
+    #[inline]
+    pub fn lunge(&self) -> &MobAnimation {
+        self.lunge.as_ref().unwrap_or(&self.attack)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MobAttack {
+    /// Damage of every strike while fighting
+    pub damage: i16,
+    /// Milliseconds between two strikes
+    pub cooldown: u64,
+    /// Damage of the first strike after closing in
+    #[serde(default)]
+    pub lunge_damage: i16,
+    /// Milliseconds until a mob may lunge again
+    #[serde(default)]
+    pub lunge_cooldown: u64,
+}
+
+/// Attacks elites and bosses use on top of their normal one
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum MobSpecial {
+    /// Smash the ground, causing an explosion of power and hitting the
+    /// player if they are within twice that distance
+    GroundSlam { power: f32, cooldown: u64 },
+    /// Dash towards the player, speed being in blocks per tick
+    Charge { speed: f32, cooldown: u64 },
+    /// Fire a projectile at the player from afar
+    Shoot {
+        #[serde(deserialize_with = "deserialize_projectile_id")]
+        projectile: ProjectileTypeId,
+        cooldown: u64,
+    },
+}
+
+impl MobSpecial {
+    /// Milliseconds until the special attack may be used again
+    #[inline]
+    pub fn cooldown(&self) -> u64 {
+        match self {
+            Self::GroundSlam { cooldown, .. }
+            | Self::Charge { cooldown, .. }
+            | Self::Shoot { cooldown, .. } => *cooldown,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            Self::GroundSlam { power, .. } if !(*power > 0.0 && *power <= 8.0) => {
+                Err(anyhow!("Ground slam power has to be between 0 and 8"))
+            }
+            Self::Charge { speed, .. } if !(*speed > 0.0 && *speed < 0.5) => {
+                Err(anyhow!("Charge speed has to be between 0 and 0.5"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+/// A tougher variant of a mob type that spawns every now and then,
+/// health, size and damage are multipliers.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MobElite {
+    /// How likely a newly spawned mob is an elite, between 0 and 1
+    pub chance: f32,
+    #[serde(default = "default_one")]
+    pub health: f32,
+    #[serde(default = "default_one")]
+    pub size: f32,
+    #[serde(default = "default_one")]
+    pub damage: f32,
+    #[serde(default)]
+    pub special: Option<MobSpecial>,
+}
+
+/// Entered once a boss drops below a fraction of its max health, speed
+/// and damage are multipliers.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MobBossPhase {
+    pub below: f32,
+    #[serde(default = "default_one")]
+    pub speed: f32,
+    #[serde(default = "default_one")]
+    pub damage: f32,
+    #[serde(default)]
+    pub special: Option<MobSpecial>,
+}
+
+/// Bosses get spawned at shrines and get tougher the more they are hurt
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MobBoss {
+    /// Sorted by below, from high to low
+    pub phases: Vec<MobBossPhase>,
+}
+
+impl MobBoss {
+    /// How many phases a boss at health_fraction has gone through, 0
+    /// meaning it's still in its initial one
+    pub fn phase(&self, health_fraction: f32) -> usize {
+        self.phases
+            .iter()
+            .take_while(|p| health_fraction < p.below)
+            .count()
+    }
+
+    /// The phase entered last, None while still in the initial one
+    pub fn phase_data(&self, phase: usize) -> Option<&MobBossPhase> {
+        phase.checked_sub(1).and_then(|i| self.phases.get(i))
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut last = 1.0;
+        for p in self.phases.iter() {
+            if !(p.below > 0.0 && p.below < last) {
+                return Err(anyhow!(
+                    "Boss phases have to be sorted from high to low health, between 0 and 1"
+                ));
+            }
+            if let Some(special) = &p.special {
+                special.validate()?;
+            }
+            last = p.below;
+        }
+        Ok(())
+    }
+}
+
+/// What the HUD shows about the boss the player is close to
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BossBar {
+    pub name: String,
+    pub health: i16,
+    pub max_health: i16,
+    pub phase: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnTime {
+    #[default]
+    Any,
+    Day,
+    Night,
+}
+
+/// Everything the spawner found out about a place a mob might spawn at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpawnSite {
+    /// The block the mob would be standing in
+    pub pos: IVec3,
+    /// The block right below pos
+    pub ground: BlockId,
+    pub light: u8,
+    pub daytime: bool,
+    /// How far away the player is
+    pub distance: f32,
+}
+
+/// Where and when a mob may spawn on its own, a weight of 0 disables that
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobSpawn {
+    pub weight: u32,
+    pub min_y: i32,
+    pub max_y: i32,
+    /// Blocks the mob may spawn on, which is what biomes boil down to
+    /// right now, empty means everywhere.
+    pub on: Vec<BlockId>,
+    pub min_light: u8,
+    pub max_light: u8,
+    pub time: SpawnTime,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl Default for MobSpawn {
+    fn default() -> Self {
+        Self {
+            weight: 0,
+            min_y: i32::MIN,
+            max_y: i32::MAX,
+            on: vec![],
+            min_light: 0,
+            max_light: 15,
+            time: SpawnTime::Any,
+            min_distance: 24.0,
+            max_distance: 64.0,
+        }
+    }
+}
+
+impl MobSpawn {
+    pub fn allows(&self, site: &SpawnSite) -> bool {
+        self.weight > 0
+            && (self.min_y..=self.max_y).contains(&site.pos.y)
+            && (self.on.is_empty() || self.on.contains(&site.ground))
+            && (self.min_light..=self.max_light).contains(&site.light)
+            && match self.time {
+                SpawnTime::Any => true,
+                SpawnTime::Day => site.daytime,
+                SpawnTime::Night => !site.daytime,
+            }
+            && (self.min_distance..=self.max_distance).contains(&site.distance)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MobType {
+    id: String,
+    name: String,
+    health: i16,
+    speed: f32,
+    size: f32,
+    scale: f32,
+    aggro_range: f32,
+    xp: u64,
+    attack: MobAttack,
+    behavior: BehaviorId,
+    animations: MobAnimations,
+    loot: Option<LootTableId>,
+    spawn: MobSpawn,
+    elite: Option<MobElite>,
+    boss: Option<MobBoss>,
+}
+
+impl MobType {
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn health(&self) -> i16 {
+        self.health
+    }
+    /// Multiplier for how fast the mob moves around
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    /// Roughly how big the mob is, used for the shadow
+    #[inline]
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+    /// How much bigger than its .vox files the mob is drawn
+    #[inline]
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+    /// How close the player has to get before the mob starts chasing them
+    #[inline]
+    pub fn aggro_range(&self) -> f32 {
+        self.aggro_range
+    }
+    /// How far away the player has to get for the mob to give up
+    #[inline]
+    pub fn give_up_range(&self) -> f32 {
+        self.aggro_range * 2.0
+    }
+    /// Experience granted for killing the mob
+    #[inline]
+    pub fn xp(&self) -> u64 {
+        self.xp
+    }
+    #[inline]
+    pub fn attack(&self) -> &MobAttack {
+        &self.attack
+    }
+    /// Index into BEHAVIORS, the tree that decides what the mob does
+    #[inline]
+    pub fn behavior(&self) -> BehaviorId {
+        self.behavior
+    }
+    #[inline]
+    pub fn animations(&self) -> &MobAnimations {
+        &self.animations
+    }
+    #[inline]
+    pub fn loot(&self) -> Option<LootTableId> {
+        self.loot
+    }
+    #[inline]
+    pub fn spawn(&self) -> &MobSpawn {
+        &self.spawn
+    }
+    #[inline]
+    pub fn elite(&self) -> Option<&MobElite> {
+        self.elite.as_ref()
+    }
+    #[inline]
+    pub fn boss(&self) -> Option<&MobBoss> {
+        self.boss.as_ref()
+    }
+
+    /// Every .vox file used by this mob, without duplicates
+    pub fn models(&self) -> Vec<&str> {
+        let a = &self.animations;
+        let mut ret: Vec<&str> = [&a.idle, &a.walk, &a.attack]
+            .into_iter()
+            .chain(a.lunge.iter())
+            .flat_map(|a| a.frames.iter().map(|f| f.as_str()))
+            .collect();
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+
+    /// Everything dropped when this mob dies
+    pub fn roll_loot(&self, ctx: &LootContext, rng: &mut impl Rng) -> Vec<Item> {
+        self.loot
+            .map(|table| roll_loot(table, ctx, rng))
+            .unwrap_or_default()
+    }
+
+    /// Pick a random type that may spawn at site, weighted by spawn weight
+    pub fn pick_spawn(types: &[Self], site: &SpawnSite, rng: &mut impl Rng) -> Option<MobTypeId> {
+        let total: u32 = types
+            .iter()
+            .filter(|t| t.spawn.allows(site))
+            .map(|t| t.spawn.weight)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for (i, t) in types
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.spawn.allows(site))
+        {
+            if roll < t.spawn.weight {
+                return Some(i as MobTypeId);
+            }
+            roll -= t.spawn.weight;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MobSpawnDefinition {
+    #[serde(default)]
+    weight: u32,
+    #[serde(default)]
+    min_y: Option<i32>,
+    #[serde(default)]
+    max_y: Option<i32>,
+    #[serde(default)]
+    on: Vec<String>,
+    #[serde(default)]
+    min_light: Option<u8>,
+    #[serde(default)]
+    max_light: Option<u8>,
+    #[serde(default)]
+    time: SpawnTime,
+    #[serde(default)]
+    min_distance: Option<f32>,
+    #[serde(default)]
+    max_distance: Option<f32>,
+}
+
+impl MobSpawnDefinition {
+    fn validate(self) -> Result<MobSpawn> {
+        let d = MobSpawn::default();
+        let ret = MobSpawn {
+            weight: self.weight,
+            min_y: self.min_y.unwrap_or(d.min_y),
+            max_y: self.max_y.unwrap_or(d.max_y),
+            on: self
+                .on
+                .iter()
+                .map(|b| block_id_or_err(b))
+                .collect::<Result<Vec<_>>>()?,
+            min_light: self.min_light.unwrap_or(d.min_light),
+            max_light: self.max_light.unwrap_or(d.max_light),
+            time: self.time,
+            min_distance: self.min_distance.unwrap_or(d.min_distance),
+            max_distance: self.max_distance.unwrap_or(d.max_distance),
+        };
+        if ret.min_y > ret.max_y {
+            return Err(anyhow!("Spawn min_y can't be bigger than max_y"));
+        }
+        if ret.min_light > ret.max_light || ret.max_light > 15 {
+            return Err(anyhow!("Spawn light levels have to be within 0..=15"));
+        }
+        if !(ret.min_distance >= 0.0 && ret.min_distance <= ret.max_distance) {
+            return Err(anyhow!(
+                "Spawn distances have to be positive, min before max"
+            ));
+        }
+        Ok(ret)
+    }
+}
+
+/// The format mobs are defined in, see assets/mobs.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MobTypeDefinition {
+    id: String,
+    name: String,
+    health: i16,
+    #[serde(default)]
+    speed: Option<f32>,
+    #[serde(default)]
+    size: Option<f32>,
+    #[serde(default)]
+    scale: Option<f32>,
+    aggro_range: f32,
+    #[serde(default)]
+    xp: u64,
+    attack: MobAttack,
+    behavior: String,
+    animations: MobAnimations,
+    #[serde(default)]
+    loot: Option<String>,
+    #[serde(default)]
+    spawn: MobSpawnDefinition,
+    #[serde(default)]
+    elite: Option<MobElite>,
+    #[serde(default)]
+    boss: Option<MobBoss>,
+}
+
+impl MobTypeDefinition {
+    fn validate(self) -> Result<MobType> {
+        if self.health <= 0 {
+            return Err(anyhow!("Health has to be at least 1"));
+        }
+        let speed = self.speed.unwrap_or(1.0);
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(anyhow!("Speed has to be a positive number"));
+        }
+        let size = self.size.unwrap_or(1.0);
+        if !(size > 0.0 && size.is_finite()) {
+            return Err(anyhow!("Size has to be a positive number"));
+        }
+        let scale = self.scale.unwrap_or(1.0);
+        if !(scale > 0.0 && scale.is_finite()) {
+            return Err(anyhow!("Scale has to be a positive number"));
+        }
+        if let Some(e) = &self.elite {
+            if !(0.0..=1.0).contains(&e.chance) {
+                return Err(anyhow!("Elite chance has to be between 0 and 1"));
+            }
+            if !(e.health > 0.0 && e.size > 0.0 && e.damage >= 0.0) {
+                return Err(anyhow!("Elite multipliers have to be positive"));
+            }
+            if let Some(special) = &e.special {
+                special.validate().context("Invalid elite special")?;
+            }
+        }
+        if let Some(boss) = &self.boss {
+            boss.validate().context("Invalid boss")?;
+        }
+        if !(self.aggro_range >= 0.0 && self.aggro_range.is_finite()) {
+            return Err(anyhow!("Aggro range can't be negative"));
+        }
+        if self.attack.damage < 0 || self.attack.lunge_damage < 0 {
+            return Err(anyhow!("Attack damage can't be negative"));
+        }
+        let behavior = behavior_id_or_err(&self.behavior)?;
+        let a = &self.animations;
+        a.idle.validate().context("Invalid idle animation")?;
+        a.walk.validate().context("Invalid walk animation")?;
+        a.attack.validate().context("Invalid attack animation")?;
+        if let Some(lunge) = &a.lunge {
+            lunge.validate().context("Invalid lunge animation")?;
+        }
+        let loot = self.loot.as_deref().map(loot_table_id_or_err).transpose()?;
+        Ok(MobType {
+            id: self.id,
+            name: self.name,
+            health: self.health,
+            speed,
+            size,
+            scale,
+            aggro_range: self.aggro_range,
+            xp: self.xp,
+            attack: self.attack,
+            behavior,
+            animations: self.animations,
+            loot,
+            spawn: self.spawn.validate().context("Invalid spawn rules")?,
+            elite: self.elite,
+            boss: self.boss,
+        })
+    }
+}
+
+/// Look up the numeric id of a mob type by its string id
+pub fn mob_type_id(id: &str) -> Option<MobTypeId> {
+    MOB_TYPES.with(|types| {
+        types
+            .borrow()
+            .iter()
+            .position(|t| t.id() == id)
+            .map(|i| i as MobTypeId)
+    })
+}
+
+impl MobType {
+    /// Returns the mob definitions from assets/mobs.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "mobs.json",
+            include_str!("../assets/mobs.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of mob definitions, the position in the list
+    /// determines the numeric id.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(
+            json,
+            "mob",
+            MobTypeId::MAX as usize,
+            MobTypeDefinition::validate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_mob_definitions() {
+        let types = MobType::new_default();
+        assert!(!types.is_empty());
+        assert_eq!(mob_type_id("crab"), Some(0));
+        assert_eq!(mob_type_id("nothing"), None);
+
+        let types = MobType::from_json(
+            r##"[
+                { "id": "slime", "name": "Slime", "health": 4, "aggro_range": 6,
+                  "attack": { "damage": 1, "cooldown": 500 }, "behavior": "skittish",
+                  "animations": {
+                    "idle": { "frames": ["slime/a.vox"], "ms": 100 },
+                    "walk": { "frames": ["slime/a.vox", "slime/b.vox"], "ms": 100 },
+                    "attack": { "frames": ["slime/c.vox"], "ms": 100 } },
+                  "loot": "crab",
+                  "spawn": { "weight": 3, "max_y": 10, "on": ["stone"], "max_light": 7 },
+                  "elite": { "chance": 0.1, "health": 2, "special": { "charge": { "speed": 0.2, "cooldown": 100 } } } },
+                { "id": "ghost", "name": "Ghost", "health": 1, "aggro_range": 6,
+                  "attack": { "damage": 1, "cooldown": 500 }, "behavior": "skittish",
+                  "animations": {
+                    "idle": { "frames": ["ghost.vox"], "ms": 100 },
+                    "walk": { "frames": ["ghost.vox"], "ms": 100 },
+                    "attack": { "frames": ["ghost.vox"], "ms": 100 } },
+                  "spawn": { "weight": 1, "min_y": 5, "time": "night", "min_distance": 8 },
+                  "scale": 2,
+                  "boss": { "phases": [
+                    { "below": 0.5, "speed": 2 },
+                    { "below": 0.25, "special": { "ground_slam": { "power": 2, "cooldown": 100 } } } ] } }
+            ]"##,
+        )
+        .unwrap();
+        let slime = &types[0];
+        assert_eq!(slime.speed(), 1.0);
+        assert_eq!(slime.give_up_range(), 12.0);
+        assert_eq!(
+            slime.models(),
+            vec!["slime/a.vox", "slime/b.vox", "slime/c.vox"]
+        );
+        assert_eq!(slime.animations().walk.frame(150), "slime/b.vox");
+        assert_eq!(slime.animations().walk.frame(250), "slime/a.vox");
+        assert_eq!(slime.animations().lunge().frame(0), "slime/c.vox");
+
+        let elite = slime.elite().unwrap();
+        assert_eq!((elite.health, elite.size, elite.damage), (2.0, 1.0, 1.0));
+        assert_eq!(elite.special.map(|s| s.cooldown()), Some(100));
+        assert!(slime.boss().is_none());
+        let ghost = &types[1];
+        assert_eq!(ghost.scale(), 2.0);
+        let boss = ghost.boss().unwrap();
+        assert_eq!(boss.phase(1.0), 0);
+        assert_eq!(boss.phase(0.4), 1);
+        assert_eq!(boss.phase(0.1), 2);
+        assert_eq!(boss.phase_data(0), None);
+        assert_eq!(boss.phase_data(1).map(|p| p.speed), Some(2.0));
+        assert_eq!(
+            boss.phase_data(2).and_then(|p| p.special),
+            Some(MobSpecial::GroundSlam {
+                power: 2.0,
+                cooldown: 100
+            })
+        );
+
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        for _ in 0..32 {
+            let loot = slime.roll_loot(&LootContext::default(), &mut rng);
+            assert!(loot.iter().all(|i| i.amount() <= 3));
+            assert!(types[1]
+                .roll_loot(&LootContext::default(), &mut rng)
+                .is_empty());
+        }
+        let stone = block_id_or_err("stone").unwrap();
+        let grass = block_id_or_err("grass").unwrap();
+        let site = |y: i32, ground, light, daytime| SpawnSite {
+            pos: IVec3::new(0, y, 0),
+            ground,
+            light,
+            daytime,
+            distance: 32.0,
+        };
+        assert!(slime.spawn().allows(&site(0, stone, 3, true)));
+        assert!(!slime.spawn().allows(&site(0, grass, 3, true)));
+        assert!(!slime.spawn().allows(&site(0, stone, 12, true)));
+        assert!(!types[1].spawn().allows(&site(20, grass, 15, true)));
+        assert!(!types[1].spawn().allows(&SpawnSite {
+            distance: 4.0,
+            ..site(20, grass, 15, false)
+        }));
+        for _ in 0..32 {
+            assert_eq!(
+                MobType::pick_spawn(&types, &site(0, stone, 3, true), &mut rng),
+                Some(0)
+            );
+            assert_eq!(
+                MobType::pick_spawn(&types, &site(20, grass, 15, false), &mut rng),
+                Some(1)
+            );
+            assert!(MobType::pick_spawn(&types, &site(7, stone, 0, false), &mut rng).is_some());
+            assert!(MobType::pick_spawn(&types, &site(7, grass, 0, true), &mut rng).is_none());
+        }
+
+        let bad = [
+            r#"{ "id": "Bad", "name": "Bad", "health": 1, "aggro_range": 1 }"#,
+            r#"{ "id": "bad", "name": "Bad", "health": 0, "aggro_range": 1,
+                 "attack": { "damage": 1, "cooldown": 1 }, "behavior": "aggressive",
+                 "animations": { "idle": { "frames": ["a.vox"], "ms": 1 },
+                   "walk": { "frames": ["a.vox"], "ms": 1 }, "attack": { "frames": ["a.vox"], "ms": 1 } } }"#,
+            r#"{ "id": "bad", "name": "Bad", "health": 1, "aggro_range": 1,
+                 "attack": { "damage": 1, "cooldown": 1 }, "behavior": "aggressive",
+                 "animations": { "idle": { "frames": [], "ms": 1 },
+                   "walk": { "frames": ["a.vox"], "ms": 1 }, "attack": { "frames": ["a.vox"], "ms": 1 } } }"#,
+            r#"{ "id": "bad", "name": "Bad", "health": 1, "aggro_range": 1,
+                 "attack": { "damage": 1, "cooldown": 1 }, "behavior": "aggressive",
+                 "animations": { "idle": { "frames": ["a.vox"], "ms": 1 },
+                   "walk": { "frames": ["a.vox"], "ms": 1 }, "attack": { "frames": ["a.vox"], "ms": 1 } },
+                 "loot": "nothing" }"#,
+            r#"{ "id": "bad", "name": "Bad", "health": 1, "aggro_range": 1,
+                 "attack": { "damage": 1, "cooldown": 1 }, "behavior": "aggressive",
+                 "animations": { "idle": { "frames": ["a.vox"], "ms": 1 },
+                   "walk": { "frames": ["a.vox"], "ms": 1 }, "attack": { "frames": ["a.vox"], "ms": 1 } },
+                 "elite": { "chance": 2 } }"#,
+            r#"{ "id": "bad", "name": "Bad", "health": 1, "aggro_range": 1,
+                 "attack": { "damage": 1, "cooldown": 1 }, "behavior": "aggressive",
+                 "animations": { "idle": { "frames": ["a.vox"], "ms": 1 },
+                   "walk": { "frames": ["a.vox"], "ms": 1 }, "attack": { "frames": ["a.vox"], "ms": 1 } },
+                 "boss": { "phases": [ { "below": 0.25 }, { "below": 0.5 } ] } }"#,
+        ];
+        for b in bad {
+            assert!(MobType::from_json(&format!("[{}]", b)).is_err(), "{}", b);
+        }
+    }
+}
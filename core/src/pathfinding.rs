@@ -0,0 +1,444 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{Aabb, Chungus, EntityId};
+use glam::{IVec3, Vec3};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+/// How many nodes a single search may look at before giving up
+pub const PATH_MAX_NODES: usize = 4096;
+
+/// How many nodes all searches combined may look at during a single tick
+pub const PATH_NODES_PER_TICK: usize = 256;
+
+const SIDES: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// What a walker is able to do, determines which paths are possible
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathConfig {
+    /// How many blocks of free space are needed to stand somewhere
+    pub height: i32,
+    /// How many blocks can be climbed with a single jump
+    pub max_jump: i32,
+    /// How far down one is willing to drop
+    pub max_drop: i32,
+    /// Whether the path may lead through water
+    pub swim: bool,
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        Self {
+            height: 1,
+            max_jump: 1,
+            max_drop: 3,
+            swim: false,
+        }
+    }
+}
+
+impl PathConfig {
+    fn is_free(&self, world: &Chungus, pos: IVec3) -> bool {
+        world.is_loaded(pos.as_vec3())
+            && !world.is_solid_i(pos)
+            && (self.swim || world.get_fluid_block(pos).unwrap_or(0) == 0)
+    }
+
+    /// Whether there is enough room at pos to fit through
+    fn has_room(&self, world: &Chungus, pos: IVec3, height: i32) -> bool {
+        (0..height).all(|y| self.is_free(world, pos + IVec3::new(0, y, 0)))
+    }
+
+    /// Whether something can stand with its feet in block pos
+    pub fn is_walkable(&self, world: &Chungus, pos: IVec3) -> bool {
+        self.has_room(world, pos, self.height) && world.is_solid_i(pos - IVec3::Y)
+    }
+
+    /// Every node reachable in a single step from pos, along with its cost
+    fn neighbours(&self, world: &Chungus, pos: IVec3) -> Vec<(IVec3, u32)> {
+        let mut ret = vec![];
+        for side in SIDES {
+            let next = pos + side;
+            if self.is_walkable(world, next) {
+                ret.push((next, 10));
+                continue;
+            }
+            if self.has_room(world, next, self.height) {
+                // Walk off the edge and fall down
+                for dy in 1..=self.max_drop {
+                    let below = next - IVec3::new(0, dy, 0);
+                    if !self.is_free(world, below) {
+                        break;
+                    }
+                    if self.is_walkable(world, below) {
+                        ret.push((below, 10 + dy as u32 * 2));
+                        break;
+                    }
+                }
+            } else {
+                // Jump up, which needs room above our head as well
+                for dy in 1..=self.max_jump {
+                    if !self.is_free(world, pos + IVec3::new(0, self.height + dy - 1, 0)) {
+                        break;
+                    }
+                    let above = next + IVec3::new(0, dy, 0);
+                    if self.is_walkable(world, above) {
+                        ret.push((above, 10 + dy as u32 * 5));
+                        break;
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct OpenNode {
+    pos: IVec3,
+    cost: u32,
+    estimate: u32,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, since BinaryHeap is a max-heap
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| other.cost.cmp(&self.cost))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathStatus {
+    /// Still looking, call step again during the next tick
+    Searching,
+    /// Every block to walk through, starting right after the start
+    Found(Vec<IVec3>),
+    /// The goal can't be reached
+    Failed,
+}
+
+/// A single A* search, that can be spread across multiple ticks
+#[derive(Clone, Debug)]
+pub struct PathSearch {
+    config: PathConfig,
+    goal: IVec3,
+    open: BinaryHeap<OpenNode>,
+    came_from: HashMap<IVec3, IVec3>,
+    cost: HashMap<IVec3, u32>,
+    closest: (IVec3, u32),
+    visited: usize,
+}
+
+fn heuristic(a: IVec3, b: IVec3) -> u32 {
+    let d = (a - b).abs();
+    (d.x + d.z) as u32 * 10 + d.y as u32 * 2
+}
+
+impl PathSearch {
+    pub fn new(config: PathConfig, start: IVec3, goal: IVec3) -> Self {
+        let estimate = heuristic(start, goal);
+        let mut open = BinaryHeap::new();
+        open.push(OpenNode {
+            pos: start,
+            cost: 0,
+            estimate,
+        });
+        Self {
+            config,
+            goal,
+            open,
+            came_from: HashMap::new(),
+            cost: HashMap::from([(start, 0)]),
+            closest: (start, estimate),
+            visited: 0,
+        }
+    }
+
+    #[inline]
+    pub fn goal(&self) -> IVec3 {
+        self.goal
+    }
+
+    fn path_to(&self, mut pos: IVec3) -> Vec<IVec3> {
+        let mut ret = VecDeque::new();
+        while let Some(prev) = self.came_from.get(&pos) {
+            ret.push_front(pos);
+            pos = *prev;
+        }
+        ret.into()
+    }
+
+    /// Whether pos is the goal, or right below it since whoever we are
+    /// chasing might be in the middle of a jump.
+    fn reached(&self, pos: IVec3) -> bool {
+        let d = self.goal - pos;
+        d.x == 0 && d.z == 0 && (0..=self.config.max_jump + 1).contains(&d.y)
+    }
+
+    /// Look at up to budget nodes. When the search runs out of nodes
+    /// entirely the path to the closest node is returned instead, so that
+    /// walkers at least get somewhat closer.
+    pub fn step(&mut self, world: &Chungus, budget: usize) -> PathStatus {
+        for _ in 0..budget {
+            let Some(node) = self.open.pop() else {
+                return self.give_up();
+            };
+            if self.reached(node.pos) {
+                return PathStatus::Found(self.path_to(node.pos));
+            }
+            if node.cost > self.cost.get(&node.pos).copied().unwrap_or(u32::MAX) {
+                continue; // Already found a cheaper way here
+            }
+            self.visited += 1;
+            if self.visited > PATH_MAX_NODES {
+                return self.give_up();
+            }
+            for (next, step_cost) in self.config.neighbours(world, node.pos) {
+                let cost = node.cost + step_cost;
+                if cost >= self.cost.get(&next).copied().unwrap_or(u32::MAX) {
+                    continue;
+                }
+                let h = heuristic(next, self.goal);
+                if h < self.closest.1 {
+                    self.closest = (next, h);
+                }
+                self.cost.insert(next, cost);
+                self.came_from.insert(next, node.pos);
+                self.open.push(OpenNode {
+                    pos: next,
+                    cost,
+                    estimate: cost + h,
+                });
+            }
+        }
+        PathStatus::Searching
+    }
+
+    fn give_up(&self) -> PathStatus {
+        let path = self.path_to(self.closest.0);
+        if path.is_empty() {
+            PathStatus::Failed
+        } else {
+            PathStatus::Found(path)
+        }
+    }
+}
+
+/// Runs path searches for many entities at once, sharing a node budget
+/// between them so that lots of mobs can't stall a tick.
+#[derive(Clone, Debug, Default)]
+pub struct Pathfinder {
+    searches: Vec<(EntityId, PathSearch)>,
+    results: HashMap<EntityId, Option<Vec<IVec3>>>,
+}
+
+impl Pathfinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start looking for a path, replacing any search id already has going
+    pub fn request(&mut self, id: EntityId, config: PathConfig, start: IVec3, goal: IVec3) {
+        self.cancel(id);
+        self.searches
+            .push((id, PathSearch::new(config, start, goal)));
+    }
+
+    pub fn cancel(&mut self, id: EntityId) {
+        self.searches.retain(|(e, _)| *e != id);
+        self.results.remove(&id);
+    }
+
+    #[inline]
+    pub fn is_searching(&self, id: EntityId) -> bool {
+        self.searches.iter().any(|(e, _)| *e == id)
+    }
+
+    /// The result of a finished search, None in the inner option means
+    /// that no path could be found.
+    pub fn take_result(&mut self, id: EntityId) -> Option<Option<Vec<IVec3>>> {
+        self.results.remove(&id)
+    }
+
+    /// Work on the pending searches, oldest first, until budget nodes
+    /// have been looked at.
+    pub fn tick(&mut self, world: &Chungus, budget: usize) {
+        let mut budget = budget;
+        while budget > 0 && !self.searches.is_empty() {
+            let per_search = (budget / self.searches.len()).max(1);
+            let id = self.searches[0].0;
+            let result = match self.searches[0].1.step(world, per_search) {
+                PathStatus::Searching => {
+                    // Give the others a turn
+                    let s = self.searches.remove(0);
+                    self.searches.push(s);
+                    budget = budget.saturating_sub(per_search);
+                    continue;
+                }
+                PathStatus::Found(path) => Some(path),
+                PathStatus::Failed => None,
+            };
+            self.results.insert(id, result);
+            self.searches.remove(0);
+            budget = budget.saturating_sub(per_search);
+        }
+    }
+}
+
+/// The path node something with that collision box is standing in
+pub fn path_node(aabb: &Aabb) -> IVec3 {
+    let center = (aabb.min + aabb.max) * 0.5;
+    Vec3::new(center.x, aabb.min.y + 0.1, center.z)
+        .floor()
+        .as_ivec3()
+}
+
+/// The center of the bottom of a path node, where to walk towards
+#[inline]
+pub fn path_waypoint(node: IVec3) -> Vec3 {
+    node.as_vec3() + Vec3::new(0.5, 0.0, 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_id, ChunkFluidData, EntityStore, CHUNK_BITS, CHUNK_MASK};
+
+    fn find(world: &Chungus, config: PathConfig, start: IVec3, goal: IVec3) -> PathStatus {
+        let mut search = PathSearch::new(config, start, goal);
+        loop {
+            match search.step(world, 64) {
+                PathStatus::Searching => continue,
+                status => return status,
+            }
+        }
+    }
+
+    /// Whether every step along the path is a possible move
+    fn is_valid(world: &Chungus, config: PathConfig, start: IVec3, path: &[IVec3]) -> bool {
+        let mut pos = start;
+        path.iter().all(|next| {
+            let ok = config.neighbours(world, pos).iter().any(|(n, _)| n == next);
+            pos = *next;
+            ok
+        })
+    }
+
+    #[test]
+    fn test_pathfinding() {
+        let mut world = crate::collision::tests::test_world();
+        let stone = block_id("stone").unwrap();
+        let config = PathConfig::default();
+        let start = IVec3::new(0, 0, 0);
+        let standing = Aabb::from_center(Vec3::new(0.5, 1.0, 0.5), Vec3::new(0.4, 1.0, 0.4));
+        assert_eq!(path_node(&standing), start);
+        assert_eq!(path_waypoint(start), Vec3::new(0.5, 0.0, 0.5));
+
+        // The wall at x = 4 is too high to jump over
+        let goal = IVec3::new(6, 0, 0);
+        assert!(config.is_walkable(&world, start));
+        assert!(!config.is_walkable(&world, IVec3::new(4, 0, 0)));
+        let mut search = PathSearch::new(config, start, goal);
+        assert_eq!(search.step(&world, 1), PathStatus::Searching);
+        match find(&world, config, start, goal) {
+            PathStatus::Found(path) => assert!(path.last().unwrap().x <= 3),
+            status => panic!("Expected a partial path, got {:?}", status),
+        }
+
+        // Open a gap, which the path should go through
+        for y in 0..4 {
+            world.set_block(IVec3::new(4, y, 6), 0);
+        }
+        let PathStatus::Found(path) = find(&world, config, start, goal) else {
+            panic!("No path through the gap");
+        };
+        assert!(path.contains(&IVec3::new(4, 0, 6)));
+        assert!(is_valid(&world, config, start, &path));
+
+        // A single step can be jumped onto, two blocks need a stair
+        world.set_block(IVec3::new(-2, 0, 0), stone);
+        let PathStatus::Found(path) = find(&world, config, start, IVec3::new(-2, 1, 0)) else {
+            panic!("Couldn't jump onto the step");
+        };
+        assert_eq!(path, vec![IVec3::new(-1, 0, 0), IVec3::new(-2, 1, 0)]);
+        world.set_block(IVec3::new(-2, 1, 0), stone);
+        world.set_block(IVec3::new(-2, 0, 1), stone);
+        let PathStatus::Found(path) = find(&world, config, start, IVec3::new(-2, 2, 0)) else {
+            panic!("Couldn't take the detour");
+        };
+        assert!(is_valid(&world, config, start, &path));
+        assert!(path.len() > 2);
+
+        // Dropping down is fine, unless it's too far
+        let ledge = IVec3::new(-6, 0, -6);
+        for y in -1..3 {
+            world.set_block(ledge + IVec3::new(0, y, 0), stone);
+        }
+        let top = ledge + IVec3::new(0, 3, 0);
+        let down = find(&world, config, top, start);
+        let PathStatus::Found(path) = down else {
+            panic!("Couldn't drop down");
+        };
+        assert!(is_valid(&world, config, top, &path));
+        let cautious = PathConfig {
+            max_drop: 2,
+            ..config
+        };
+        assert_eq!(find(&world, cautious, top, start), PathStatus::Failed);
+
+        // Water is avoided, unless swimming is allowed
+        let water_pos = IVec3::new(-1, 0, 3);
+        let cp = water_pos >> CHUNK_BITS;
+        world.insert_fluid(cp, ChunkFluidData::new());
+        world
+            .get_fluid_mut(&cp)
+            .unwrap()
+            .set(1, water_pos & CHUNK_MASK);
+        assert!(!config.is_walkable(&world, water_pos));
+        let swimmer = PathConfig {
+            swim: true,
+            ..config
+        };
+        assert!(swimmer.is_walkable(&world, water_pos));
+
+        // Unloaded chunks can't be walked through
+        let far = IVec3::new(200, 0, 0);
+        assert!(!config.is_walkable(&world, far));
+        assert!(!world.is_loaded(far.as_vec3()));
+    }
+
+    #[test]
+    fn test_pathfinder_budget() {
+        let world = crate::collision::tests::test_world();
+        let mut store = EntityStore::new();
+        let a = store.spawn().id();
+        let b = store.spawn().id();
+        let config = PathConfig::default();
+        let mut pf = Pathfinder::new();
+        pf.request(a, config, IVec3::new(-7, 0, -7), IVec3::new(3, 0, 7));
+        pf.request(b, config, IVec3::new(0, 0, 0), IVec3::new(1, 0, 0));
+        pf.tick(&world, 8);
+        assert_eq!(pf.take_result(a), None);
+        assert!(pf.is_searching(a));
+        assert!(matches!(pf.take_result(b), Some(Some(_))));
+        for _ in 0..100 {
+            pf.tick(&world, PATH_NODES_PER_TICK);
+        }
+        let path = pf.take_result(a).unwrap().unwrap();
+        assert_eq!(path.last(), Some(&IVec3::new(3, 0, 7)));
+        assert!(!pf.is_searching(a));
+        pf.request(a, config, IVec3::ZERO, IVec3::X);
+        pf.cancel(a);
+        pf.tick(&world, PATH_NODES_PER_TICK);
+        assert_eq!(pf.take_result(a), None);
+    }
+}
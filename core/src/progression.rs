@@ -0,0 +1,338 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::load_definitions;
+use crate::{Item, RecipeItemDefinition};
+use anyhow::{anyhow, Context, Result};
+use rand::seq::index::sample;
+use rand::Rng;
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Index into Progression::perks
+pub type PerkId = u16;
+
+thread_local! {
+    pub static PROGRESSION:RefCell<Progression> = RefCell::new(Progression::new_default());
+}
+
+/// How much experience is needed to get from one level to the next
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ExperienceCurve {
+    /// base * factor^(level - 1)
+    Formula { base: u64, factor: f64 },
+    /// Explicit values starting at level 1, the last one is used for every level after that
+    Table(Vec<u64>),
+}
+
+impl Default for ExperienceCurve {
+    fn default() -> Self {
+        Self::Formula {
+            base: 32,
+            factor: 3.0,
+        }
+    }
+}
+
+impl ExperienceCurve {
+    /// Experience needed to get from level to level + 1
+    pub fn xp_for_level(&self, level: u8) -> u64 {
+        let level = level.max(1);
+        let xp = match self {
+            Self::Formula { base, factor } => {
+                (*base as f64 * factor.powi(level as i32 - 1)).min(u64::MAX as f64) as u64
+            }
+            Self::Table(table) => table
+                .get(level as usize - 1)
+                .or_else(|| table.last())
+                .copied()
+                .unwrap_or(1),
+        };
+        xp.max(1)
+    }
+}
+
+/// Bonuses granted by perks, they add up when multiple perks are chosen
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PerkStats {
+    #[serde(default)]
+    pub max_health: i16,
+    #[serde(default)]
+    pub armor: u16,
+    /// Movement speed modifier in percent
+    #[serde(default)]
+    pub speed: i16,
+    /// Mining speed modifier in percent
+    #[serde(default)]
+    pub mining: i16,
+    /// Additional experience gained in percent
+    #[serde(default)]
+    pub xp: i16,
+}
+
+impl std::ops::Add for PerkStats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            max_health: self.max_health.saturating_add(rhs.max_health),
+            armor: self.armor.saturating_add(rhs.armor),
+            speed: self.speed.saturating_add(rhs.speed),
+            mining: self.mining.saturating_add(rhs.mining),
+            xp: self.xp.saturating_add(rhs.xp),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Perk {
+    id: String,
+    name: String,
+    description: String,
+    stats: PerkStats,
+}
+
+impl Perk {
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    #[inline]
+    pub fn stats(&self) -> PerkStats {
+        self.stats
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelReward {
+    MaxHealth(i16),
+    Item(Item),
+    /// Let the player choose one out of this many random perks
+    PerkChoice(u8),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Progression {
+    curve: ExperienceCurve,
+    base_health: i16,
+    /// Rewards with None as their level are granted on every level up
+    rewards: Vec<(Option<u8>, LevelReward)>,
+    perks: Vec<Perk>,
+}
+
+impl Progression {
+    #[inline]
+    pub fn curve(&self) -> &ExperienceCurve {
+        &self.curve
+    }
+    #[inline]
+    pub fn perks(&self) -> &[Perk] {
+        &self.perks
+    }
+
+    pub fn perk(&self, id: PerkId) -> Option<&Perk> {
+        self.perks.get(id as usize)
+    }
+
+    pub fn perk_id(&self, id: &str) -> Option<PerkId> {
+        self.perks
+            .iter()
+            .position(|p| p.id == id)
+            .map(|i| i as PerkId)
+    }
+
+    /// Rewards for reaching level
+    pub fn rewards(&self, level: u8) -> impl Iterator<Item = LevelReward> + '_ {
+        self.rewards
+            .iter()
+            .filter(move |(l, _)| l.map(|l| l == level).unwrap_or(true))
+            .map(|(_, r)| *r)
+    }
+
+    /// Max health at a particular level, including the rewards of all the levels before
+    pub fn max_health_at(&self, level: u8) -> i16 {
+        (2..=level).fold(self.base_health, |hp, level| {
+            self.rewards(level).fold(hp, |hp, r| match r {
+                LevelReward::MaxHealth(n) => hp.saturating_add(n),
+                _ => hp,
+            })
+        })
+    }
+
+    /// Pick up to count distinct random perks for the player to choose from
+    pub fn roll_perks(&self, rng: &mut impl Rng, count: u8) -> Vec<PerkId> {
+        let count = (count as usize).min(self.perks.len());
+        sample(rng, self.perks.len(), count)
+            .into_iter()
+            .map(|i| i as PerkId)
+            .collect()
+    }
+}
+
+/// Shorthand for the max health at a level, without any equipment or perks
+pub fn base_max_health(level: u8) -> i16 {
+    PROGRESSION.with(|p| p.borrow().max_health_at(level))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PerkDefinition {
+    id: String,
+    name: String,
+    description: String,
+    #[serde(default)]
+    stats: PerkStats,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelRewardDefinition {
+    #[serde(default)]
+    level: Option<u8>,
+    #[serde(default)]
+    max_health: Option<i16>,
+    #[serde(default)]
+    perks: Option<u8>,
+    #[serde(default)]
+    block: Option<String>,
+    #[serde(default)]
+    item: Option<String>,
+    #[serde(default)]
+    amount: Option<u16>,
+}
+
+impl LevelRewardDefinition {
+    fn validate(self) -> Result<(Option<u8>, LevelReward)> {
+        let has_item = self.block.is_some() || self.item.is_some();
+        let reward = match (self.max_health, self.perks, has_item) {
+            (Some(hp), None, false) => LevelReward::MaxHealth(hp),
+            (None, Some(n), false) if n > 0 => LevelReward::PerkChoice(n),
+            (None, None, true) => LevelReward::Item(
+                RecipeItemDefinition {
+                    block: self.block,
+                    item: self.item,
+                    amount: self.amount,
+                }
+                .validate()?,
+            ),
+            _ => {
+                return Err(anyhow!(
+                    "Exactly one of max_health, perks or block/item has to be set"
+                ))
+            }
+        };
+        Ok((self.level, reward))
+    }
+}
+
+/// The format progression is defined in, see assets/progression.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProgressionDefinition {
+    #[serde(default)]
+    curve: ExperienceCurve,
+    base_health: i16,
+    #[serde(default)]
+    rewards: Vec<LevelRewardDefinition>,
+    #[serde(default)]
+    perks: Vec<PerkDefinition>,
+}
+
+impl Progression {
+    /// Returns the progression from assets/progression.json, unless a mod replaces it
+    pub fn new_default() -> Self {
+        load_definitions(
+            "progression.json",
+            include_str!("../assets/progression.json"),
+            Self::from_json,
+        )
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let def: ProgressionDefinition =
+            serde_json::from_str(json).context("Invalid progression definition")?;
+        if def.base_health <= 0 {
+            return Err(anyhow!("base_health has to be positive"));
+        }
+        if let ExperienceCurve::Table(t) = &def.curve {
+            if t.is_empty() || t.contains(&0) {
+                return Err(anyhow!("The experience table needs positive entries"));
+            }
+        }
+        let mut rewards = Vec::with_capacity(def.rewards.len());
+        for (i, r) in def.rewards.into_iter().enumerate() {
+            rewards.push(
+                r.validate()
+                    .with_context(|| format!("Invalid reward #{}", i))?,
+            );
+        }
+        let mut perks: Vec<Perk> = Vec::with_capacity(def.perks.len());
+        for p in def.perks.into_iter() {
+            if perks.iter().any(|o| o.id == p.id) {
+                return Err(anyhow!("Duplicate perk {:?}", p.id));
+            }
+            perks.push(Perk {
+                id: p.id,
+                name: p.name,
+                description: p.description,
+                stats: p.stats,
+            });
+        }
+        Ok(Self {
+            curve: def.curve,
+            base_health: def.base_health,
+            rewards,
+            perks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn test_progression() {
+        let p = Progression::new_default();
+        assert_eq!(p.curve().xp_for_level(1), 32);
+        assert_eq!(p.curve().xp_for_level(2), 96);
+        assert_eq!(p.max_health_at(1), 12);
+        assert_eq!(p.max_health_at(3), 20);
+
+        let table = ExperienceCurve::Table(vec![10, 20]);
+        assert_eq!(table.xp_for_level(1), 10);
+        assert_eq!(table.xp_for_level(9), 20);
+
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let choice = p.roll_perks(&mut rng, 3);
+        assert_eq!(choice.len(), 3);
+        assert!(choice.iter().all(|id| p.perk(*id).is_some()));
+        assert_ne!(choice[0], choice[1]);
+
+        let p = Progression::from_json(
+            r#"{ "curve": { "table": [5, 7] }, "base_health": 8, "rewards": [
+                { "level": 2, "item": "grenade", "amount": 2 },
+                { "perks": 2 }
+            ] }"#,
+        )
+        .unwrap();
+        assert_eq!(p.rewards(2).count(), 2);
+        assert_eq!(p.rewards(3).count(), 1);
+        assert_eq!(p.max_health_at(5), 8);
+        assert!(Progression::from_json(
+            r#"{ "base_health": 8, "rewards": [{ "max_health": 2, "perks": 1 }] }"#
+        )
+        .is_err());
+    }
+}
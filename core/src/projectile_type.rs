@@ -0,0 +1,322 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::definition::{load_definitions, parse_definitions};
+use crate::{EntityId, MS_PER_TICK};
+use anyhow::{anyhow, Result};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use std::cell::RefCell;
+
+/// Global projectile type id, index into PROJECTILE_TYPES
+pub type ProjectileTypeId = u16;
+
+thread_local! {
+    pub static PROJECTILE_TYPES:RefCell<Vec<ProjectileType>> = RefCell::new(ProjectileType::new_default());
+}
+
+/// Who fired a projectile, it never hits whoever that was
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectileOwner {
+    #[default]
+    Player,
+    Entity(EntityId),
+}
+
+/// What a projectile does when it runs into a block
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockImpact {
+    /// Bounce off, keeping as much speed as its bounciness allows
+    #[default]
+    Bounce,
+    Explode,
+    /// Get stuck and stay there for a while
+    Stick,
+    Vanish,
+}
+
+/// What a projectile does when it runs into something that can take damage
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityImpact {
+    /// Fly right through without doing anything
+    #[default]
+    Ignore,
+    Explode,
+    /// Hurt whatever got hit first and vanish
+    Damage,
+    /// Hurt everything along the way, every target only once
+    Pierce,
+}
+
+/// Anything that gets thrown or shot, see assets/projectiles.json
+#[derive(Clone, Debug, Default)]
+pub struct ProjectileType {
+    id: String,
+    model: String,
+    speed: f32,
+    gravity: f32,
+    bounciness: f32,
+    fuse: Option<u64>,
+    explosion: f32,
+    damage: i16,
+    on_block: BlockImpact,
+    on_entity: EntityImpact,
+    range: f32,
+    linger: u64,
+    cooldown: u64,
+}
+
+impl ProjectileType {
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    /// Path of the .vox model within client/assets the projectile crate draws it with
+    #[inline]
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+    /// How fast it starts out, in blocks per tick
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    /// Added to the downwards velocity every tick
+    #[inline]
+    pub fn gravity(&self) -> f32 {
+        self.gravity
+    }
+    /// How much velocity is kept when bouncing off of a block, between 0 and 1
+    #[inline]
+    pub fn bounciness(&self) -> f32 {
+        self.bounciness
+    }
+    /// Ticks until it explodes, or vanishes if it doesn't explode at all
+    #[inline]
+    pub fn fuse_ticks(&self) -> Option<u64> {
+        self.fuse.map(|ms| ms / MS_PER_TICK)
+    }
+    /// Power of the explosion, 0 if it doesn't explode
+    #[inline]
+    pub fn explosion(&self) -> f32 {
+        self.explosion
+    }
+    #[inline]
+    pub fn damage(&self) -> i16 {
+        self.damage
+    }
+    #[inline]
+    pub fn on_block(&self) -> BlockImpact {
+        self.on_block
+    }
+    #[inline]
+    pub fn on_entity(&self) -> EntityImpact {
+        self.on_entity
+    }
+    /// Projectiles further away from the player than this get removed
+    #[inline]
+    pub fn range(&self) -> f32 {
+        self.range
+    }
+    /// How many ticks a projectile stays around after getting stuck
+    #[inline]
+    pub fn linger_ticks(&self) -> u64 {
+        self.linger / MS_PER_TICK
+    }
+    /// How long the player has to wait in between throws, in milliseconds
+    #[inline]
+    pub fn cooldown(&self) -> u64 {
+        self.cooldown
+    }
+}
+
+fn default_gravity() -> f32 {
+    0.0005
+}
+fn default_bounciness() -> f32 {
+    0.4
+}
+fn default_range() -> f32 {
+    256.0
+}
+fn default_linger() -> u64 {
+    10_000
+}
+fn default_cooldown() -> u64 {
+    600
+}
+
+/// The format projectiles are defined in, see assets/projectiles.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectileTypeDefinition {
+    id: String,
+    model: String,
+    speed: f32,
+    #[serde(default = "default_gravity")]
+    gravity: f32,
+    #[serde(default = "default_bounciness")]
+    bounciness: f32,
+    #[serde(default)]
+    fuse: Option<u64>,
+    #[serde(default)]
+    explosion: f32,
+    #[serde(default)]
+    damage: i16,
+    #[serde(default)]
+    on_block: BlockImpact,
+    #[serde(default)]
+    on_entity: EntityImpact,
+    #[serde(default = "default_range")]
+    range: f32,
+    #[serde(default = "default_linger")]
+    linger: u64,
+    #[serde(default = "default_cooldown")]
+    cooldown: u64,
+}
+
+impl ProjectileTypeDefinition {
+    fn validate(self) -> Result<ProjectileType> {
+        if !self.model.ends_with(".vox") {
+            return Err(anyhow!("Model {:?} is not a .vox file", self.model));
+        }
+        // Anything faster might tunnel through entities in between ticks
+        if !(self.speed > 0.0 && self.speed <= 1.0) {
+            return Err(anyhow!("Speed has to be between 0 and 1"));
+        }
+        if !(self.gravity >= 0.0 && self.gravity.is_finite()) {
+            return Err(anyhow!("Gravity can't be negative"));
+        }
+        if !(0.0..=1.0).contains(&self.bounciness) {
+            return Err(anyhow!("Bounciness has to be between 0 and 1"));
+        }
+        if !(0.0..=16.0).contains(&self.explosion) {
+            return Err(anyhow!("Explosion power has to be between 0 and 16"));
+        }
+        let explodes =
+            self.on_block == BlockImpact::Explode || self.on_entity == EntityImpact::Explode;
+        if explodes && self.explosion <= 0.0 {
+            return Err(anyhow!("Exploding projectiles need an explosion power"));
+        }
+        let hurts = matches!(self.on_entity, EntityImpact::Damage | EntityImpact::Pierce);
+        if hurts && self.damage <= 0 {
+            return Err(anyhow!("Projectiles that hurt need some damage"));
+        }
+        if self.range <= 0.0 || self.range.is_nan() {
+            return Err(anyhow!("Range has to be positive"));
+        }
+        Ok(ProjectileType {
+            id: self.id,
+            model: self.model,
+            speed: self.speed,
+            gravity: self.gravity,
+            bounciness: self.bounciness,
+            fuse: self.fuse,
+            explosion: self.explosion,
+            damage: self.damage,
+            on_block: self.on_block,
+            on_entity: self.on_entity,
+            range: self.range,
+            linger: self.linger,
+            cooldown: self.cooldown,
+        })
+    }
+}
+
+/// Look up the numeric id of a projectile type by its string id
+pub fn projectile_type_id(id: &str) -> Option<ProjectileTypeId> {
+    PROJECTILE_TYPES.with(|types| {
+        types
+            .borrow()
+            .iter()
+            .position(|t| t.id() == id)
+            .map(|i| i as ProjectileTypeId)
+    })
+}
+
+/// Same as projectile_type_id, but returns an error naming the missing type
+pub fn projectile_type_id_or_err(id: &str) -> Result<ProjectileTypeId> {
+    projectile_type_id(id).ok_or_else(|| anyhow!("Unknown projectile id {:?}", id))
+}
+
+/// Lets definitions refer to projectiles by their string id
+pub(crate) fn deserialize_projectile_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<ProjectileTypeId, D::Error> {
+    let id = String::deserialize(deserializer)?;
+    projectile_type_id_or_err(&id).map_err(D::Error::custom)
+}
+
+impl ProjectileType {
+    /// Returns the projectile definitions from assets/projectiles.json, unless a mod replaces them
+    pub fn new_default() -> Vec<Self> {
+        load_definitions(
+            "projectiles.json",
+            include_str!("../assets/projectiles.json"),
+            Self::from_json,
+        )
+    }
+
+    /// Parse a list of projectile definitions, the position in the list
+    /// determines the numeric id.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        parse_definitions(
+            json,
+            "projectile",
+            ProjectileTypeId::MAX as usize,
+            ProjectileTypeDefinition::validate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projectile_definitions() {
+        let types = ProjectileType::new_default();
+        assert!(!types.is_empty());
+        assert_eq!(projectile_type_id("grenade"), Some(0));
+        assert_eq!(projectile_type_id("nothing"), None);
+        assert!(projectile_type_id_or_err("nothing").is_err());
+
+        let types = ProjectileType::from_json(
+            r#"[
+                { "id": "dart", "model": "arrow.vox", "speed": 0.5, "damage": 2,
+                  "on_block": "stick", "on_entity": "pierce", "linger": 400 },
+                { "id": "bomb", "model": "grenade.vox", "speed": 0.3, "fuse": 2000,
+                  "explosion": 4, "bounciness": 0.8 }
+            ]"#,
+        )
+        .unwrap();
+        let dart = &types[0];
+        assert_eq!(dart.gravity(), 0.0005);
+        assert_eq!(dart.on_block(), BlockImpact::Stick);
+        assert_eq!(dart.on_entity(), EntityImpact::Pierce);
+        assert_eq!(dart.fuse_ticks(), None);
+        assert_eq!(dart.linger_ticks(), 100);
+        let bomb = &types[1];
+        assert_eq!(bomb.on_block(), BlockImpact::Bounce);
+        assert_eq!(bomb.on_entity(), EntityImpact::Ignore);
+        assert_eq!(bomb.fuse_ticks(), Some(500));
+        assert_eq!(bomb.range(), 256.0);
+
+        let bad = [
+            r#"{ "id": "Bad", "model": "a.vox", "speed": 0.5 }"#,
+            r#"{ "id": "bad", "model": "a", "speed": 0.5 }"#,
+            r#"{ "id": "bad", "model": "a.vox", "speed": 2 }"#,
+            r#"{ "id": "bad", "model": "a.vox", "speed": 0.5, "bounciness": 1.5 }"#,
+            r#"{ "id": "bad", "model": "a.vox", "speed": 0.5, "on_block": "explode" }"#,
+            r#"{ "id": "bad", "model": "a.vox", "speed": 0.5, "on_entity": "damage" }"#,
+            r#"{ "id": "bad", "model": "a.vox", "speed": 0.5, "on_entity": "shrink" }"#,
+        ];
+        for b in bad {
+            assert!(
+                ProjectileType::from_json(&format!("[{}]", b)).is_err(),
+                "{}",
+                b
+            );
+        }
+    }
+}
@@ -11,7 +11,6 @@ pub struct ChunkRequestQueue {
     simple_light: HashSet<IVec3>,
     complex_light: HashSet<IVec3>,
     fluid: HashSet<IVec3>,
-    _meta: HashSet<IVec3>,
 }
 
 impl ChunkRequestQueue {
@@ -0,0 +1,346 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{Character, GameState, Message, Reactor, DEFAULT_SEED, MS_PER_TICK};
+use anyhow::{anyhow, Context, Result};
+use glam::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bump this whenever the format changes in an incompatible way
+pub const RUN_SAVE_VERSION: u32 = 1;
+
+/// How many entries the high score table keeps
+pub const HIGH_SCORE_COUNT: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RunStats {
+    pub kills: u32,
+    pub blocks_mined: u32,
+    /// How many blocks below the starting point the player got
+    pub depth: u32,
+    pub ticks: u64,
+}
+
+impl RunStats {
+    #[inline]
+    pub fn seconds(&self) -> u64 {
+        self.ticks * MS_PER_TICK / 1000
+    }
+}
+
+/// A single attempt, from spawning until the player dies
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Run {
+    seed: u32,
+    stats: RunStats,
+    #[serde(default)]
+    start_y: Option<f32>,
+    #[serde(skip)]
+    finished: bool,
+    /// Position in the high score table, once the run is over
+    #[serde(skip)]
+    rank: Option<usize>,
+}
+
+impl Default for Run {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl Run {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            stats: RunStats::default(),
+            start_y: None,
+            finished: false,
+            rank: None,
+        }
+    }
+
+    #[inline]
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+    #[inline]
+    pub fn stats(&self) -> &RunStats {
+        &self.stats
+    }
+    #[inline]
+    pub fn stats_mut(&mut self) -> &mut RunStats {
+        &mut self.stats
+    }
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    #[inline]
+    pub fn rank(&self) -> Option<usize> {
+        self.rank
+    }
+
+    /// Update the time and depth stats, called once every tick
+    pub fn track(&mut self, pos: Vec3) {
+        let start_y = *self.start_y.get_or_insert(pos.y);
+        self.stats.ticks += 1;
+        let depth = (start_y - pos.y).max(0.0) as u32;
+        self.stats.depth = self.stats.depth.max(depth);
+    }
+
+    /// End the run, putting it into the high score table if it's good enough
+    pub fn finish(&mut self, player: &Character, high_scores: &mut HighScores) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.rank = high_scores.insert(HighScore {
+            seed: self.seed,
+            score: player.score(),
+            level: player.experience().level(),
+            stats: self.stats,
+        });
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HighScore {
+    pub seed: u32,
+    pub score: u64,
+    pub level: u8,
+    pub stats: RunStats,
+}
+
+/// The best runs so far, ordered from best to worst
+#[derive(Clone, Debug, Default)]
+pub struct HighScores {
+    entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    /// Add an entry, returns its rank if it made it into the table
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if rank >= HIGH_SCORE_COUNT {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HIGH_SCORE_COUNT);
+        Some(rank)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunSave {
+    version: u32,
+    /// The run still in progress, if there is one
+    #[serde(default)]
+    run: Option<Run>,
+    #[serde(default)]
+    high_scores: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn save_json(&self, run: Option<&Run>) -> Result<String> {
+        let save = RunSave {
+            version: RUN_SAVE_VERSION,
+            run: run.filter(|r| !r.is_finished()).copied(),
+            high_scores: self.entries.clone(),
+        };
+        Ok(serde_json::to_string_pretty(&save)?)
+    }
+
+    /// Returns the high scores along with the run that was in progress
+    pub fn load_json(json: &str) -> Result<(Self, Option<Run>)> {
+        let save: RunSave = serde_json::from_str(json).context("Invalid run save")?;
+        if save.version > RUN_SAVE_VERSION {
+            return Err(anyhow!(
+                "Run save version {} is newer than {}",
+                save.version,
+                RUN_SAVE_VERSION
+            ));
+        }
+        let mut high_scores = Self::new();
+        save.high_scores.into_iter().for_each(|e| {
+            high_scores.insert(e);
+        });
+        Ok((high_scores, save.run))
+    }
+
+    pub fn save_to_file(&self, path: &Path, run: Option<&Run>) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.save_json(run)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<(Self, Option<Run>)> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        Self::load_json(&json)
+    }
+}
+
+impl Run {
+    pub fn add_handler(reactor: &mut Reactor<Message>, game: &GameState) {
+        {
+            let player = game.player_rc();
+            let run = game.run_rc();
+            let high_scores = game.high_scores_rc();
+            let path = game.run_save_path().map(|p| p.to_path_buf());
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let player = player.borrow();
+                let mut run = run.borrow_mut();
+                if run.is_finished() {
+                    return;
+                }
+                if !player.is_dead() {
+                    run.track(player.pos());
+                    return;
+                }
+                let mut high_scores = high_scores.borrow_mut();
+                run.finish(&player, &mut high_scores);
+                if let Some(path) = &path {
+                    if let Err(err) = high_scores.save_to_file(path, None) {
+                        eprintln!(
+                            "Couldn't save the high scores to {}: {:#}",
+                            path.display(),
+                            err
+                        );
+                    }
+                }
+            };
+            reactor.add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
+        }
+        {
+            let run = game.run_rc();
+            let f = move |_: &Reactor<Message>, msg: Message| {
+                let mut run = run.borrow_mut();
+                if let Message::MobDied {
+                    by_player: true, ..
+                } = msg
+                {
+                    if !run.is_finished() {
+                        run.stats_mut().kills += 1;
+                    }
+                }
+            };
+            reactor.add_sink(
+                Message::MobDied {
+                    pos: Vec3::ZERO,
+                    by_player: false,
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let run = game.run_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let mut run = run.borrow_mut();
+                if !run.is_finished() {
+                    run.stats_mut().blocks_mined += 1;
+                }
+            };
+            reactor.add_sink(
+                Message::BlockBreak {
+                    pos: IVec3::ZERO,
+                    block: 0,
+                },
+                Box::new(f),
+            );
+        }
+        {
+            let run = game.run_rc();
+            let world = game.world_rc();
+            let f = move |reactor: &Reactor<Message>, msg: Message| {
+                if let Message::RunStart { seed } = msg {
+                    world.borrow_mut().set_seed(seed);
+                    run.replace(Run::new(seed));
+                    reactor.dispatch(Message::ResetEverything);
+                }
+            };
+            reactor.add_sink(Message::RunStart { seed: 0 }, Box::new(f));
+        }
+        {
+            let run = game.run_rc();
+            reactor.add_sink(
+                Message::ResetEverything,
+                Box::new(move |_: &Reactor<Message>, _msg: Message| {
+                    let seed = run.borrow().seed();
+                    run.replace(Run::new(seed));
+                }),
+            );
+        }
+        if let Some(path) = game.run_save_path().map(|p| p.to_path_buf()) {
+            let run = game.run_rc();
+            let high_scores = game.high_scores_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let run = run.borrow();
+                if let Err(err) = high_scores.borrow().save_to_file(&path, Some(&run)) {
+                    eprintln!("Couldn't save the run to {}: {:#}", path.display(), err);
+                }
+            };
+            reactor.add_sink(Message::GameQuit, Box::new(f));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_scores() {
+        let mut hs = HighScores::new();
+        let entry = |score| HighScore {
+            seed: 1,
+            score,
+            level: 1,
+            stats: RunStats::default(),
+        };
+        for score in 0..HIGH_SCORE_COUNT as u64 {
+            assert_eq!(hs.insert(entry(score)), Some(0));
+        }
+        assert_eq!(hs.insert(entry(0)), None);
+        assert_eq!(hs.insert(entry(5)), Some(5));
+        assert_eq!(hs.entries().len(), HIGH_SCORE_COUNT);
+        assert_eq!(hs.entries()[0].score, 9);
+        assert_eq!(hs.entries().last().unwrap().score, 1);
+
+        let mut run = Run::new(42);
+        run.track(Vec3::new(0.0, 8.0, 0.0));
+        run.track(Vec3::new(0.0, -4.5, 0.0));
+        run.track(Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(run.stats().depth, 12);
+        assert_eq!(run.stats().ticks, 3);
+
+        let json = hs.save_json(Some(&run)).unwrap();
+        let (loaded, loaded_run) = HighScores::load_json(&json).unwrap();
+        assert_eq!(loaded.entries(), hs.entries());
+        assert_eq!(loaded_run, Some(run));
+
+        let mut c = Character::new();
+        c.init();
+        run.finish(&c, &mut hs);
+        assert!(run.is_finished());
+        assert_eq!(run.rank(), None);
+        let json = hs.save_json(Some(&run)).unwrap();
+        assert_eq!(HighScores::load_json(&json).unwrap().1, None);
+    }
+}
@@ -0,0 +1,102 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{Aabb, EntityId};
+use glam::IVec3;
+use std::collections::HashMap;
+
+/// Cell size used for entity collisions, bigger than every collision box
+pub const SPATIAL_HASH_CELL_SIZE: f32 = 4.0;
+
+/// Broad phase for entity collisions, sorts boxes into a uniform grid so
+/// only boxes sharing a cell have to be checked against each other.
+#[derive(Clone, Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<(EntityId, Aabb)>>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(SPATIAL_HASH_CELL_SIZE)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cells_of(&self, aabb: &Aabb) -> impl Iterator<Item = IVec3> {
+        let min = (aabb.min / self.cell_size).floor().as_ivec3();
+        let max = (aabb.max / self.cell_size).floor().as_ivec3();
+        (min.x..=max.x).flat_map(move |x| {
+            (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+        })
+    }
+
+    /// Add a box to every cell it touches
+    pub fn insert(&mut self, id: EntityId, aabb: Aabb) {
+        let cells: Vec<IVec3> = self.cells_of(&aabb).collect();
+        for cell in cells {
+            self.cells.entry(cell).or_default().push((id, aabb));
+        }
+    }
+
+    /// Every entity whose box intersects aabb, sorted by id
+    pub fn query(&self, aabb: &Aabb) -> Vec<EntityId> {
+        let mut ret: Vec<EntityId> = self
+            .cells_of(aabb)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(|(_, b)| b.intersects(aabb))
+            .map(|(id, _)| *id)
+            .collect();
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+
+    /// Every pair of intersecting boxes, with the lower id first
+    pub fn pairs(&self) -> Vec<(EntityId, EntityId)> {
+        let mut ret = vec![];
+        for cell in self.cells.values() {
+            for (i, (a, a_box)) in cell.iter().enumerate() {
+                for (b, b_box) in cell[i + 1..].iter() {
+                    if a != b && a_box.intersects(b_box) {
+                        ret.push(((*a).min(*b), (*a).max(*b)));
+                    }
+                }
+            }
+        }
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntityStore;
+    use glam::Vec3;
+
+    #[test]
+    fn test_spatial_hash() {
+        let mut store = EntityStore::new();
+        let ids: Vec<EntityId> = (0..4).map(|_| store.spawn().id()).collect();
+        let mut hash = SpatialHash::new(2.0);
+        let b = |x: f32| Aabb::from_center(Vec3::new(x, 0.0, 0.0), Vec3::splat(0.5));
+        hash.insert(ids[0], b(-0.3));
+        // Crosses the border between two cells
+        hash.insert(ids[1], b(0.3));
+        hash.insert(ids[2], b(1.9));
+        hash.insert(ids[3], b(40.0));
+        assert_eq!(hash.pairs(), vec![(ids[0], ids[1])]);
+        assert_eq!(hash.query(&b(1.0)), vec![ids[1], ids[2]]);
+        assert_eq!(hash.query(&b(20.0)), vec![]);
+        assert_eq!(hash.query(&b(39.5)), vec![ids[3]]);
+    }
+}
@@ -4,22 +4,44 @@ use anyhow::Result;
 use glam::IVec3;
 use std::{
     cell::{Ref, RefCell, RefMut},
+    path::{Path, PathBuf},
     rc::Rc,
     time::Instant,
 };
 
 use crate::{
-    Character, Chungus, ChunkRequestQueue, Message, Reactor, CHUNK_BITS, CHUNK_MASK, CHUNK_SIZE,
+    BlockEntityStash, BlockId, BossBar, Character, Chungus, ChunkRequestQueue, EntityStore,
+    HighScores, Message, MobStash, Reactor, Run, CHUNK_BITS, CHUNK_MASK, CHUNK_SIZE, COMMANDS,
+    GAME_LOG,
 };
 
-const MS_PER_TICK: u64 = 4;
+pub const MS_PER_TICK: u64 = 4;
+
+/// How long a whole day and night take
+pub const TICKS_PER_DAY: u64 = 20 * 60 * 1000 / MS_PER_TICK;
+
+/// The first two thirds of every day are daytime, the rest is night
+#[inline]
+pub fn is_daytime(ticks: u64) -> bool {
+    ticks % TICKS_PER_DAY < TICKS_PER_DAY * 2 / 3
+}
 
 pub struct GameState {
     clock: Rc<RefCell<Instant>>,
     pub ticks_elapsed: u64,
     world: Rc<RefCell<Chungus>>,
     player: Rc<RefCell<Character>>,
+    entities: Rc<RefCell<EntityStore>>,
     running: Rc<RefCell<bool>>,
+    player_save: Option<PathBuf>,
+    run: Rc<RefCell<Run>>,
+    high_scores: Rc<RefCell<HighScores>>,
+    run_save: Option<PathBuf>,
+    mob_stash: Rc<RefCell<MobStash>>,
+    mob_save: Option<PathBuf>,
+    block_entity_save: Option<PathBuf>,
+    /// The boss the player is fighting right now, if any
+    boss_bar: Rc<RefCell<Option<BossBar>>>,
 }
 
 impl GameState {
@@ -30,11 +52,113 @@ impl GameState {
             clock: Rc::new(RefCell::new(Instant::now())),
             running: Rc::new(RefCell::new(true)),
             player,
+            entities: Rc::new(RefCell::new(EntityStore::new())),
             ticks_elapsed: 0,
             world: Rc::new(RefCell::new(Chungus::new()?)),
+            player_save: None,
+            run: Rc::new(RefCell::new(Run::default())),
+            high_scores: Rc::new(RefCell::new(HighScores::new())),
+            run_save: None,
+            mob_stash: Rc::new(RefCell::new(MobStash::new())),
+            mob_save: None,
+            block_entity_save: None,
+            boss_bar: Rc::new(RefCell::new(None)),
         })
     }
 
+    /// Restore the player from path, if it exists, and save it there again
+    /// when quitting. A save that can't be loaded is kept around with a
+    /// .broken extension and the game starts with a fresh player instead.
+    pub fn with_player_save(mut self, path: &Path) -> Self {
+        if path.exists() {
+            let res = self.player.borrow_mut().load_from_file(path);
+            let lines = match res {
+                Ok(warnings) => warnings,
+                Err(err) => {
+                    let _ = std::fs::rename(path, path.with_extension("broken"));
+                    vec![format!("Couldn't load the player: {:#}", err)]
+                }
+            };
+            GAME_LOG.with(|log| {
+                let mut log = log.borrow_mut();
+                lines.into_iter().for_each(|l| log.push(l));
+            });
+        }
+        self.player_save = Some(path.to_path_buf());
+        self
+    }
+
+    /// Restore the high scores and the run in progress from path, and save
+    /// them there again whenever a run ends or the game quits. The run is
+    /// only continued if the player is still alive.
+    pub fn with_run_save(mut self, path: &Path) -> Self {
+        if path.exists() {
+            match HighScores::load_from_file(path) {
+                Ok((high_scores, run)) => {
+                    self.high_scores.replace(high_scores);
+                    if let Some(run) = run.filter(|_| !self.player().is_dead()) {
+                        self.world_mut().set_seed(run.seed());
+                        self.run.replace(run);
+                    }
+                }
+                Err(err) => {
+                    let _ = std::fs::rename(path, path.with_extension("broken"));
+                    GAME_LOG.with(|log| {
+                        let msg = format!("Couldn't load the high scores: {:#}", err);
+                        log.borrow_mut().push(msg);
+                    });
+                }
+            }
+        }
+        self.run_save = Some(path.to_path_buf());
+        self
+    }
+
+    /// Restore the mobs that were around when the game was saved, this has
+    /// to come after with_run_save since mobs only get restored within the
+    /// world they were saved in.
+    pub fn with_mob_save(mut self, path: &Path) -> Self {
+        if path.exists() {
+            let seed = self.world().seed();
+            match MobStash::load_from_file(path, seed) {
+                Ok(stash) => {
+                    self.mob_stash.replace(stash);
+                }
+                Err(err) => {
+                    let _ = std::fs::rename(path, path.with_extension("broken"));
+                    GAME_LOG.with(|log| {
+                        let msg = format!("Couldn't load the mobs: {:#}", err);
+                        log.borrow_mut().push(msg);
+                    });
+                }
+            }
+        }
+        self.mob_save = Some(path.to_path_buf());
+        self
+    }
+
+    /// Restore chests, signs and furnaces of chunks that aren't loaded yet,
+    /// just like with_mob_save this has to come after with_run_save.
+    pub fn with_block_entity_save(mut self, path: &Path) -> Self {
+        if path.exists() {
+            let seed = self.world().seed();
+            match BlockEntityStash::load_from_file(path, seed) {
+                Ok(stash) => {
+                    self.world_mut().set_block_entity_stash(stash);
+                }
+                Err(err) => {
+                    let _ = std::fs::rename(path, path.with_extension("broken"));
+                    GAME_LOG.with(|log| {
+                        let msg = format!("Couldn't load the block entities: {:#}", err);
+                        log.borrow_mut().push(msg);
+                    });
+                }
+            }
+        }
+        self.block_entity_save = Some(path.to_path_buf());
+        self
+    }
+
     #[inline]
     pub fn get_millis(&self) -> u64 {
         self.clock
@@ -50,10 +174,58 @@ impl GameState {
         self.player.borrow()
     }
 
+    #[inline]
     pub fn score(&self) -> u64 {
-        let player = self.player();
-        let xp = player.experience();
-        xp.xp_total() + xp.xp()
+        self.player().score()
+    }
+
+    #[inline]
+    pub fn run(&self) -> Ref<'_, Run> {
+        self.run.borrow()
+    }
+
+    #[inline]
+    pub fn run_rc(&self) -> Rc<RefCell<Run>> {
+        self.run.clone()
+    }
+
+    #[inline]
+    pub fn high_scores(&self) -> Ref<'_, HighScores> {
+        self.high_scores.borrow()
+    }
+
+    #[inline]
+    pub fn high_scores_rc(&self) -> Rc<RefCell<HighScores>> {
+        self.high_scores.clone()
+    }
+
+    #[inline]
+    pub fn run_save_path(&self) -> Option<&Path> {
+        self.run_save.as_deref()
+    }
+
+    #[inline]
+    pub fn mob_stash_rc(&self) -> Rc<RefCell<MobStash>> {
+        self.mob_stash.clone()
+    }
+
+    #[inline]
+    pub fn mob_save_path(&self) -> Option<&Path> {
+        self.mob_save.as_deref()
+    }
+
+    pub fn block_entity_save_path(&self) -> Option<&Path> {
+        self.block_entity_save.as_deref()
+    }
+
+    #[inline]
+    pub fn boss_bar(&self) -> Ref<'_, Option<BossBar>> {
+        self.boss_bar.borrow()
+    }
+
+    #[inline]
+    pub fn boss_bar_rc(&self) -> Rc<RefCell<Option<BossBar>>> {
+        self.boss_bar.clone()
     }
 
     #[inline]
@@ -66,6 +238,21 @@ impl GameState {
         self.player.clone()
     }
 
+    #[inline]
+    pub fn entities(&self) -> Ref<'_, EntityStore> {
+        self.entities.borrow()
+    }
+
+    #[inline]
+    pub fn entities_mut(&self) -> RefMut<'_, EntityStore> {
+        self.entities.borrow_mut()
+    }
+
+    #[inline]
+    pub fn entities_rc(&self) -> Rc<RefCell<EntityStore>> {
+        self.entities.clone()
+    }
+
     #[inline]
     pub fn ticks(&self) -> u64 {
         self.ticks_elapsed
@@ -99,6 +286,11 @@ impl GameState {
     pub fn add_handler(&self, reactor: &mut Reactor<Message>) {
         Character::add_handler(reactor, self);
         Chungus::add_handler(reactor, self);
+        Run::add_handler(reactor, self);
+        EntityStore::add_handler(reactor, self);
+        MobStash::add_handler(reactor, self);
+        BlockEntityStash::add_handler(reactor, self);
+        COMMANDS.with(|cmds| cmds.borrow_mut().add_default_commands(self));
 
         {
             let running = self.running.clone();
@@ -109,6 +301,25 @@ impl GameState {
             };
             reactor.add_sink(Message::GameQuit, Box::new(f));
         }
+        if let Some(path) = self.player_save.clone() {
+            let player = self.player_rc();
+            let f = move |_: &Reactor<Message>, _msg: Message| {
+                let player = player.borrow();
+                // Dead players start over
+                let res = if player.is_dead() {
+                    std::fs::remove_file(&path).or_else(|e| match e.kind() {
+                        std::io::ErrorKind::NotFound => Ok(()),
+                        _ => Err(e.into()),
+                    })
+                } else {
+                    player.save_to_file(&path)
+                };
+                if let Err(err) = res {
+                    eprintln!("Couldn't save the player to {}: {:#}", path.display(), err);
+                }
+            };
+            reactor.add_sink(Message::GameQuit, Box::new(f));
+        }
     }
 
     pub fn tick(&mut self, reactor: &Reactor<Message>, request: &mut ChunkRequestQueue) {
@@ -127,7 +338,7 @@ impl GameState {
         self.world().get(&pos).is_some()
     }
 
-    pub fn get_single_block(&self, (x, y, z): (i32, i32, i32)) -> u8 {
+    pub fn get_single_block(&self, (x, y, z): (i32, i32, i32)) -> BlockId {
         let pos = IVec3::new(
             x / CHUNK_SIZE as i32,
             y / CHUNK_SIZE as i32,
@@ -135,8 +346,11 @@ impl GameState {
         );
         let world = self.world_mut();
         if let Some(chnk) = world.get(&pos) {
-            chnk.data[(x & CHUNK_MASK) as usize][(y & CHUNK_MASK) as usize]
-                [(z & CHUNK_MASK) as usize]
+            chnk.get(
+                (x & CHUNK_MASK) as usize,
+                (y & CHUNK_MASK) as usize,
+                (z & CHUNK_MASK) as usize,
+            )
         } else {
             0
         }
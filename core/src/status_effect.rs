@@ -0,0 +1,245 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{Chungus, Health, MS_PER_TICK};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Index into FLUIDS
+pub const FLUID_LAVA: u8 = 3;
+
+/// How long the burning effect lasts after leaving the lava, in ticks
+const BURNING_AFTER_LAVA: u32 = 3000 / MS_PER_TICK as u32;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusEffectKind {
+    #[default]
+    Poison,
+    Regeneration,
+    Haste,
+    Burning,
+}
+
+pub const STATUS_EFFECT_KINDS: [StatusEffectKind; 4] = [
+    StatusEffectKind::Poison,
+    StatusEffectKind::Regeneration,
+    StatusEffectKind::Haste,
+    StatusEffectKind::Burning,
+];
+
+/// What happens when an effect gets applied while one of the same kind is still active
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusEffectStacking {
+    /// Keep the stronger effect and the longer duration
+    Refresh,
+    /// Strengths add up to a maximum, the duration is refreshed
+    Intensify(u8),
+    /// Durations add up, the stronger effect is kept
+    Extend,
+}
+
+impl StatusEffectKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Poison => "Poison",
+            Self::Regeneration => "Regeneration",
+            Self::Haste => "Haste",
+            Self::Burning => "Burning",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        STATUS_EFFECT_KINDS
+            .iter()
+            .find(|k| k.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// How many ticks pass between two applications of the effect, 0 for
+    /// effects that don't do anything periodically.
+    pub fn interval(self) -> u32 {
+        let ms = match self {
+            Self::Poison => 1000,
+            Self::Regeneration => 2000,
+            Self::Haste => 0,
+            Self::Burning => 500,
+        };
+        ms / MS_PER_TICK as u32
+    }
+
+    pub fn stacking(self) -> StatusEffectStacking {
+        match self {
+            Self::Poison => StatusEffectStacking::Intensify(5),
+            Self::Regeneration => StatusEffectStacking::Extend,
+            Self::Haste | Self::Burning => StatusEffectStacking::Refresh,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub strength: u8,
+    /// Remaining duration in ticks
+    pub duration: u32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusEffectKind, strength: u8, duration: u32) -> Self {
+        Self {
+            kind,
+            strength: strength.max(1),
+            duration,
+        }
+    }
+
+    pub fn with_seconds(kind: StatusEffectKind, strength: u8, seconds: f32) -> Self {
+        let duration = (seconds.max(0.0) * 1000.0 / MS_PER_TICK as f32) as u32;
+        Self::new(kind, strength, duration)
+    }
+
+    #[inline]
+    pub fn seconds_left(&self) -> u32 {
+        (self.duration as u64 * MS_PER_TICK / 1000) as u32
+    }
+}
+
+/// All effects currently active on a character or mob
+#[derive(Clone, Debug, Default)]
+pub struct StatusEffects {
+    active: Vec<(StatusEffect, u32)>,
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.active.iter().map(|(e, _)| e)
+    }
+
+    pub fn get(&self, kind: StatusEffectKind) -> Option<StatusEffect> {
+        self.iter().find(|e| e.kind == kind).copied()
+    }
+
+    pub fn remove(&mut self, kind: StatusEffectKind) {
+        self.active.retain(|(e, _)| e.kind != kind);
+    }
+
+    pub fn apply(&mut self, effect: StatusEffect) {
+        if effect.duration == 0 {
+            return;
+        }
+        let Some((old, _)) = self.active.iter_mut().find(|(e, _)| e.kind == effect.kind) else {
+            self.active.push((effect, 0));
+            return;
+        };
+        match effect.kind.stacking() {
+            StatusEffectStacking::Refresh => {
+                old.strength = old.strength.max(effect.strength);
+                old.duration = old.duration.max(effect.duration);
+            }
+            StatusEffectStacking::Intensify(max) => {
+                old.strength = old.strength.saturating_add(effect.strength).min(max);
+                old.duration = old.duration.max(effect.duration);
+            }
+            StatusEffectStacking::Extend => {
+                old.strength = old.strength.max(effect.strength);
+                old.duration = old.duration.saturating_add(effect.duration);
+            }
+        }
+    }
+
+    /// Multiplier for acceleration and movement speed
+    pub fn speed_multiplier(&self) -> f32 {
+        self.get(StatusEffectKind::Haste)
+            .map(|e| 1.0 + e.strength as f32 * 0.25)
+            .unwrap_or(1.0)
+    }
+
+    /// Advance all effects by a single tick, healing right away but only
+    /// returning the damage, so the caller can reduce it by armor first.
+    pub fn tick(&mut self, health: &mut Health) -> i16 {
+        let mut damage = 0;
+        for (effect, elapsed) in self.active.iter_mut() {
+            *elapsed += 1;
+            effect.duration = effect.duration.saturating_sub(1);
+            let interval = effect.kind.interval();
+            if interval == 0 || *elapsed % interval != 0 {
+                continue;
+            }
+            let amount = effect.strength as i16;
+            match effect.kind {
+                StatusEffectKind::Poison | StatusEffectKind::Burning => {
+                    damage += amount;
+                }
+                StatusEffectKind::Regeneration => health.heal(amount),
+                StatusEffectKind::Haste => (),
+            }
+        }
+        self.active.retain(|(e, _)| e.duration > 0);
+        damage
+    }
+
+    /// Touching lava at any of the points sets one on fire, water puts it out again
+    pub fn check_surroundings(&mut self, world: &Chungus, points: &[Vec3]) {
+        let fluids: Vec<u8> = points
+            .iter()
+            .filter_map(|p| world.get_fluid_block(p.floor().as_ivec3()))
+            .collect();
+        if fluids.contains(&FLUID_LAVA) {
+            let effect = StatusEffect::new(StatusEffectKind::Burning, 1, BURNING_AFTER_LAVA);
+            self.apply(effect);
+        } else if fluids.iter().any(|f| *f != 0) {
+            self.remove(StatusEffectKind::Burning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_effects() {
+        let mut fx = StatusEffects::new();
+        let mut health = Health::new(20);
+        let interval = StatusEffectKind::Poison.interval();
+
+        fx.apply(StatusEffect::new(StatusEffectKind::Poison, 1, interval * 2));
+        fx.apply(StatusEffect::new(StatusEffectKind::Poison, 1, interval));
+        assert_eq!(fx.get(StatusEffectKind::Poison).unwrap().strength, 2);
+        let mut damage = 0;
+        for _ in 0..interval * 3 {
+            let dmg = fx.tick(&mut health);
+            health.damage(dmg);
+            damage += dmg;
+        }
+        assert_eq!(damage, 4);
+        assert_eq!(health.health(), 16);
+        assert!(fx.get(StatusEffectKind::Poison).is_none());
+
+        let regen = StatusEffect::new(StatusEffectKind::Regeneration, 1, 10);
+        fx.apply(regen);
+        fx.apply(regen);
+        assert_eq!(fx.get(StatusEffectKind::Regeneration).unwrap().duration, 20);
+
+        fx.apply(StatusEffect::new(StatusEffectKind::Haste, 2, 10));
+        fx.apply(StatusEffect::new(StatusEffectKind::Haste, 1, 50));
+        let haste = fx.get(StatusEffectKind::Haste).unwrap();
+        assert_eq!((haste.strength, haste.duration), (2, 50));
+        assert_eq!(fx.speed_multiplier(), 1.5);
+        fx.remove(StatusEffectKind::Haste);
+        assert_eq!(fx.speed_multiplier(), 1.0);
+
+        assert_eq!(
+            StatusEffectKind::from_name("burning"),
+            Some(StatusEffectKind::Burning)
+        );
+    }
+}
@@ -1,8 +1,7 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::point_lies_within_chunk;
 use crate::Chungus;
-use crate::{ChunkBlockData, ChunkFluidData, Message, Reactor, CHUNK_SIZE};
+use crate::{ChunkBlockData, ChunkFluidData, CHUNK_SIZE};
 use glam::IVec3;
 use rand::prelude::*;
 use rand::Rng;
@@ -13,6 +12,7 @@ pub use asset::*;
 
 fn gen_fluid(chnk: &ChunkBlockData, water_y: i32) -> ChunkFluidData {
     let mut ret = ChunkFluidData::new();
+    let chnk = chnk.decode();
     for x in 0..CHUNK_SIZE as i32 {
         for y in 0..CHUNK_SIZE as i32 {
             if y > water_y {
@@ -22,13 +22,19 @@ fn gen_fluid(chnk: &ChunkBlockData, water_y: i32) -> ChunkFluidData {
                 let x = x as usize;
                 let y = y as usize;
                 let z = z as usize;
-                ret.data[x][y][z] = (chnk.data[x][y][z] == 0) as u8;
+                ret.data[x][y][z] = (chnk[x][y][z] == 0) as u8;
             }
         }
     }
     ret
 }
 
+/// One in this many chunks gets a shrine, if its surface is grassy
+const SHRINE_CHANCE: u32 = 48;
+/// How far the pillars of a shrine are from its center
+const SHRINE_RADIUS: i32 = 3;
+const SHRINE_PILLAR_HEIGHT: i32 = 3;
+
 fn grass_height(x: i32, z: i32) -> i32 {
     let d = ((x * x + z * z) as f32).sqrt() as i32;
     let deg = (x as f32).atan2(z as f32);
@@ -49,18 +55,13 @@ fn grass_height(x: i32, z: i32) -> i32 {
     }
 }
 
-pub fn chunk(
-    world: &Chungus,
-    pos: IVec3,
-    reactor: &Reactor<Message>,
-) -> (ChunkBlockData, ChunkFluidData) {
+pub fn chunk(world: &Chungus, pos: IVec3) -> (ChunkBlockData, ChunkFluidData) {
     let assets = world.assets();
 
-    let mut rng = XorShiftRng::seed_from_u64(
-        (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z)
-            .try_into()
-            .unwrap(),
-    );
+    let pos_seed: u64 = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z)
+        .try_into()
+        .unwrap();
+    let mut rng = XorShiftRng::seed_from_u64(pos_seed ^ ((world.seed() as u64) << 32));
     let px = pos.x * CHUNK_SIZE as i32;
     let py = pos.y * CHUNK_SIZE as i32;
     let pz = pos.z * CHUNK_SIZE as i32;
@@ -72,7 +73,11 @@ pub fn chunk(
             let pzz = pz + z;
             let floor_y = (-28).max(grass_height(pxx, pzz));
             if floor_y < 3 {
-                r.set_pillar(23, IVec3::new(x, -(1 << 30) - py, z), floor_y - py);
+                r.set_pillar(
+                    assets.blocks.sand,
+                    IVec3::new(x, -(1 << 30) - py, z),
+                    floor_y - py,
+                );
                 if rng.gen_range(1..2000) == 1 {
                     let i = rng.gen_range(0..assets.rocks.len());
                     let pos = IVec3::new(
@@ -85,8 +90,16 @@ pub fn chunk(
                     }
                 }
             } else {
-                r.set_pillar(1, IVec3::new(x, -(1 << 30) - py, z), floor_y - py);
-                r.set_pillar(2, IVec3::new(x, (floor_y - py) - 1, z), floor_y - py);
+                r.set_pillar(
+                    assets.blocks.dirt,
+                    IVec3::new(x, -(1 << 30) - py, z),
+                    floor_y - py,
+                );
+                r.set_pillar(
+                    assets.blocks.grass,
+                    IVec3::new(x, (floor_y - py) - 1, z),
+                    floor_y - py,
+                );
 
                 if rng.gen_range(1..400) == 1 {
                     let i = rng.gen_range(0..assets.bushes.len());
@@ -98,12 +111,6 @@ pub fn chunk(
                     if assets.bushes[i].fits(pos) {
                         r.blit(&assets.bushes[i], pos);
                     }
-                } else if rng.gen_range(1..800) == 1 {
-                    let mut mob_pos = ((pos * CHUNK_SIZE as i32) + IVec3::new(x, 0, z)).as_vec3();
-                    mob_pos.y = floor_y as f32 + 1.0;
-                    if point_lies_within_chunk(mob_pos, pos) {
-                        reactor.dispatch(Message::WorldgenSpawnMob { pos: mob_pos });
-                    }
                 } else if rng.gen_range(1..1000) == 1 {
                     let i = rng.gen_range(0..assets.rocks.len());
                     let pos = IVec3::new(
@@ -139,6 +146,33 @@ pub fn chunk(
         }
     }
 
+    if rng.gen_range(0..SHRINE_CHANCE) == 0 {
+        let x = rng.gen_range(SHRINE_RADIUS..CHUNK_SIZE as i32 - SHRINE_RADIUS);
+        let z = rng.gen_range(SHRINE_RADIUS..CHUNK_SIZE as i32 - SHRINE_RADIUS);
+        shrine(&mut r, &assets.blocks, IVec3::new(px, py, pz), x, z);
+    }
+
     let fluid = gen_fluid(&r, -2 - py);
     (r, fluid)
 }
+
+/// Put a shrine into the grass at column x/z of the chunk at origin, with
+/// a marble pillar at every corner. Nothing happens if the surface isn't
+/// grassy or lies within another chunk.
+fn shrine(r: &mut ChunkBlockData, blocks: &WorldgenBlocks, origin: IVec3, x: i32, z: i32) {
+    let floor = |x: i32, z: i32| grass_height(origin.x + x, origin.z + z);
+    let y = floor(x, z);
+    if y < 3 || !(1..=CHUNK_SIZE as i32).contains(&(y - origin.y)) {
+        return;
+    }
+    r.set_block(blocks.shrine, IVec3::new(x, y - origin.y - 1, z));
+    for (dx, dz) in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+        let (x, z) = (x + dx * SHRINE_RADIUS, z + dz * SHRINE_RADIUS);
+        let y = floor(x, z).max(-28) - origin.y;
+        r.set_pillar(
+            blocks.marble_pillar,
+            IVec3::new(x, y, z),
+            y + SHRINE_PILLAR_HEIGHT,
+        );
+    }
+}
@@ -1,19 +1,42 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use crate::{ChunkBlockData, CHUNK_SIZE};
+use crate::{block_id_or_err, BlockId, ChunkBlockData, CHUNK_SIZE};
 use anyhow::{anyhow, Result};
 use glam::IVec3;
 use vox_format::types::Model;
 
 #[derive(Debug)]
 pub struct WorldgenAsset {
-    pub palette: Vec<u8>,
+    pub palette: Vec<BlockId>,
     pub data: Vec<u8>,
     pub size: IVec3,
 }
 
+/// Blocks used directly by the world generator
+#[derive(Debug)]
+pub struct WorldgenBlocks {
+    pub dirt: BlockId,
+    pub grass: BlockId,
+    pub sand: BlockId,
+    pub shrine: BlockId,
+    pub marble_pillar: BlockId,
+}
+
+impl WorldgenBlocks {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            dirt: block_id_or_err("dirt")?,
+            grass: block_id_or_err("grass")?,
+            sand: block_id_or_err("sand")?,
+            shrine: block_id_or_err("shrine")?,
+            marble_pillar: block_id_or_err("marble_pillar")?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct WorldgenAssetList {
+    pub blocks: WorldgenBlocks,
     pub trees: [WorldgenAsset; 3],
     pub bushes: [WorldgenAsset; 3],
     pub rocks: [WorldgenAsset; 3],
@@ -24,38 +47,39 @@ impl WorldgenAssetList {
     pub fn new() -> Result<Self> {
         let trees = [
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/tree.vox"))?
-                .with_palette(vec![0, 5, 11]),
+                .with_palette(&["air", "spruce_log", "oak_leaves"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/tree_b.vox"))?
-                .with_palette(vec![0, 5, 11]),
+                .with_palette(&["air", "spruce_log", "oak_leaves"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/tree_c.vox"))?
-                .with_palette(vec![0, 5, 11]),
+                .with_palette(&["air", "spruce_log", "oak_leaves"])?,
         ];
 
         let bushes = [
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/bush_a.vox"))?
-                .with_palette(vec![0, 10, 6]),
+                .with_palette(&["air", "oak_log", "spruce_leaves"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/bush_b.vox"))?
-                .with_palette(vec![0, 6, 10]),
+                .with_palette(&["air", "spruce_leaves", "oak_log"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/bush_c.vox"))?
-                .with_palette(vec![0, 6, 10]),
+                .with_palette(&["air", "spruce_leaves", "oak_log"])?,
         ];
 
         let rocks = [
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/rock_a.vox"))?
-                .with_palette(vec![0, 3]),
+                .with_palette(&["air", "stone"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/rock_b.vox"))?
-                .with_palette(vec![0, 3, 4]),
+                .with_palette(&["air", "stone", "coal"])?,
             WorldgenAsset::from_vox_data(include_bytes!("../../assets/rock_c.vox"))?
-                .with_palette(vec![0, 3, 12]),
+                .with_palette(&["air", "stone", "hematite"])?,
         ];
 
         let spruce_trees =
             [
                 WorldgenAsset::from_vox_data(include_bytes!("../../assets/spruce_a.vox"))?
-                    .with_palette(vec![0, 5, 11]),
+                    .with_palette(&["air", "spruce_log", "oak_leaves"])?,
             ];
 
         Ok(Self {
+            blocks: WorldgenBlocks::new()?,
             trees,
             bushes,
             rocks,
@@ -104,7 +128,7 @@ impl WorldgenAsset {
             && pos.z + self.size.z < CHUNK_SIZE as i32
     }
 
-    pub fn get_block(&self, pos: IVec3) -> u8 {
+    pub fn get_block(&self, pos: IVec3) -> BlockId {
         let off = pos.x + (pos.y * self.size.x) + (pos.z * self.size.x * self.size.y);
         self.palette[self.data[off as usize] as usize]
     }
@@ -163,8 +187,12 @@ impl WorldgenAsset {
         }
     }
 
-    pub fn with_palette(mut self, palette: Vec<u8>) -> Self {
-        self.palette = palette;
-        self
+    /// Map the colors of the .vox model to blocks, referred to by their string id
+    pub fn with_palette(mut self, palette: &[&str]) -> Result<Self> {
+        self.palette = palette
+            .iter()
+            .map(|id| block_id_or_err(id))
+            .collect::<Result<Vec<BlockId>>>()?;
+        Ok(self)
     }
 }
@@ -2,31 +2,36 @@
 // All rights reserved. AGPL-3.0+ license.
 use anyhow::Result;
 use glam::{IVec3, Mat4, Vec3};
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
 use std::cell::RefCell;
-use wolkenwelten_client::{ClientState, RenderInitArgs, RenderPassArgs};
-use wolkenwelten_core::{BlockItem, Chungus, Entity, Item, Message, Reactor};
-
-thread_local! {
-    pub static DROPS: RefCell<ItemDropList> = RefCell::new(ItemDropList::new());
-}
+use wolkenwelten_client::{draw_item, ClientState, RenderInitArgs, RenderPassArgs};
+use wolkenwelten_core::{
+    roll_block_loot, BlockId, Chungus, EntityId, EntityStore, Item, LootContext, Message, Physics,
+    Reactor, Renderable, Transform,
+};
 
 const ITEM_DROP_PICKUP_RANGE: f32 = 1.5;
 
-#[derive(Clone, Default, Debug)]
+/// An item lying around in the world, waiting to be picked up
+#[derive(Clone, Copy, Default, Debug)]
 pub struct ItemDrop {
     item: Item,
-    ent: Entity,
+    /// Set once the player had no room for it, so that it doesn't get
+    /// offered again until they walk away.
+    refused: bool,
 }
 
 fn item_drop_draw(
     frame: &mut glium::Frame,
     fe: &ClientState,
-    entity: &ItemDrop,
+    drop: &ItemDrop,
+    transform: &Transform,
     view: &Mat4,
     projection: &Mat4,
 ) -> Result<()> {
-    let rot = entity.rot();
-    let pos = entity.pos();
+    let rot = transform.rot;
+    let pos = transform.pos;
     let model = Mat4::from_scale(Vec3::new(1.0 / 32.0, 1.0 / 32.0, 1.0 / 32.0));
     let model = Mat4::from_rotation_x(rot.x.to_radians()) * model;
     let model = Mat4::from_rotation_y(rot.y.to_radians()) * model;
@@ -34,105 +39,104 @@ fn item_drop_draw(
     let vp = projection.mul_mat4(view);
     let mvp = vp.mul_mat4(&model);
 
-    match entity.item() {
-        Item::Block(bi) => fe.meshes.blocks[bi.block as usize].draw(
-            frame,
-            &fe.textures.blocks_raw,
-            &fe.shaders.mesh,
-            &mvp,
-        ),
-        Item::None => Ok(()),
-        /*
-        _ => fe.meshes
-            .bag
-            .draw(frame, fe.block_indeces(), &fe.shaders.block, &mvp, 1.0)
-        */
-    }
+    draw_item(frame, fe, drop.item(), &mvp)
 }
 
 impl ItemDrop {
-    pub fn new(pos: Vec3, item: Item) -> Self {
-        let mut ent = Entity::new();
-        ent.set_pos(pos);
-        Self { item, ent }
-    }
-    #[inline]
-    pub fn pos(&self) -> Vec3 {
-        self.ent.pos()
-    }
-    #[inline]
-    pub fn rot(&self) -> Vec3 {
-        self.ent.rot()
-    }
     #[inline]
     pub fn item(&self) -> Item {
         self.item
     }
 
-    #[inline]
-    pub fn set_vel(&mut self, vel: Vec3) {
-        self.ent.set_vel(vel);
-    }
-
-    #[inline]
-    pub fn tick(&mut self, world: &Chungus) -> bool {
-        self.ent.set_rot(self.ent.rot() + Vec3::new(0.0, 0.2, 0.0));
-        self.ent.tick(world)
-    }
-}
-
-#[derive(Clone, Default, Debug)]
-pub struct ItemDropList {
-    drops: Vec<ItemDrop>,
-}
-
-impl ItemDropList {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn clear(&mut self) {
-        self.drops.clear();
-    }
-
-    pub fn add_from_block_break(&mut self, pos: IVec3, block: u8) {
+    pub fn spawn(entities: &mut EntityStore, pos: Vec3, vel: Vec3, item: Item) -> EntityId {
+        entities
+            .spawn()
+            .with(Transform::new(pos))
+            .with(Physics::default().with_vel(vel))
+            .with(Renderable::default())
+            .with(ItemDrop {
+                item,
+                refused: false,
+            })
+            .id()
+    }
+
+    /// Drop whatever the loot table of block says, see roll_block_loot
+    pub fn spawn_from_block_break(
+        entities: &mut EntityStore,
+        pos: IVec3,
+        block: BlockId,
+        ctx: &LootContext,
+        rng: &mut impl Rng,
+    ) {
         let pos = pos.as_vec3() + Vec3::new(0.5, 0.5, 0.5);
-        let item = BlockItem::new(block, 1).into();
-        self.drops.push(ItemDrop::new(pos, item));
-    }
-
-    pub fn add(&mut self, pos: Vec3, vel: Vec3, item: Item) {
-        let mut drop = ItemDrop::new(pos, item);
-        drop.set_vel(vel);
-        self.drops.push(drop);
-    }
-
-    #[inline]
-    pub fn iter(&self) -> std::slice::Iter<ItemDrop> {
-        self.drops.iter()
-    }
-
-    #[inline]
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<ItemDrop> {
-        self.drops.iter_mut()
-    }
-
-    pub fn tick_all(&mut self, reactor: &Reactor<Message>, player_pos: Vec3, world: &Chungus) {
-        self.drops.retain_mut(|d| {
-            d.tick(world);
-            let dist = d.pos() - player_pos;
-            let dd = dist.x * dist.x + dist.y * dist.y + dist.z * dist.z;
-            if dd > (256.0 * 256.0) {
-                false
-            } else if dd < ITEM_DROP_PICKUP_RANGE * ITEM_DROP_PICKUP_RANGE {
-                reactor.dispatch(Message::ItemDropPickup {
-                    pos: d.pos(),
-                    item: d.item(),
+        for item in roll_block_loot(block, ctx, rng) {
+            Self::spawn(entities, pos, Vec3::ZERO, item);
+        }
+    }
+
+    pub fn tick_all(
+        entities: &mut EntityStore,
+        reactor: &Reactor<Message>,
+        player_pos: Vec3,
+        world: &Chungus,
+    ) {
+        let mut gone = vec![];
+        {
+            let drops = entities.components::<ItemDrop>();
+            let mut transforms = entities.components_mut::<Transform>();
+            let mut physics = entities.components_mut::<Physics>();
+            for id in drops.keys() {
+                let (Some(t), Some(p)) = (transforms.get_mut(id), physics.get_mut(id)) else {
+                    continue;
+                };
+                t.rot += Vec3::new(0.0, 0.2, 0.0);
+                p.bounce(t, world);
+                if (t.pos - player_pos).length_squared() > 256.0 * 256.0 {
+                    gone.push(*id);
+                }
+            }
+        }
+        let near = entities.within_radius(player_pos, ITEM_DROP_PICKUP_RANGE);
+        for (id, d) in entities.components_mut::<ItemDrop>().iter_mut() {
+            if !near.contains(id) {
+                d.refused = false;
+            }
+        }
+        for id in near {
+            let Some(d) = entities.get::<ItemDrop>(id) else {
+                continue;
+            };
+            if d.refused {
+                continue;
+            }
+            let pos = entities
+                .get::<Transform>(id)
+                .map(|t| t.pos)
+                .unwrap_or(player_pos);
+            let msg = Message::ItemDropPickup {
+                pos,
+                item: d.item(),
+            };
+            let left = reactor
+                .dispatch_with_answer(msg)
+                .into_iter()
+                .find_map(|m| match m {
+                    Message::ItemDropPickup { item, .. } => Some(item),
+                    _ => None,
                 });
-                false
-            } else {
-                true
+            match left {
+                Some(item) => {
+                    if let Some(d) = entities.components_mut::<ItemDrop>().get_mut(&id) {
+                        d.refused = item == d.item;
+                        d.item = item;
+                    }
+                }
+                None => gone.push(id),
             }
+        }
+        gone.into_iter().for_each(|id| {
+            entities.despawn(id);
         });
     }
 }
@@ -141,21 +145,34 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
     {
         let player = args.game.player_rc();
         let world = args.game.world_rc();
+        let entities = args.game.entities_rc();
         let f = move |reactor: &Reactor<Message>, _msg: Message| {
             let player_pos = player.borrow().pos();
-            DROPS.with(|drops| {
-                drops
-                    .borrow_mut()
-                    .tick_all(reactor, player_pos, &world.borrow());
-            });
+            ItemDrop::tick_all(
+                &mut entities.borrow_mut(),
+                reactor,
+                player_pos,
+                &world.borrow(),
+            );
         };
         args.reactor
             .add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
     }
     {
+        let entities = args.game.entities_rc();
+        let player = args.game.player_rc();
+        let rng = RefCell::new(XorShiftRng::from_entropy());
         let f = move |_reactor: &Reactor<Message>, msg: Message| {
             if let Message::BlockBreak { pos, block } = msg {
-                DROPS.with(|drops| drops.borrow_mut().add_from_block_break(pos, block));
+                // Blocks blown up by explosions use the players tool and level as well
+                let ctx = LootContext::from_character(&player.borrow());
+                ItemDrop::spawn_from_block_break(
+                    &mut entities.borrow_mut(),
+                    pos,
+                    block,
+                    &ctx,
+                    &mut *rng.borrow_mut(),
+                );
             }
         };
         args.reactor.add_sink(
@@ -167,11 +184,10 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
         );
     }
     {
+        let entities = args.game.entities_rc();
         let f = move |_reactor: &Reactor<Message>, msg: Message| {
             if let Message::ItemDropNew { pos, item } = msg {
-                DROPS.with(|drops| {
-                    drops.borrow_mut().add(pos, Vec3::ZERO, item);
-                });
+                ItemDrop::spawn(&mut entities.borrow_mut(), pos, Vec3::ZERO, item);
             }
         };
         args.reactor.add_sink(
@@ -182,33 +198,7 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
             Box::new(f),
         );
     }
-    {
-        let f = move |_reactor: &Reactor<Message>, msg: Message| {
-            if let Message::Explosion { pos, power } = msg {
-                let p = power * power;
-                DROPS.with(|drops| {
-                    drops
-                        .borrow_mut()
-                        .iter_mut()
-                        .filter(|m| (pos - m.pos()).length_squared() < p)
-                        .for_each(|m| {
-                            let d = pos - m.pos();
-                            let mut dir = d.normalize() * d * 0.2;
-                            dir.y = -0.5;
-                            m.set_vel(dir * -0.04);
-                        });
-                });
-            }
-        };
-        args.reactor.add_sink(
-            Message::Explosion {
-                pos: Vec3::ZERO,
-                power: 0.0,
-            },
-            Box::new(f),
-        );
-    }
-
+    let entities = args.game.entities_rc();
     args.reactor.add_sink(
         Message::CharacterDropItem {
             pos: Vec3::ZERO,
@@ -217,39 +207,30 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
         },
         Box::new(move |_reactor: &Reactor<Message>, msg: Message| {
             if let Message::CharacterDropItem { pos, vel, item } = msg {
-                DROPS.with(|drops| {
-                    drops.borrow_mut().add(pos, vel, item);
-                });
+                ItemDrop::spawn(&mut entities.borrow_mut(), pos, vel, item);
             }
         }),
     );
 
-    args.render_reactor.entity_provider.push(Box::new(move |v| {
-        DROPS.with(|drops| {
-            for e in drops.borrow().iter() {
-                v.push(e.ent.clone());
-            }
-        });
-    }));
-
-    args.reactor.add_sink(
-        Message::ResetEverything,
-        Box::new(move |_: &Reactor<Message>, _msg: Message| {
-            DROPS.with(|drops| {
-                drops.borrow_mut().clear();
-            });
-        }),
-    );
+    args.game.entities_mut().register::<ItemDrop>();
 
     args.render_reactor
         .world_render
         .push(Box::new(move |args: RenderPassArgs| {
-            DROPS.with(|drops| {
-                for entity in drops.borrow().iter() {
-                    let _ =
-                        item_drop_draw(args.frame, args.fe, entity, &args.view, &args.projection);
+            let entities = args.game.entities();
+            let transforms = entities.components::<Transform>();
+            for (id, drop) in entities.components::<ItemDrop>().iter() {
+                if let Some(transform) = transforms.get(id) {
+                    let _ = item_drop_draw(
+                        args.frame,
+                        args.fe,
+                        drop,
+                        transform,
+                        &args.view,
+                        &args.projection,
+                    );
                 }
-            });
+            }
             args
         }));
 
@@ -2,22 +2,25 @@
 // All rights reserved. AGPL-3.0+ license.
 use super::BlockVertex;
 use wolkenwelten_core::{
-    blit_chunk_data, BlockType, ChunkBlockData, ChunkBuffer, ChunkFluidData, ChunkLightData,
-    ChunkPosIter, Side, CHUNK_SIZE,
+    blit_chunk_data, BlockId, BlockMeta, BlockOrientation, BlockShape, BlockType, ChunkBlockData,
+    ChunkBuffer, ChunkFluidData, ChunkLightData, ChunkPosIter, Side, CHUNK_SIZE,
 };
 type SideBuffer = [[[u8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+type FaceFn = fn(&mut Vec<BlockVertex>, (u8, u8, u8), (u8, u8, u8), (u8, bool), u16);
 
 #[derive(Copy, Clone, Debug, Default)]
 struct PlaneEntry {
     pub width: [[u8; CHUNK_SIZE]; CHUNK_SIZE],
     pub height: [[u8; CHUNK_SIZE]; CHUNK_SIZE],
-    pub block: [[u8; CHUNK_SIZE]; CHUNK_SIZE],
+    pub block: [[BlockId; CHUNK_SIZE]; CHUNK_SIZE],
+    pub meta: [[u8; CHUNK_SIZE]; CHUNK_SIZE],
     pub light: [[u16; CHUNK_SIZE]; CHUNK_SIZE],
 }
 impl PlaneEntry {
     pub fn new() -> Self {
         Self {
             block: [[0; CHUNK_SIZE]; CHUNK_SIZE],
+            meta: [[0; CHUNK_SIZE]; CHUNK_SIZE],
             width: [[0; CHUNK_SIZE]; CHUNK_SIZE],
             height: [[0; CHUNK_SIZE]; CHUNK_SIZE],
             light: [[0; CHUNK_SIZE]; CHUNK_SIZE],
@@ -31,6 +34,7 @@ impl PlaneEntry {
                 }
                 if (x < CHUNK_SIZE - 2)
                     && (self.block[x][y] == self.block[x + 1][y])
+                    && (self.meta[x][y] == self.meta[x + 1][y])
                     && (self.light[x][y] == self.light[x + 1][y])
                     && (self.width[x][y] == self.width[x + 1][y])
                 {
@@ -40,6 +44,7 @@ impl PlaneEntry {
 
                 if (y < CHUNK_SIZE - 2)
                     && (self.block[x][y] == self.block[x][y + 1])
+                    && (self.meta[x][y] == self.meta[x][y + 1])
                     && (self.light[x][y] == self.light[x][y + 1])
                     && (self.height[x][y] == self.height[x][y + 1])
                 {
@@ -51,14 +56,21 @@ impl PlaneEntry {
     }
 }
 
+/// The 4th bit of the side tells the shader to swap the texture axes,
+/// which rotates the texture by 90 degrees.
+#[inline]
+fn side_bits(side: Side, rotate: bool) -> u8 {
+    u8::from(side) | ((rotate as u8) << 3)
+}
+
 fn add_face_front(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (w, h, d): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Front.into();
+    let side = side_bits(Side::Front, rotate);
     let z = z + d;
     vertices.push(BlockVertex::new(
         x,
@@ -98,10 +110,10 @@ fn add_face_back(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (w, h, _): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Back.into();
+    let side = side_bits(Side::Back, rotate);
     vertices.push(BlockVertex::new(
         x,
         y,
@@ -140,10 +152,10 @@ fn add_face_top(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (w, h, d): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Top.into();
+    let side = side_bits(Side::Top, rotate);
     let y = y + h;
     vertices.push(BlockVertex::new(
         x,
@@ -183,10 +195,10 @@ fn add_face_bottom(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (w, _, d): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Bottom.into();
+    let side = side_bits(Side::Bottom, rotate);
     vertices.push(BlockVertex::new(
         x,
         y,
@@ -225,10 +237,10 @@ fn add_face_left(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (_, h, d): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Left.into();
+    let side = side_bits(Side::Left, rotate);
     vertices.push(BlockVertex::new(
         x,
         y,
@@ -267,10 +279,10 @@ fn add_face_right(
     vertices: &mut Vec<BlockVertex>,
     (x, y, z): (u8, u8, u8),
     (w, h, d): (u8, u8, u8),
-    texture_index: u8,
+    (texture_index, rotate): (u8, bool),
     light: u16,
 ) {
-    let side: u8 = Side::Right.into();
+    let side = side_bits(Side::Right, rotate);
     let x = x + w;
     vertices.push(BlockVertex::new(
         x,
@@ -330,9 +342,10 @@ fn light_front_back(light_data: &ChunkBuffer, x: usize, y: usize, z: usize) -> u
     ((a + b + c + d) / 4).min(15)
 }
 
-fn gen_front(
+fn gen_front<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -355,7 +368,8 @@ fn gen_front(
                 found += 1;
                 plane.width[y][x] = 1;
                 plane.height[y][x] = 1;
-                plane.block[y][x] = block_data[x + 1][y + 1][z + 1];
+                plane.block[y][x] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[y][x] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[y][x] = light_front_back(light_data, x, y, z + 2)
                     | (light_front_back(light_data, x + 1, y, z + 2) << 4)
                     | (light_front_back(light_data, x + 1, y + 1, z + 2) << 8)
@@ -377,10 +391,12 @@ fn gen_front(
                 let cw = plane.width[y][x];
                 let ch = plane.height[y][x];
                 let b = block_types.get(plane.block[y][x] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_front(vertices, pos, size, b.tex_front(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[y][x]);
+                    let tex = b.texture_for(Side::Front, meta);
+                    add_face_front(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -388,9 +404,10 @@ fn gen_front(
     (vertices.len() - start) / 4
 }
 
-fn gen_back(
+fn gen_back<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -413,7 +430,8 @@ fn gen_back(
                 found += 1;
                 plane.width[y][x] = 1;
                 plane.height[y][x] = 1;
-                plane.block[y][x] = block_data[x + 1][y + 1][z + 1];
+                plane.block[y][x] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[y][x] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[y][x] = light_front_back(light_data, x, y, z)
                     | (light_front_back(light_data, x, y + 1, z) << 4)
                     | (light_front_back(light_data, x + 1, y + 1, z) << 8)
@@ -435,10 +453,12 @@ fn gen_back(
                 let ch = plane.height[y][x];
                 let light = plane.light[y][x];
                 let b = block_types.get(plane.block[y][x] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_back(vertices, pos, size, b.tex_back(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[y][x]);
+                    let tex = b.texture_for(Side::Back, meta);
+                    add_face_back(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -446,9 +466,10 @@ fn gen_back(
     (vertices.len() - start) / 4
 }
 
-fn gen_top(
+fn gen_top<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -471,7 +492,8 @@ fn gen_top(
                 found += 1;
                 plane.width[z][x] = 1;
                 plane.height[z][x] = 1;
-                plane.block[z][x] = block_data[x + 1][y + 1][z + 1];
+                plane.block[z][x] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[z][x] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[z][x] = light_top_bottom(light_data, x, y + 2, z)
                     | (light_top_bottom(light_data, x, y + 2, z + 1) << 4)
                     | (light_top_bottom(light_data, x + 1, y + 2, z + 1) << 8)
@@ -493,10 +515,12 @@ fn gen_top(
                 let cd = plane.height[z][x];
                 let light = plane.light[z][x];
                 let b = block_types.get(plane.block[z][x] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_top(vertices, pos, size, b.tex_top(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[z][x]);
+                    let tex = b.texture_for(Side::Top, meta);
+                    add_face_top(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -504,9 +528,10 @@ fn gen_top(
     (vertices.len() - start) / 4
 }
 
-fn gen_bottom(
+fn gen_bottom<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -529,7 +554,8 @@ fn gen_bottom(
                 found += 1;
                 plane.width[z][x] = 1;
                 plane.height[z][x] = 1;
-                plane.block[z][x] = block_data[x + 1][y + 1][z + 1];
+                plane.block[z][x] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[z][x] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[z][x] = light_top_bottom(light_data, x, y, z)
                     | (light_top_bottom(light_data, x + 1, y, z) << 4)
                     | (light_top_bottom(light_data, x + 1, y, z + 1) << 8)
@@ -551,10 +577,12 @@ fn gen_bottom(
                 let cd = plane.height[z][x];
                 let light = plane.light[z][x];
                 let b = block_types.get(plane.block[z][x] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_bottom(vertices, pos, size, b.tex_bottom(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[z][x]);
+                    let tex = b.texture_for(Side::Bottom, meta);
+                    add_face_bottom(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -562,9 +590,10 @@ fn gen_bottom(
     (vertices.len() - start) / 4
 }
 
-fn gen_left(
+fn gen_left<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -587,7 +616,8 @@ fn gen_left(
                 found += 1;
                 plane.width[y][z] = 1;
                 plane.height[y][z] = 1;
-                plane.block[y][z] = block_data[x + 1][y + 1][z + 1];
+                plane.block[y][z] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[y][z] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[y][z] = light_left_right(light_data, x, y, z)
                     | (light_left_right(light_data, x, y, z + 1) << 4)
                     | (light_left_right(light_data, x, y + 1, z + 1) << 8)
@@ -609,10 +639,12 @@ fn gen_left(
                 let ch = plane.height[y][z];
                 let light = plane.light[y][z];
                 let b = block_types.get(plane.block[y][z] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_left(vertices, pos, size, b.tex_left(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[y][z]);
+                    let tex = b.texture_for(Side::Left, meta);
+                    add_face_left(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -620,9 +652,10 @@ fn gen_left(
     (vertices.len() - start) / 4
 }
 
-fn gen_right(
+fn gen_right<T: Copy + Into<BlockId>>(
     vertices: &mut Vec<BlockVertex>,
-    (block_data, light_data, side_cache, block_types): (
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<T>,
         &ChunkBuffer,
         &ChunkBuffer,
         &SideBuffer,
@@ -645,7 +678,8 @@ fn gen_right(
                 found += 1;
                 plane.width[y][z] = 1;
                 plane.height[y][z] = 1;
-                plane.block[y][z] = block_data[x + 1][y + 1][z + 1];
+                plane.block[y][z] = block_data[x + 1][y + 1][z + 1].into();
+                plane.meta[y][z] = meta_data[x + 1][y + 1][z + 1];
                 plane.light[y][z] = light_left_right(light_data, x + 2, y, z)
                     | (light_left_right(light_data, x + 2, y + 1, z) << 4)
                     | (light_left_right(light_data, x + 2, y + 1, z + 1) << 8)
@@ -667,10 +701,12 @@ fn gen_right(
                 let ch = plane.height[y][z];
                 let light = plane.light[y][z];
                 let b = block_types.get(plane.block[y][z] as usize);
-                let pos = (x as u8, y as u8, z as u8);
-                let size = (cw, ch, cd);
+                let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+                let size = (cw * 2, ch * 2, cd * 2);
                 if let Some(b) = b {
-                    add_face_right(vertices, pos, size, b.tex_right(), light);
+                    let meta = BlockMeta::from_raw(plane.meta[y][z]);
+                    let tex = b.texture_for(Side::Right, meta);
+                    add_face_right(vertices, pos, size, tex, light);
                 }
             }
         }
@@ -678,7 +714,11 @@ fn gen_right(
     (vertices.len() - start) / 4
 }
 
-fn calc_block_data(block_data: &mut ChunkBuffer, chunks: &[&ChunkBlockData; 27]) {
+fn calc_block_data(
+    block_data: &mut ChunkBuffer<BlockId>,
+    meta_data: &mut ChunkBuffer,
+    chunks: &[&ChunkBlockData; 27],
+) {
     for cx in 0..3 {
         for cy in 0..3 {
             for cz in 0..3 {
@@ -687,7 +727,9 @@ fn calc_block_data(block_data: &mut ChunkBuffer, chunks: &[&ChunkBlockData; 27])
                     (cy * CHUNK_SIZE) as isize - (CHUNK_SIZE as isize - 1),
                     (cz * CHUNK_SIZE) as isize - (CHUNK_SIZE as isize - 1),
                 ];
-                blit_chunk_data(block_data, &chunks[cx * 3 * 3 + cy * 3 + cz].data, off)
+                let chunk = chunks[cx * 3 * 3 + cy * 3 + cz];
+                chunk.blit_into(block_data, off);
+                chunk.blit_meta_into(meta_data, off);
             }
         }
     }
@@ -723,50 +765,261 @@ fn calc_fluid_data(d: &mut ChunkBuffer, fluids: &[&ChunkFluidData; 27]) {
     }
 }
 
-/// Check which sides need to be drawn for a given position.
-fn calc_sides(x: usize, y: usize, z: usize, block_data: &ChunkBuffer) -> u8 {
-    if block_data[x][y][z] == 0 {
+/// Check which sides need to be drawn for a given position, faces are only
+/// hidden by opaque cubes or by a neighbouring block of the same type, so
+/// that there are no faces drawn in between panes of glass.
+fn calc_sides(
+    x: usize,
+    y: usize,
+    z: usize,
+    block_data: &ChunkBuffer<BlockId>,
+    block_types: &[BlockType],
+) -> u8 {
+    let b = block_data[x][y][z];
+    if b == 0 || !is_cube(b, block_types) {
         0
     } else {
-        ((block_data[x][y][z + 1] == 0) as u8)
-            | (((block_data[x][y][z - 1] == 0) as u8) << 1)
-            | (((block_data[x][y + 1][z] == 0) as u8) << 2)
-            | (((block_data[x][y - 1][z] == 0) as u8) << 3)
-            | (((block_data[x - 1][y][z] == 0) as u8) << 4)
-            | (((block_data[x + 1][y][z] == 0) as u8) << 5)
+        let visible = |n: BlockId| n != b && !is_opaque_cube(n, block_types);
+        (visible(block_data[x][y][z + 1]) as u8)
+            | ((visible(block_data[x][y][z - 1]) as u8) << 1)
+            | ((visible(block_data[x][y + 1][z]) as u8) << 2)
+            | ((visible(block_data[x][y - 1][z]) as u8) << 3)
+            | ((visible(block_data[x - 1][y][z]) as u8) << 4)
+            | ((visible(block_data[x + 1][y][z]) as u8) << 5)
     }
 }
 
+/// Unknown block ids, like the ones used by voxel models, are drawn as opaque cubes
+#[inline]
+fn is_cube(b: BlockId, block_types: &[BlockType]) -> bool {
+    block_types
+        .get(b as usize)
+        .map(|t| t.shape() == BlockShape::Cube)
+        .unwrap_or(true)
+}
+
+#[inline]
+fn is_opaque_cube(b: BlockId, block_types: &[BlockType]) -> bool {
+    b != 0
+        && block_types
+            .get(b as usize)
+            .map(|t| t.is_opaque_cube())
+            .unwrap_or(true)
+}
+
 /// Fill the side cache, every entry is a bitmask describing which faces
 /// need to be drawn and which can be skipped.
-fn calc_side_cache(side_cache: &mut SideBuffer, block_data: &ChunkBuffer) {
+fn calc_side_cache(
+    side_cache: &mut SideBuffer,
+    block_data: &ChunkBuffer<BlockId>,
+    block_types: &[BlockType],
+) {
     for (x, y, z) in ChunkPosIter::new() {
-        side_cache[x][y][z] = calc_sides(x + 1, y + 1, z + 1, block_data);
+        side_cache[x][y][z] = calc_sides(x + 1, y + 1, z + 1, block_data, block_types);
+    }
+}
+
+/// Rotate a box within a single block according to meta, every value is in
+/// half-voxel units so a block spans from 0 to 2 along every axis.
+fn rotate_box(
+    (off, size): ((u8, u8, u8), (u8, u8, u8)),
+    bt: &BlockType,
+    meta: BlockMeta,
+) -> ((u8, u8, u8), (u8, u8, u8)) {
+    if bt.orientation() != BlockOrientation::Facing {
+        return (off, size);
+    }
+    let (x, y, z) = off;
+    let (w, h, d) = size;
+    match meta.facing() {
+        Side::Back => ((2 - (x + w), y, 2 - (z + d)), size),
+        Side::Right => ((z, y, 2 - (x + w)), (d, h, w)),
+        Side::Left => ((2 - (z + d), y, x), (d, h, w)),
+        _ => (off, size),
     }
 }
 
+/// Add a box within a single block, pos is the lower corner of the block
+/// whereas shape describes the unrotated box, all in half-voxel units. Faces
+/// lying on the border of the block get skipped if the neighbour there is
+/// occluding them, skip contains sides of the unrotated box to leave out.
+#[allow(clippy::too_many_arguments)]
+fn add_box(
+    faces: &mut [Vec<BlockVertex>; 7],
+    pos: (u8, u8, u8),
+    shape: ((u8, u8, u8), (u8, u8, u8)),
+    bt: &BlockType,
+    meta: BlockMeta,
+    lights: [u16; 6],
+    occluded: [bool; 6],
+    skip: u8,
+) {
+    let (off, size) = rotate_box(shape, bt, meta);
+    let p = (pos.0 + off.0, pos.1 + off.1, pos.2 + off.2);
+    let border = [
+        off.2 + size.2 == 2,
+        off.2 == 0,
+        off.1 + size.1 == 2,
+        off.1 == 0,
+        off.0 == 0,
+        off.0 + size.0 == 2,
+    ];
+    let add_face: [FaceFn; 6] = [
+        add_face_front,
+        add_face_back,
+        add_face_top,
+        add_face_bottom,
+        add_face_left,
+        add_face_right,
+    ];
+    for (i, add_face) in add_face.iter().enumerate() {
+        let side = Side::from(i as u8);
+        let (unrotated, _) = meta.unrotate_side(bt.orientation(), side);
+        if (skip & (1 << u8::from(unrotated))) != 0 || (border[i] && occluded[i]) {
+            continue;
+        }
+        let tex = bt.texture_for(side, meta);
+        add_face(&mut faces[i], p, size, tex, lights[i]);
+    }
+}
+
+/// Two diagonal quads, each one added twice so it can be seen from both sides
+fn add_cross_plant(
+    vertices: &mut Vec<BlockVertex>,
+    (x, y, z): (u8, u8, u8),
+    (texture_index, rotate): (u8, bool),
+    light: u16,
+) {
+    let side = side_bits(Side::Front, rotate);
+    let light = (light & 0xF) as u8;
+    let diagonals = [((x, z), (x + 2, z + 2)), ((x, z + 2), (x + 2, z))];
+    for (a, b) in diagonals {
+        for ((ax, az), (bx, bz)) in [(a, b), (b, a)] {
+            vertices.push(BlockVertex::new(ax, y, az, texture_index, side, light));
+            vertices.push(BlockVertex::new(bx, y, bz, texture_index, side, light));
+            vertices.push(BlockVertex::new(bx, y + 2, bz, texture_index, side, light));
+            vertices.push(BlockVertex::new(ax, y + 2, az, texture_index, side, light));
+        }
+    }
+}
+
+/// Generate the faces of every block that isn't a simple cube, these can't
+/// be merged with their neighbours so every face is emitted on its own.
+/// The first 6 entries are sorted by side, just like the regular faces, the
+/// last one contains faces that can be seen from every direction.
+fn gen_shapes(
+    block_data: &ChunkBuffer<BlockId>,
+    meta_data: &ChunkBuffer,
+    light_data: &ChunkBuffer,
+    block_types: &[BlockType],
+) -> [Vec<BlockVertex>; 7] {
+    let mut ret: [Vec<BlockVertex>; 7] = Default::default();
+    for (x, y, z) in ChunkPosIter::new() {
+        let (bx, by, bz) = (x + 1, y + 1, z + 1);
+        let b = block_data[bx][by][bz];
+        if b == 0 {
+            continue;
+        }
+        let Some(bt) = block_types.get(b as usize) else {
+            continue;
+        };
+        let meta = BlockMeta::from_raw(meta_data[bx][by][bz]);
+        let pos = (x as u8 * 2, y as u8 * 2, z as u8 * 2);
+        let light = |x: usize, y: usize, z: usize| {
+            let l = light_data[x][y][z] as u16;
+            l | (l << 4) | (l << 8) | (l << 12)
+        };
+        let neighbors = [
+            (bx, by, bz + 1),
+            (bx, by, bz - 1),
+            (bx, by + 1, bz),
+            (bx, by - 1, bz),
+            (bx - 1, by, bz),
+            (bx + 1, by, bz),
+        ];
+        let lights = neighbors.map(|(x, y, z)| light(x, y, z));
+        let occluded = neighbors.map(|(x, y, z)| is_opaque_cube(block_data[x][y][z], block_types));
+        match bt.shape() {
+            BlockShape::Cube => (),
+            BlockShape::CrossPlant => {
+                let tex = bt.texture_for(Side::Front, meta);
+                add_cross_plant(&mut ret[6], pos, tex, light(bx, by, bz));
+            }
+            BlockShape::Slab => {
+                let shape = ((0, 0, 0), (2, 1, 2));
+                add_box(&mut ret, pos, shape, bt, meta, lights, occluded, 0);
+            }
+            BlockShape::Stairs => {
+                let back = ((0, 0, 0), (2, 2, 1));
+                add_box(&mut ret, pos, back, bt, meta, lights, occluded, 0);
+                // The back face of the lower step is always covered by the upper one
+                let front = ((0, 0, 1), (2, 1, 1));
+                let skip = 1 << u8::from(Side::Back);
+                add_box(&mut ret, pos, front, bt, meta, lights, occluded, skip);
+            }
+        }
+    }
+    ret
+}
+
+/// Move the faces generated by gen_shapes over, returning the amount of squares
+fn append_faces(vertices: &mut Vec<BlockVertex>, faces: &mut Vec<BlockVertex>) -> usize {
+    let count = faces.len() / 4;
+    vertices.append(faces);
+    count
+}
+
+/// Generate all faces of a chunk, the first 6 sides are sorted by the direction
+/// they are facing so they can be culled as a whole, while the 7th one
+/// has to be drawn from every direction.
+fn gen_block_sides(
+    vertices: &mut Vec<BlockVertex>,
+    (block_data, meta_data, light_data, side_cache, block_types): (
+        &ChunkBuffer<BlockId>,
+        &ChunkBuffer,
+        &ChunkBuffer,
+        &SideBuffer,
+        &Vec<BlockType>,
+    ),
+) -> [usize; 7] {
+    let mut shapes = gen_shapes(block_data, meta_data, light_data, block_types);
+    let data = (block_data, meta_data, light_data, side_cache, block_types);
+    let mut side_square_count = [0; 7];
+
+    side_square_count[0] = gen_front(vertices, data) + append_faces(vertices, &mut shapes[0]);
+    side_square_count[1] = gen_back(vertices, data) + append_faces(vertices, &mut shapes[1]);
+    side_square_count[2] = gen_top(vertices, data) + append_faces(vertices, &mut shapes[2]);
+    side_square_count[3] = gen_bottom(vertices, data) + append_faces(vertices, &mut shapes[3]);
+    side_square_count[4] = gen_left(vertices, data) + append_faces(vertices, &mut shapes[4]);
+    side_square_count[5] = gen_right(vertices, data) + append_faces(vertices, &mut shapes[5]);
+    side_square_count[6] = append_faces(vertices, &mut shapes[6]);
+
+    side_square_count
+}
+
 fn calc_fluid_sides(
     x: usize,
     y: usize,
     z: usize,
-    block_data: &ChunkBuffer,
+    block_data: &ChunkBuffer<BlockId>,
     fluid_data: &ChunkBuffer,
 ) -> u8 {
+    let empty = |x: usize, y: usize, z: usize| block_data[x][y][z] == 0 && fluid_data[x][y][z] == 0;
     if (block_data[x][y][z] != 0) || (fluid_data[x][y][z] == 0) {
         0
     } else {
-        (((block_data[x][y][z + 1] | fluid_data[x][y][z + 1]) == 0) as u8)
-            | ((((block_data[x][y][z - 1] | fluid_data[x][y][z - 1]) == 0) as u8) << 1)
-            | ((((block_data[x][y + 1][z] | fluid_data[x][y + 1][z]) == 0) as u8) << 2)
-            | ((((block_data[x][y - 1][z] | fluid_data[x][y - 1][z]) == 0) as u8) << 3)
-            | ((((block_data[x - 1][y][z] | fluid_data[x - 1][y][z]) == 0) as u8) << 4)
-            | ((((block_data[x + 1][y][z] | fluid_data[x + 1][y][z]) == 0) as u8) << 5)
+        (empty(x, y, z + 1) as u8)
+            | ((empty(x, y, z - 1) as u8) << 1)
+            | ((empty(x, y + 1, z) as u8) << 2)
+            | ((empty(x, y - 1, z) as u8) << 3)
+            | ((empty(x - 1, y, z) as u8) << 4)
+            | ((empty(x + 1, y, z) as u8) << 5)
     }
 }
 
 fn calc_fluid_side_cache(
     side_cache: &mut SideBuffer,
-    block_data: &ChunkBuffer,
+    block_data: &ChunkBuffer<BlockId>,
     fluid_data: &ChunkBuffer,
 ) {
     for (x, y, z) in ChunkPosIter::new() {
@@ -781,27 +1034,27 @@ pub fn generate(
     chunks: &[&ChunkBlockData; 27],
     lights: &[&ChunkLightData; 27],
     block_types: &Vec<BlockType>,
-) -> (Vec<BlockVertex>, [usize; 6]) {
+) -> (Vec<BlockVertex>, [usize; 7]) {
     let mut vertices: Vec<BlockVertex> = Vec::with_capacity(1024);
 
-    let mut block_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut block_data: ChunkBuffer<BlockId> =
+        [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut meta_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut light_data: ChunkBuffer = [[[15; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut side_cache: SideBuffer = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
 
-    calc_block_data(&mut block_data, chunks);
+    calc_block_data(&mut block_data, &mut meta_data, chunks);
     calc_light_data(&mut light_data, lights);
-    calc_side_cache(&mut side_cache, &block_data);
-
-    let data = (&block_data, &light_data, &side_cache, block_types);
-    let mut side_square_count = [0; 6];
-
-    side_square_count[0] = gen_front(&mut vertices, data);
-    side_square_count[1] = gen_back(&mut vertices, data);
-    side_square_count[2] = gen_top(&mut vertices, data);
-    side_square_count[3] = gen_bottom(&mut vertices, data);
-    side_square_count[4] = gen_left(&mut vertices, data);
-    side_square_count[5] = gen_right(&mut vertices, data);
+    calc_side_cache(&mut side_cache, &block_data, block_types);
 
+    let data = (
+        &block_data,
+        &meta_data,
+        &light_data,
+        &side_cache,
+        block_types,
+    );
+    let side_square_count = gen_block_sides(&mut vertices, data);
     (vertices, side_square_count)
 }
 
@@ -812,28 +1065,29 @@ pub fn generate_simple(
     chunk: &ChunkBlockData,
     light: &ChunkLightData,
     block_types: &Vec<BlockType>,
-) -> (Vec<BlockVertex>, [usize; 6]) {
+) -> (Vec<BlockVertex>, [usize; 7]) {
     let mut vertices: Vec<BlockVertex> = Vec::with_capacity(1024);
 
-    let mut block_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut block_data: ChunkBuffer<BlockId> =
+        [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut meta_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut light_data: ChunkBuffer = [[[15; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut side_cache: SideBuffer = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
 
     let off = [1; 3];
-    blit_chunk_data(&mut block_data, &chunk.data, off);
+    chunk.blit_into(&mut block_data, off);
+    chunk.blit_meta_into(&mut meta_data, off);
     blit_chunk_data(&mut light_data, &light.data, off);
-    calc_side_cache(&mut side_cache, &block_data);
-
-    let data = (&block_data, &light_data, &side_cache, block_types);
-    let mut side_square_count = [0; 6];
-
-    side_square_count[0] = gen_front(&mut vertices, data);
-    side_square_count[1] = gen_back(&mut vertices, data);
-    side_square_count[2] = gen_top(&mut vertices, data);
-    side_square_count[3] = gen_bottom(&mut vertices, data);
-    side_square_count[4] = gen_left(&mut vertices, data);
-    side_square_count[5] = gen_right(&mut vertices, data);
+    calc_side_cache(&mut side_cache, &block_data, block_types);
 
+    let data = (
+        &block_data,
+        &meta_data,
+        &light_data,
+        &side_cache,
+        block_types,
+    );
+    let side_square_count = gen_block_sides(&mut vertices, data);
     (vertices, side_square_count)
 }
 
@@ -842,21 +1096,25 @@ pub fn generate_fluid(
     lights: &[&ChunkLightData; 27],
     fluids: &[&ChunkFluidData; 27],
     fluid_types: &Vec<BlockType>,
-) -> (Vec<BlockVertex>, [usize; 6]) {
+) -> (Vec<BlockVertex>, [usize; 7]) {
     let mut vertices: Vec<BlockVertex> = Vec::with_capacity(1024);
 
-    let mut block_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut block_data: ChunkBuffer<BlockId> =
+        [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut fluid_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let mut meta_data: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut light_data: ChunkBuffer = [[[15; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
     let mut side_cache: SideBuffer = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
 
-    calc_block_data(&mut block_data, chunks);
+    calc_block_data(&mut block_data, &mut meta_data, chunks);
     calc_light_data(&mut light_data, lights);
     calc_fluid_data(&mut fluid_data, fluids);
     calc_fluid_side_cache(&mut side_cache, &block_data, &fluid_data);
 
-    let data = (&fluid_data, &light_data, &side_cache, fluid_types);
-    let mut side_square_count = [0; 6];
+    // Fluids don't have any orientation, so we can just pass a zeroed buffer here
+    let no_meta: ChunkBuffer = [[[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    let data = (&fluid_data, &no_meta, &light_data, &side_cache, fluid_types);
+    let mut side_square_count = [0; 7];
 
     side_square_count[0] = gen_front(&mut vertices, data);
     side_square_count[1] = gen_back(&mut vertices, data);
@@ -0,0 +1,602 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::{walk_direction, Mob, MobBody, MobState};
+use glam::{Vec3, Vec3Swizzles};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use wolkenwelten_core::{
+    BehaviorAction, BehaviorCondition, BehaviorContext, BehaviorStatus, Character, Chungus,
+    EntityId, Message, MobSpecial, MobType, ProjectileOwner, Reactor, BEHAVIORS, MS_PER_TICK,
+    PROJECTILE_TYPES,
+};
+
+/// How close the player has to be to get hit
+const MOB_REACH: f32 = 2.0;
+/// Within this distance a fighting mob stops walking towards the player
+const MOB_CLOSE_ENOUGH: f32 = 1.3;
+/// For how long a mob holds a grudge after getting hit
+const MOB_PROVOKED_MS: u64 = 10_000;
+/// How long a lunge animation lasts until the mob starts fighting normally
+const MOB_LUNGE_MS: u64 = 200;
+/// How far from home a wandering mob strolls
+const MOB_WANDER_RADIUS: f32 = 8.0;
+/// After this long a mob gives up on reaching its wander target
+const MOB_WANDER_TIMEOUT_MS: u64 = 10_000;
+/// Other mobs of the same type within this distance form a flock
+pub const MOB_FLOCK_RANGE: f32 = 8.0;
+/// How close to the center of its flock a mob wants to be
+const MOB_FLOCK_SPREAD: f32 = 3.0;
+/// How close to home is close enough when returning
+const MOB_HOME_DISTANCE: f32 = 2.0;
+/// For how long a charging mob hits whatever it runs into
+const MOB_CHARGE_MS: u64 = 800;
+/// Charges don't start from closer than this
+const MOB_CHARGE_MIN_DISTANCE: f32 = 4.0;
+
+const WALK_SPEED: f32 = 1.5;
+const RUN_SPEED: f32 = 2.0;
+const CHASE_SPEED: f32 = 1.2;
+
+#[inline]
+fn ms_to_ticks(ms: u64) -> u64 {
+    ms / MS_PER_TICK
+}
+
+/// Everything a mob gets to see and touch while deciding what to do, runs
+/// the behavior tree of the mobs type.
+pub struct MobContext<'a> {
+    pub id: EntityId,
+    pub mob: &'a mut Mob,
+    pub mob_type: &'a MobType,
+    pub body: MobBody<'a>,
+    pub world: &'a Chungus,
+    pub player: &'a Character,
+    pub rng: &'a mut XorShiftRng,
+    pub reactor: &'a Reactor<Message>,
+    /// Positions of nearby mobs of the same type
+    pub flock: &'a [Vec3],
+}
+
+impl<'a> MobContext<'a> {
+    #[inline]
+    fn pos(&self) -> Vec3 {
+        self.body.transform.pos
+    }
+
+    /// Whether the player may be chased or attacked at all
+    fn player_targetable(&self) -> bool {
+        !self.player.no_clip() && !self.player.is_dead()
+    }
+
+    fn player_distance(&self) -> f32 {
+        (self.player.pos() - self.pos()).length()
+    }
+
+    /// Bigger mobs reach further
+    fn reach(&self) -> f32 {
+        MOB_REACH * self.mob.scale(self.mob_type)
+    }
+
+    fn player_distance_xz(&self) -> f32 {
+        (self.player.pos() - self.pos()).xz().length()
+    }
+
+    fn enter(&mut self, state: fn(u64) -> MobState) {
+        self.mob.enter(state);
+    }
+
+    /// Turn towards target and start moving, speed being relative to the normal walking speed
+    fn walk_towards(&mut self, target: Vec3, speed: f32) {
+        let diff = (target - self.pos()).xz();
+        let deg = diff.y.atan2(diff.x).to_degrees();
+        Mob::turn_towards(self.body.transform, Vec3::new(0.0, -deg - 90.0, 0.0));
+        self.mob.movement = walk_direction(self.body.transform.rot) * speed;
+    }
+
+    fn stand_still(&mut self) {
+        self.mob.movement = Vec3::ZERO;
+    }
+
+    fn wander(&mut self) -> BehaviorStatus {
+        let age = self.mob.age;
+        if age < self.mob.rest_until {
+            self.enter(MobState::Idle);
+            self.stand_still();
+            return BehaviorStatus::Running;
+        }
+        let (target, deadline) = match self.mob.wander {
+            Some(w) => w,
+            None => {
+                let a = self.rng.gen_range(0.0..std::f32::consts::TAU);
+                let r = self.rng.gen_range(0.0..MOB_WANDER_RADIUS);
+                let target = self.mob.home + Vec3::new(a.cos() * r, 0.0, a.sin() * r);
+                let w = (target, age + ms_to_ticks(MOB_WANDER_TIMEOUT_MS));
+                self.mob.wander = Some(w);
+                w
+            }
+        };
+        if (target - self.pos()).xz().length() < 1.0 || age > deadline {
+            self.mob.wander = None;
+            self.mob.rest_until = age + ms_to_ticks(self.rng.gen_range(2000..8000));
+            self.enter(MobState::Idle);
+            self.stand_still();
+        } else {
+            self.enter(MobState::Walk);
+            self.walk_towards(target, WALK_SPEED);
+        }
+        BehaviorStatus::Running
+    }
+
+    fn flee(&mut self) -> BehaviorStatus {
+        if !self.player_targetable() || self.player_distance() > self.mob_type.give_up_range() {
+            return BehaviorStatus::Failure;
+        }
+        let away = (self.pos() - self.player.pos()).xz();
+        let away = if away.length_squared() > 0.0 {
+            away.normalize()
+        } else {
+            glam::Vec2::X
+        };
+        let target = self.pos() + Vec3::new(away.x, 0.0, away.y) * 4.0;
+        self.mob.wander = None;
+        self.enter(MobState::Run);
+        self.walk_towards(target, RUN_SPEED);
+        BehaviorStatus::Running
+    }
+
+    fn chase(&mut self) -> BehaviorStatus {
+        if !self.player_targetable() || self.player_distance() > self.mob_type.give_up_range() {
+            return BehaviorStatus::Failure;
+        }
+        self.mob.wander = None;
+        self.enter(MobState::ChasePlayer);
+        if self.player_distance() < self.reach() {
+            self.stand_still();
+            return BehaviorStatus::Success;
+        }
+        let pos = self.pos();
+        let target = self.mob.next_waypoint(pos).unwrap_or(self.player.pos());
+        self.walk_towards(target, CHASE_SPEED);
+        BehaviorStatus::Running
+    }
+
+    fn attack(&mut self) -> BehaviorStatus {
+        if !self.player_targetable() || self.player_distance() > self.reach() {
+            return BehaviorStatus::Failure;
+        }
+        let pos = self.pos();
+        let age = self.mob.age;
+        let attack = self.mob_type.attack();
+        self.walk_towards(self.player.pos(), CHASE_SPEED);
+        if self.player_distance() < MOB_CLOSE_ENOUGH * self.mob.scale(self.mob_type) {
+            self.stand_still();
+        }
+        match self.mob.state {
+            MobState::InstantAttackPlayer(t) => {
+                self.stand_still();
+                if age - t > ms_to_ticks(MOB_LUNGE_MS) {
+                    self.mob.state = MobState::FightPlayer(age);
+                }
+            }
+            MobState::FightPlayer(t) => {
+                if age - t > ms_to_ticks(attack.cooldown) {
+                    let damage = self.mob.damage(self.mob_type, attack.damage);
+                    self.reactor.defer(Message::MobStrike { pos, damage });
+                    self.mob.state = MobState::FightPlayer(age);
+                }
+            }
+            _ => {
+                if self.mob.may_instant_attack(self.mob_type) {
+                    self.mob.cooldown();
+                    let damage = self.mob.damage(self.mob_type, attack.lunge_damage);
+                    self.reactor.defer(Message::MobStrike { pos, damage });
+                    self.mob.state = MobState::InstantAttackPlayer(age);
+                    self.stand_still();
+                } else {
+                    self.mob.state = MobState::FightPlayer(age);
+                }
+            }
+        }
+        BehaviorStatus::Running
+    }
+
+    /// Use the special attack, if there is one and it has cooled down
+    fn special(&mut self) -> BehaviorStatus {
+        let Some(special) = self.mob.special(self.mob_type) else {
+            return BehaviorStatus::Failure;
+        };
+        let age = self.mob.age;
+        let cooling = self
+            .mob
+            .special_at
+            .map(|t| age - t < ms_to_ticks(special.cooldown()))
+            .unwrap_or(false);
+        if cooling || !self.player_targetable() {
+            return BehaviorStatus::Failure;
+        }
+        let pos = self.pos();
+        let distance = self.player_distance();
+        match special {
+            MobSpecial::GroundSlam { power, .. } => {
+                if distance > power * 2.0 {
+                    return BehaviorStatus::Failure;
+                }
+                let damage = self
+                    .mob
+                    .damage(self.mob_type, self.mob_type.attack().damage);
+                self.reactor.defer(Message::Explosion {
+                    pos,
+                    power,
+                    by_player: false,
+                });
+                self.reactor.defer(Message::MobStrike { pos, damage });
+                self.mob.state = MobState::FightPlayer(age);
+                self.stand_still();
+            }
+            MobSpecial::Charge { speed, .. } => {
+                if distance < MOB_CHARGE_MIN_DISTANCE || distance > self.mob_type.give_up_range() {
+                    return BehaviorStatus::Failure;
+                }
+                let dir = (self.player.pos() - pos).xz().normalize_or_zero();
+                let vel = &mut self.body.physics.vel;
+                vel.x = dir.x * speed;
+                vel.z = dir.y * speed;
+                self.mob.charge_until = age + ms_to_ticks(MOB_CHARGE_MS);
+                self.enter(MobState::ChasePlayer);
+                self.walk_towards(self.player.pos(), RUN_SPEED);
+            }
+            MobSpecial::Shoot { projectile, .. } => {
+                if distance < self.reach() || distance > self.mob_type.give_up_range() {
+                    return BehaviorStatus::Failure;
+                }
+                let Some((speed, gravity)) = PROJECTILE_TYPES.with(|types| {
+                    types
+                        .borrow()
+                        .get(projectile as usize)
+                        .map(|t| (t.speed(), t.gravity()))
+                }) else {
+                    return BehaviorStatus::Failure;
+                };
+                // Aim a little higher to make up for the projectile falling on its way
+                let ticks = distance / speed;
+                let target = self.player.pos() + Vec3::Y * (gravity * ticks * ticks * 0.5);
+                let vel = (target - pos).normalize_or_zero() * speed;
+                self.reactor.defer(Message::ProjectileSpawn {
+                    pos,
+                    vel,
+                    projectile,
+                    owner: ProjectileOwner::Entity(self.id),
+                });
+                self.mob.state = MobState::FightPlayer(age);
+                self.walk_towards(self.player.pos(), 0.0);
+            }
+        }
+        self.mob.special_at = Some(age);
+        BehaviorStatus::Success
+    }
+
+    /// Running into the player while charging hurts them, but only once
+    fn charge_hit(&mut self) {
+        if self.mob.age > self.mob.charge_until {
+            return;
+        }
+        if self.player_targetable() && self.player_distance() < self.reach() {
+            let pos = self.pos();
+            let damage = self
+                .mob
+                .damage(self.mob_type, self.mob_type.attack().lunge_damage);
+            self.reactor.defer(Message::MobStrike { pos, damage });
+            self.mob.charge_until = 0;
+        }
+    }
+
+    fn return_home(&mut self) -> BehaviorStatus {
+        let home = self.mob.home;
+        if (home - self.pos()).xz().length() < MOB_HOME_DISTANCE {
+            return BehaviorStatus::Success;
+        }
+        self.mob.wander = None;
+        self.enter(MobState::Walk);
+        self.walk_towards(home, WALK_SPEED);
+        BehaviorStatus::Running
+    }
+
+    fn flock(&mut self) -> BehaviorStatus {
+        if self.flock.is_empty() {
+            return BehaviorStatus::Failure;
+        }
+        let center = self.flock.iter().copied().sum::<Vec3>() / self.flock.len() as f32;
+        if (center - self.pos()).xz().length() < MOB_FLOCK_SPREAD {
+            return BehaviorStatus::Failure;
+        }
+        self.mob.wander = None;
+        self.enter(MobState::Walk);
+        self.walk_towards(center, WALK_SPEED);
+        BehaviorStatus::Running
+    }
+
+    /// Run the behavior tree and move the mob accordingly
+    pub fn tick(&mut self) {
+        let pos = self.pos();
+        if !self.world.is_loaded(pos) {
+            return; // Just freeze the mob until we have loaded the area, this shouldn't happen if at all possible
+        }
+        self.mob.age += 1;
+        self.mob.effects.check_surroundings(
+            self.world,
+            &[pos, pos + Vec3::new(0.0, -super::MOB_SIZE, 0.0)],
+        );
+        let damage = self.mob.effects.tick(self.body.health);
+        if damage > 0 {
+            self.body.health.damage(damage);
+            self.mob.hit_by_player = false;
+            self.reactor.defer(Message::MobHurt { pos, damage });
+        }
+
+        let behavior = self.mob_type.behavior() as usize;
+        let status = BEHAVIORS.with(|b| b.borrow().get(behavior).map(|b| b.root().tick(self)));
+        if status.unwrap_or(BehaviorStatus::Failure) == BehaviorStatus::Failure {
+            self.enter(MobState::Idle);
+            self.stand_still();
+        }
+        self.charge_hit();
+
+        self.mob.jump(&mut self.body, self.world, self.rng);
+        self.mob
+            .tick_physics(self.mob_type, &mut self.body, self.world);
+    }
+}
+
+impl<'a> BehaviorContext for MobContext<'a> {
+    fn check(&mut self, condition: BehaviorCondition) -> bool {
+        match condition {
+            BehaviorCondition::PlayerNear => {
+                let range = if self.mob.is_alert() {
+                    self.mob_type.give_up_range()
+                } else {
+                    self.mob_type.aggro_range()
+                };
+                self.player_targetable() && self.player_distance_xz() < range
+            }
+            BehaviorCondition::PlayerInReach => {
+                self.player_targetable() && self.player_distance() < self.reach()
+            }
+            BehaviorCondition::Provoked => self
+                .mob
+                .provoked
+                .map(|t| self.mob.age.saturating_sub(t) < ms_to_ticks(MOB_PROVOKED_MS))
+                .unwrap_or(false),
+            BehaviorCondition::HealthBelow(f) => {
+                let h = &self.body.health;
+                (h.health() as f32) < h.max_health() as f32 * f
+            }
+            BehaviorCondition::FarFromHome(d) => (self.mob.home - self.pos()).xz().length() > d,
+            BehaviorCondition::FlockNearby => !self.flock.is_empty(),
+            BehaviorCondition::Underwater => Mob::is_underwater(self.world, self.pos()),
+        }
+    }
+
+    fn act(&mut self, action: BehaviorAction) -> BehaviorStatus {
+        match action {
+            BehaviorAction::Idle => {
+                self.enter(MobState::Idle);
+                self.stand_still();
+                BehaviorStatus::Success
+            }
+            BehaviorAction::Wander => self.wander(),
+            BehaviorAction::Flee => self.flee(),
+            BehaviorAction::Chase => self.chase(),
+            BehaviorAction::Attack => self.attack(),
+            BehaviorAction::ReturnHome => self.return_home(),
+            BehaviorAction::Flock => self.flock(),
+            BehaviorAction::Special => self.special(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::IVec3;
+    use rand::SeedableRng;
+    use wolkenwelten_core::{
+        mob_type_id, EntityStore, Health, MobStash, MobType, Pathfinder, Physics, Renderable,
+        Transform, MOB_TYPES,
+    };
+
+    fn run(
+        entities: &mut EntityStore,
+        player: &Character,
+        world: &Chungus,
+        ticks: usize,
+    ) -> Reactor<Message> {
+        let reactor = Reactor::new();
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let mut pathfinder = Pathfinder::new();
+        for _ in 0..ticks {
+            Mob::tick_all(entities, &reactor, player, world, &mut rng, &mut pathfinder);
+        }
+        reactor
+    }
+
+    fn pos(entities: &EntityStore, id: wolkenwelten_core::EntityId) -> Vec3 {
+        entities.get::<Transform>(id).unwrap().pos
+    }
+
+    #[test]
+    fn test_mob_behavior() {
+        let world = Chungus::new_flat("stone", 24);
+        let mut entities = EntityStore::new();
+        entities.register::<Mob>();
+        let crab = mob_type_id("crab").unwrap();
+        let mut player = Character::new();
+        player.set_pos(Vec3::new(100.5, 1.0, 0.5));
+
+        // Without the player around, crabs just stroll around near home
+        let home = Vec3::new(0.5, 0.7, 0.5);
+        let id = Mob::spawn(&mut entities, home, Vec3::ZERO, crab).unwrap();
+        run(&mut entities, &player, &world, 5000);
+        let p = pos(&entities, id);
+        assert!((p - home).xz().length() < 12.0);
+        assert!(p.y > 0.0 && p.y < 1.5);
+
+        // Once the player comes close, they chase and attack them
+        player.set_pos(pos(&entities, id) + Vec3::new(6.0, 0.5, 0.0));
+        let reactor = run(&mut entities, &player, &world, 1);
+        assert!(entities.components::<Mob>()[&id].is_chasing());
+        assert!(!reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::MobStrike { .. })));
+        let reactor = run(&mut entities, &player, &world, 3000);
+        assert!((pos(&entities, id) - player.pos()).length() < 3.0);
+        assert!(reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::MobStrike { .. })));
+
+        // A skittish mob type runs away instead
+        let mut bunny = MobType::from_json(&format!(
+            "[{}]",
+            include_str!("../../core/assets/mobs.json")
+                .lines()
+                .find(|l| l.contains("\"crab\""))
+                .unwrap()
+                .trim()
+                .trim_end_matches(',')
+                .replace("\"id\": \"crab\"", "\"id\": \"bunny\"")
+                .replace("\"aggressive\"", "\"skittish\"")
+        ))
+        .unwrap();
+        let bunny_id = MOB_TYPES.with(|t| {
+            let mut t = t.borrow_mut();
+            t.append(&mut bunny);
+            (t.len() - 1) as u16
+        });
+        entities.clear();
+        let id = Mob::spawn(&mut entities, home, Vec3::ZERO, bunny_id).unwrap();
+        player.set_pos(home + Vec3::new(4.0, 0.5, 0.0));
+        let reactor = run(&mut entities, &player, &world, 1000);
+        assert!((pos(&entities, id) - player.pos()).length() > 6.0);
+        assert!(matches!(
+            entities.components::<Mob>()[&id].state(),
+            MobState::Run(_)
+        ));
+        assert!(!reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::MobStrike { .. })));
+    }
+
+    #[test]
+    fn test_mob_elites_and_bosses() {
+        let world = Chungus::new_flat("stone", 24);
+        let mut entities = EntityStore::new();
+        entities.register::<Mob>();
+        let crab = mob_type_id("crab").unwrap();
+        let mut player = Character::new();
+        player.set_pos(Vec3::new(100.5, 1.0, 0.5));
+
+        // Elites are tougher, bigger, hit harder and may charge at the player
+        let home = Vec3::new(0.5, 0.7, 0.5);
+        let normal = Mob::spawn(&mut entities, home, Vec3::ZERO, crab).unwrap();
+        let elite =
+            Mob::spawn_elite(&mut entities, home + Vec3::X * 8.0, Vec3::ZERO, crab).unwrap();
+        let max_health = |e: &EntityStore, id| e.get::<Health>(id).unwrap().max_health();
+        let size = |e: &EntityStore, id| e.get::<Renderable>(id).unwrap().size;
+        assert!(max_health(&entities, elite) > max_health(&entities, normal));
+        assert!(size(&entities, elite) > size(&entities, normal));
+        MOB_TYPES.with(|t| {
+            let t = &t.borrow()[crab as usize];
+            let mobs = entities.components::<Mob>();
+            assert!(mobs[&elite].is_elite() && !mobs[&normal].is_elite());
+            assert!(mobs[&elite].damage(t, 2) > mobs[&normal].damage(t, 2));
+            assert!(matches!(
+                mobs[&elite].special(t),
+                Some(MobSpecial::Charge { .. })
+            ));
+            assert_eq!(mobs[&normal].special(t), None);
+        });
+        entities.despawn(normal);
+        player.set_pos(pos(&entities, elite) + Vec3::new(8.0, 0.5, 0.0));
+        run(&mut entities, &player, &world, 1);
+        let vel = entities.get::<Physics>(elite).unwrap().vel;
+        assert!(vel.x > 0.1);
+        let reactor = run(&mut entities, &player, &world, 200);
+        assert!(reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::MobStrike { .. })));
+
+        // Shrines summon a single boss, that gets tougher the more it's hurt
+        entities.clear();
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let shrine = IVec3::new(0, -1, 0);
+        let mut stash = MobStash::new();
+        let boss = Mob::summon_boss(&mut entities, &stash, shrine, &mut rng).unwrap();
+        assert!(Mob::summon_boss(&mut entities, &stash, shrine, &mut rng).is_none());
+
+        // Bosses that got stashed while the player was away still count
+        let mut pathfinder = Pathfinder::new();
+        Mob::stash_where(&mut entities, &mut stash, &mut pathfinder, |_| true);
+        assert!(!entities.contains(boss));
+        assert!(Mob::summon_boss(&mut entities, &stash, shrine, &mut rng).is_none());
+        let saved = stash.mobs().next().unwrap().clone();
+        stash.clear();
+        let boss = Mob::restore(&mut entities, &saved).unwrap();
+        player.set_pos(pos(&entities, boss) + Vec3::new(4.0, 0.0, 0.0));
+        let bar = Mob::boss_bar(&entities, player.pos()).unwrap();
+        assert_eq!(bar.name, "Crab King");
+        assert_eq!((bar.phase, bar.health), (0, bar.max_health));
+        assert!(Mob::boss_bar(&entities, Vec3::new(500.0, 0.0, 0.0)).is_none());
+
+        if let Some(h) = entities.components_mut::<Health>().get_mut(&boss) {
+            h.damage(h.max_health() / 2);
+        }
+        let reactor = run(&mut entities, &player, &world, 10);
+        assert_eq!(Mob::boss_bar(&entities, player.pos()).unwrap().phase, 1);
+        assert!(reactor
+            .log()
+            .iter()
+            .any(|m| matches!(m, Message::Explosion { .. })));
+
+        // Close to death it starts throwing pebbles from afar
+        if let Some(h) = entities.components_mut::<Health>().get_mut(&boss) {
+            h.damage(h.max_health() * 2 / 5);
+        }
+        player.set_pos(pos(&entities, boss) + Vec3::new(10.0, 0.0, 0.0));
+        let reactor = run(&mut entities, &player, &world, 400);
+        assert_eq!(Mob::boss_bar(&entities, player.pos()).unwrap().phase, 3);
+        assert!(reactor.log().iter().any(|m| matches!(
+            m,
+            Message::ProjectileSpawn { vel, owner: ProjectileOwner::Entity(o), .. }
+                if *o == boss && vel.x > 0.0
+        )));
+
+        if let Some(h) = entities.components_mut::<Health>().get_mut(&boss) {
+            h.damage(h.max_health());
+        }
+        let reactor = run(&mut entities, &player, &world, 1);
+        assert!(reactor.log().iter().any(|m| matches!(
+            m,
+            Message::MobDied {
+                by_player: false,
+                ..
+            }
+        )));
+        assert!(Mob::boss_bar(&entities, player.pos()).is_none());
+
+        // Only mobs slain by the player count as their kills
+        let id = Mob::spawn(&mut entities, home, Vec3::ZERO, crab).unwrap();
+        let reactor = Reactor::new();
+        crate::hurt_mob(&reactor, &entities, id, player.pos(), i16::MAX, true);
+        let reactor = run(&mut entities, &player, &world, 1);
+        assert!(reactor.log().iter().any(|m| matches!(
+            m,
+            Message::MobDied {
+                by_player: true,
+                ..
+            }
+        )));
+    }
+}
@@ -1,479 +1,533 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
-use anyhow::Result;
-use glam::{Mat4, Vec3, Vec3Swizzles};
+use anyhow::{anyhow, Result};
+use glam::{IVec3, Mat4, Vec3, Vec3Swizzles};
 use glium::Display;
 use rand::prelude::*;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::mem::discriminant;
 use std::rc::Rc;
-use std::{cell::RefCell, time::Instant};
-use wolkenwelten_client::{ClientState, Frustum, RenderInitArgs, RenderPassArgs, VoxelMesh};
+use wolkenwelten_client::{
+    voxel_data, ClientState, Frustum, RenderInitArgs, RenderPassArgs, VoxelMesh,
+};
 use wolkenwelten_core::Character;
-use wolkenwelten_core::{BlockItem, Chungus, Entity, Health, Item, Message, Reactor, SfxId};
-
-thread_local! {
-    pub static MOBS:RefCell<MobList> = RefCell::new(MobList::new());
-}
+use wolkenwelten_core::{
+    block_id, knockback, mob_type_id, path_node, path_waypoint, Aabb, BossBar, Chungus, EntityId,
+    EntityStore, Health, LootContext, Message, MobBossPhase, MobElite, MobSpecial, MobStash,
+    MobType, MobTypeId, PathConfig, Pathfinder, Physics, ProjectileOwner, Reactor, Renderable,
+    SfxId, StatusEffect, StatusEffects, Transform, GAME_LOG, KNOCKBACK_PER_DAMAGE, MOB_TYPES,
+    MS_PER_TICK, PATH_NODES_PER_TICK,
+};
+
+mod ai;
+mod spawner;
+pub use ai::{MobContext, MOB_FLOCK_RANGE};
+pub use spawner::*;
 
 const MOB_SIZE: f32 = 0.4;
 const MOB_ACCELERATION: f32 = 0.01;
 const MOB_STOP_RATE: f32 = MOB_ACCELERATION * 2.0;
-const MOB_STOP_FIGHTING_DISTANCE: f32 = 24.0;
-const MOB_START_CHASING_DISTANCE: f32 = 12.0;
 
-const COL_WIDTH: f32 = 0.8;
-const COL_DEPTH: f32 = 0.8;
+/// How close a mob has to get to a waypoint before heading for the next one
+const MOB_WAYPOINT_DISTANCE: f32 = 0.4;
+/// How many blocks the player may move before a chasing mob looks for a new path
+const MOB_REPATH_DISTANCE: i32 = 1;
+
+/// Bosses further away than this don't show up in the HUD
+pub const BOSS_BAR_DISTANCE: f32 = 48.0;
+
+const COL_WIDTH: f32 = 0.5;
+const COL_DEPTH: f32 = 0.5;
 
-const COL_POINT_TOP: Vec3 = Vec3::new(0.0, MOB_SIZE, 0.0);
-const COL_POINT_BOTTOM: Vec3 = Vec3::new(0.0, -MOB_SIZE * 1.5, 0.0);
-const COL_POINT_LEFT: Vec3 = Vec3::new(-COL_WIDTH, 0.0, 0.0);
-const COL_POINT_RIGHT: Vec3 = Vec3::new(COL_WIDTH, 0.0, 0.0);
-const COL_POINT_FRONT: Vec3 = Vec3::new(0.0, 0.0, COL_DEPTH);
-const COL_POINT_BACK: Vec3 = Vec3::new(0.0, 0.0, -COL_DEPTH);
+/// The collision box, relative to the position of the mob
+const COL_BOX: Aabb = Aabb::new(
+    Vec3::new(-COL_WIDTH, -MOB_SIZE * 1.5, -COL_DEPTH),
+    Vec3::new(COL_WIDTH, MOB_SIZE, COL_DEPTH),
+);
 
-#[derive(Copy, Clone, Debug)]
+/// What a mob is doing right now, set by the actions of its behavior tree
+/// and used to pick an animation. Contains the age of the mob when the
+/// state was entered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum MobState {
-    Idle(Instant),
-    Walk(Instant),
-    Run(Instant),
-    WalkBack(Instant),
-    TurnRight(Instant),
-    TurnLeft(Instant),
-    ChasePlayer(Instant),
-    FightPlayer(Instant),
-    InstantAttackPlayer(Instant),
-    Dance(Instant),
+    Idle(u64),
+    Walk(u64),
+    Run(u64),
+    ChasePlayer(u64),
+    FightPlayer(u64),
+    InstantAttackPlayer(u64),
 }
 
 impl Default for MobState {
     fn default() -> Self {
-        Self::Idle(Instant::now())
+        Self::Idle(0)
+    }
+}
+
+impl MobState {
+    #[inline]
+    fn since(self) -> u64 {
+        match self {
+            Self::Idle(t)
+            | Self::Walk(t)
+            | Self::Run(t)
+            | Self::ChasePlayer(t)
+            | Self::FightPlayer(t)
+            | Self::InstantAttackPlayer(t) => t,
+        }
     }
 }
 
+/// The AI of a mob, position, velocity and health are separate components
 #[derive(Clone, Debug)]
 pub struct Mob {
-    pos: Vec3,
-    vel: Vec3,
-    rot: Vec3,
+    kind: MobTypeId,
     movement: Vec3,
-    model_index: i32,
     state: MobState,
-    health: Health,
-    cooldown: Instant,
+    effects: StatusEffects,
+    /// How many ticks the mob has been around for, used instead of wall clock time
+    age: u64,
+    /// Age of the last lunge
+    cooldown: u64,
+    /// Where the mob spawned, it won't wander off too far from here
+    home: Vec3,
+    /// Age when the mob last got hit by the player
+    provoked: Option<u64>,
+    /// Where the mob is strolling to and until when it may take to get there
+    wander: Option<(Vec3, u64)>,
+    /// Don't start wandering again before this age
+    rest_until: u64,
+    /// Path nodes still to walk through, the next one being last
+    path: Vec<IVec3>,
+    /// Where the current path leads to
+    path_goal: Option<IVec3>,
+    /// Whether this is the tougher variant of its type, see MobType::elite
+    elite: bool,
+    /// Age when the special attack was last used
+    special_at: Option<u64>,
+    /// Charging at the player until this age
+    charge_until: u64,
+    /// Boss phase, 0 being the initial one
+    phase: usize,
+    /// Whether the last damage taken came from the player, only those kills count
+    hit_by_player: bool,
+}
+
+/// The components of a mob that get borrowed from the entity store while it's ticking
+pub struct MobBody<'a> {
+    pub transform: &'a mut Transform,
+    pub physics: &'a mut Physics,
+    pub health: &'a mut Health,
+}
+
+fn walk_direction(rot: Vec3) -> Vec3 {
+    Vec3::new(
+        ((-rot.y - 90.0) * PI / 180.0).cos(),
+        0.0,
+        ((-rot.y - 90.0) * PI / 180.0).sin(),
+    )
+    .normalize()
 }
 
-fn mob_load_meshes(display: &Display) -> Result<Vec<Vec<VoxelMesh>>> {
-    Ok(vec![vec![
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/idle_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/idle_2.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/walk_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/idle_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/walk_2.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/idle_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/idle_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/attack_1.vox"))?,
-        VoxelMesh::from_vox_data(display, include_bytes!("../assets/crab/attack_2.vox"))?,
-    ]])
+fn is_underwater_point(world: &Chungus, pos: Vec3) -> bool {
+    if let Some(fluid) = world.get_fluid_block(pos.as_ivec3()) {
+        fluid != 0
+    } else {
+        false
+    }
+}
+
+/// Load every model used by one of the MOB_TYPES, keyed by path within client/assets/
+fn mob_load_meshes(display: &Display) -> Result<HashMap<String, VoxelMesh>> {
+    let mut ret = HashMap::new();
+    MOB_TYPES.with(|types| {
+        for t in types.borrow().iter() {
+            for model in t.models() {
+                if ret.contains_key(model) {
+                    continue;
+                }
+                let Some(data) = voxel_data(model) else {
+                    return Err(anyhow!("Mob {:?} uses unknown model {:?}", t.id(), model));
+                };
+                ret.insert(model.to_string(), VoxelMesh::from_vox_data(display, data)?);
+            }
+        }
+        Ok(ret)
+    })
 }
 
 impl Mob {
-    pub fn new(pos: Vec3, mut rot: Vec3, model_index: i32) -> Self {
-        let mut ent = Entity::new();
-        ent.set_pos(pos);
-        rot.x = 0.0;
-        rot.z = 0.0;
-        let vel = Vec3::ZERO;
-        ent.set_size(2.0);
+    pub fn new(kind: MobTypeId, home: Vec3) -> Self {
         Self {
-            pos,
-            rot,
-            vel,
+            kind,
             movement: Vec3::ZERO,
-            model_index,
-            state: MobState::Walk(Instant::now()),
-            health: Health::new(12),
-            cooldown: Instant::now(),
+            state: MobState::default(),
+            effects: StatusEffects::new(),
+            age: 0,
+            cooldown: 0,
+            home,
+            provoked: None,
+            wander: None,
+            rest_until: 0,
+            path: vec![],
+            path_goal: None,
+            elite: false,
+            special_at: None,
+            charge_until: 0,
+            phase: 0,
+            hit_by_player: false,
         }
     }
+
+    /// Spawn a mob of one of the MOB_TYPES, returns None for unknown types.
+    /// Mobs bigger than usual get lifted so that their feet end up where
+    /// those of a normal sized one would be.
     #[inline]
-    pub fn pos(&self) -> Vec3 {
-        self.pos
+    pub fn spawn(
+        entities: &mut EntityStore,
+        pos: Vec3,
+        rot: Vec3,
+        kind: MobTypeId,
+    ) -> Option<EntityId> {
+        Self::spawn_with(entities, pos, rot, kind, false)
     }
+
+    /// Spawn the elite variant of a mob type, or a normal one if it has none
     #[inline]
-    pub fn rot(&self) -> Vec3 {
-        self.rot
+    pub fn spawn_elite(
+        entities: &mut EntityStore,
+        pos: Vec3,
+        rot: Vec3,
+        kind: MobTypeId,
+    ) -> Option<EntityId> {
+        Self::spawn_with(entities, pos, rot, kind, true)
+    }
+
+    fn spawn_with(
+        entities: &mut EntityStore,
+        pos: Vec3,
+        mut rot: Vec3,
+        kind: MobTypeId,
+        elite: bool,
+    ) -> Option<EntityId> {
+        let (mob, health, size, scale) = MOB_TYPES.with(|types| {
+            let types = types.borrow();
+            let t = types.get(kind as usize)?;
+            let mut mob = Mob::new(kind, pos);
+            mob.elite = elite && t.elite().is_some();
+            let health = mob
+                .elite_data(t)
+                .map(|e| (t.health() as f32 * e.health).round() as i16)
+                .unwrap_or(t.health());
+            let scale = mob.scale(t);
+            Some((mob, health.max(1), t.size() * scale, scale))
+        })?;
+        rot.x = 0.0;
+        rot.z = 0.0;
+        let col_box = COL_BOX.scale(scale);
+        let pos = pos + Vec3::new(0.0, COL_BOX.min.y - col_box.min.y, 0.0);
+        let id = entities
+            .spawn()
+            .with(Transform::new(pos).with_rot(rot))
+            .with(Physics::new(col_box))
+            .with(Health::new(health))
+            .with(Renderable::new(size).with_model(kind as u32))
+            .with(mob)
+            .id();
+        Some(id)
+    }
+
+    /// Index into MOB_TYPES
+    #[inline]
+    pub fn kind(&self) -> MobTypeId {
+        self.kind
     }
     #[inline]
-    pub fn set_rot(&mut self, rot: Vec3) {
-        self.rot = rot;
+    pub fn state(&self) -> MobState {
+        self.state
     }
     #[inline]
-    pub fn set_vel(&mut self, vel: Vec3) {
-        self.vel = vel;
+    pub fn home(&self) -> Vec3 {
+        self.home
     }
     #[inline]
-    pub fn model_index(&self) -> i32 {
-        self.model_index
+    pub fn is_elite(&self) -> bool {
+        self.elite
     }
     #[inline]
-    pub fn set_state(&mut self, state: MobState) {
-        self.state = state;
+    pub fn phase(&self) -> usize {
+        self.phase
     }
     #[inline]
-    pub fn set_idle_state(&mut self) {
-        self.state = MobState::Idle(Instant::now());
+    pub fn effects(&self) -> &StatusEffects {
+        &self.effects
+    }
+    #[inline]
+    pub fn apply_effect(&mut self, effect: StatusEffect) {
+        self.effects.apply(effect);
     }
 
     #[inline]
-    pub fn may_instant_attack(&self) -> bool {
-        self.cooldown.elapsed().as_millis() > 1200
+    fn elite_data<'a>(&self, mob_type: &'a MobType) -> Option<&'a MobElite> {
+        mob_type.elite().filter(|_| self.elite)
     }
 
     #[inline]
-    pub fn cooldown(&mut self) {
-        self.cooldown = Instant::now();
+    fn boss_phase<'a>(&self, mob_type: &'a MobType) -> Option<&'a MobBossPhase> {
+        mob_type.boss().and_then(|b| b.phase_data(self.phase))
     }
 
-    pub fn anime_index(&self) -> usize {
-        match self.state {
-            MobState::InstantAttackPlayer(_) => 8,
-            MobState::Dance(t) | MobState::FightPlayer(t) => {
-                6 + (t.elapsed().as_millis() as usize / 200) % 3
-            }
-            MobState::Idle(t) => (t.elapsed().as_millis() as usize / 1000) % 2,
-            MobState::TurnLeft(t)
-            | MobState::TurnRight(t)
-            | MobState::WalkBack(t)
-            | MobState::Walk(t) => 2 + (t.elapsed().as_millis() as usize / 200) % 4,
-            MobState::ChasePlayer(t) | MobState::Run(t) => {
-                2 + (t.elapsed().as_millis() as usize / 100) % 4
-            }
-        }
+    /// How big the mob is compared to its .vox files
+    pub fn scale(&self, mob_type: &MobType) -> f32 {
+        mob_type.scale() * self.elite_data(mob_type).map(|e| e.size).unwrap_or(1.0)
     }
 
-    fn player_aggresive(&mut self, player: &Character) {
-        if player.no_clip() || player.is_dead() {
-            return;
-        }
-        match self.state {
-            MobState::InstantAttackPlayer(_)
-            | MobState::ChasePlayer(_)
-            | MobState::FightPlayer(_) => return,
-            _ => (),
-        }
-        let player_pos = player.pos();
-        let diff = (player_pos - self.pos()).xz();
-        let distance = diff.length_squared();
-        if distance > MOB_START_CHASING_DISTANCE * MOB_START_CHASING_DISTANCE {
-            return;
-        }
-        self.set_state(MobState::ChasePlayer(Instant::now()));
+    /// Scale the damage of an attack, elites and angry bosses hit harder
+    pub fn damage(&self, mob_type: &MobType, damage: i16) -> i16 {
+        let elite = self.elite_data(mob_type).map(|e| e.damage).unwrap_or(1.0);
+        let phase = self.boss_phase(mob_type).map(|p| p.damage).unwrap_or(1.0);
+        (damage as f32 * elite * phase).round() as i16
     }
 
-    pub fn turn_towards(&mut self, goal: Vec3) {
-        let a = goal.y - self.rot().y;
-        let b = goal.y + 360.0 - self.rot().y;
-        let mut rot = self.rot();
-        let c = if a.abs() < b.abs() { a } else { b };
-        if c > 0.0 {
-            rot.y += 1.0;
+    #[inline]
+    pub fn speed_multiplier(&self, mob_type: &MobType) -> f32 {
+        self.boss_phase(mob_type).map(|p| p.speed).unwrap_or(1.0)
+    }
+
+    /// The special attack of the current boss phase, or of the elite variant
+    pub fn special(&self, mob_type: &MobType) -> Option<MobSpecial> {
+        self.boss_phase(mob_type)
+            .and_then(|p| p.special)
+            .or_else(|| self.elite_data(mob_type).and_then(|e| e.special))
+    }
+
+    /// Move on to the next boss phase once health has dropped far enough,
+    /// returns the new phase if it changed.
+    pub fn update_phase(&mut self, mob_type: &MobType, health: &Health) -> Option<usize> {
+        let boss = mob_type.boss()?;
+        let phase = boss.phase(health.health() as f32 / health.max_health() as f32);
+        if phase > self.phase {
+            self.phase = phase;
+            Some(phase)
         } else {
-            rot.y -= 1.0;
+            None
         }
-        self.set_rot(rot);
     }
 
-    pub fn walk_direction(&self) -> Vec3 {
-        let a = self.rot;
-        Vec3::new(
-            ((-a.y - 90.0) * PI / 180.0).cos(),
-            0.0,
-            ((-a.y - 90.0) * PI / 180.0).sin(),
-        )
-        .normalize()
+    /// Switch to another state, staying in the same one keeps animations going
+    fn enter(&mut self, state: fn(u64) -> MobState) {
+        let state = state(self.age);
+        if discriminant(&state) != discriminant(&self.state) {
+            self.state = state;
+        }
+    }
+
+    /// Remember that the player hurt us, behaviors may hold a grudge
+    #[inline]
+    pub fn provoke(&mut self) {
+        self.provoked = Some(self.age);
     }
 
-    pub fn direction(&self) -> Vec3 {
-        let a = self.rot;
-        Vec3::new(
-            ((a.x - 90.0) * PI / 180.0).cos() * (-a.y * PI / 180.0).cos(),
-            (-a.y * PI / 180.0).sin(),
-            ((a.x - 90.0) * PI / 180.0).sin() * (-a.y * PI / 180.0).cos(),
+    #[inline]
+    pub fn is_chasing(&self) -> bool {
+        matches!(
+            self.state,
+            MobState::ChasePlayer(_) | MobState::FightPlayer(_) | MobState::InstantAttackPlayer(_)
         )
     }
 
-    pub fn to_entity(&self) -> Entity {
-        Entity {
-            pos: self.pos,
-            rot: self.rot,
-            vel: self.vel,
-            size: 2.0,
-        }
+    /// Chasing or running away, either way the player has been noticed
+    #[inline]
+    pub fn is_alert(&self) -> bool {
+        self.is_chasing() || matches!(self.state, MobState::Run(_))
     }
 
-    pub fn would_collide_at(&self, world: &Chungus, pos: Vec3) -> bool {
-        world.is_solid(pos + Vec3::new(-MOB_SIZE, 0.0, 0.0))
-            | world.is_solid(pos + Vec3::new(MOB_SIZE, 0.0, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, -MOB_SIZE, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, MOB_SIZE, 0.0))
-            | world.is_solid(pos + Vec3::new(0.0, 0.0, -MOB_SIZE))
-            | world.is_solid(pos + Vec3::new(0.0, 0.0, MOB_SIZE))
+    /// Follow a new path, given as returned by the pathfinder
+    pub fn set_path(&mut self, mut path: Vec<IVec3>) {
+        path.reverse();
+        self.path = path;
     }
 
-    pub fn is_colliding(&self, world: &Chungus) -> bool {
-        self.would_collide_at(world, self.pos())
+    pub fn clear_path(&mut self) {
+        self.path.clear();
+        self.path_goal = None;
     }
 
-    fn is_underwater_point(world: &Chungus, pos: Vec3) -> bool {
-        if let Some(fluid) = world.get_fluid_block(pos.as_ivec3()) {
-            fluid != 0
-        } else {
-            false
+    /// Where to walk next, skipping every waypoint we've already reached
+    fn next_waypoint(&mut self, pos: Vec3) -> Option<Vec3> {
+        while let Some(node) = self.path.last() {
+            let waypoint = path_waypoint(*node);
+            if (waypoint - pos).xz().length_squared()
+                > MOB_WAYPOINT_DISTANCE * MOB_WAYPOINT_DISTANCE
+            {
+                return Some(waypoint);
+            }
+            self.path.pop();
         }
+        None
     }
 
-    fn is_solid_pillar(&self, pos: Vec3, world: &Chungus) -> bool {
-        world.is_solid(pos)
-            || world.is_solid(pos + Vec3::new(0.0, -0.4, 0.0))
-            || world.is_solid(pos + Vec3::new(0.0, 0.8, 0.0))
+    /// Request a new path whenever the goal moved too far away from where
+    /// the current one leads to.
+    fn update_path(&mut self, id: EntityId, aabb: &Aabb, goal: IVec3, pathfinder: &mut Pathfinder) {
+        if let Some(result) = pathfinder.take_result(id) {
+            self.set_path(result.unwrap_or_default());
+        }
+        if !self.is_chasing() {
+            if self.path_goal.is_some() {
+                pathfinder.cancel(id);
+                self.clear_path();
+            }
+            return;
+        }
+        let moved = self
+            .path_goal
+            .map(|g| (g - goal).abs().max_element() > MOB_REPATH_DISTANCE)
+            .unwrap_or(true);
+        if moved && !pathfinder.is_searching(id) {
+            self.path_goal = Some(goal);
+            pathfinder.request(id, PathConfig::default(), path_node(aabb), goal);
+        }
     }
 
-    pub fn is_underwater(&self, world: &Chungus) -> bool {
-        Self::is_underwater_point(world, self.pos() + Vec3::new(0.0, -0.8, 0.0))
+    #[inline]
+    pub fn may_instant_attack(&self, mob_type: &MobType) -> bool {
+        (self.age - self.cooldown) * MS_PER_TICK > mob_type.attack().lunge_cooldown
     }
 
     #[inline]
-    pub fn may_jump(&self, world: &Chungus) -> bool {
-        world.is_solid(self.pos + COL_POINT_BOTTOM)
+    pub fn cooldown(&mut self) {
+        self.cooldown = self.age;
     }
 
-    pub fn tick_physics(&mut self, world: &Chungus) {
-        if !world.is_loaded(self.pos) {
-            return; // Just freeze the mob until we have loaded the area, this shouldn't happen if at all possible
+    /// The model to draw right now, a path within assets/
+    pub fn frame<'a>(&self, mob_type: &'a MobType) -> &'a str {
+        let a = mob_type.animations();
+        let ms = (self.age - self.state.since()) as u128 * MS_PER_TICK as u128;
+        match self.state {
+            MobState::InstantAttackPlayer(_) => a.lunge().frame(ms),
+            MobState::FightPlayer(_) => a.attack.frame(ms),
+            MobState::Idle(_) => a.idle.frame(ms),
+            MobState::Walk(_) => a.walk.frame(ms),
+            MobState::ChasePlayer(_) | MobState::Run(_) => a.walk.frame(ms * 2),
         }
-        let underwater = self.is_underwater(world);
+    }
 
-        let accel = if self.movement.xz().length() > 0.01 {
-            MOB_ACCELERATION
+    pub fn turn_towards(transform: &mut Transform, goal: Vec3) {
+        let a = goal.y - transform.rot.y;
+        let b = goal.y + 360.0 - transform.rot.y;
+        let c = if a.abs() < b.abs() { a } else { b };
+        if c > 0.0 {
+            transform.rot.y += 1.0;
         } else {
-            MOB_STOP_RATE
-        };
-        let accel = if underwater { accel * 0.5 } else { accel };
-
-        self.vel.x = self.vel.x * (1.0 - accel) + (self.movement.x * 0.01) * accel;
-        self.vel.z = self.vel.z * (1.0 - accel) + (self.movement.z * 0.01) * accel;
+            transform.rot.y -= 1.0;
+        }
+    }
 
-        self.vel.y -= if underwater { 0.0001 } else { 0.0005 };
-        let old = self.vel;
+    pub fn is_underwater(world: &Chungus, pos: Vec3) -> bool {
+        is_underwater_point(world, pos + Vec3::new(0.0, -0.8, 0.0))
+    }
 
-        if underwater {
-            self.vel *= 0.99;
-            self.vel.y *= 0.997;
+    /// Jump up whatever is in the way, following the path if there is one
+    fn jump(&mut self, body: &mut MobBody, world: &Chungus, rng: &mut XorShiftRng) {
+        if self.movement.length_squared() < 0.01 {
+            return;
         }
-
-        if self.is_solid_pillar(self.pos + COL_POINT_LEFT, world) {
-            self.vel.x = self.vel.x.max(0.0);
+        let pos = body.transform.pos;
+        let physics = &mut body.physics;
+        let aabb = physics.aabb(pos);
+        if !world.is_grounded(&aabb) {
+            return;
         }
-        if self.is_solid_pillar(self.pos + COL_POINT_RIGHT, world) {
-            self.vel.x = self.vel.x.min(0.0);
+        let vel = physics.vel;
+        // Follow the path if there is one, otherwise jump whenever we're stuck
+        let climb = self.path.last().map(|n| n.y > path_node(&aabb).y);
+        let wants_jump = climb.unwrap_or_else(|| {
+            vel.length_squared() < self.movement.length_squared() * 0.000002 && rng.gen_ratio(1, 50)
+        });
+        if wants_jump && !physics.would_collide_at(world, pos + Vec3::new(0.0, 1.0, 0.0)) {
+            let accel = 0.03;
+            physics.vel = Vec3::new(
+                vel.x * (1.0 - accel) + (self.movement.x * accel),
+                0.04,
+                vel.z * (1.0 - accel) + (self.movement.z * accel),
+            );
         }
+    }
 
-        if world.is_solid(self.pos + COL_POINT_BOTTOM) {
-            self.vel.y = self.vel.y.max(0.0);
-        }
-        if world.is_solid(self.pos + COL_POINT_TOP) {
-            self.vel.y = self.vel.y.min(0.0);
+    pub fn tick_physics(&mut self, mob_type: &MobType, body: &mut MobBody, world: &Chungus) {
+        let pos = body.transform.pos;
+        if !world.is_loaded(pos) {
+            return; // Just freeze the mob until we have loaded the area, this shouldn't happen if at all possible
         }
+        let underwater = Self::is_underwater(world, pos);
+        let vel = &mut body.physics.vel;
 
-        if self.is_solid_pillar(self.pos + COL_POINT_FRONT, world) {
-            self.vel.z = self.vel.z.min(0.0);
-        }
-        if self.is_solid_pillar(self.pos + COL_POINT_BACK, world) {
-            self.vel.z = self.vel.z.max(0.0);
-        }
+        let haste = self.effects.speed_multiplier();
+        let accel = if self.movement.xz().length() > 0.01 {
+            MOB_ACCELERATION * haste
+        } else {
+            MOB_STOP_RATE
+        };
+        let accel = if underwater { accel * 0.5 } else { accel };
 
-        let force = (old - self.vel).length();
-        if force > 0.05 {
-            let amount = (force * 14.0) as i16;
-            if amount > 0 {
-                let damage = amount * amount;
-                self.health.damage(damage);
-            }
-        }
+        let speed = 0.01 * haste * mob_type.speed() * self.speed_multiplier(mob_type);
+        vel.x = vel.x * (1.0 - accel) + (self.movement.x * speed) * accel;
+        vel.z = vel.z * (1.0 - accel) + (self.movement.z * speed) * accel;
 
-        let len = self.vel.length();
+        vel.y -= if underwater { 0.0001 } else { 0.0005 };
+        let len = vel.length();
         if len > 0.5 {
-            self.vel *= 1.0 - (len - 0.2).clamp(0.0001, 1.0);
+            *vel *= 1.0 - (len - 0.2).clamp(0.0001, 1.0);
         }
-        self.pos += self.vel;
-    }
+        let old = *vel;
 
-    #[inline]
-    pub fn tick(
-        &mut self,
-        world: &Chungus,
-        rng: &mut XorShiftRng,
-        player: &Character,
-        reactor: &Reactor<Message>,
-    ) {
-        if !world.is_loaded(self.pos) {
-            return; // Just freeze the mob until we have loaded the area, this shouldn't happen if at all possible
+        if underwater {
+            *vel *= 0.99;
+            vel.y *= 0.997;
         }
 
-        self.player_aggresive(player);
-        match self.state {
-            MobState::Idle(_t) => {
-                if rng.gen_range(0..10000) == 0 {
-                    self.state = MobState::Run(Instant::now())
-                }
-                if rng.gen_range(0..10000) == 0 {
-                    self.state = MobState::WalkBack(Instant::now())
-                }
-                if rng.gen_range(0..5000) == 0 {
-                    self.state = MobState::Walk(Instant::now())
-                }
-                if rng.gen_range(0..500) == 0 {
-                    self.state = MobState::TurnLeft(Instant::now())
-                }
-                if rng.gen_range(0..500) == 0 {
-                    self.state = MobState::TurnRight(Instant::now())
-                }
-                self.movement = Vec3::ZERO;
-            }
-            MobState::Run(_t) => {
-                if rng.gen_range(0..400) == 0 {
-                    self.set_idle_state();
-                };
-                self.movement = self.walk_direction() * 2.0;
-            }
-            MobState::Walk(_t) => {
-                if rng.gen_range(0..4000) == 0 {
-                    self.set_idle_state();
-                };
-                self.movement = self.walk_direction() * 1.5;
-            }
-            MobState::Dance(t) => {
-                if t.elapsed().as_millis() > 500 {
-                    self.set_idle_state();
-                };
-                self.set_rot(self.rot() - Vec3::new(0.0, 0.1, 0.0));
-                self.movement = self.walk_direction() * -1.15;
-            }
-            MobState::WalkBack(_t) => {
-                if rng.gen_range(0..1000) == 0 {
-                    self.set_idle_state();
-                };
-                self.movement = self.walk_direction() * -1.15;
-            }
-            MobState::TurnLeft(_t) => {
-                if rng.gen_range(0..100) == 0 {
-                    self.set_idle_state();
-                };
-                self.set_rot(self.rot() - Vec3::new(0.0, 0.1, 0.0));
-                self.movement = Vec3::ZERO;
-            }
-            MobState::TurnRight(_t) => {
-                if rng.gen_range(0..100) == 0 {
-                    self.set_idle_state();
-                };
-                self.set_rot(self.rot() + Vec3::new(0.0, 0.1, 0.0));
-                self.movement = Vec3::ZERO;
-            }
-            MobState::InstantAttackPlayer(t) => {
-                if t.elapsed().as_millis() > 200 {
-                    self.set_state(MobState::FightPlayer(Instant::now()));
-                }
-                self.movement = Vec3::ZERO;
-            }
-            MobState::FightPlayer(_t) | MobState::ChasePlayer(_t) => {
-                let player_pos = player.pos();
-                let diff = player_pos - self.pos();
-                let distance = diff.length_squared();
-                if player.is_dead() {
-                    self.set_state(MobState::Dance(Instant::now()));
-                } else if distance > MOB_STOP_FIGHTING_DISTANCE * MOB_STOP_FIGHTING_DISTANCE {
-                    self.set_idle_state();
-                } else {
-                    let diff_2d = (player_pos - self.pos()).xz();
-                    let deg = diff_2d.y.atan2(diff_2d.x).to_degrees();
-                    let rot = Vec3::new(0.0, -deg - 90.0, 0.0);
-                    self.turn_towards(rot);
-                    self.movement = Vec3::ZERO;
-                    if distance > 2.0 * 2.0 {
-                        self.movement = self.walk_direction() * 1.2;
-                        if let MobState::FightPlayer(_) = self.state {
-                            self.set_state(MobState::ChasePlayer(Instant::now()));
-                        }
-                    } else if let MobState::ChasePlayer(_) = self.state {
-                        if self.may_instant_attack() {
-                            self.cooldown();
-                            reactor.defer(Message::MobStrike {
-                                pos: self.pos(),
-                                damage: 1,
-                            });
-                            self.set_state(MobState::InstantAttackPlayer(Instant::now()));
-                        } else {
-                            self.set_state(MobState::FightPlayer(Instant::now()));
-                        }
-                    } else if let MobState::FightPlayer(t) = self.state {
-                        if distance > 1.3 * 1.3 {
-                            self.movement = self.walk_direction() * 1.2;
-                        }
-                        if t.elapsed().as_millis() > 600 {
-                            reactor.defer(Message::MobStrike {
-                                pos: self.pos(),
-                                damage: 3,
-                            });
-                            self.set_state(MobState::FightPlayer(Instant::now()));
-                        }
-                    }
-                }
-            }
-        };
+        let collision = world.sweep_aabb(&body.physics.aabb(pos), body.physics.vel);
+        body.physics.vel = collision.slide(body.physics.vel);
 
-        match self.state {
-            MobState::Run(_) | MobState::ChasePlayer(_) => {
-                if self.may_jump(world) {
-                    let pos = self.pos() + Vec3::new(0.0, 1.0, 0.0);
-                    if !self.would_collide_at(world, pos)
-                        && self.vel.length_squared() < self.movement.length_squared() * 0.000002
-                        && rng.gen_ratio(1, 50)
-                    {
-                        self.vel.y = 0.04;
-                        let accel = 0.03;
-                        self.vel.x = self.vel.x * (1.0 - accel) + (self.movement.x * accel);
-                        self.vel.z = self.vel.z * (1.0 - accel) + (self.movement.z * accel);
-                    }
-                }
+        let force = (old - body.physics.vel).length();
+        if force > 0.05 {
+            let amount = (force * 14.0) as i16;
+            if amount > 0 {
+                body.health.damage(amount.saturating_mul(amount));
+                self.hit_by_player = false;
             }
-            _ => (),
         }
+        body.transform.pos += collision.movement;
+    }
 
-        self.tick_physics(world);
+    /// Where to draw the model of a mob, scale being relative to its .vox files
+    fn model_matrix(transform: &Transform, scale: f32) -> Mat4 {
+        let rot = transform.rot;
+        let pos = transform.pos + Vec3::new(0.0, -8.0 / 32.0 * scale, 0.0);
+        let model = Mat4::from_scale(Vec3::splat(scale / 16.0));
+        let model = Mat4::from_rotation_x(rot.x.to_radians()) * model;
+        let model = Mat4::from_rotation_y(rot.y.to_radians()) * model;
+        Mat4::from_translation(pos) * model
     }
 
     fn draw(
-        &self,
+        model: &Mat4,
         frame: &mut glium::Frame,
         fe: &ClientState,
-        meshes: &[VoxelMesh],
+        mesh: &VoxelMesh,
         view: &Mat4,
         projection: &Mat4,
         color_alpha: f32,
     ) -> Result<()> {
-        let rot = self.rot();
-        let pos = self.pos() + Vec3::new(0.0, -8.0 / 32.0, 0.0);
-        let model = Mat4::from_scale(Vec3::new(1.0 / 16.0, 1.0 / 16.0, 1.0 / 16.0));
-        let model = Mat4::from_rotation_x(rot.x.to_radians()) * model;
-        let model = Mat4::from_rotation_y(rot.y.to_radians()) * model;
-        let model = Mat4::from_translation(pos) * model;
         let vp = projection.mul_mat4(view);
-        let mvp = vp.mul_mat4(&model);
+        let mvp = vp.mul_mat4(model);
 
-        meshes[self.anime_index()].draw(
+        mesh.draw(
             frame,
             fe.block_indeces(),
             &fe.shaders.voxel,
@@ -483,76 +537,319 @@ impl Mob {
     }
 }
 
-#[derive(Clone, Default, Debug)]
-pub struct MobList {
-    mobs: Vec<Mob>,
-}
-
-impl MobList {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn clear(&mut self) {
-        self.mobs.clear();
-    }
-
-    pub fn add(&mut self, pos: Vec3, rot: Vec3, model_index: i32) {
-        self.mobs.push(Mob::new(pos, rot, model_index));
-    }
-
-    #[inline]
-    pub fn iter(&self) -> std::slice::Iter<Mob> {
-        self.mobs.iter()
-    }
-
-    #[inline]
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Mob> {
-        self.mobs.iter_mut()
-    }
-
+impl Mob {
+    /// Tick every mob, the ones that died get removed
     pub fn tick_all(
-        &mut self,
+        entities: &mut EntityStore,
         reactor: &Reactor<Message>,
         player: &Character,
         world: &Chungus,
         rng: &mut XorShiftRng,
+        pathfinder: &mut Pathfinder,
     ) {
-        let player_pos = player.pos();
-        self.mobs.retain_mut(|m| {
-            m.tick(world, rng, player, reactor);
-            let dist = m.pos() - player_pos;
-            let dd = dist.x * dist.x + dist.y * dist.y + dist.z * dist.z;
-            if m.health.is_dead() {
-                let item = Item::Block(BlockItem::new(18, rng.gen_range(1..=3)));
-                let pos = m.pos();
-                reactor.defer(Message::ItemDropNew { pos, item });
-                reactor.defer(Message::MobDied { pos });
+        let player_node = path_node(&player.aabb());
+        let loot = LootContext::from_character(player);
+        let mut gone = vec![];
+        MOB_TYPES.with(|types| {
+            let types = types.borrow();
+            let mut mobs = entities.components_mut::<Mob>();
+            let mut transforms = entities.components_mut::<Transform>();
+            let mut physics = entities.components_mut::<Physics>();
+            let mut health = entities.components_mut::<Health>();
+            let everyone: Vec<(EntityId, MobTypeId, Vec3)> = mobs
+                .iter()
+                .filter_map(|(id, m)| transforms.get(id).map(|t| (*id, m.kind, t.pos)))
+                .collect();
+            for (id, m) in mobs.iter_mut() {
+                let (Some(mob_type), Some(transform), Some(physics), Some(health)) = (
+                    types.get(m.kind as usize),
+                    transforms.get_mut(id),
+                    physics.get_mut(id),
+                    health.get_mut(id),
+                ) else {
+                    continue;
+                };
+                let pos = transform.pos;
+                let flock: Vec<Vec3> = everyone
+                    .iter()
+                    .filter(|(o, kind, p)| {
+                        o != id
+                            && *kind == m.kind
+                            && (*p - pos).length_squared() < MOB_FLOCK_RANGE * MOB_FLOCK_RANGE
+                    })
+                    .map(|(_, _, p)| *p)
+                    .collect();
+                let aabb = physics.aabb(pos);
+                m.update_path(*id, &aabb, player_node, pathfinder);
+                let mut ctx = MobContext {
+                    id: *id,
+                    mob: m,
+                    mob_type,
+                    body: MobBody {
+                        transform,
+                        physics,
+                        health,
+                    },
+                    world,
+                    player,
+                    rng,
+                    reactor,
+                    flock: &flock,
+                };
+                ctx.tick();
+                let pos = ctx.body.transform.pos;
+                if ctx.body.health.is_dead() {
+                    for item in mob_type.roll_loot(&loot, ctx.rng) {
+                        reactor.defer(Message::ItemDropNew { pos, item });
+                    }
+                    if mob_type.boss().is_some() {
+                        let text = format!("The {} has been defeated", mob_type.name());
+                        GAME_LOG.with(|log| log.borrow_mut().push(text));
+                    }
+                    reactor.defer(Message::MobDied {
+                        pos,
+                        by_player: ctx.mob.hit_by_player,
+                    });
+                    gone.push(*id);
+                } else if ctx.mob.update_phase(mob_type, ctx.body.health).is_some() {
+                    let text = format!("The {} grows furious", mob_type.name());
+                    GAME_LOG.with(|log| log.borrow_mut().push(text));
+                }
             }
-            m.health.is_alive() && dd < (256.0 * 256.0)
         });
+        gone.into_iter().for_each(|id| {
+            pathfinder.cancel(id);
+            entities.despawn(id);
+        });
+        pathfinder.tick(world, PATH_NODES_PER_TICK);
+    }
+
+    /// What the HUD should show about the closest living boss near pos
+    pub fn boss_bar(entities: &EntityStore, pos: Vec3) -> Option<BossBar> {
+        let mobs = entities.components::<Mob>();
+        let transforms = entities.components::<Transform>();
+        let health = entities.components::<Health>();
+        MOB_TYPES.with(|types| {
+            let types = types.borrow();
+            mobs.iter()
+                .filter_map(|(id, m)| {
+                    let t = types.get(m.kind as usize).filter(|t| t.boss().is_some())?;
+                    let h = health.get(id).filter(|h| h.is_alive())?;
+                    let dist = (transforms.get(id)?.pos - pos).length();
+                    (dist < BOSS_BAR_DISTANCE).then_some((dist, t, h, m.phase))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, t, h, phase)| BossBar {
+                    name: t.name().to_string(),
+                    health: h.health(),
+                    max_health: h.max_health(),
+                    phase,
+                })
+        })
+    }
+
+    /// Summon the boss of a shrine at pos, unless one is around already or
+    /// waiting in the stash. Returns the boss if one got summoned.
+    pub fn summon_boss(
+        entities: &mut EntityStore,
+        stash: &MobStash,
+        pos: IVec3,
+        rng: &mut XorShiftRng,
+    ) -> Option<EntityId> {
+        let bosses: Vec<MobTypeId> = MOB_TYPES.with(|types| {
+            types
+                .borrow()
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| t.boss().is_some())
+                .map(|(i, _)| i as MobTypeId)
+                .collect()
+        });
+        let awake = entities
+            .components::<Mob>()
+            .values()
+            .any(|m| bosses.contains(&m.kind));
+        let stashed = stash
+            .mobs()
+            .filter_map(|m| mob_type_id(&m.kind))
+            .any(|kind| bosses.contains(&kind));
+        if awake || stashed {
+            return None;
+        }
+        let kind = *bosses.choose(rng)?;
+        let home = pos.as_vec3() + Vec3::new(0.5, 2.0, 0.5);
+        let rot = Vec3::new(0.0, rng.gen_range(0.0..360.0), 0.0);
+        Self::spawn(entities, home, rot, kind)
+    }
+}
+
+/// Hurt and knock back a mob if id is one, the player only gets
+/// experience and the kill if by_player is set.
+fn hurt_mob(
+    reactor: &Reactor<Message>,
+    entities: &EntityStore,
+    id: EntityId,
+    from: Vec3,
+    damage: i16,
+    by_player: bool,
+) {
+    let mut mobs = entities.components_mut::<Mob>();
+    let transforms = entities.components::<Transform>();
+    let mut physics = entities.components_mut::<Physics>();
+    let mut health = entities.components_mut::<Health>();
+    let (Some(m), Some(t), Some(p), Some(health)) = (
+        mobs.get_mut(&id),
+        transforms.get(&id),
+        physics.get_mut(&id),
+        health.get_mut(&id),
+    ) else {
+        return;
+    };
+    let pos = t.pos;
+    p.vel += knockback(from, pos, damage as f32 * KNOCKBACK_PER_DAMAGE);
+    *health -= damage;
+    m.provoke();
+    m.hit_by_player = by_player;
+    if by_player && health.is_dead() {
+        let xp = MOB_TYPES.with(|types| types.borrow().get(m.kind as usize).map(|t| t.xp()));
+        if let Some(xp) = xp {
+            reactor.defer(Message::CharacterGainExperience { pos, xp });
+        }
     }
+    let msg = Message::MobHurt { pos, damage };
+    reactor.reply(msg);
+    reactor.defer(msg);
+    reactor.defer(Message::SfxPlay {
+        pos,
+        volume: 0.3,
+        sfx: SfxId::Punch,
+    });
 }
 
 pub fn init(args: RenderInitArgs) -> RenderInitArgs {
+    args.game.entities_mut().register::<Mob>();
     let rng = Rc::new(RefCell::new(XorShiftRng::from_entropy()));
+    let pathfinder = Rc::new(RefCell::new(Pathfinder::new()));
     {
         let player = args.game.player_rc();
         let world = args.game.world_rc();
+        let entities = args.game.entities_rc();
+        let stash = args.game.mob_stash_rc();
+        let boss_bar = args.game.boss_bar_rc();
         let rng = rng.clone();
-        let f = move |reactor: &Reactor<Message>, _msg: Message| {
+        let pathfinder = pathfinder.clone();
+        let f = move |reactor: &Reactor<Message>, msg: Message| {
             let mut rng = rng.borrow_mut();
-            MOBS.with(|mobs| {
-                let player = player.borrow();
-                let world = world.borrow();
-                mobs.borrow_mut()
-                    .tick_all(reactor, &player, &world, &mut rng);
-            });
+            let player = player.borrow();
+            let world = world.borrow();
+            let mut entities = entities.borrow_mut();
+            let mut pathfinder = pathfinder.borrow_mut();
+            Mob::tick_all(
+                &mut entities,
+                reactor,
+                &player,
+                &world,
+                &mut rng,
+                &mut pathfinder,
+            );
+            boss_bar.replace(Mob::boss_bar(&entities, player.pos()));
+            if let Message::GameTick { ticks } = msg {
+                if ticks % MOB_SPAWN_INTERVAL == 0 {
+                    Mob::tick_spawner(
+                        &mut entities,
+                        &mut stash.borrow_mut(),
+                        &mut pathfinder,
+                        &world,
+                        player.pos(),
+                        ticks,
+                        &mut rng,
+                    );
+                }
+            }
         };
         args.reactor
             .add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
     }
     {
+        let entities = args.game.entities_rc();
+        let stash = args.game.mob_stash_rc();
+        let pathfinder = pathfinder.clone();
+        let f = move |_: &Reactor<Message>, _msg: Message| {
+            Mob::stash_where(
+                &mut entities.borrow_mut(),
+                &mut stash.borrow_mut(),
+                &mut pathfinder.borrow_mut(),
+                |_| true,
+            );
+        };
+        args.reactor.add_sink(Message::GameQuit, Box::new(f));
+    }
+    {
+        let entities = args.game.entities_rc();
+        let world = args.game.world_rc();
+        let stash = args.game.mob_stash_rc();
+        let rng = rng.clone();
+        let f = move |_: &Reactor<Message>, msg: Message| {
+            if let Message::PlayerInteract { pos } = msg {
+                let mut world = world.borrow_mut();
+                let shrine = block_id("shrine");
+                if shrine.is_none() || world.get_block(pos) != shrine {
+                    return;
+                }
+                let mut entities = entities.borrow_mut();
+                let stash = stash.borrow();
+                let mut rng = rng.borrow_mut();
+                let Some(id) = Mob::summon_boss(&mut entities, &stash, pos, &mut rng) else {
+                    return;
+                };
+                if let Some(marble) = block_id("marble_blocks") {
+                    world.set_block(pos, marble);
+                }
+                let kind = entities.components::<Mob>().get(&id).map(|m| m.kind);
+                let name = kind.and_then(|kind| {
+                    MOB_TYPES.with(|t| t.borrow().get(kind as usize).map(|t| t.name().to_string()))
+                });
+                if let Some(name) = name {
+                    let text = format!("The {} awakens", name);
+                    GAME_LOG.with(|log| log.borrow_mut().push(text));
+                }
+            }
+        };
+        args.reactor
+            .add_sink(Message::PlayerInteract { pos: IVec3::ZERO }, Box::new(f));
+    }
+    {
+        let entities = args.game.entities_rc();
+        let f = move |reactor: &Reactor<Message>, msg: Message| {
+            if let Message::ProjectileHit {
+                pos,
+                target,
+                damage,
+                owner,
+            } = msg
+            {
+                let by_player = owner == ProjectileOwner::Player;
+                hurt_mob(reactor, &entities.borrow(), target, pos, damage, by_player);
+            }
+        };
+        args.reactor.add_sink(
+            Message::ProjectileHit {
+                pos: Vec3::ZERO,
+                target: EntityId::default(),
+                damage: 0,
+                owner: ProjectileOwner::Player,
+            },
+            Box::new(f),
+        );
+    }
+    args.reactor.add_sink(
+        Message::ResetEverything,
+        Box::new(move |_: &Reactor<Message>, _msg: Message| {
+            pathfinder.replace(Pathfinder::new());
+        }),
+    );
+    {
+        let entities = args.game.entities_rc();
         let f = move |reactor: &Reactor<Message>, msg: Message| {
             if let Message::CharacterAttack {
                 char_pos,
@@ -561,35 +858,10 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
                 ..
             } = msg
             {
-                MOBS.with(|mobs| {
-                    mobs.borrow_mut()
-                        .iter_mut()
-                        .filter(|m| (attack_pos - m.pos()).length_squared() < 2.0 * 2.0)
-                        .for_each(|m| {
-                            let mut dir = (char_pos - m.pos()).normalize();
-                            dir.y -= 0.2;
-                            m.set_vel(m.vel + dir * -0.01);
-                            m.health -= damage;
-                            m.set_state(MobState::ChasePlayer(Instant::now()));
-                            if m.health.is_dead() {
-                                reactor.defer(Message::CharacterGainExperience {
-                                    pos: m.pos(),
-                                    xp: 8,
-                                });
-                            }
-                            let msg = Message::MobHurt {
-                                pos: m.pos(),
-                                damage,
-                            };
-                            reactor.reply(msg);
-                            reactor.defer(msg);
-                            reactor.defer(Message::SfxPlay {
-                                pos: m.pos(),
-                                volume: 0.3,
-                                sfx: SfxId::Punch,
-                            });
-                        });
-                });
+                let entities = entities.borrow();
+                for id in entities.within_radius(attack_pos, 2.0) {
+                    hurt_mob(reactor, &entities, id, char_pos, damage, true);
+                }
             }
         };
         args.reactor.add_sink(
@@ -602,90 +874,101 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
         );
     }
     {
+        let entities = args.game.entities_rc();
         let f = move |reactor: &Reactor<Message>, msg: Message| {
-            if let Message::Explosion { pos, power } = msg {
-                let p = power * power;
-                MOBS.with(|mobs| {
-                    mobs.borrow_mut()
-                        .iter_mut()
-                        .filter(|m| (pos - m.pos()).length_squared() < p)
-                        .for_each(|m| {
-                            let d = pos - m.pos();
-                            let p = d.length() * 0.2;
-                            let mut dir = d.normalize() * d * 0.2;
-                            dir.y = -0.5;
-                            m.set_vel(dir * -0.04);
-                            let damage = p.ceil() as i16;
-                            m.health -= damage;
-                            reactor.defer(Message::MobHurt {
-                                pos: m.pos(),
-                                damage,
-                            });
-                        });
-                });
+            if let Message::Explosion {
+                pos,
+                power,
+                by_player,
+            } = msg
+            {
+                let entities = entities.borrow();
+                let mut mobs = entities.components_mut::<Mob>();
+                let transforms = entities.components::<Transform>();
+                let mut health = entities.components_mut::<Health>();
+                for id in entities.within_radius(pos, power) {
+                    let (Some(m), Some(t), Some(health)) =
+                        (mobs.get_mut(&id), transforms.get(&id), health.get_mut(&id))
+                    else {
+                        continue;
+                    };
+                    m.hit_by_player = by_player;
+                    let amount = (pos - t.pos).length() * 0.2;
+                    let damage = amount.ceil() as i16;
+                    *health -= damage;
+                    reactor.defer(Message::MobHurt { pos: t.pos, damage });
+                }
             }
         };
         args.reactor.add_sink(
             Message::Explosion {
                 pos: Vec3::ZERO,
                 power: 0.0,
+                by_player: false,
             },
             Box::new(f),
         );
     }
     {
+        let entities = args.game.entities_rc();
         let f = move |_reactor: &Reactor<Message>, msg: Message| {
-            if let Message::WorldgenSpawnMob { pos, .. } = msg {
-                let mut rng = rng.borrow_mut();
-                MOBS.with(|mobs| {
-                    let model_index = 0;
-                    mobs.borrow_mut().add(
-                        pos,
-                        Vec3::new(0.0, rng.gen_range(0.0..360.0), 0.0),
-                        model_index,
-                    );
-                });
+            if let Message::MobApplyEffect {
+                pos,
+                radius,
+                effect,
+            } = msg
+            {
+                let entities = entities.borrow();
+                let mut mobs = entities.components_mut::<Mob>();
+                for id in entities.within_radius(pos, radius) {
+                    if let Some(m) = mobs.get_mut(&id) {
+                        m.apply_effect(effect);
+                    }
+                }
             }
         };
-        args.reactor
-            .add_sink(Message::WorldgenSpawnMob { pos: Vec3::ZERO }, Box::new(f));
+        args.reactor.add_sink(
+            Message::MobApplyEffect {
+                pos: Vec3::ZERO,
+                radius: 0.0,
+                effect: StatusEffect::default(),
+            },
+            Box::new(f),
+        );
     }
-
-    args.reactor.add_sink(
-        Message::ResetEverything,
-        Box::new(move |_: &Reactor<Message>, _msg: Message| {
-            MOBS.with(|mobs| {
-                mobs.borrow_mut().clear();
-            });
-        }),
-    );
-
-    args.render_reactor.entity_provider.push(Box::new(move |v| {
-        MOBS.with(|mobs| {
-            for e in mobs.borrow().iter() {
-                v.push(e.to_entity());
-            }
-        });
-    }));
     {
-        let meshes: Vec<Vec<VoxelMesh>> =
-            mob_load_meshes(&args.fe.display).expect("Error loading crab mesh");
+        let meshes = mob_load_meshes(&args.fe.display).expect("Error loading mob meshes");
         args.render_reactor
             .world_render
             .push(Box::new(move |args: RenderPassArgs| {
                 let mvp = args.projection * args.view;
                 let frustum = Frustum::extract(&mvp);
-                MOBS.with(|mobs| {
-                    for mob in mobs.borrow().iter() {
-                        if frustum.contains_cube(mob.pos() - MOB_SIZE, MOB_SIZE * 2.0) {
-                            let player_pos = args.game.player().pos();
-                            let dist = (mob.pos() - player_pos).length();
+                let player_pos = args.game.player().pos();
+                let entities = args.game.entities();
+                let transforms = entities.components::<Transform>();
+                let renderables = entities.components::<Renderable>();
+                MOB_TYPES.with(|types| {
+                    let types = types.borrow();
+                    for (id, mob) in entities.components::<Mob>().iter() {
+                        let (Some(t), Some(r)) = (transforms.get(id), renderables.get(id)) else {
+                            continue;
+                        };
+                        let Some(mob_type) = types.get(r.model as usize) else {
+                            continue;
+                        };
+                        let Some(mesh) = meshes.get(mob.frame(mob_type)) else {
+                            continue;
+                        };
+                        let scale = mob.scale(mob_type);
+                        if frustum.contains_cube(t.pos - MOB_SIZE * scale, MOB_SIZE * 2.0 * scale) {
+                            let dist = (t.pos - player_pos).length();
                             let color_alpha =
                                 ((args.render_distance - dist) / 32.0).clamp(0.0, 1.0);
-                            let _ = mob.draw(
+                            let _ = Mob::draw(
+                                &Mob::model_matrix(t, scale),
                                 args.frame,
                                 args.fe,
-                                &meshes[mob.model_index() as usize],
+                                mesh,
                                 &args.view,
                                 &args.projection,
                                 color_alpha,
@@ -0,0 +1,298 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use super::Mob;
+use glam::{IVec3, Vec3};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use wolkenwelten_core::{
+    is_daytime, mob_type_id, Chungus, EntityId, EntityStore, Health, MobStash, MobType, PathConfig,
+    Pathfinder, SavedMob, SpawnSite, Transform, CHUNK_SIZE, MOB_TYPES,
+};
+
+/// How many ticks pass in between spawner runs
+pub const MOB_SPAWN_INTERVAL: u64 = 250;
+/// How many mobs may be around the player at most
+pub const MOB_CAP: usize = 24;
+/// How many mobs may be within MOB_AREA_RADIUS of a new spawn
+pub const MOB_AREA_CAP: usize = 4;
+pub const MOB_AREA_RADIUS: f32 = 24.0;
+/// Mobs further away than this get stashed until the player returns
+pub const MOB_DESPAWN_DISTANCE: f32 = 96.0;
+/// Stashed mobs come back once the player is within this distance of them,
+/// this has to stay below MOB_DESPAWN_DISTANCE so they don't get stashed again
+pub const MOB_RESTORE_DISTANCE: f32 = 80.0;
+/// How many random spots are tried per spawner run
+const MOB_SPAWN_ATTEMPTS: usize = 4;
+/// How far up and down a spot is searched for ground
+const MOB_SPAWN_SCAN_HEIGHT: i32 = 16;
+
+impl Mob {
+    /// Turn a mob into something that can be stashed or saved
+    pub fn save(&self, transform: &Transform, health: &Health) -> Option<SavedMob> {
+        let kind = MOB_TYPES.with(|types| {
+            types
+                .borrow()
+                .get(self.kind as usize)
+                .map(|t| t.id().to_string())
+        })?;
+        Some(SavedMob {
+            kind,
+            pos: transform.pos,
+            rot: transform.rot,
+            health: health.health(),
+            home: self.home,
+            elite: self.elite,
+        })
+    }
+
+    /// Bring a stashed mob back, returns None if its type is gone by now
+    pub fn restore(entities: &mut EntityStore, saved: &SavedMob) -> Option<EntityId> {
+        let kind = mob_type_id(&saved.kind)?;
+        let id = Self::spawn_with(entities, saved.pos, saved.rot, kind, saved.elite)?;
+        let mut transforms = entities.components_mut::<Transform>();
+        let mut health = entities.components_mut::<Health>();
+        let mut mobs = entities.components_mut::<Mob>();
+        if let Some(t) = transforms.get_mut(&id) {
+            t.pos = saved.pos;
+        }
+        if let (Some(h), Some(m)) = (health.get_mut(&id), mobs.get_mut(&id)) {
+            let damage = h.max_health() - saved.health.clamp(1, h.max_health());
+            h.damage(damage);
+            m.home = saved.home;
+            // Bosses pick up where they left off, without announcing it again
+            MOB_TYPES.with(|types| {
+                if let Some(t) = types.borrow().get(kind as usize) {
+                    m.update_phase(t, h);
+                }
+            });
+        }
+        Some(id)
+    }
+
+    /// Move every mob that matches f into the stash
+    pub fn stash_where(
+        entities: &mut EntityStore,
+        stash: &mut MobStash,
+        pathfinder: &mut Pathfinder,
+        f: impl Fn(Vec3) -> bool,
+    ) {
+        let mut gone = vec![];
+        {
+            let mobs = entities.components::<Mob>();
+            let transforms = entities.components::<Transform>();
+            let health = entities.components::<Health>();
+            for (id, m) in mobs.iter() {
+                let (Some(t), Some(h)) = (transforms.get(id), health.get(id)) else {
+                    continue;
+                };
+                if h.is_alive() && f(t.pos) {
+                    if let Some(saved) = m.save(t, h) {
+                        stash.insert(saved);
+                    }
+                    gone.push(*id);
+                }
+            }
+        }
+        gone.into_iter().for_each(|id| {
+            pathfinder.cancel(id);
+            entities.despawn(id);
+        });
+    }
+
+    /// Stash far away mobs, bring back the ones the player got close to
+    /// again and spawn new ones as long as the caps allow.
+    #[allow(clippy::too_many_arguments)]
+    pub fn tick_spawner(
+        entities: &mut EntityStore,
+        stash: &mut MobStash,
+        pathfinder: &mut Pathfinder,
+        world: &Chungus,
+        player_pos: Vec3,
+        ticks: u64,
+        rng: &mut XorShiftRng,
+    ) {
+        let despawn_sq = MOB_DESPAWN_DISTANCE * MOB_DESPAWN_DISTANCE;
+        Self::stash_where(entities, stash, pathfinder, |pos| {
+            (pos - player_pos).length_squared() > despawn_sq || !world.is_loaded(pos)
+        });
+
+        // Only look into chunks that might hold a mob close enough
+        let half_diagonal = CHUNK_SIZE as f32 * 0.5 * 3.0_f32.sqrt();
+        let candidates: Vec<IVec3> = stash
+            .chunks()
+            .filter(|chunk| {
+                let center = (*chunk * CHUNK_SIZE as i32).as_vec3() + CHUNK_SIZE as f32 * 0.5;
+                (center - player_pos).length() < MOB_RESTORE_DISTANCE + half_diagonal
+            })
+            .collect();
+        for chunk in candidates {
+            for saved in stash.take(chunk) {
+                let near = (saved.pos - player_pos).length() < MOB_RESTORE_DISTANCE;
+                if near && world.is_loaded(saved.pos) {
+                    Self::restore(entities, &saved);
+                } else {
+                    stash.insert(saved);
+                }
+            }
+        }
+
+        let daytime = is_daytime(ticks);
+        for _ in 0..MOB_SPAWN_ATTEMPTS {
+            if Self::count(entities, player_pos, f32::MAX) >= MOB_CAP {
+                return;
+            }
+            let Some(site) = find_spawn_site(world, player_pos, daytime, rng) else {
+                continue;
+            };
+            let pos = site.pos.as_vec3() + Vec3::new(0.5, 1.0, 0.5);
+            let nearby =
+                Self::count(entities, pos, MOB_AREA_RADIUS) + stash.count(MobStash::chunk_of(pos));
+            if nearby >= MOB_AREA_CAP {
+                continue;
+            }
+            let kind = MOB_TYPES.with(|types| {
+                let types = types.borrow();
+                let kind = MobType::pick_spawn(&types, &site, rng)?;
+                let elite = types[kind as usize]
+                    .elite()
+                    .map(|e| rng.gen_bool(e.chance as f64))
+                    .unwrap_or(false);
+                Some((kind, elite))
+            });
+            if let Some((kind, elite)) = kind {
+                let rot = Vec3::new(0.0, rng.gen_range(0.0..360.0), 0.0);
+                Self::spawn_with(entities, pos, rot, kind, elite);
+            }
+        }
+    }
+
+    /// How many mobs are within radius of pos
+    fn count(entities: &EntityStore, pos: Vec3, radius: f32) -> usize {
+        let mobs = entities.components::<Mob>();
+        let transforms = entities.components::<Transform>();
+        mobs.keys()
+            .filter_map(|id| transforms.get(id))
+            .filter(|t| (t.pos - pos).length_squared() <= radius * radius)
+            .count()
+    }
+}
+
+/// Look for a spot to stand on somewhere around the player
+fn find_spawn_site(
+    world: &Chungus,
+    player_pos: Vec3,
+    daytime: bool,
+    rng: &mut impl Rng,
+) -> Option<SpawnSite> {
+    let angle: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+    let distance: f32 = rng.gen_range(16.0..64.0);
+    let column = player_pos + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance;
+    let column = column.floor().as_ivec3();
+    let config = PathConfig::default();
+    (-MOB_SPAWN_SCAN_HEIGHT..=MOB_SPAWN_SCAN_HEIGHT)
+        .rev()
+        .map(|dy| column + IVec3::new(0, dy, 0))
+        .find(|pos| config.is_walkable(world, *pos))
+        .and_then(|pos| {
+            Some(SpawnSite {
+                pos,
+                ground: world.get_block(pos - IVec3::Y)?,
+                light: world.light_level(pos)?,
+                daytime,
+                distance: (pos.as_vec3() - player_pos).length(),
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn mob_positions(entities: &EntityStore) -> Vec<Vec3> {
+        let mobs = entities.components::<Mob>();
+        let transforms = entities.components::<Transform>();
+        mobs.keys().map(|id| transforms[id].pos).collect()
+    }
+
+    #[test]
+    fn test_mob_spawner() {
+        let world = Chungus::new_flat("grass", 96);
+        let mut entities = EntityStore::new();
+        entities.register::<Mob>();
+        let mut stash = MobStash::new();
+        let mut pathfinder = Pathfinder::new();
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let mut spawner = |entities: &mut EntityStore, stash: &mut MobStash, player: Vec3| {
+            Mob::tick_spawner(
+                entities,
+                stash,
+                &mut pathfinder,
+                &world,
+                player,
+                0,
+                &mut rng,
+            )
+        };
+
+        let home = Vec3::new(0.5, 0.5, 0.5);
+        for _ in 0..200 {
+            spawner(&mut entities, &mut stash, home);
+        }
+        let spawned = mob_positions(&entities);
+        assert!(!spawned.is_empty());
+        assert!(spawned.len() <= MOB_CAP);
+        assert!(spawned.iter().all(|p| (*p - home).length() >= 24.0));
+
+        // Walking away stashes every mob, without spawning any new ones
+        let away = Vec3::new(1000.0, 0.5, 0.5);
+        spawner(&mut entities, &mut stash, away);
+        assert!(mob_positions(&entities).is_empty());
+        assert_eq!(stash.len(), spawned.len());
+
+        // And coming back brings them back where they were
+        spawner(&mut entities, &mut stash, home);
+        let restored = mob_positions(&entities);
+        assert!(restored.iter().any(|p| spawned.contains(p)));
+        assert!(restored.len() <= MOB_CAP);
+
+        // Quitting stashes everyone that is still around
+        Mob::stash_where(&mut entities, &mut stash, &mut pathfinder, |_| true);
+        assert!(mob_positions(&entities).is_empty());
+        assert!(stash.len() >= spawned.len());
+
+        // Mobs are restored based on their own position, not their chunk, so
+        // they don't get stashed again right away
+        entities.clear();
+        stash.clear();
+        let edge = Vec3::new(home.x + 90.0, 0.5, home.z);
+        let kind = MOB_TYPES.with(|t| t.borrow()[0].id().to_string());
+        stash.insert(SavedMob {
+            kind,
+            pos: edge,
+            rot: Vec3::ZERO,
+            health: 1,
+            home: edge,
+            elite: false,
+        });
+        // Close enough to the center of the chunk, but not to the mob itself
+        let mut spawner = |entities: &mut EntityStore, stash: &mut MobStash, player: Vec3| {
+            Mob::tick_spawner(
+                entities,
+                stash,
+                &mut pathfinder,
+                &world,
+                player,
+                0,
+                &mut rng,
+            )
+        };
+        let chunk = MobStash::chunk_of(edge);
+        spawner(&mut entities, &mut stash, Vec3::new(5.0, 0.5, 0.5));
+        assert_eq!(stash.count(chunk), 1);
+        assert!(!mob_positions(&entities).contains(&edge));
+        spawner(&mut entities, &mut stash, edge - Vec3::X * 40.0);
+        assert_eq!(stash.count(chunk), 0);
+        assert!(mob_positions(&entities).contains(&edge));
+    }
+}
@@ -2,6 +2,7 @@ const msgs = [
 	`Welcome to WolkenWelten ${WWC.VERSION}!`,
 	"Use WASD to move, Space to jump and Shift to sprint",
 	"Use your mouse to mine/place blocks or punch crabs",
+	"Press T to chat or / to enter a command, /help lists all of them",
 	"Nothing will be saved, as soon as you die or quit it's gone"
 ].reverse();
 const PopMsg = () => {
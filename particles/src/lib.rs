@@ -387,7 +387,10 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
     );
 
     args.reactor.add_sink(
-        Message::MobDied { pos: Vec3::ZERO },
+        Message::MobDied {
+            pos: Vec3::ZERO,
+            by_player: false,
+        },
         Box::new(move |_: &Reactor<Message>, msg: Message| {
             PARTICLES.with(|particles| {
                 if let Message::MobHurt { pos, .. } = msg {
@@ -399,11 +402,15 @@ pub fn init(args: RenderInitArgs) -> RenderInitArgs {
     );
 
     args.reactor.add_sink(
-        Message::EntityCollision { pos: Vec3::ZERO },
+        Message::ProjectileExplode {
+            pos: Vec3::ZERO,
+            power: 0.0,
+            by_player: false,
+        },
         Box::new(move |_: &Reactor<Message>, msg: Message| {
             PARTICLES.with(|particles| {
-                if let Message::EntityCollision { pos } = msg {
-                    particles.borrow_mut().fx_explosion(pos, 9.0);
+                if let Message::ProjectileExplode { pos, power, .. } = msg {
+                    particles.borrow_mut().fx_explosion(pos, power + 2.0);
                 }
             });
         }),
@@ -0,0 +1,594 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use anyhow::Result;
+use glam::{Mat4, Quat, Vec3};
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use std::cell::RefCell;
+use wolkenwelten_client::{voxel_data, ClientState, RenderInitArgs, RenderPassArgs, VoxelMesh};
+use wolkenwelten_core::{
+    BlockImpact, Character, Chungus, EntityId, EntityImpact, EntityStore, Health, Message, Physics,
+    ProjectileOwner, ProjectileType, ProjectileTypeId, Reactor, Renderable, Transform,
+    PROJECTILE_TYPES,
+};
+
+/// Something that got thrown or shot, how it behaves is up to its
+/// ProjectileType.
+#[derive(Clone, Debug, Default)]
+pub struct Projectile {
+    pub kind: ProjectileTypeId,
+    pub owner: ProjectileOwner,
+    age: u64,
+    /// The age at which it got stuck in a block
+    stuck: Option<u64>,
+    /// Where it is pointing, used for drawing
+    dir: Vec3,
+    /// Everything a piercing projectile already went through
+    hit: Vec<EntityId>,
+    hit_player: bool,
+}
+
+fn draw(
+    mesh: &VoxelMesh,
+    frame: &mut glium::Frame,
+    fe: &ClientState,
+    pos: Vec3,
+    dir: Vec3,
+    view: &Mat4,
+    projection: &Mat4,
+) -> Result<()> {
+    // Models point along +Z, so turn that into the direction of flight
+    let rot = if dir == Vec3::ZERO {
+        Quat::IDENTITY
+    } else {
+        Quat::from_rotation_arc(Vec3::Z, dir)
+    };
+    let model = Mat4::from_scale_rotation_translation(Vec3::splat(1.0 / 16.0), rot, pos);
+    let vp = projection.mul_mat4(view);
+    let mvp = vp.mul_mat4(&model);
+    mesh.draw(frame, fe.block_indeces(), &fe.shaders.voxel, &mvp, 1.0)
+}
+
+/// What should happen to a projectile at the end of a tick
+enum Outcome {
+    Keep,
+    Explode(Vec3, f32),
+    Gone,
+}
+
+impl Projectile {
+    pub fn spawn(
+        entities: &mut EntityStore,
+        pos: Vec3,
+        vel: Vec3,
+        kind: ProjectileTypeId,
+        owner: ProjectileOwner,
+    ) -> EntityId {
+        entities
+            .spawn()
+            .with(Transform::new(pos))
+            .with(Physics::default().with_vel(vel))
+            .with(Renderable::default())
+            .with(Projectile {
+                kind,
+                owner,
+                dir: vel.normalize_or_zero(),
+                ..Default::default()
+            })
+            .id()
+    }
+
+    #[inline]
+    pub fn is_stuck(&self) -> bool {
+        self.stuck.is_some()
+    }
+
+    /// Move a single projectile and check whether it hit a block, ran out of
+    /// time or flew too far away.
+    fn fly(
+        &mut self,
+        t: &ProjectileType,
+        transform: &mut Transform,
+        physics: &mut Physics,
+        world: &Chungus,
+        player_pos: Vec3,
+    ) -> Outcome {
+        self.age += 1;
+        if let Some(fuse) = t.fuse_ticks() {
+            if self.age >= fuse {
+                return if t.explosion() > 0.0 {
+                    Outcome::Explode(transform.pos, t.explosion())
+                } else {
+                    Outcome::Gone
+                };
+            }
+        }
+        if let Some(since) = self.stuck {
+            return if self.age - since >= t.linger_ticks() {
+                Outcome::Gone
+            } else {
+                Outcome::Keep
+            };
+        }
+        if (transform.pos - player_pos).length_squared() > t.range() * t.range() {
+            return Outcome::Gone;
+        }
+        if physics.bounce_with(transform, world, t.gravity(), t.bounciness()) {
+            match t.on_block() {
+                BlockImpact::Bounce => {}
+                BlockImpact::Explode => {
+                    return Outcome::Explode(transform.pos, t.explosion());
+                }
+                BlockImpact::Stick => {
+                    self.stuck = Some(self.age);
+                    physics.vel = Vec3::ZERO;
+                    return Outcome::Keep;
+                }
+                BlockImpact::Vanish => return Outcome::Gone,
+            }
+        }
+        if physics.vel != Vec3::ZERO {
+            self.dir = physics.vel.normalize();
+        }
+        Outcome::Keep
+    }
+
+    /// Check whether a projectile ran into something that can take damage,
+    /// ProjectileHit messages get deferred for every new victim.
+    fn strike(
+        &mut self,
+        t: &ProjectileType,
+        aabb_hits: Vec<EntityId>,
+        pos: Vec3,
+        player_hit: bool,
+        reactor: &Reactor<Message>,
+    ) -> Outcome {
+        if self.is_stuck() || t.on_entity() == EntityImpact::Ignore {
+            return Outcome::Keep;
+        }
+        let owner = self.owner;
+        let victims: Vec<EntityId> = aabb_hits
+            .into_iter()
+            .filter(|id| owner != ProjectileOwner::Entity(*id) && !self.hit.contains(id))
+            .collect();
+        let player_hit = player_hit && !self.hit_player;
+        if victims.is_empty() && !player_hit {
+            return Outcome::Keep;
+        }
+        let damage = t.damage();
+        match t.on_entity() {
+            EntityImpact::Ignore => Outcome::Keep,
+            EntityImpact::Explode => Outcome::Explode(pos, t.explosion()),
+            EntityImpact::Damage => {
+                if let Some(target) = victims.first() {
+                    reactor.defer(Message::ProjectileHit {
+                        pos,
+                        target: *target,
+                        damage,
+                        owner,
+                    });
+                } else {
+                    reactor.defer(Message::MobStrike { pos, damage });
+                }
+                Outcome::Gone
+            }
+            EntityImpact::Pierce => {
+                for target in victims {
+                    reactor.defer(Message::ProjectileHit {
+                        pos,
+                        target,
+                        damage,
+                        owner,
+                    });
+                    self.hit.push(target);
+                }
+                if player_hit {
+                    reactor.defer(Message::MobStrike { pos, damage });
+                    self.hit_player = true;
+                }
+                Outcome::Keep
+            }
+        }
+    }
+
+    /// Move every projectile and let it hit whatever is in the way, the
+    /// ones that exploded, vanished or flew too far away get removed.
+    pub fn tick_all(
+        entities: &mut EntityStore,
+        reactor: &Reactor<Message>,
+        player: &Character,
+        world: &Chungus,
+    ) {
+        let mut outcomes = vec![];
+        PROJECTILE_TYPES.with(|types| {
+            let types = types.borrow();
+            {
+                let mut projectiles = entities.components_mut::<Projectile>();
+                let mut transforms = entities.components_mut::<Transform>();
+                let mut physics = entities.components_mut::<Physics>();
+                for (id, projectile) in projectiles.iter_mut() {
+                    let (Some(t), Some(p), Some(kind)) = (
+                        transforms.get_mut(id),
+                        physics.get_mut(id),
+                        types.get(projectile.kind as usize),
+                    ) else {
+                        continue;
+                    };
+                    let outcome = projectile.fly(kind, t, p, world, player.pos());
+                    if !matches!(outcome, Outcome::Keep) {
+                        outcomes.push((*id, outcome));
+                    }
+                }
+            }
+
+            // Only the player is allowed to get hurt by projectiles, other
+            // entities need some Health.
+            let player_vulnerable = !player.no_clip() && !player.is_dead();
+            let health = entities.components::<Health>();
+            let transforms = entities.components::<Transform>();
+            let physics = entities.components::<Physics>();
+            let mut projectiles = entities.components_mut::<Projectile>();
+            for (id, projectile) in projectiles.iter_mut() {
+                if outcomes.iter().any(|(e, _)| e == id) {
+                    continue;
+                }
+                let (Some(t), Some(p), Some(kind)) = (
+                    transforms.get(id),
+                    physics.get(id),
+                    types.get(projectile.kind as usize),
+                ) else {
+                    continue;
+                };
+                let aabb = p.aabb(t.pos);
+                let hits = entities
+                    .overlapping(&aabb)
+                    .into_iter()
+                    .filter(|other| health.contains_key(other))
+                    .collect();
+                let player_hit = player_vulnerable
+                    && projectile.owner != ProjectileOwner::Player
+                    && player.aabb().intersects(&aabb);
+                let outcome = projectile.strike(kind, hits, t.pos, player_hit, reactor);
+                if !matches!(outcome, Outcome::Keep) {
+                    outcomes.push((*id, outcome));
+                }
+            }
+        });
+        for (id, outcome) in outcomes {
+            if let Outcome::Explode(pos, power) = outcome {
+                let by_player = entities
+                    .components::<Projectile>()
+                    .get(&id)
+                    .map(|p| p.owner == ProjectileOwner::Player)
+                    .unwrap_or(false);
+                reactor.defer(Message::ProjectileExplode {
+                    pos,
+                    power,
+                    by_player,
+                });
+            }
+            entities.despawn(id);
+        }
+    }
+}
+
+pub fn init(args: RenderInitArgs) -> RenderInitArgs {
+    {
+        let player = args.game.player_rc();
+        let clock = args.game.clock_rc();
+        let entities = args.game.entities_rc();
+        let f = move |reactor: &Reactor<Message>, msg: Message| {
+            if let Message::PlayerShoot { projectile } = msg {
+                let Some((speed, cooldown)) = PROJECTILE_TYPES.with(|types| {
+                    types
+                        .borrow()
+                        .get(projectile as usize)
+                        .map(|t| (t.speed(), t.cooldown()))
+                }) else {
+                    return;
+                };
+                let mut player = player.borrow_mut();
+                let now = clock.borrow().elapsed().as_millis() as u64;
+                if player.may_act(now) {
+                    player.set_animation_hit();
+                    player.set_cooldown(now + cooldown);
+                    Projectile::spawn(
+                        &mut entities.borrow_mut(),
+                        player.pos(),
+                        player.direction() * speed,
+                        projectile,
+                        ProjectileOwner::Player,
+                    );
+                    reactor.dispatch(Message::CharacterShoot { pos: player.pos() });
+                }
+            }
+        };
+        args.reactor
+            .add_sink(Message::PlayerShoot { projectile: 0 }, Box::new(f));
+    }
+    {
+        let entities = args.game.entities_rc();
+        let f = move |_reactor: &Reactor<Message>, msg: Message| {
+            if let Message::ProjectileSpawn {
+                pos,
+                vel,
+                projectile,
+                owner,
+            } = msg
+            {
+                Projectile::spawn(&mut entities.borrow_mut(), pos, vel, projectile, owner);
+            }
+        };
+        args.reactor.add_sink(
+            Message::ProjectileSpawn {
+                pos: Vec3::ZERO,
+                vel: Vec3::ZERO,
+                projectile: 0,
+                owner: ProjectileOwner::Player,
+            },
+            Box::new(f),
+        );
+    }
+    {
+        let player = args.game.player_rc();
+        let world = args.game.world_rc();
+        let entities = args.game.entities_rc();
+        let f = move |reactor: &Reactor<Message>, _msg: Message| {
+            Projectile::tick_all(
+                &mut entities.borrow_mut(),
+                reactor,
+                &player.borrow(),
+                &world.borrow(),
+            );
+        };
+        args.reactor
+            .add_sink(Message::GameTick { ticks: 0 }, Box::new(f));
+    }
+    {
+        let world = args.game.world_rc();
+        let rng = RefCell::new(XorShiftRng::from_entropy());
+        let f = move |reactor: &Reactor<Message>, msg: Message| {
+            if let Message::ProjectileExplode {
+                pos,
+                power,
+                by_player,
+            } = msg
+            {
+                world
+                    .borrow_mut()
+                    .add_explosion(pos, power, &mut rng.borrow_mut(), reactor);
+                reactor.defer(Message::Explosion {
+                    pos,
+                    power,
+                    by_player,
+                });
+            }
+        };
+        args.reactor.add_sink(
+            Message::ProjectileExplode {
+                pos: Vec3::ZERO,
+                power: 0.0,
+                by_player: false,
+            },
+            Box::new(f),
+        );
+    }
+
+    args.game.entities_mut().register::<Projectile>();
+
+    // One mesh for every projectile type, indexed by its id
+    let meshes: Vec<VoxelMesh> = PROJECTILE_TYPES.with(|types| {
+        types
+            .borrow()
+            .iter()
+            .map(|t| {
+                let data = voxel_data(t.model()).unwrap_or_else(|| {
+                    panic!("Projectile {:?} uses unknown model {:?}", t.id(), t.model())
+                });
+                VoxelMesh::from_vox_data(&args.fe.display, data)
+                    .unwrap_or_else(|e| panic!("Error while loading {}.vox: {}", t.model(), e))
+            })
+            .collect()
+    });
+    args.render_reactor
+        .world_render
+        .push(Box::new(move |args: RenderPassArgs| {
+            let entities = args.game.entities();
+            let transforms = entities.components::<Transform>();
+            for (id, projectile) in entities.components::<Projectile>().iter() {
+                let Some(mesh) = meshes.get(projectile.kind as usize) else {
+                    continue;
+                };
+                if let Some(transform) = transforms.get(id) {
+                    let _ = draw(
+                        mesh,
+                        args.frame,
+                        args.fe,
+                        transform.pos,
+                        projectile.dir,
+                        &args.view,
+                        &args.projection,
+                    );
+                }
+            }
+            args
+        }));
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::IVec3;
+    use wolkenwelten_core::{block_id, projectile_type_id};
+
+    /// A stone floor with a wall at x = 8
+    fn stub_world() -> Chungus {
+        let mut world = Chungus::new_flat("stone", 8);
+        let stone = block_id("stone").unwrap();
+        for y in 0..8 {
+            for z in -8..8 {
+                world.set_block(IVec3::new(8, y, z), stone);
+            }
+        }
+        world
+    }
+
+    fn target(entities: &mut EntityStore, pos: Vec3) -> EntityId {
+        entities
+            .spawn()
+            .with(Transform::new(pos))
+            .with(Physics::default())
+            .with(Health::new(4))
+            .id()
+    }
+
+    fn run(
+        entities: &mut EntityStore,
+        player: &Character,
+        world: &Chungus,
+        ticks: usize,
+    ) -> Reactor<Message> {
+        let reactor = Reactor::new();
+        for _ in 0..ticks {
+            Projectile::tick_all(entities, &reactor, player, world);
+        }
+        reactor
+    }
+
+    fn count(reactor: &Reactor<Message>, f: impl Fn(&Message) -> bool) -> usize {
+        reactor.log().iter().filter(|m| f(m)).count()
+    }
+
+    #[test]
+    fn test_projectiles() {
+        let world = stub_world();
+        let mut entities = EntityStore::new();
+        entities.register::<Projectile>();
+        let mut player = Character::new();
+        player.init();
+        player.set_pos(Vec3::new(0.5, 4.0, -6.0));
+        let is_explosion = |m: &Message| matches!(m, Message::ProjectileExplode { .. });
+        let is_hit = |m: &Message| matches!(m, Message::ProjectileHit { .. });
+
+        // Grenades go off as soon as they hit something that can get hurt
+        let grenade = projectile_type_id("grenade").unwrap();
+        let start = Vec3::new(0.5, 0.5, 0.5);
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.2,
+            grenade,
+            ProjectileOwner::Player,
+        );
+        assert_eq!(entities.get::<Physics>(id).unwrap().vel, Vec3::X * 0.2);
+        let reactor = run(&mut entities, &player, &world, 1);
+        assert_eq!(count(&reactor, is_explosion), 0);
+        assert!(entities.contains(id));
+        let victim = target(&mut entities, start + Vec3::X * 2.0);
+        let reactor = run(&mut entities, &player, &world, 20);
+        assert_eq!(count(&reactor, is_explosion), 1);
+        assert!(!entities.contains(id));
+        entities.despawn(victim);
+
+        // ...or a block, while timed grenades bounce off and wait for their fuse
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.2,
+            grenade,
+            ProjectileOwner::Player,
+        );
+        let reactor = run(&mut entities, &player, &world, 100);
+        assert_eq!(count(&reactor, is_explosion), 1);
+        assert!(!entities.contains(id));
+        let timed = projectile_type_id("timed_grenade").unwrap();
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.2,
+            timed,
+            ProjectileOwner::Player,
+        );
+        let reactor = run(&mut entities, &player, &world, 100);
+        assert_eq!(count(&reactor, is_explosion), 0);
+        assert!(entities.get::<Physics>(id).unwrap().vel.x < 0.0);
+        let reactor = run(&mut entities, &player, &world, 1000);
+        assert_eq!(count(&reactor, is_explosion), 1);
+        assert!(!entities.contains(id));
+
+        // Arrows hurt the first thing they hit, but never whoever shot them
+        let arrow = projectile_type_id("arrow").unwrap();
+        let shooter = target(&mut entities, start);
+        let victim = target(&mut entities, start + Vec3::X * 3.0);
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.2,
+            arrow,
+            ProjectileOwner::Entity(shooter),
+        );
+        let reactor = run(&mut entities, &player, &world, 30);
+        assert_eq!(count(&reactor, is_hit), 1);
+        assert!(reactor.log().iter().any(|m| matches!(
+            m,
+            Message::ProjectileHit { target, owner: ProjectileOwner::Entity(o), .. }
+                if *target == victim && *o == shooter
+        )));
+        assert!(!entities.contains(id));
+        entities.despawn(shooter);
+        entities.despawn(victim);
+
+        // When they miss they get stuck in the wall for a while
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.5,
+            arrow,
+            ProjectileOwner::Player,
+        );
+        run(&mut entities, &player, &world, 40);
+        assert!(entities.components::<Projectile>()[&id].is_stuck());
+        let pos = entities.get::<Transform>(id).unwrap().pos;
+        assert!(pos.x > 7.0 && pos.x < 8.0, "{}", pos);
+        run(&mut entities, &player, &world, 100);
+        assert_eq!(entities.get::<Transform>(id).unwrap().pos, pos);
+        let linger = PROJECTILE_TYPES.with(|t| t.borrow()[arrow as usize].linger_ticks());
+        run(&mut entities, &player, &world, linger as usize);
+        assert!(!entities.contains(id));
+
+        // Spears go right through everything in their way
+        let spear = projectile_type_id("spear").unwrap();
+        target(&mut entities, start + Vec3::X * 2.0);
+        target(&mut entities, start + Vec3::X * 4.0);
+        let id = Projectile::spawn(
+            &mut entities,
+            start,
+            Vec3::X * 0.3,
+            spear,
+            ProjectileOwner::Player,
+        );
+        let reactor = run(&mut entities, &player, &world, 30);
+        assert_eq!(count(&reactor, is_hit), 2);
+        assert!(entities.components::<Projectile>()[&id].is_stuck());
+
+        // Mobs shooting at the player strike them instead
+        player.set_pos(start + Vec3::new(3.0, 0.0, 3.0));
+        let pebble = projectile_type_id("pebble").unwrap();
+        let mob = EntityId::default();
+        let id = Projectile::spawn(
+            &mut entities,
+            start + Vec3::Z * 3.0,
+            Vec3::X * 0.2,
+            pebble,
+            ProjectileOwner::Entity(mob),
+        );
+        let reactor = run(&mut entities, &player, &world, 30);
+        assert_eq!(
+            count(&reactor, |m| matches!(m, Message::MobStrike { .. })),
+            1
+        );
+        assert!(!entities.contains(id));
+    }
+}
@@ -0,0 +1,41 @@
+// Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
+// All rights reserved. AGPL-3.0+ license.
+use crate::{defun, CMD_QUEUE};
+use v8::{ContextScope, HandleScope, Local, ObjectTemplate};
+use wolkenwelten_core::{Message, Reactor, COMMANDS};
+
+/// Registers a command that gets handled by JS, since we can't call into
+/// V8 from within a command handler we just queue the invocation and pass
+/// it along with the next call to WolkenWelten.tick
+fn fun_add_command(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    let name = args
+        .get(0)
+        .to_string(scope)
+        .unwrap()
+        .to_rust_string_lossy(scope);
+    let help = args
+        .get(1)
+        .to_string(scope)
+        .unwrap()
+        .to_rust_string_lossy(scope);
+    let cmd_name = name.clone();
+    COMMANDS.with(|cmds| {
+        cmds.borrow_mut().add(
+            &name,
+            &help,
+            Box::new(move |_: &Reactor<Message>, args: &[&str]| {
+                let args = args.iter().map(|s| s.to_string()).collect();
+                CMD_QUEUE.with(|q| q.borrow_mut().push((cmd_name.clone(), args)));
+                Ok(String::new())
+            }),
+        );
+    });
+}
+
+pub fn init(scope: &mut ContextScope<HandleScope>, obj: &Local<ObjectTemplate>) {
+    defun(scope, obj, "addCommand", fun_add_command);
+}
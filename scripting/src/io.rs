@@ -3,7 +3,7 @@
 use crate::{defun, MSG_QUEUE};
 use glam::Vec3;
 use v8::{ContextScope, HandleScope, Local, ObjectTemplate};
-use wolkenwelten_core::{Message, SfxId, GAME_LOG};
+use wolkenwelten_core::{Message, SfxId, StatusEffect, StatusEffectKind, GAME_LOG};
 
 fn fun_log(
     scope: &mut v8::HandleScope,
@@ -75,9 +75,31 @@ fn fun_sfx_play(
     }
 }
 
+fn fun_apply_effect(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    let kind = args
+        .get(0)
+        .to_string(scope)
+        .unwrap()
+        .to_rust_string_lossy(scope);
+    let strength = args.get(1).int32_value(scope);
+    let seconds = args.get(2).number_value(scope);
+    if let (Some(kind), Some(strength), Some(seconds)) =
+        (StatusEffectKind::from_name(&kind), strength, seconds)
+    {
+        let effect = StatusEffect::with_seconds(kind, strength.clamp(1, 255) as u8, seconds as f32);
+        let msg = Message::CharacterApplyEffect { effect };
+        MSG_QUEUE.with(|q| q.borrow_mut().push(msg));
+    }
+}
+
 pub fn init(scope: &mut ContextScope<HandleScope>, obj: &Local<ObjectTemplate>) {
     defun(scope, obj, "eprint", fun_eprint);
     defun(scope, obj, "print", fun_print);
     defun(scope, obj, "sfxPlay", fun_sfx_play);
+    defun(scope, obj, "applyEffect", fun_apply_effect);
     defun(scope, obj, "game_log", fun_log);
 }
@@ -1,18 +1,21 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::time::Instant;
 use v8::{ContextScope, HandleScope};
 use wolkenwelten_client::{start_client, RenderInit};
-use wolkenwelten_core::{Chungus, GameState, Message, Reactor};
+use wolkenwelten_core::{Chungus, GameState, Message, Reactor, BLOCKS};
 
+mod command;
 mod io;
 mod world;
 
 thread_local! {
     pub static WORLD: RefCell<Option<Rc<RefCell<Chungus>>>> = RefCell::new(None);
     static MSG_QUEUE: RefCell<Vec<Message>> = RefCell::new(vec![]);
+    static CMD_QUEUE: RefCell<Vec<(String, Vec<String>)>> = RefCell::new(vec![]);
 }
 
 fn eval(scope: &mut ContextScope<HandleScope>, source: &str) {
@@ -75,6 +78,21 @@ pub fn start_runtime(
             &mut scope.borrow_mut(),
             include_str!("../target/dist/stdlib.js"),
         );
+        {
+            let ids: HashMap<String, usize> = BLOCKS.with(|blocks| {
+                blocks
+                    .borrow()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (b.id().to_string(), i))
+                    .collect()
+            });
+            let json = serde_json::to_string(&ids).unwrap();
+            eval(
+                &mut scope.borrow_mut(),
+                &format!("WW.setBlockIds({});", json),
+            );
+        }
 
         {
             let clock = Instant::now();
@@ -84,9 +102,10 @@ pub fn start_runtime(
                 let msgs: Vec<Message> = log.iter().copied().collect();
                 let json = serde_json::to_string(&msgs);
                 log.clear();
-                if let Ok(json) = json {
+                let cmds = CMD_QUEUE.with(|q| serde_json::to_string(&q.take()));
+                if let (Ok(json), Ok(cmds)) = (json, cmds) {
                     let millis = clock.elapsed().as_millis() as u64;
-                    let code = format!("WolkenWelten.tick({}, {});", millis, json);
+                    let code = format!("WolkenWelten.tick({}, {}, {});", millis, json, cmds);
                     eval(&mut scope.borrow_mut(), code.as_str());
                 }
                 MSG_QUEUE.with(move |f| {
@@ -104,6 +123,7 @@ pub fn start_runtime(
                 "VERSION",
                 env!("CARGO_PKG_VERSION"),
             );
+            command::init(&mut scope.borrow_mut(), &wwc);
             io::init(&mut scope.borrow_mut(), &wwc);
             world::init(&mut scope.borrow_mut(), &wwc);
             {
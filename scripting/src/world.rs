@@ -3,6 +3,7 @@
 use crate::{defun, WORLD};
 use glam::IVec3;
 use v8::{ContextScope, HandleScope, Local, ObjectTemplate};
+use wolkenwelten_core::BlockId;
 
 fn fun_get_block(
     scope: &mut v8::HandleScope,
@@ -41,7 +42,7 @@ fn fun_set_block(
         WORLD.with(|world| {
             let world = &*world.borrow();
             let world = world.as_ref().unwrap();
-            world.borrow_mut().set_block(pos, block as u8);
+            world.borrow_mut().set_block(pos, block as BlockId);
         });
     }
 }
@@ -2,14 +2,19 @@
 type SfxId = number;
 type BlockId = number;
 type MessageType = string;
+type CommandName = string;
+type StatusEffectKind = "poison" | "regeneration" | "haste" | "burning";
+type CommandInvocation = [CommandName, Array<string>];
 
 interface WWCInterface {
     getBlock:(x:number, y:number, z:number) => BlockId,
     setBlock:(x:number, y:number, z:number, block:BlockId) => void,
     sfxPlay:(x:number, y:number, z:number, volume:number, sfx:SfxId) => void,
+    applyEffect:(kind:StatusEffectKind, strength:number, seconds:number) => void,
     print:(value:any) => void,
     eprint:(value:any) => void,
 	game_log:(value:any) => void,
+	addCommand:(name:string, help:string) => void,
 
 	VERSION:string,
 }
@@ -107,14 +112,6 @@ const WolkenWelten = (() => {
 		msgHandler.clear();
 	}
 
-	const tick = (ticks:number, msgs:Array<Message>) => {
-		curMillis = ticks;
-		for(const msg of msgs){
-			dispatch(msg);
-		}
-		runQueue();
-	};
-
 	const log = (value:any) => {
 		const v = value.toString();
 		WWC.print(v+"\n");
@@ -122,6 +119,39 @@ const WolkenWelten = (() => {
 	}
 	const error = (value:any) => WWC.eprint(value.toString()+"\n");
 
+	let cmdHandler:Map<CommandName, (args:Array<string>) => any> = new Map();
+
+	const addCommand = (name:CommandName, help:string, λ:(args:Array<string>) => any) => {
+		cmdHandler.set(name, λ);
+		WWC.addCommand(name, help);
+	};
+
+	const runCommand = ([name, args]:CommandInvocation) => {
+		const λ = cmdHandler.get(name);
+		if(!λ){
+			return;
+		}
+		try {
+			const ret = λ(args);
+			if((ret !== undefined) && (ret !== null)){
+				log(ret);
+			}
+		} catch(e) {
+			log(`Error: ${e}`);
+		}
+	};
+
+	const tick = (ticks:number, msgs:Array<Message>, cmds:Array<CommandInvocation>) => {
+		curMillis = ticks;
+		for(const msg of msgs){
+			dispatch(msg);
+		}
+		for(const cmd of cmds){
+			runCommand(cmd);
+		}
+		runQueue();
+	};
+
 	return {
 		tick,
 		setTimeout,
@@ -131,6 +161,7 @@ const WolkenWelten = (() => {
 		addMsgHandler,
 		clearMsgHandler,
 		removeMsgHandler,
+		addCommand,
 		error,
 		log
 	};
@@ -150,6 +181,8 @@ const WW = {
 	getBlock: (p:Vec3) => WWC.getBlock(p.x, p.y, p.z),
 	setBlock: (p:Vec3, b:BlockId) => WWC.setBlock(p.x, p.y, p.z, b),
 	sfxPlay: (p:Vec3, v:number, sfx:SfxId) => WWC.sfxPlay(p.x, p.y, p.z, v, sfx),
+	/* Grant the player a status effect, strength is usually between 1 and 5 */
+	applyEffect: (kind:StatusEffectKind, strength:number, seconds:number) => WWC.applyEffect(kind, strength, seconds),
 	sfx: {
 		jump: 1,
 		hook_fire: 2,
@@ -160,34 +193,19 @@ const WW = {
 		pock: 7,
 		tock: 8
 	},
-	block: {
-		air: 0,
-		dirt: 1,
-		grass: 2,
-		stone: 3,
-		coal: 4,
-		spruceLog: 5,
-		spruceLeaves: 6,
-		dryGrass: 7,
-		roots: 8,
-		obsidian: 9,
-		oakLog: 10,
-		oakLeaves: 11,
-		hematite: 12,
-		marbleBlock: 13,
-		marblePillar: 14,
-		marbleBlocks: 15,
-		acaciaLeaves: 16,
-		boards: 17,
-		crystals: 18,
-		sakuraLeaves: 19,
-		birchLog: 20,
-		flowerBush: 21,
-		dateBush: 22,
-	},
+	block: {} as Record<string, BlockId>,
 	addMsgHandler: WolkenWelten.addMsgHandler,
 	clearMsgHandler: WolkenWelten.clearMsgHandler,
 	removeMsgHandler: WolkenWelten.removeMsgHandler,
+	addCommand: WolkenWelten.addCommand,
+
+	/* Called by the runtime with the ids from the block registry, keys are turned into camelCase */
+	setBlockIds: (ids:Record<string, BlockId>) => {
+		for(const id in ids){
+			const key = id.replace(/_([a-z0-9])/g, (_, c) => c.toUpperCase());
+			WW.block[key] = ids[id];
+		}
+	},
 };
 //WW.addMsgHandler("BlockBreak", m => m.block === WW.block.dirt && WW.sfxPlay(m.pos, 1.0, WW.sfx.bomb));
 
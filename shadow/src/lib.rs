@@ -4,7 +4,7 @@ use anyhow::Result;
 use glam::Vec3;
 use glium::{draw_parameters::PolygonOffset, uniform, Surface};
 use wolkenwelten_client::{Mesh, MeshVertex, RenderInitArgs, RenderPassArgs, ShaderList, Texture};
-use wolkenwelten_core::GameState;
+use wolkenwelten_core::{GameState, Renderable, Transform};
 
 fn add_vert(
     vertices: &mut Vec<MeshVertex>,
@@ -48,9 +48,17 @@ fn add_shadow(vertices: &mut Vec<MeshVertex>, p: Vec3, game: &GameState, size: f
 
 fn prepare(args: &RenderPassArgs) -> Result<Mesh> {
     let mut vertices: Vec<MeshVertex> = vec![];
-    let entity_positions = args.render_reactor.entity_pos();
-    for p in entity_positions.iter() {
-        add_shadow(&mut vertices, p.pos(), args.game, p.size());
+    let shadows: Vec<(Vec3, f32)> = {
+        let entities = args.game.entities();
+        let transforms = entities.components::<Transform>();
+        let renderables = entities.components::<Renderable>();
+        renderables
+            .iter()
+            .filter_map(|(id, r)| Some((transforms.get(id)?.pos, r.size)))
+            .collect()
+    };
+    for (pos, size) in shadows {
+        add_shadow(&mut vertices, pos, args.game, size);
     }
     let mesh = Mesh::from_vec(&args.fe.display, &vertices)?;
     Ok(mesh)
@@ -187,6 +187,7 @@ impl<'a> SfxList<'a> {
             Message::Explosion {
                 pos: Vec3::ZERO,
                 power: 0.0,
+                by_player: false,
             },
             1.0,
         );
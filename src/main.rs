@@ -1,9 +1,18 @@
 // Wolkenwelten - Copyright (C) 2022 - Benjamin Vincent Schulenburg
 // All rights reserved. AGPL-3.0+ license.
+use std::path::Path;
 use wolkenwelten_client::RenderInit;
 use wolkenwelten_core::{GameState, Reactor};
 use wolkenwelten_scripting::start_runtime;
 
+/// Where the player is stored in between sessions, relative to the working directory
+const PLAYER_SAVE_FILE: &str = "save/player.json";
+/// The high scores and the seed of the current run
+const RUN_SAVE_FILE: &str = "save/run.json";
+/// Mobs that were around when quitting, only used within the same run
+const MOB_SAVE_FILE: &str = "save/mobs.json";
+const BLOCK_ENTITY_SAVE_FILE: &str = "save/block_entities.json";
+
 /// Here we just create a new GameState, optionally add the Sfx handler and
 /// then start pass that along to the wolkenwelten-client-winit crate.
 pub fn main() {
@@ -40,10 +49,10 @@ pub fn main() {
         render_init_fun.push(Box::new(wolkenwelten_mob::init));
     }
 
-    #[cfg(feature = "grenade")]
+    #[cfg(feature = "projectile")]
     {
-        extern crate wolkenwelten_grenade;
-        render_init_fun.push(Box::new(wolkenwelten_grenade::init));
+        extern crate wolkenwelten_projectile;
+        render_init_fun.push(Box::new(wolkenwelten_projectile::init));
     }
 
     #[cfg(feature = "item-drop")]
@@ -58,7 +67,12 @@ pub fn main() {
         render_init_fun.push(Box::new(wolkenwelten_particles::init));
     }
 
-    let game_state = GameState::new().expect("Couldn't initialize game backend");
+    let game_state = GameState::new()
+        .expect("Couldn't initialize game backend")
+        .with_player_save(Path::new(PLAYER_SAVE_FILE))
+        .with_run_save(Path::new(RUN_SAVE_FILE))
+        .with_mob_save(Path::new(MOB_SAVE_FILE))
+        .with_block_entity_save(Path::new(BLOCK_ENTITY_SAVE_FILE));
     game_state.add_handler(&mut reactor);
     start_runtime(game_state, reactor, render_init_fun);
 }